@@ -0,0 +1,133 @@
+//! Scenes of the main loop for the benchmarks
+
+use embedded_graphics::pixelcolor::{Rgb565, RgbColor};
+use embedded_graphics::prelude::Point;
+use pixels_core::framebuffer::FrameBuffer;
+use pixels_core::math::{self, Projection, Rotation, Vec3};
+use pixels_core::mock::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use pixels_core::rng::Rng;
+use pixels_core::scene::Scene;
+use pixels_core::settings::Settings;
+
+/// The scenes that animate on their own, the canvas is drawn over the network
+pub const SCENES: [Scene; 3] = [Scene::Cube, Scene::Wireframe, Scene::Particles];
+
+/// Frames measured per scene, after the particles have built up
+pub const FRAMES: usize = 600;
+pub const WARMUP_FRAMES: usize = 120;
+const MAX_PARTICLES: usize = 200;
+
+const CUBE_EDGES: [(usize, usize); 12] = [
+    (0, 1),
+    (1, 2),
+    (2, 3),
+    (3, 0),
+    (4, 5),
+    (5, 6),
+    (6, 7),
+    (7, 4),
+    (0, 4),
+    (1, 5),
+    (2, 6),
+    (3, 7),
+];
+
+const COLORS: [Rgb565; 6] = [
+    Rgb565::RED,
+    Rgb565::GREEN,
+    Rgb565::BLUE,
+    Rgb565::YELLOW,
+    Rgb565::CYAN,
+    Rgb565::MAGENTA,
+];
+
+/// Cube and particles of the main loop with the default settings
+pub struct Animation {
+    settings: Settings,
+    projection: Projection,
+    rotation: Rotation,
+    spin: Rotation,
+    vertices: [Vec3; 8],
+    particles: Vec<(Vec3, Vec3, Rgb565)>,
+    rng: Rng,
+}
+
+impl Animation {
+    pub fn new() -> Self {
+        let settings = Settings::default();
+        let center = Point::new(DISPLAY_WIDTH as i32 / 2, DISPLAY_HEIGHT as i32 / 2);
+        Self {
+            projection: Projection::new(settings.fov, settings.projection_distance, center),
+            rotation: Rotation::IDENTITY,
+            spin: math::axis_angle(math::vec3(0.0, 1.0, 0.0), settings.rotation_speed),
+            vertices: [
+                math::vec3(-1.0, -1.0, -1.0),
+                math::vec3(1.0, -1.0, -1.0),
+                math::vec3(1.0, 1.0, -1.0),
+                math::vec3(-1.0, 1.0, -1.0),
+                math::vec3(-1.0, -1.0, 1.0),
+                math::vec3(1.0, -1.0, 1.0),
+                math::vec3(1.0, 1.0, 1.0),
+                math::vec3(-1.0, 1.0, 1.0),
+            ],
+            particles: Vec::new(),
+            rng: Rng::new(0x5EED),
+            settings,
+        }
+    }
+
+    pub fn step(&mut self) {
+        self.rotation = math::compose(self.spin, self.rotation);
+
+        for _ in 0..self.settings.emission_rate {
+            // Particles stay active once emitted
+            if self.particles.len() == MAX_PARTICLES {
+                break;
+            }
+            let mut direction = [0.0f32; 3];
+            direction.fill_with(|| self.rng.next_f32() * 2.0 - 1.0);
+            let len = direction
+                .iter()
+                .map(|d| d * d)
+                .sum::<f32>()
+                .sqrt()
+                .max(0.01);
+            let [x, y, z] = direction.map(|d| d / len * self.settings.particle_speed);
+            let color = COLORS[self.rng.next_u32() as usize % COLORS.len()];
+            self.particles
+                .push((math::vec3(0.0, 0.0, 0.0), math::vec3(x, y, z), color));
+        }
+
+        for (pos, vel, _) in &mut self.particles {
+            math::step_particle(pos, vel);
+        }
+    }
+
+    pub fn draw(&self, scene: Scene, frame: &mut FrameBuffer) {
+        if scene.draws_cube() {
+            let points = self
+                .vertices
+                .map(|v| self.projection.project(self.rotation, v));
+            for (start, end) in CUBE_EDGES {
+                if let (Some(start), Some(end)) = (points[start], points[end]) {
+                    frame.draw_line(start, end);
+                }
+            }
+        }
+
+        if scene.draws_particles() {
+            for &(pos, _, color) in &self.particles {
+                let Some(point) = self.projection.project(self.rotation, pos) else {
+                    continue;
+                };
+                if point.x >= 1
+                    && point.x < DISPLAY_WIDTH as i32 - 1
+                    && point.y >= 1
+                    && point.y < DISPLAY_HEIGHT as i32 - 1
+                {
+                    frame.draw_colored_point(point, color);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,112 @@
+//! Memory and CPU the `delta-transfer` feature spends for the bytes it saves
+//!
+//! Renders the animated scenes like the main loop and splits the merged
+//! dirty regions of every frame to the pixels that changed. Prints the
+//! bytes sent with and without the split, the time of the compare pass on
+//! the host and the SPI time the saved bytes take on the panel of the mock
+//! board. The memory cost is one more framebuffer, listed for every board.
+//!
+//! ```bash
+//! cargo bench --bench delta --features delta-transfer
+//! ```
+
+use std::time::{Duration, Instant};
+
+use embedded_graphics::pixelcolor::Rgb565;
+use pixels_core::config::{TILE_SIZE, TRANSFER_OVERHEAD_BYTES};
+use pixels_core::dirty_region::{RegionOptimizer, BYTES_PER_PIXEL};
+use pixels_core::framebuffer::{DirtyRect, FrameBuffer, Geometry};
+use pixels_core::mock::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+
+use common::{Animation, FRAMES, SCENES, WARMUP_FRAMES};
+
+mod common;
+
+/// Resolutions of the boards in `src/board`
+const BOARDS: [(&str, u16, u16); 5] = [
+    ("devkit-st7789-320x170", 320, 170),
+    ("devkit-st7789-240x240", 240, 240),
+    ("devkit-gc9a01", 240, 240),
+    ("devkit-ili9341", 320, 240),
+    ("t-display-s3-amoled", 536, 240),
+];
+
+/// SPI clock of the ST7789 320x170 devkit, the board the mock stands in for
+const SPI_FREQUENCY_MHZ: f32 = 62.0;
+
+fn bytes(rects: &[DirtyRect]) -> usize {
+    rects.iter().map(DirtyRect::pixel_count).sum::<usize>() * BYTES_PER_PIXEL
+}
+
+fn main() {
+    println!("Third framebuffer for what the panel shows:");
+    for (board, width, height) in BOARDS {
+        let bytes = width as usize * height as usize * BYTES_PER_PIXEL;
+        println!("  {board:<22} {width}x{height} {bytes:>7} bytes");
+    }
+
+    let geometry = Geometry::new(DISPLAY_WIDTH, DISPLAY_HEIGHT, TILE_SIZE);
+    let width = DISPLAY_WIDTH as usize;
+    let optimizer = RegionOptimizer::new(TRANSFER_OVERHEAD_BYTES);
+
+    println!();
+    println!("{DISPLAY_WIDTH}x{DISPLAY_HEIGHT} at {SPI_FREQUENCY_MHZ} MHz, per frame:");
+    println!(
+        "{:<10} {:>12} {:>13} {:>11} {:>10} {:>12}",
+        "scene", "merged bytes", "changed bytes", "saved bytes", "SPI us", "compare us"
+    );
+
+    for scene in SCENES {
+        let mut frame = FrameBuffer::new(geometry, TRANSFER_OVERHEAD_BYTES);
+        let mut animation = Animation::new();
+        // What the panel shows, follows the copy the framebuffer keeps
+        let mut sent = vec![Rgb565::new(0, 0, 0); geometry.pixel_count()];
+        let mut changed = Vec::new();
+        let (mut merged_bytes, mut changed_bytes) = (0, 0);
+        let mut compare = Duration::ZERO;
+
+        for index in 0..WARMUP_FRAMES + FRAMES {
+            animation.step();
+            frame.clear_buffer();
+            animation.draw(scene, &mut frame);
+            let Ok(()) = frame.rasterize();
+            frame.swap_buffers();
+            frame.update_dirty_regions();
+
+            // The compare pass of the frame again, on its own
+            changed.clear();
+            let start = Instant::now();
+            for rect in frame.merged_regions() {
+                optimizer.split_changed(rect, frame.front_buffer(), &sent, width, &mut changed);
+            }
+            let time = start.elapsed();
+            assert_eq!(changed, frame.dirty_regions());
+
+            if index >= WARMUP_FRAMES {
+                merged_bytes += bytes(frame.merged_regions());
+                changed_bytes += bytes(&changed);
+                compare += time;
+            }
+
+            for rect in &changed {
+                for y in rect.y_start as usize..=rect.y_end as usize {
+                    let row =
+                        y * width + rect.x_start as usize..y * width + rect.x_end as usize + 1;
+                    sent[row.clone()].copy_from_slice(&frame.front_buffer()[row]);
+                }
+            }
+            frame.finish_frame();
+        }
+
+        let saved = (merged_bytes - changed_bytes) / FRAMES;
+        println!(
+            "{:<10} {:>12} {:>13} {:>11} {:>10.1} {:>12.1}",
+            scene.name(),
+            merged_bytes / FRAMES,
+            changed_bytes / FRAMES,
+            saved,
+            (saved * 8) as f32 / SPI_FREQUENCY_MHZ,
+            compare.as_secs_f32() * 1e6 / FRAMES as f32,
+        );
+    }
+}
@@ -0,0 +1,118 @@
+//! Bytes and transfers per frame of the dirty region merging
+//!
+//! Renders the animated scenes like the main loop and compares sending
+//! every run of dirty tiles on its own, the merged regions of the transfer
+//! cost model and the whole frame. With `--features delta-transfer` the
+//! regions are also split to the pixels that changed
+//! (`RegionOptimizer::split_changed`). Times are host times, they only
+//! compare the models with each other.
+//!
+//! ```bash
+//! cargo bench --bench regions
+//! cargo bench --bench regions --features delta-transfer
+//! ```
+
+use std::time::{Duration, Instant};
+
+use pixels_core::config::{TILE_SIZE, TRANSFER_OVERHEAD_BYTES};
+use pixels_core::dirty_region::BYTES_PER_PIXEL;
+use pixels_core::framebuffer::{FrameBuffer, Geometry};
+use pixels_core::mock::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use pixels_core::scene::Scene;
+
+use common::{Animation, FRAMES, SCENES, WARMUP_FRAMES};
+
+mod common;
+
+/// Totals over the measured frames
+#[derive(Default)]
+struct Totals {
+    bytes: usize,
+    transfers: usize,
+    time: Duration,
+}
+
+impl Totals {
+    fn print(&self, scene: Scene, model: &str) {
+        let bytes = self.bytes / FRAMES;
+        let transfers = self.transfers as f32 / FRAMES as f32;
+        // What the cost model minimizes, transfers in bytes of pixel data
+        let cost = bytes + self.transfers * TRANSFER_OVERHEAD_BYTES / FRAMES;
+        println!(
+            "{:<10} {:<11} {:>11} {:>15.1} {:>10} {:>11.1}",
+            scene.name(),
+            model,
+            bytes,
+            transfers,
+            cost,
+            self.time.as_secs_f32() * 1e6 / FRAMES as f32,
+        );
+    }
+}
+
+fn main() {
+    let geometry = Geometry::new(DISPLAY_WIDTH, DISPLAY_HEIGHT, TILE_SIZE);
+    let frame_bytes = geometry.pixel_count() * BYTES_PER_PIXEL;
+
+    println!(
+        "{DISPLAY_WIDTH}x{DISPLAY_HEIGHT}, {TILE_SIZE} px tiles, {TRANSFER_OVERHEAD_BYTES} bytes per transfer{}",
+        if cfg!(feature = "delta-transfer") {
+            ", delta transfer"
+        } else {
+            ""
+        }
+    );
+    println!(
+        "{:<10} {:<11} {:>11} {:>15} {:>10} {:>11}",
+        "scene", "model", "bytes/frame", "transfers/frame", "cost/frame", "us/frame"
+    );
+
+    for scene in SCENES {
+        // An overhead of 0 never bridges clean pixels, every run of dirty
+        // tiles is a transfer of its own
+        let mut models = [
+            (
+                "tile runs",
+                FrameBuffer::new(geometry, 0),
+                Totals::default(),
+            ),
+            (
+                "cost model",
+                FrameBuffer::new(geometry, TRANSFER_OVERHEAD_BYTES),
+                Totals::default(),
+            ),
+        ];
+        let mut animation = Animation::new();
+
+        for index in 0..WARMUP_FRAMES + FRAMES {
+            animation.step();
+            for (_, frame, totals) in &mut models {
+                frame.clear_buffer();
+                animation.draw(scene, frame);
+                let Ok(()) = frame.rasterize();
+                frame.swap_buffers();
+
+                let start = Instant::now();
+                frame.update_dirty_regions();
+                let time = start.elapsed();
+
+                if index >= WARMUP_FRAMES {
+                    totals.bytes += frame.dirty_pixel_count() * BYTES_PER_PIXEL;
+                    totals.transfers += frame.dirty_regions().len();
+                    totals.time += time;
+                }
+                frame.finish_frame();
+            }
+        }
+
+        for (model, _, totals) in &models {
+            totals.print(scene, model);
+        }
+        Totals {
+            bytes: frame_bytes * FRAMES,
+            transfers: FRAMES,
+            time: Duration::ZERO,
+        }
+        .print(scene, "full frame");
+    }
+}
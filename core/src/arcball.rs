@@ -0,0 +1,147 @@
+//! Arcball rotation control
+//!
+//! Screen positions are mapped onto a virtual sphere in front of the cube, a
+//! drag rotates the sphere point under the finger to the new position so the
+//! cube follows the finger. A fling keeps the cube spinning around the axis
+//! perpendicular to the movement until damping stops it.
+
+use embedded_graphics::prelude::Point;
+use micromath::F32Ext;
+
+use crate::math::{self, Rotation};
+
+/// Share of the spin speed lost per second
+const SPIN_DAMPING: f32 = 2.0;
+/// Spins slower than this, in radians per second, stop
+const MIN_SPIN_SPEED: f32 = 0.5;
+/// Fastest spin in radians per second
+const MAX_SPIN_SPEED: f32 = 20.0;
+
+/// Rotation left over from a fling
+struct Spin {
+    /// Unit rotation axis
+    axis: (f32, f32, f32),
+    /// Radians per second
+    speed: f32,
+}
+
+pub struct Arcball {
+    /// Screen position of the sphere center
+    center: Point,
+    /// Sphere radius in pixels
+    radius: f32,
+    /// A finger is on the screen
+    held: bool,
+    spin: Option<Spin>,
+}
+
+impl Arcball {
+    pub fn new(center: Point, radius: f32) -> Self {
+        Self {
+            center,
+            radius,
+            held: false,
+            spin: None,
+        }
+    }
+
+    /// Touch down, stops the spin
+    pub fn hold(&mut self) {
+        self.held = true;
+        self.spin = None;
+    }
+
+    /// Touch up, a following [`Arcball::fling`] starts the spin
+    pub fn release(&mut self) {
+        self.held = false;
+    }
+
+    /// Rotation or spin in progress, auto-rotation is paused meanwhile
+    pub fn is_active(&self) -> bool {
+        self.held || self.spin.is_some()
+    }
+
+    /// Rotation moving the sphere point under `from` to `to`
+    pub fn drag(&self, from: Point, to: Point) -> Option<Rotation> {
+        let a = self.sphere_point(from);
+        let b = self.sphere_point(to);
+
+        let axis = cross(a, b);
+        let sin = length(axis);
+        if sin < f32::EPSILON {
+            return None;
+        }
+
+        let angle = sin.atan2(dot(a, b));
+        Some(rotation(scale(axis, 1.0 / sin), angle))
+    }
+
+    /// Starts spinning with the release velocity in pixels per second
+    pub fn fling(&mut self, vx: f32, vy: f32) {
+        let speed = (vx * vx + vy * vy).sqrt();
+        if speed < f32::EPSILON {
+            return;
+        }
+
+        // Same axis as a drag through the sphere center in that direction,
+        // the surface there moves one radius per radian
+        self.spin = Some(Spin {
+            axis: (vy / speed, -vx / speed, 0.0),
+            speed: (speed / self.radius).min(MAX_SPIN_SPEED),
+        });
+    }
+
+    /// Rotation of the spin over `dt` seconds, `None` once it has stopped
+    pub fn step(&mut self, dt: f32) -> Option<Rotation> {
+        let spin = self.spin.as_mut()?;
+        let angle = spin.speed * dt;
+        let axis = spin.axis;
+
+        spin.speed *= (-SPIN_DAMPING * dt).exp();
+        if spin.speed < MIN_SPIN_SPEED {
+            self.spin = None;
+        }
+
+        Some(rotation(axis, angle))
+    }
+
+    /// Point of the unit sphere facing the camera under a screen position,
+    /// positions outside the sphere map to its rim
+    fn sphere_point(&self, position: Point) -> (f32, f32, f32) {
+        let x = (position.x - self.center.x) as f32 / self.radius;
+        let y = (position.y - self.center.y) as f32 / self.radius;
+
+        let d2 = x * x + y * y;
+        if d2 >= 1.0 {
+            let d = d2.sqrt();
+            (x / d, y / d, 0.0)
+        } else {
+            // The camera looks along +z, the front of the sphere is at -z
+            (x, y, -(1.0 - d2).sqrt())
+        }
+    }
+}
+
+fn rotation(axis: (f32, f32, f32), angle: f32) -> Rotation {
+    math::axis_angle(math::vec3(axis.0, axis.1, axis.2), angle)
+}
+
+fn cross(a: (f32, f32, f32), b: (f32, f32, f32)) -> (f32, f32, f32) {
+    (
+        a.1 * b.2 - a.2 * b.1,
+        a.2 * b.0 - a.0 * b.2,
+        a.0 * b.1 - a.1 * b.0,
+    )
+}
+
+fn dot(a: (f32, f32, f32), b: (f32, f32, f32)) -> f32 {
+    a.0 * b.0 + a.1 * b.1 + a.2 * b.2
+}
+
+fn length(a: (f32, f32, f32)) -> f32 {
+    dot(a, a).sqrt()
+}
+
+fn scale(a: (f32, f32, f32), s: f32) -> (f32, f32, f32) {
+    (a.0 * s, a.1 * s, a.2 * s)
+}
@@ -0,0 +1,219 @@
+//! AMOLED burn-in mitigation
+//!
+//! OLED pixels age with the light they emit, so anything that stays in place
+//! for hours leaves a shadow. Pure logic like [`crate::power`], the caller
+//! moves and draws:
+//! - the scene origin and the static overlays orbit by a few pixels on a slow
+//!   Lissajous path, [`BurnIn::orbit`]
+//! - the on-time of the overlays is added up per region of the screen, a
+//!   [`Hud`] element moves to its place mirrored top to bottom when that is
+//!   less worn and is dimmed once both places are worn
+//! - after a while on, an idle screen shows full-screen colors for a few
+//!   seconds so all pixels age alike, [`BurnIn::refresh`]
+
+use core::f32::consts::TAU;
+use core::ops::Range;
+
+use embedded_graphics::pixelcolor::{Rgb565, RgbColor};
+use embedded_graphics::prelude::{Point, Size};
+use embedded_graphics::primitives::Rectangle;
+use micromath::F32Ext;
+
+/// How far and how often things move, in pixels and milliseconds of on-time
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Mitigation {
+    /// Largest shift of the scene origin and the overlays
+    pub orbit_radius: i32,
+    /// Time for one orbit across, the one down takes three quarters of it
+    pub orbit_period_ms: u64,
+    /// On-time between pixel refreshes
+    pub refresh_interval_ms: u64,
+    /// Time each color of the pixel refresh is shown
+    pub refresh_step_ms: u64,
+    /// An overlay moves once its place has this much more on-time than the other
+    pub relocate_margin_ms: u32,
+    /// An overlay is dimmed once its place has this much on-time
+    pub dim_after_ms: u32,
+}
+
+/// Regions of the wear map across and down the screen
+const WEAR_COLUMNS: usize = 16;
+const WEAR_ROWS: usize = 8;
+
+/// Shown one after another by the pixel refresh
+const REFRESH_COLORS: [Rgb565; 4] = [Rgb565::RED, Rgb565::GREEN, Rgb565::BLUE, Rgb565::WHITE];
+
+pub struct BurnIn {
+    mitigation: Mitigation,
+    screen: Size,
+    /// On-time of the overlays in each region, in milliseconds
+    wear: [[u32; WEAR_COLUMNS]; WEAR_ROWS],
+    /// On-time since the last pixel refresh
+    since_refresh_ms: u64,
+    /// Start of the running pixel refresh
+    refresh_start: Option<u64>,
+}
+
+/// A static overlay, at its home place or mirrored top to bottom
+pub struct Hud {
+    places: [Rectangle; 2],
+    current: usize,
+    /// Direction towards the inside of the screen at each place
+    inward: [Point; 2],
+}
+
+impl BurnIn {
+    pub fn new(mitigation: Mitigation, screen: Size) -> Self {
+        Self {
+            mitigation,
+            screen,
+            wear: [[0; WEAR_COLUMNS]; WEAR_ROWS],
+            since_refresh_ms: 0,
+            refresh_start: None,
+        }
+    }
+
+    /// Shift of the scene origin at `now_ms`
+    ///
+    /// Rounded from a continuous path, so it changes by one pixel at a time,
+    /// and over a few orbits it passes every offset within the radius.
+    pub fn orbit(&self, now_ms: u64) -> Point {
+        let x_period = self.mitigation.orbit_period_ms.max(1);
+        let y_period = (x_period * 3 / 4).max(1);
+        let radius = self.mitigation.orbit_radius as f32;
+        let along = |period: u64| {
+            let phase = (now_ms % period) as f32 / period as f32;
+            (radius * (phase * TAU).sin()).round() as i32
+        };
+        Point::new(along(x_period), along(y_period))
+    }
+
+    /// Adds `ms` of on-time at the place of `hud` and moves it when the other
+    /// place is less worn
+    pub fn track(&mut self, hud: &mut Hud, ms: u64) {
+        self.add_wear(hud.places[hud.current], ms);
+
+        let here = self.wear_of(hud.places[hud.current]);
+        let there = self.wear_of(hud.places[1 - hud.current]);
+        if here.saturating_sub(there) >= self.mitigation.relocate_margin_ms {
+            hud.current = 1 - hud.current;
+        }
+    }
+
+    /// Whether `hud` is to be drawn dimmer at its place
+    pub fn dimmed(&self, hud: &Hud) -> bool {
+        self.wear_of(hud.places[hud.current]) >= self.mitigation.dim_after_ms
+    }
+
+    /// Color the whole screen shows for the pixel refresh, `None` without one
+    ///
+    /// Call every frame the panel is on with the time since the last frame. A
+    /// refresh starts once it is due while `idle` and stops early when the
+    /// screen is no longer `idle`.
+    pub fn refresh(&mut self, now_ms: u64, frame_ms: u64, idle: bool) -> Option<Rgb565> {
+        self.since_refresh_ms = self.since_refresh_ms.saturating_add(frame_ms);
+        if !idle {
+            self.refresh_start = None;
+            return None;
+        }
+
+        if self.refresh_start.is_none()
+            && self.since_refresh_ms >= self.mitigation.refresh_interval_ms
+        {
+            self.refresh_start = Some(now_ms);
+        }
+        let start = self.refresh_start?;
+        let step = (now_ms.saturating_sub(start) / self.mitigation.refresh_step_ms.max(1)) as usize;
+        let color = REFRESH_COLORS.get(step).copied();
+        if color.is_none() {
+            self.refresh_start = None;
+            self.since_refresh_ms = 0;
+        }
+        color
+    }
+
+    fn add_wear(&mut self, area: Rectangle, ms: u64) {
+        let ms = ms.min(u32::MAX as u64) as u32;
+        let (columns, rows) = self.regions(area);
+        for row in &mut self.wear[rows] {
+            for wear in &mut row[columns.clone()] {
+                *wear = wear.saturating_add(ms);
+            }
+        }
+    }
+
+    /// Highest on-time of the regions `area` covers
+    fn wear_of(&self, area: Rectangle) -> u32 {
+        let (columns, rows) = self.regions(area);
+        self.wear[rows]
+            .iter()
+            .flat_map(|row| &row[columns.clone()])
+            .copied()
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Columns and rows of the wear map `area` covers, empty outside the screen
+    fn regions(&self, area: Rectangle) -> (Range<usize>, Range<usize>) {
+        let Some(end) = area.bottom_right() else {
+            return (0..0, 0..0);
+        };
+        let start = area.top_left;
+        (
+            span(start.x, end.x, self.screen.width, WEAR_COLUMNS),
+            span(start.y, end.y, self.screen.height, WEAR_ROWS),
+        )
+    }
+}
+
+impl Hud {
+    /// An overlay at `home` on a screen of size `screen`
+    pub fn new(home: Rectangle, screen: Size) -> Self {
+        let mirrored = Rectangle::new(
+            Point::new(
+                home.top_left.x,
+                screen.height as i32 - home.top_left.y - home.size.height as i32,
+            ),
+            home.size,
+        );
+        let inward = |area: Rectangle| {
+            let center = area.center();
+            Point::new(
+                toward_center(center.x, screen.width),
+                toward_center(center.y, screen.height),
+            )
+        };
+        Self {
+            places: [home, mirrored],
+            current: 0,
+            inward: [inward(home), inward(mirrored)],
+        }
+    }
+
+    /// Shift from the home place, with the `orbit` turned towards the inside of
+    /// the screen, so an overlay at the edge stays on screen
+    pub fn offset(&self, orbit: Point) -> Point {
+        let inward = self.inward[self.current];
+        let orbit = Point::new(orbit.x.abs() * inward.x, orbit.y.abs() * inward.y);
+        self.places[self.current].top_left - self.places[0].top_left + orbit
+    }
+}
+
+/// Regions `start..=end` covers along one axis of `length` pixels cut into `count`
+fn span(start: i32, end: i32, length: u32, count: usize) -> Range<usize> {
+    let length = length.max(1) as i32;
+    if end < 0 || start >= length {
+        return 0..0;
+    }
+    let region = |pixel: i32| pixel.clamp(0, length - 1) as usize * count / length as usize;
+    region(start)..region(end) + 1
+}
+
+/// `1` from a position in the first half of `length`, `-1` from the second
+fn toward_center(position: i32, length: u32) -> i32 {
+    if position * 2 < length as i32 {
+        1
+    } else {
+        -1
+    }
+}
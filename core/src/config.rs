@@ -0,0 +1,18 @@
+//! Tunables of the logic in this crate, the ones of the firmware are in its
+//! own `src/config.rs`
+
+use crate::touch::Calibration;
+
+pub const TILE_SIZE: u16 = 32; // 32x32 pixel tiles
+
+/// Cost of one display transfer (address window commands, chip select, DMA
+/// setup) in bytes of pixel data. Dirty regions are merged as long as the
+/// clean pixels sent with them cost less than the transfer they save.
+pub const TRANSFER_OVERHEAD_BYTES: usize = 1024;
+
+/// Correction of touch positions after rotating them like the display, until
+/// a calibration (long press, then tap the crosshairs) is saved in the settings
+pub const TOUCH_CALIBRATION: Calibration = Calibration::IDENTITY;
+
+/// Largest recording in bytes, about one byte per frame plus five per touch event
+pub const RECORDING_CAPACITY: usize = 16 * 1024;
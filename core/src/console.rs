@@ -0,0 +1,351 @@
+//! Line-based serial console
+//!
+//! Commands are typed on the USB serial port, one per line, e.g. in the
+//! `espflash` monitor. Every word can be shortened to a unique prefix and Tab
+//! completes it. Responses are plain lines with one item per line, so they
+//! are easy to read back, grep and complete against:
+//!
+//! ```text
+//! set rot 0.05
+//! rotation_speed=0.05
+//! ```
+
+use core::fmt::{self, Write};
+
+use crate::scene::Scene;
+use crate::settings::{Settings, KEYS};
+use crate::text::TextBuffer;
+
+/// Longest command line, longer lines are cut
+const LINE_LEN: usize = 64;
+const BACKSPACE: u8 = 0x08;
+const DELETE: u8 = 0x7F;
+const TAB: u8 = b'\t';
+
+struct Info {
+    name: &'static str,
+    usage: &'static str,
+    summary: &'static str,
+}
+
+const COMMANDS: [Info; 12] = [
+    Info {
+        name: "help",
+        usage: "help [command]",
+        summary: "list the commands or show one",
+    },
+    Info {
+        name: "get",
+        usage: "get [key]",
+        summary: "show one or all settings",
+    },
+    Info {
+        name: "set",
+        usage: "set <key> <value>",
+        summary: "change a setting until reboot",
+    },
+    Info {
+        name: "save",
+        usage: "save",
+        summary: "store the settings in flash",
+    },
+    Info {
+        name: "scene",
+        usage: "scene [name]",
+        summary: "switch the scene or list the scenes",
+    },
+    Info {
+        name: "pause",
+        usage: "pause",
+        summary: "stop the animation, drawing continues",
+    },
+    Info {
+        name: "resume",
+        usage: "resume",
+        summary: "continue the animation",
+    },
+    Info {
+        name: "step",
+        usage: "step [frames]",
+        summary: "animate one or more frames while paused",
+    },
+    Info {
+        name: "profile",
+        usage: "profile",
+        summary: "show the frame profiler statistics",
+    },
+    Info {
+        name: "screenshot",
+        usage: "screenshot",
+        summary: "send the next frame over serial",
+    },
+    Info {
+        name: "crash",
+        usage: "crash",
+        summary: "show the crash report of the last run",
+    },
+    Info {
+        name: "reset",
+        usage: "reset",
+        summary: "remove all particles",
+    },
+];
+
+const NAMES: [&str; COMMANDS.len()] = {
+    let mut names = [""; COMMANDS.len()];
+    let mut i = 0;
+    while i < COMMANDS.len() {
+        names[i] = COMMANDS[i].name;
+        i += 1;
+    }
+    names
+};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Command<'a> {
+    Help(Option<&'static str>),
+    Get(Option<&'static str>),
+    Set(&'static str, &'a str),
+    Save,
+    Scene(Option<Scene>),
+    Pause,
+    Resume,
+    Step(u32),
+    Profile,
+    Screenshot,
+    Crash,
+    Reset,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Error<'a> {
+    /// `word` is not a prefix of any of `choices`
+    Unknown(&'a str, &'static [&'static str]),
+    /// `word` is a prefix of more than one of `choices`
+    Ambiguous(&'a str, &'static [&'static str]),
+    /// The named argument is missing
+    Missing(&'static str),
+    InvalidNumber(&'a str),
+    /// Arguments after the last one of the command
+    Extra(&'a str),
+}
+
+impl fmt::Display for Error<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Error::Unknown(word, choices) => {
+                write!(f, "unknown '{}', expected one of:", word)?;
+                for choice in choices {
+                    write!(f, "\n  {}", choice)?;
+                }
+                Ok(())
+            }
+            Error::Ambiguous(word, choices) => {
+                write!(f, "ambiguous '{}', candidates:", word)?;
+                for choice in choices.iter().filter(|c| c.starts_with(word)) {
+                    write!(f, "\n  {}", choice)?;
+                }
+                Ok(())
+            }
+            Error::Missing(argument) => write!(f, "missing {}", argument),
+            Error::InvalidNumber(word) => write!(f, "'{}' is not a number", word),
+            Error::Extra(word) => write!(f, "unexpected '{}'", word),
+        }
+    }
+}
+
+/// Parses a command line, words are separated by whitespace
+pub fn parse(line: &str) -> Result<Command<'_>, Error<'_>> {
+    let mut words = line.split_whitespace();
+    let name = resolve(words.next().ok_or(Error::Missing("command"))?, &NAMES)?;
+
+    let command = match name {
+        "help" => Command::Help(optional(words.next(), &NAMES)?),
+        "get" => Command::Get(optional(words.next(), &KEYS)?),
+        "set" => {
+            let key = resolve(words.next().ok_or(Error::Missing("key"))?, &KEYS)?;
+            Command::Set(key, words.next().ok_or(Error::Missing("value"))?)
+        }
+        "save" => Command::Save,
+        "scene" => {
+            Command::Scene(optional(words.next(), &Scene::NAMES)?.and_then(Scene::from_name))
+        }
+        "pause" => Command::Pause,
+        "resume" => Command::Resume,
+        "step" => match words.next() {
+            Some(word) => Command::Step(word.parse().map_err(|_| Error::InvalidNumber(word))?),
+            None => Command::Step(1),
+        },
+        "profile" => Command::Profile,
+        "screenshot" => Command::Screenshot,
+        "crash" => Command::Crash,
+        // "reset", the last name
+        _ => Command::Reset,
+    };
+
+    match words.next() {
+        Some(extra) => Err(Error::Extra(extra)),
+        None => Ok(command),
+    }
+}
+
+/// The one of `choices` that is `word` or starts with it
+fn resolve<'a>(word: &'a str, choices: &'static [&'static str]) -> Result<&'static str, Error<'a>> {
+    if let Some(&exact) = choices.iter().find(|&&choice| choice == word) {
+        return Ok(exact);
+    }
+
+    let mut matches = choices.iter().filter(|choice| choice.starts_with(word));
+    match (matches.next(), matches.next()) {
+        (Some(&choice), None) => Ok(choice),
+        (Some(_), Some(_)) => Err(Error::Ambiguous(word, choices)),
+        _ => Err(Error::Unknown(word, choices)),
+    }
+}
+
+fn optional<'a>(
+    word: Option<&'a str>,
+    choices: &'static [&'static str],
+) -> Result<Option<&'static str>, Error<'a>> {
+    word.map(|word| resolve(word, choices)).transpose()
+}
+
+/// Writes the usage of every command, or of `command` only
+pub fn help<W: Write>(command: Option<&str>, out: &mut W) -> fmt::Result {
+    for info in COMMANDS
+        .iter()
+        .filter(|info| command.is_none_or(|name| name == info.name))
+    {
+        writeln!(out, "{:<18} {}", info.usage, info.summary)?;
+    }
+    Ok(())
+}
+
+/// Writes `key=value` lines for the setting `key`, or for all settings
+pub fn write_settings<W: Write>(
+    settings: &Settings,
+    key: Option<&str>,
+    out: &mut W,
+) -> fmt::Result {
+    for key in KEYS.iter().filter(|&&k| key.is_none_or(|key| key == k)) {
+        write!(out, "{}=", key)?;
+        settings.write_value(key, out)?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Completions of the last word of `line`: the byte offset where that word
+/// starts and the words it can be completed to
+pub fn complete(line: &str) -> (usize, impl Iterator<Item = &'static str> + '_) {
+    let start = line.rfind(char::is_whitespace).map_or(0, |space| space + 1);
+    let word = &line[start..];
+
+    let mut previous = line[..start].split_whitespace();
+    let choices: &'static [&'static str] = match (previous.next(), previous.next()) {
+        (None, _) => &NAMES,
+        (Some(command), None) => match resolve(command, &NAMES) {
+            Ok("help") => &NAMES,
+            Ok("get" | "set") => &KEYS,
+            Ok("scene") => &Scene::NAMES,
+            _ => &[],
+        },
+        _ => &[],
+    };
+
+    (
+        start,
+        choices
+            .iter()
+            .copied()
+            .filter(move |choice| choice.starts_with(word)),
+    )
+}
+
+/// Line being typed, with backspace and Tab completion
+pub struct LineEditor {
+    line: TextBuffer<LINE_LEN>,
+    /// The line was returned, the next byte starts a new one
+    done: bool,
+}
+
+impl Default for LineEditor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LineEditor {
+    pub const fn new() -> Self {
+        Self {
+            line: TextBuffer::new(),
+            done: false,
+        }
+    }
+
+    /// Handles a received byte, writing what the terminal should show to
+    /// `echo`. Returns the line when Enter completes a non-empty one.
+    pub fn push<W: Write>(&mut self, byte: u8, echo: &mut W) -> Option<&str> {
+        if self.done {
+            self.line.clear();
+            self.done = false;
+        }
+
+        match byte {
+            b'\r' | b'\n' => {
+                if self.line.as_str().trim().is_empty() {
+                    self.line.clear();
+                    return None;
+                }
+                let _ = echo.write_str("\n");
+                self.done = true;
+                return Some(self.line.as_str());
+            }
+            // Erases the last character on the terminal too
+            BACKSPACE | DELETE if self.line.pop().is_some() => {
+                let _ = echo.write_str("\x08 \x08");
+            }
+            TAB => self.complete(echo),
+            // Printable ASCII, cut at the end of the buffer
+            b' '..=b'~' => {
+                let c = byte as char;
+                if self.line.write_char(c).is_ok() {
+                    let _ = echo.write_char(c);
+                }
+            }
+            _ => {}
+        }
+
+        None
+    }
+
+    /// The line returned last by [`LineEditor::push`], or the one being typed
+    pub fn line(&self) -> &str {
+        self.line.as_str()
+    }
+
+    /// Completes a unique match, or lists the candidates and shows the line
+    /// again
+    fn complete<W: Write>(&mut self, echo: &mut W) {
+        let line = self.line.clone();
+        let (start, mut candidates) = complete(line.as_str());
+        let typed = line.as_str().len() - start;
+
+        match (candidates.next(), candidates.next()) {
+            (Some(only), None) => {
+                let rest = &only[typed..];
+                if self.line.write_str(rest).is_ok() && self.line.write_char(' ').is_ok() {
+                    let _ = write!(echo, "{} ", rest);
+                }
+            }
+            (Some(first), Some(second)) => {
+                let _ = write!(echo, "\n{}\n{}\n", first, second);
+                for candidate in candidates {
+                    let _ = writeln!(echo, "{}", candidate);
+                }
+                let _ = echo.write_str(line.as_str());
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,252 @@
+//! Crash reports that survive the reset
+//!
+//! The panic handler, or the firmware giving up on the display, encodes a
+//! [`CrashReport`] into a record in memory that is kept over a reset (see
+//! `src/panic.rs`), the next boot decodes it, shows it on the screen and
+//! keeps it for the `crash` console command. A watchdog reset leaves no
+//! record, it is reported from the reset reason.
+//!
+//! Record layout, little endian:
+//!
+//! - magic `PXCR`
+//! - cause (`u8`), message length (`u8`), backtrace length (`u8`), 1 reserved byte
+//! - message, [`MESSAGE_LEN`] bytes
+//! - backtrace, [`BACKTRACE_LEN`] program counters, `u32` each
+//! - CRC-32 of everything before it
+
+use core::fmt::{self, Write};
+
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::Point;
+use embedded_graphics::primitives::Rectangle;
+use pixels_protocol::crc::crc32;
+
+use crate::display::DisplayTrait;
+use crate::text::TextBuffer;
+
+/// Longest panic message kept, with its location
+pub const MESSAGE_LEN: usize = 200;
+/// Innermost frames of the backtrace kept
+pub const BACKTRACE_LEN: usize = 16;
+pub const RECORD_LEN: usize = HEADER_LEN + MESSAGE_LEN + BACKTRACE_LEN * 4 + CRC_LEN;
+
+const MAGIC: &[u8; 4] = b"PXCR";
+const HEADER_LEN: usize = 8;
+const CRC_LEN: usize = 4;
+const _: () = assert!(MESSAGE_LEN <= u8::MAX as usize);
+
+/// Character size of the 10x20 font
+const CHAR_WIDTH: i32 = 10;
+const CHAR_HEIGHT: i32 = 20;
+const MARGIN: i32 = 8;
+const BACKGROUND: Rgb565 = Rgb565::new(10, 0, 0);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Cause {
+    Panic,
+    /// The render loop stopped feeding the watchdog
+    Watchdog,
+    /// The display did not start, or kept failing after every recovery
+    Display,
+}
+
+#[derive(Clone, PartialEq)]
+pub struct CrashReport {
+    pub cause: Cause,
+    /// Panic message with its location, cut to [`MESSAGE_LEN`] bytes
+    pub message: TextBuffer<MESSAGE_LEN>,
+    backtrace: [u32; BACKTRACE_LEN],
+    frames: usize,
+}
+
+impl CrashReport {
+    pub const fn new(cause: Cause) -> Self {
+        Self {
+            cause,
+            message: TextBuffer::new(),
+            backtrace: [0; BACKTRACE_LEN],
+            frames: 0,
+        }
+    }
+
+    /// Adds the program counter of the next outer frame, the ones past
+    /// [`BACKTRACE_LEN`] are dropped
+    pub fn push_frame(&mut self, program_counter: u32) {
+        if self.frames < BACKTRACE_LEN {
+            self.backtrace[self.frames] = program_counter;
+            self.frames += 1;
+        }
+    }
+
+    /// Program counters, innermost frame first
+    pub fn backtrace(&self) -> &[u32] {
+        &self.backtrace[..self.frames]
+    }
+
+    pub fn encode(&self, record: &mut [u8; RECORD_LEN]) {
+        let message = self.message.as_str().as_bytes();
+        record.fill(0);
+        record[..4].copy_from_slice(MAGIC);
+        record[4] = match self.cause {
+            Cause::Panic => 0,
+            Cause::Watchdog => 1,
+            Cause::Display => 2,
+        };
+        record[5] = message.len() as u8;
+        record[6] = self.frames as u8;
+
+        record[HEADER_LEN..HEADER_LEN + message.len()].copy_from_slice(message);
+        let backtrace = HEADER_LEN + MESSAGE_LEN;
+        for (i, pc) in self.backtrace().iter().enumerate() {
+            let at = backtrace + i * 4;
+            record[at..at + 4].copy_from_slice(&pc.to_le_bytes());
+        }
+
+        let end = RECORD_LEN - CRC_LEN;
+        let crc = crc32(&record[..end]);
+        record[end..].copy_from_slice(&crc.to_le_bytes());
+    }
+
+    /// The report of a valid record, `None` for anything else, like the
+    /// random contents after a power cycle
+    pub fn decode(record: &[u8; RECORD_LEN]) -> Option<Self> {
+        let end = RECORD_LEN - CRC_LEN;
+        let crc = u32::from_le_bytes([
+            record[end],
+            record[end + 1],
+            record[end + 2],
+            record[end + 3],
+        ]);
+        if !record.starts_with(MAGIC) || crc != crc32(&record[..end]) {
+            return None;
+        }
+
+        let cause = match record[4] {
+            0 => Cause::Panic,
+            1 => Cause::Watchdog,
+            2 => Cause::Display,
+            _ => return None,
+        };
+        let len = record[5] as usize;
+        let frames = record[6] as usize;
+        if len > MESSAGE_LEN || frames > BACKTRACE_LEN {
+            return None;
+        }
+
+        let mut report = Self::new(cause);
+        let message = core::str::from_utf8(&record[HEADER_LEN..HEADER_LEN + len]).ok()?;
+        report.message.write_str(message).ok()?;
+        let backtrace = HEADER_LEN + MESSAGE_LEN;
+        for i in 0..frames {
+            let at = backtrace + i * 4;
+            report.push_frame(u32::from_le_bytes([
+                record[at],
+                record[at + 1],
+                record[at + 2],
+                record[at + 3],
+            ]));
+        }
+        Some(report)
+    }
+
+    /// Writes the report as lines, for the console
+    pub fn write<W: Write>(&self, out: &mut W) -> fmt::Result {
+        writeln!(out, "cause={}", self.cause_name())?;
+        for line in self.message.as_str().lines() {
+            writeln!(out, "{}", line)?;
+        }
+        if !self.backtrace().is_empty() {
+            writeln!(out, "backtrace:")?;
+            for pc in self.backtrace() {
+                writeln!(out, "0x{:08x}", pc)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Draws the report over the whole screen, with a hint how to close it at
+    /// the bottom. What does not fit is left out.
+    pub fn draw<D: DisplayTrait>(&self, display: &mut D, hint: &str) -> Result<(), D::Error> {
+        let size = display.size();
+        display.fill_rect(Rectangle::new(Point::zero(), size), BACKGROUND)?;
+
+        let columns = ((size.width as i32 - 2 * MARGIN) / CHAR_WIDTH).max(1) as usize;
+        let last_line = (size.height as i32 - 2 * MARGIN) / CHAR_HEIGHT - 1;
+        let mut lines = Lines {
+            display,
+            line: 0,
+            // The hint gets the last line
+            last_line: last_line - 1,
+        };
+
+        let title = match self.cause {
+            Cause::Panic => "Crashed: panic",
+            Cause::Watchdog => "Crashed: watchdog reset",
+            Cause::Display => "Crashed: display failed",
+        };
+        lines.write(title)?;
+        for line in self.message.as_str().lines() {
+            for part in wrap(line, columns) {
+                lines.write(part)?;
+            }
+        }
+
+        let mut addresses = TextBuffer::<64>::new();
+        // "0x" and 8 digits, a space between them
+        let per_line = ((columns + 1) / 11).max(1);
+        for chunk in self.backtrace().chunks(per_line) {
+            addresses.clear();
+            for pc in chunk {
+                let _ = write!(addresses, "0x{:08x} ", pc);
+            }
+            lines.write(addresses.as_str().trim_end())?;
+        }
+
+        lines.line = last_line;
+        lines.last_line = last_line;
+        lines.write(hint)
+    }
+
+    fn cause_name(&self) -> &'static str {
+        match self.cause {
+            Cause::Panic => "panic",
+            Cause::Watchdog => "watchdog",
+            Cause::Display => "display",
+        }
+    }
+}
+
+/// Text lines from the top of the screen
+struct Lines<'a, D> {
+    display: &'a mut D,
+    line: i32,
+    last_line: i32,
+}
+
+impl<D: DisplayTrait> Lines<'_, D> {
+    fn write(&mut self, text: &str) -> Result<(), D::Error> {
+        if self.line > self.last_line {
+            return Ok(());
+        }
+        let position = Point::new(MARGIN, MARGIN + self.line * CHAR_HEIGHT);
+        self.line += 1;
+        self.display.write(text, position)
+    }
+}
+
+/// `line` cut into parts of at most `columns` characters
+fn wrap(line: &str, columns: usize) -> impl Iterator<Item = &str> {
+    let mut rest = line;
+    core::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        let end = rest
+            .char_indices()
+            .nth(columns)
+            .map_or(rest.len(), |(i, _)| i);
+        let (part, next) = rest.split_at(end);
+        rest = next;
+        Some(part)
+    })
+}
@@ -0,0 +1,222 @@
+//! Dirty region optimizer
+//!
+//! Merges horizontal runs of dirty tiles into larger rectangles, across
+//! clean gaps in a tile row and down into the next tile row. A merge is
+//! taken when the bytes of clean pixels it adds cost less than the fixed
+//! overhead of the transfer it saves (address window commands, chip
+//! select, DMA setup).
+
+use alloc::vec::Vec;
+#[cfg(feature = "delta-transfer")]
+use embedded_graphics::pixelcolor::Rgb565;
+
+use crate::framebuffer::DirtyRect;
+
+/// Bytes per RGB565 pixel on the wire
+pub const BYTES_PER_PIXEL: usize = 2;
+
+/// Reusable state of the optimizer, avoids allocations per frame
+pub struct RegionOptimizer {
+    /// Cost of one transfer, in bytes of pixel data
+    transfer_overhead: usize,
+    /// Runs of the tile row being processed
+    runs: Vec<DirtyRect>,
+    /// Rectangles reaching down to the previous tile row
+    open: Vec<DirtyRect>,
+    /// Rectangles reaching down to the current tile row
+    next_open: Vec<DirtyRect>,
+    /// Result of the last [`RegionOptimizer::optimize`]
+    regions: Vec<DirtyRect>,
+}
+
+impl RegionOptimizer {
+    pub fn new(transfer_overhead: usize) -> Self {
+        Self {
+            transfer_overhead,
+            runs: Vec::new(),
+            open: Vec::new(),
+            next_open: Vec::new(),
+            regions: Vec::new(),
+        }
+    }
+
+    /// Optimized regions of the last call to [`RegionOptimizer::optimize`]
+    pub fn regions(&self) -> &[DirtyRect] {
+        &self.regions
+    }
+
+    /// Merges `batches` into a small set of disjoint rectangles
+    ///
+    /// `batches` are horizontal runs of dirty tiles, one tile row high and
+    /// ordered by row and then column.
+    pub fn optimize(&mut self, batches: impl Iterator<Item = DirtyRect>) {
+        self.regions.clear();
+        self.open.clear();
+        self.runs.clear();
+
+        for batch in batches {
+            if self
+                .runs
+                .last()
+                .is_some_and(|run| run.y_start != batch.y_start)
+            {
+                self.merge_row();
+            }
+
+            // Bridge clean gaps within the row when that is cheaper than a new transfer
+            let overhead = self.transfer_overhead;
+            match self.runs.last_mut() {
+                Some(run) if is_worth_merging(overhead, run, &batch, &union(run, &batch)) => {
+                    *run = union(run, &batch);
+                }
+                _ => self.runs.push(batch),
+            }
+        }
+
+        if !self.runs.is_empty() {
+            self.merge_row();
+        }
+        self.regions.append(&mut self.open);
+    }
+
+    /// Splits `rect` into the parts where `frame` differs from `sent`
+    ///
+    /// Unchanged rows are skipped and each row is trimmed to its changed
+    /// columns. Consecutive rows are merged again as long as the unchanged
+    /// pixels in between cost less than a transfer, so splitting never costs
+    /// more than it saves. Both buffers have `width` pixels per line.
+    #[cfg(feature = "delta-transfer")]
+    pub fn split_changed(
+        &self,
+        rect: &DirtyRect,
+        frame: &[Rgb565],
+        sent: &[Rgb565],
+        width: usize,
+        out: &mut Vec<DirtyRect>,
+    ) {
+        let mut part: Option<DirtyRect> = None;
+
+        for (y, (new, old)) in
+            (rect.y_start..).zip(rect.rows(frame, width).zip(rect.rows(sent, width)))
+        {
+            let mut changed = new.iter().zip(old).map(|(a, b)| a != b);
+            let Some(first) = changed.position(|c| c) else {
+                continue;
+            };
+            let last = new
+                .iter()
+                .zip(old)
+                .rposition(|(a, b)| a != b)
+                .unwrap_or(first);
+
+            let span = DirtyRect {
+                x_start: rect.x_start + first as u16,
+                y_start: y,
+                x_end: rect.x_start + last as u16,
+                y_end: y,
+            };
+
+            part = match part {
+                Some(current) => {
+                    let merged = union(&current, &span);
+                    if is_worth_merging(self.transfer_overhead, &current, &span, &merged) {
+                        Some(merged)
+                    } else {
+                        out.push(current);
+                        Some(span)
+                    }
+                }
+                None => Some(span),
+            };
+        }
+
+        out.extend(part);
+    }
+
+    /// Extends open rectangles down into the runs of the current row
+    fn merge_row(&mut self) {
+        self.next_open.clear();
+
+        for i in 0..self.runs.len() {
+            let run = self.runs[i];
+
+            // Cheapest open rectangle that can grow down to this run
+            let mut best: Option<(usize, usize, DirtyRect)> = None;
+            for (j, rect) in self.open.iter().enumerate() {
+                if rect.y_end + 1 != run.y_start {
+                    continue;
+                }
+
+                let merged = union(rect, &run);
+                if !is_worth_merging(self.transfer_overhead, rect, &run, &merged)
+                    || self.overlaps_others(&merged, j, i)
+                {
+                    continue;
+                }
+
+                let waste = waste(rect, &run, &merged);
+                if best.is_none_or(|(_, best_waste, _)| waste < best_waste) {
+                    best = Some((j, waste, merged));
+                }
+            }
+
+            match best {
+                Some((j, _, merged)) => {
+                    self.open.swap_remove(j);
+                    self.next_open.push(merged);
+                }
+                None => self.next_open.push(run),
+            }
+        }
+
+        // Rectangles that were not extended are finished
+        self.regions.append(&mut self.open);
+        core::mem::swap(&mut self.open, &mut self.next_open);
+        self.runs.clear();
+    }
+
+    /// Whether `merged` would cover any rectangle other than the ones it is made of
+    fn overlaps_others(&self, merged: &DirtyRect, open_idx: usize, run_idx: usize) -> bool {
+        let open = self
+            .open
+            .iter()
+            .enumerate()
+            .filter(|&(j, _)| j != open_idx)
+            .map(|(_, rect)| rect);
+        let runs = self
+            .runs
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| i != run_idx)
+            .map(|(_, rect)| rect);
+
+        open.chain(runs)
+            .chain(self.next_open.iter())
+            .chain(self.regions.iter())
+            .any(|rect| intersects(merged, rect))
+    }
+}
+
+/// Whether the clean pixels `merged` adds cost less than the transfer it saves
+fn is_worth_merging(overhead: usize, a: &DirtyRect, b: &DirtyRect, merged: &DirtyRect) -> bool {
+    waste(a, b, merged) * BYTES_PER_PIXEL < overhead
+}
+
+/// Bounding box of two rectangles
+fn union(a: &DirtyRect, b: &DirtyRect) -> DirtyRect {
+    DirtyRect {
+        x_start: a.x_start.min(b.x_start),
+        y_start: a.y_start.min(b.y_start),
+        x_end: a.x_end.max(b.x_end),
+        y_end: a.y_end.max(b.y_end),
+    }
+}
+
+fn intersects(a: &DirtyRect, b: &DirtyRect) -> bool {
+    a.x_start <= b.x_end && b.x_start <= a.x_end && a.y_start <= b.y_end && b.y_start <= a.y_end
+}
+
+/// Pixels `merged` sends that neither of the disjoint rectangles `a` and `b` would
+fn waste(a: &DirtyRect, b: &DirtyRect, merged: &DirtyRect) -> usize {
+    merged.pixel_count() - a.pixel_count() - b.pixel_count()
+}
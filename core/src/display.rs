@@ -0,0 +1,205 @@
+//! Drawing interface of the display, implemented by the firmware's panel
+//! driver and by [`crate::mock::MockDisplay`] on the host
+
+use core::fmt::{self, Debug};
+use embedded_graphics::geometry::{Point, Size};
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::primitives::Rectangle;
+
+use crate::panel::{ColorLut, PanelCommand};
+use crate::recovery::Severity;
+
+/// Display interface trait for MIPI DCS panel controllers
+///
+/// Provides basic drawing operations for text and primitives.
+/// Implementations should handle the low-level display communication.
+#[allow(async_fn_in_trait)]
+pub trait DisplayTrait {
+    /// Error type
+    type Error: Debug;
+
+    /// Writes text to the display at the specified position
+    ///
+    /// # Arguments
+    /// * `text` - The text string to display
+    /// * `position` - Starting position coordinates as Point(x,y)
+    ///
+    /// # Returns
+    /// * `Ok(())` on successful write
+    /// * `Err(Error)` if the write operation fails
+    fn write(&mut self, text: &str, position: Point) -> Result<(), Self::Error>;
+
+    /// Updates the display with the current framebuffer contents
+    ///
+    /// Implementations may return before the transfer is finished, in that
+    /// case the next call waits for it before swapping buffers.
+    ///
+    /// # Returns
+    /// * `Ok(())` on successful update
+    /// * `Err(Error)` if the update operation fails
+    async fn update_with_buffer(&mut self) -> Result<(), Self::Error>;
+
+    /// Draws a line between two points
+    ///
+    /// # Arguments
+    /// * `begin` - Starting point coordinates as Point(x,y)  
+    /// * `end` - Ending point coordinates as Point(x,y)
+    ///
+    /// # Returns
+    /// * `Ok(())` on successful line draw
+    /// * `Err(Error)` if the draw operation fails
+    fn draw_line(&mut self, begin: Point, end: Point) -> Result<(), Self::Error>;
+
+    /// Fills a rectangle with a solid color
+    ///
+    /// # Arguments
+    /// * `area` - Rectangle to fill, clipped to the display
+    /// * `color` - Fill color
+    ///
+    /// # Returns
+    /// * `Ok(())` on successful fill
+    /// * `Err(Error)` if the draw operation fails
+    fn fill_rect(&mut self, area: Rectangle, color: Rgb565) -> Result<(), Self::Error>;
+
+    /// Returns the display resolution in pixels
+    fn size(&self) -> Size;
+
+    /// Writes text that is the same as in the last frame, at the same
+    /// position and in the same color
+    ///
+    /// Unlike [`DisplayTrait::write`] it does not mark its tiles dirty, so
+    /// nothing is sent for it.
+    ///
+    /// # Arguments
+    /// * `text` - The text string to display
+    /// * `position` - Starting position coordinates as Point(x,y)
+    fn write_unchanged(&mut self, text: &str, position: Point);
+
+    /// Clears what is left of unchanged text that moved away
+    ///
+    /// # Arguments
+    /// * `area` - Rectangle to clear in this and the next frame
+    fn erase(&mut self, area: Rectangle);
+
+    /// Sets the color of the text and lines drawn from now on
+    fn set_ink(&mut self, color: Rgb565);
+
+    /// Returns the color of the text and lines
+    fn ink(&self) -> Rgb565;
+
+    /// Sets the panel brightness
+    ///
+    /// Only panels without a separate backlight, like the RM67162 AMOLED,
+    /// react to it.
+    ///
+    /// # Arguments
+    /// * `level` - `0` is off and `255` full brightness
+    ///
+    /// # Returns
+    /// * `Ok(())` once the panel has the command
+    /// * `Err(Error)` if sending the command fails
+    async fn set_brightness(&mut self, level: u8) -> Result<(), Self::Error>;
+
+    /// Turns the panel off and puts it to sleep, it keeps the last frame
+    async fn sleep(&mut self) -> Result<(), Self::Error>;
+
+    /// Wakes the panel from [`DisplayTrait::sleep`] and turns it back on
+    async fn wake(&mut self) -> Result<(), Self::Error>;
+
+    /// Inverts the colors shown by the panel
+    ///
+    /// # Arguments
+    /// * `inverted` - `true` inverts the colors, `false` shows them normally
+    ///
+    /// # Returns
+    /// * `Ok(())` once the panel has the command
+    /// * `Err(Error)` if sending the command fails
+    async fn set_inversion(&mut self, inverted: bool) -> Result<(), Self::Error>;
+
+    /// Corrects the colors of every pixel sent to the panel, from the next
+    /// frame on, which is sent whole
+    ///
+    /// # Arguments
+    /// * `lut` - Tables for the three channels, [`ColorLut::IDENTITY`] turns the correction off
+    fn set_color_correction(&mut self, lut: ColorLut);
+
+    /// Sends the next frame whole, after an error left the panel behind
+    fn invalidate(&mut self);
+
+    /// Resets the panel and sends its init sequence, for when it stopped
+    /// answering or shows garbage
+    ///
+    /// The panel comes back with its initial settings, like full brightness,
+    /// and the next frame is sent whole.
+    ///
+    /// # Returns
+    /// * `Ok(())` once the panel is initialized
+    /// * `Err(Error)` if it did not take the init sequence
+    async fn reinit(&mut self) -> Result<(), Self::Error>;
+}
+
+/// A display error and what the driver was doing when it happened, `E` is
+/// the error of the panel interface
+#[derive(Debug)]
+pub struct DisplayError<E> {
+    pub operation: Operation,
+    pub cause: Cause<E>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operation {
+    /// Setting up the bus and its DMA buffers
+    Setup,
+    /// Resetting the panel and sending its init sequence
+    Init,
+    /// Sending a frame
+    Flush,
+    Command(PanelCommand),
+}
+
+#[derive(Debug)]
+pub enum Cause<E> {
+    /// A step of the setup, it cannot be retried
+    Setup(&'static str),
+    /// The panel did not take its init sequence
+    Init,
+    /// A transfer of the interface failed
+    Interface(E),
+    /// A failed re-init took the interface with it
+    NoPanel,
+}
+
+impl<E> DisplayError<E> {
+    pub fn new(operation: Operation, cause: impl Into<Cause<E>>) -> Self {
+        Self {
+            operation,
+            cause: cause.into(),
+        }
+    }
+
+    pub fn setup(step: &'static str) -> Self {
+        Self::new(Operation::Setup, Cause::Setup(step))
+    }
+
+    /// What it takes to recover, see [`crate::recovery`]
+    pub fn severity(&self) -> Severity {
+        match (&self.cause, self.operation) {
+            (Cause::Setup(_) | Cause::Init | Cause::NoPanel, _) => Severity::Fatal,
+            // A frame is sent whole again, a command may have left the panel half set up
+            (_, Operation::Flush) => Severity::Transient,
+            _ => Severity::Persistent,
+        }
+    }
+}
+
+impl<E: Debug> fmt::Display for DisplayError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?} failed: {:?}", self.operation, self.cause)
+    }
+}
+
+impl<E> From<E> for Cause<E> {
+    fn from(error: E) -> Self {
+        Cause::Interface(error)
+    }
+}
@@ -0,0 +1,71 @@
+//! Parts of rasterizing on the second core that do not touch the hardware
+//!
+//! The firmware hands every frame to the second core through a [`Handoff`],
+//! as a [`crate::framebuffer::RasterJob`], and draws the next one meanwhile.
+
+use core::cell::UnsafeCell;
+use core::mem::MaybeUninit;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// Single-producer single-consumer queue between the two cores
+///
+/// `head` is only written by the producer, `tail` only by the consumer. A
+/// slot is owned by the producer until `head` moves past it and by the
+/// consumer until `tail` does.
+pub struct Handoff<T, const N: usize> {
+    slots: [UnsafeCell<MaybeUninit<T>>; N],
+    /// Number of values pushed
+    head: AtomicUsize,
+    /// Number of values popped
+    tail: AtomicUsize,
+}
+
+// SAFETY: values are moved between threads, access to the slots is ordered by head and tail
+unsafe impl<T: Send, const N: usize> Sync for Handoff<T, N> {}
+
+impl<T, const N: usize> Handoff<T, N> {
+    pub const fn new() -> Self {
+        Self {
+            slots: [const { UnsafeCell::new(MaybeUninit::uninit()) }; N],
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    /// Appends `value`, gives it back if the queue is full
+    ///
+    /// Must only be called from one core.
+    pub fn push(&self, value: T) -> Result<(), T> {
+        let head = self.head.load(Ordering::Relaxed);
+        if head.wrapping_sub(self.tail.load(Ordering::Acquire)) == N {
+            return Err(value);
+        }
+
+        // SAFETY: the slot is not visible to the consumer until head is published
+        unsafe { (*self.slots[head % N].get()).write(value) };
+        self.head.store(head.wrapping_add(1), Ordering::Release);
+        Ok(())
+    }
+
+    /// Removes the oldest value
+    ///
+    /// Must only be called from one core.
+    pub fn pop(&self) -> Option<T> {
+        let tail = self.tail.load(Ordering::Relaxed);
+        if tail == self.head.load(Ordering::Acquire) {
+            return None;
+        }
+
+        // SAFETY: the slot was initialized before head was published and is
+        // not reused by the producer until tail is published
+        let value = unsafe { (*self.slots[tail % N].get()).assume_init_read() };
+        self.tail.store(tail.wrapping_add(1), Ordering::Release);
+        Some(value)
+    }
+}
+
+impl<T, const N: usize> Default for Handoff<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
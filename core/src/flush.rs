@@ -0,0 +1,123 @@
+//! Asynchronous flush of dirty regions
+//!
+//! A finished frame is moved into a [`FlushJob`] and sent to the panel by a
+//! separate task while the next frame is rendered. The job, including the
+//! buffer, comes back once the transfer is done and acts as the fence before
+//! the next buffer swap.
+
+use alloc::vec::Vec;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::IntoStorage;
+use embedded_hal::digital::OutputPin;
+use embedded_hal_async::spi::SpiDevice;
+use mipidsi::interface::SpiError;
+
+use crate::framebuffer::DirtyRect;
+use crate::panel::{ColorLut, PanelCommand};
+
+/// DCS "Column Address Set", "Page Address Set" and "Memory Write"
+const CASET: u8 = 0x2A;
+const RASET: u8 = 0x2B;
+const RAMWR: u8 = 0x2C;
+
+/// A finished frame and the regions of it that have to be sent
+pub struct FlushJob<E> {
+    pub buffer: Vec<Rgb565>,
+    /// Pixels per line of `buffer`
+    pub width: usize,
+    pub rects: Vec<DirtyRect>,
+    /// Sent before the regions
+    pub commands: Vec<PanelCommand>,
+    /// Color correction of the pixels, `None` sends them unchanged
+    pub lut: Option<ColorLut>,
+    /// Reset the panel and send its init sequence instead
+    pub reinit: bool,
+    /// Outcome of the transfer, set by the flush task
+    pub result: Result<(), E>,
+}
+
+/// Sends the commands and then the dirty regions of `job` to the panel
+///
+/// Pixels are converted to big-endian RGB565 in `staging` and written in
+/// chunks of its size. `offset` is added to every address window.
+pub async fn flush<SPI, DC, E>(
+    spi: &mut SPI,
+    dc: &mut DC,
+    staging: &mut [u8],
+    job: &FlushJob<E>,
+    offset: (u16, u16),
+) -> Result<(), SpiError<SPI::Error, DC::Error>>
+where
+    SPI: SpiDevice,
+    DC: OutputPin,
+{
+    for command in &job.commands {
+        let (instruction, args) = command.dcs();
+        send_command(spi, dc, instruction, args).await?;
+    }
+
+    // Only whole pixels per chunk
+    let chunk_len = staging.len() & !1;
+
+    for rect in &job.rects {
+        let x_start = rect.x_start + offset.0;
+        let x_end = rect.x_end + offset.0;
+        let y_start = rect.y_start + offset.1;
+        let y_end = rect.y_end + offset.1;
+
+        send_command(spi, dc, CASET, &window(x_start, x_end)).await?;
+        send_command(spi, dc, RASET, &window(y_start, y_end)).await?;
+        send_command(spi, dc, RAMWR, &[]).await?;
+
+        let mut len = 0;
+        for row in rect.rows(&job.buffer, job.width) {
+            for &color in row {
+                let color = job.lut.as_ref().map_or(color, |lut| lut.apply(color));
+                staging[len..len + 2].copy_from_slice(&color.into_storage().to_be_bytes());
+                len += 2;
+
+                if len == chunk_len {
+                    spi.write(&staging[..len]).await.map_err(SpiError::Spi)?;
+                    len = 0;
+                }
+            }
+        }
+
+        if len > 0 {
+            spi.write(&staging[..len]).await.map_err(SpiError::Spi)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Start and end address as DCS parameters
+fn window(start: u16, end: u16) -> [u8; 4] {
+    let [start_hi, start_lo] = start.to_be_bytes();
+    let [end_hi, end_lo] = end.to_be_bytes();
+    [start_hi, start_lo, end_hi, end_lo]
+}
+
+/// Sends a command with DC low, then its parameters with DC high
+///
+/// DC is left high so pixel data can follow a memory write command.
+async fn send_command<SPI, DC>(
+    spi: &mut SPI,
+    dc: &mut DC,
+    command: u8,
+    args: &[u8],
+) -> Result<(), SpiError<SPI::Error, DC::Error>>
+where
+    SPI: SpiDevice,
+    DC: OutputPin,
+{
+    dc.set_low().map_err(SpiError::Dc)?;
+    spi.write(&[command]).await.map_err(SpiError::Spi)?;
+    dc.set_high().map_err(SpiError::Dc)?;
+
+    if !args.is_empty() {
+        spi.write(args).await.map_err(SpiError::Spi)?;
+    }
+
+    Ok(())
+}
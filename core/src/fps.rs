@@ -0,0 +1,141 @@
+//! FPS counter
+//!
+//! The frame time is smoothed with an exponential moving average, so the
+//! counter only changes when the frame rate does. The time of every frame
+//! comes from the caller, in milliseconds. While the text stays the same, in
+//! the same place and color, its tiles are not marked dirty and nothing is
+//! sent for it.
+
+use core::fmt::Write;
+use embedded_graphics::pixelcolor::{Rgb565, RgbColor};
+use embedded_graphics::prelude::{Point, Size};
+use embedded_graphics::primitives::Rectangle;
+
+use crate::display::DisplayTrait;
+use crate::text::TextBuffer;
+
+/// Weight of the newest frame in the moving average
+const SMOOTHING: f32 = 0.1;
+
+/// Number of frame times in the graph
+const GRAPH_LEN: usize = 32;
+/// Pixels between the bars of the graph (2-pixel stroke)
+const GRAPH_SPACING: i32 = 3;
+/// Height of the graph, one pixel per millisecond
+const GRAPH_HEIGHT: u32 = 32;
+/// Line height of the 10x20 font
+const TEXT_HEIGHT: i32 = 20;
+/// Width of the counter text, 8 characters of the 10x20 font
+const TEXT_WIDTH: u32 = 80;
+
+pub struct FpsMeter {
+    /// Time of the last frame in milliseconds
+    last_frame: Option<u64>,
+    /// Smoothed frame time in milliseconds
+    frame_time: f32,
+    /// Frame times of the last frames in milliseconds, a ring buffer
+    history: [u32; GRAPH_LEN],
+    next: usize,
+    text: TextBuffer<16>,
+    /// Text drawn in the last frame
+    shown: TextBuffer<16>,
+    /// Position and color of the text drawn in the last frame
+    shown_at: Option<(Point, Rgb565)>,
+}
+
+impl FpsMeter {
+    pub const fn new() -> Self {
+        Self {
+            last_frame: None,
+            frame_time: 0.0,
+            history: [0; GRAPH_LEN],
+            next: 0,
+            text: TextBuffer::new(),
+            shown: TextBuffer::new(),
+            shown_at: None,
+        }
+    }
+
+    /// Records the time since the last call, call once per frame with the
+    /// time of the frame in milliseconds
+    pub fn tick(&mut self, now_ms: u64) {
+        if let Some(last_frame) = self.last_frame.replace(now_ms) {
+            let millis = now_ms.saturating_sub(last_frame);
+            self.frame_time = if self.frame_time == 0.0 {
+                millis as f32
+            } else {
+                self.frame_time + (millis as f32 - self.frame_time) * SMOOTHING
+            };
+
+            self.history[self.next] = millis.min(u32::MAX as u64) as u32;
+            self.next = (self.next + 1) % GRAPH_LEN;
+        }
+    }
+
+    /// Smoothed frames per second, 0 until two frames have been recorded
+    pub fn fps(&self) -> u32 {
+        if self.frame_time > 0.0 {
+            (1000.0 / self.frame_time + 0.5) as u32
+        } else {
+            0
+        }
+    }
+
+    /// Area covered by the counter and, with `graph`, the frame times
+    pub fn size(graph: bool) -> Size {
+        if graph {
+            Size::new(
+                TEXT_WIDTH.max(GRAPH_LEN as u32 * GRAPH_SPACING as u32),
+                TEXT_HEIGHT as u32 + GRAPH_HEIGHT,
+            )
+        } else {
+            Size::new(TEXT_WIDTH, TEXT_HEIGHT as u32)
+        }
+    }
+
+    /// Draws the counter at `position` and, with `graph`, the frame times below it
+    pub fn draw<D: DisplayTrait>(
+        &mut self,
+        display: &mut D,
+        position: Point,
+        graph: bool,
+    ) -> Result<(), D::Error> {
+        self.text.clear();
+        let _ = write!(self.text, "FPS: {:>3}", self.fps());
+
+        let at = (position, display.ink());
+        if self.text == self.shown && self.shown_at == Some(at) {
+            display.write_unchanged(self.text.as_str(), position);
+        } else {
+            if let Some((shown_position, _)) = self.shown_at.filter(|&(p, _)| p != position) {
+                display.erase(Rectangle::new(shown_position, Self::size(false)));
+            }
+            // The text has no background, the old glyphs are still in the back buffer
+            display.fill_rect(Rectangle::new(position, Self::size(false)), Rgb565::BLACK)?;
+            display.write(self.text.as_str(), position)?;
+            self.shown = self.text.clone();
+            self.shown_at = Some(at);
+        }
+
+        if graph {
+            let bottom = position.y + TEXT_HEIGHT + GRAPH_HEIGHT as i32;
+            // Oldest frame on the left
+            for i in 0..GRAPH_LEN {
+                let millis = self.history[(self.next + i) % GRAPH_LEN].min(GRAPH_HEIGHT);
+                if millis > 0 {
+                    let x = position.x + i as i32 * GRAPH_SPACING;
+                    display
+                        .draw_line(Point::new(x, bottom - millis as i32), Point::new(x, bottom))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for FpsMeter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
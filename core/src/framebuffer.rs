@@ -0,0 +1,906 @@
+use alloc::string::String;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::Infallible;
+use embedded_graphics::draw_target::DrawTarget;
+use embedded_graphics::geometry::{OriginDimensions, Point, Size};
+use embedded_graphics::mono_font::iso_8859_1::FONT_10X20 as FONT;
+use embedded_graphics::mono_font::MonoTextStyle;
+use embedded_graphics::pixelcolor::{Rgb565, RgbColor};
+use embedded_graphics::prelude::Primitive;
+use embedded_graphics::primitives::{Line, PrimitiveStyle, PrimitiveStyleBuilder, Rectangle};
+use embedded_graphics::text::{Baseline, Text};
+use embedded_graphics::{Drawable, Pixel};
+
+use crate::dirty_region::RegionOptimizer;
+
+/// Stroke width of lines
+const LINE_WIDTH: u32 = 2;
+
+/// Resolution and tile size of a framebuffer
+///
+/// All tile and bounds calculations of the renderer derive from this, so the
+/// same code can drive panels of any size (536x240, 240x240, 320x170, ...).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Geometry {
+    pub width: u16,
+    pub height: u16,
+    pub tile_size: u16,
+}
+
+impl Geometry {
+    /// # Panics
+    ///
+    /// If the width, the height or the tile size is 0. In a const, that fails
+    /// the build instead.
+    pub const fn new(width: u16, height: u16, tile_size: u16) -> Self {
+        let geometry = Self {
+            width,
+            height,
+            tile_size,
+        };
+        geometry.validate();
+        geometry
+    }
+
+    /// The tile math divides by the tile size and clips to `width - 1` and
+    /// `height - 1`, none of them may be 0
+    const fn validate(&self) {
+        assert!(self.width > 0 && self.height > 0, "geometry without pixels");
+        assert!(self.tile_size > 0, "geometry with a tile size of 0");
+    }
+
+    /// Number of tile columns (last column may be partial)
+    pub const fn tiles_x(&self) -> usize {
+        self.width.div_ceil(self.tile_size) as usize
+    }
+
+    /// Number of tile rows (last row may be partial)
+    pub const fn tiles_y(&self) -> usize {
+        self.height.div_ceil(self.tile_size) as usize
+    }
+
+    pub const fn total_tiles(&self) -> usize {
+        self.tiles_x() * self.tiles_y()
+    }
+
+    pub const fn pixel_count(&self) -> usize {
+        self.width as usize * self.height as usize
+    }
+
+    /// Pixel bounds of the tiles `tile_x1..=tile_x2`, `tile_y1..=tile_y2`, clipped to the screen
+    pub fn tile_rect(
+        &self,
+        tile_x1: usize,
+        tile_y1: usize,
+        tile_x2: usize,
+        tile_y2: usize,
+    ) -> DirtyRect {
+        let tile_size = self.tile_size as usize;
+        DirtyRect {
+            x_start: (tile_x1 * tile_size) as u16,
+            y_start: (tile_y1 * tile_size) as u16,
+            x_end: (((tile_x2 + 1) * tile_size).min(self.width as usize) - 1) as u16,
+            y_end: (((tile_y2 + 1) * tile_size).min(self.height as usize) - 1) as u16,
+        }
+    }
+}
+
+/// Screen region to transfer, bounds are inclusive
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DirtyRect {
+    pub x_start: u16,
+    pub y_start: u16,
+    pub x_end: u16,
+    pub y_end: u16,
+}
+
+impl DirtyRect {
+    pub const fn width(&self) -> usize {
+        (self.x_end - self.x_start + 1) as usize
+    }
+
+    pub const fn height(&self) -> usize {
+        (self.y_end - self.y_start + 1) as usize
+    }
+
+    pub const fn pixel_count(&self) -> usize {
+        self.width() * self.height()
+    }
+
+    /// Rows of `buffer` (with `width` pixels per line) covered by this rect
+    pub fn rows<'a>(
+        &self,
+        buffer: &'a [Rgb565],
+        width: usize,
+    ) -> impl Iterator<Item = &'a [Rgb565]> + 'a {
+        let x_start = self.x_start as usize;
+        let rect_width = self.width();
+
+        (self.y_start..=self.y_end).map(move |y| {
+            let row_start = (y as usize) * width + x_start;
+            &buffer[row_start..row_start + rect_width]
+        })
+    }
+}
+
+#[derive(Clone)]
+struct TileTracker {
+    dirty: Vec<bool>,
+}
+
+impl TileTracker {
+    fn new(geometry: &Geometry) -> Self {
+        Self {
+            dirty: vec![false; geometry.total_tiles()],
+        }
+    }
+
+    fn mark_rect(&mut self, geometry: &Geometry, x1: u16, y1: u16, x2: u16, y2: u16) {
+        let min_x = x1.min(x2).min(geometry.width - 1);
+        let max_x = x1.max(x2).min(geometry.width - 1);
+        let min_y = y1.min(y2).min(geometry.height - 1);
+        let max_y = y1.max(y2).min(geometry.height - 1);
+
+        let tile_x1 = (min_x / geometry.tile_size) as usize;
+        let tile_x2 = (max_x / geometry.tile_size) as usize;
+        let tile_y1 = (min_y / geometry.tile_size) as usize;
+        let tile_y2 = (max_y / geometry.tile_size) as usize;
+
+        let tiles_x = geometry.tiles_x();
+        for ty in tile_y1..=tile_y2 {
+            for tx in tile_x1..=tile_x2 {
+                let tile_idx = ty * tiles_x + tx;
+                if tile_idx < self.dirty.len() {
+                    self.dirty[tile_idx] = true;
+                }
+            }
+        }
+    }
+
+    fn clear(&mut self) {
+        self.dirty.fill(false);
+    }
+
+    fn is_dirty(&self, tile_idx: usize) -> bool {
+        tile_idx < self.dirty.len() && self.dirty[tile_idx]
+    }
+}
+
+struct BufferDrawTarget<'a> {
+    buffer: &'a mut [Rgb565],
+    width: usize,
+    height: usize,
+}
+
+impl<'a> DrawTarget for BufferDrawTarget<'a> {
+    type Color = Rgb565;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
+    where
+        I: IntoIterator<Item = Pixel<Self::Color>>,
+    {
+        for Pixel(coord, color) in pixels {
+            if coord.x >= 0
+                && coord.x < self.width as i32
+                && coord.y >= 0
+                && coord.y < self.height as i32
+            {
+                let index = (coord.y as usize) * self.width + coord.x as usize;
+                if index < self.buffer.len() {
+                    self.buffer[index] = color;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a> OriginDimensions for BufferDrawTarget<'a> {
+    fn size(&self) -> Size {
+        Size::new(self.width as u32, self.height as u32)
+    }
+}
+
+/// A primitive recorded for rasterizing at the end of the frame
+#[derive(Clone, Copy)]
+enum DrawCommand {
+    Line {
+        start: Point,
+        end: Point,
+        color: Rgb565,
+    },
+    Point {
+        position: Point,
+        color: Rgb565,
+    },
+    Fill {
+        area: Rectangle,
+        color: Rgb565,
+    },
+    /// `text` is a range of the recorded text
+    Text {
+        position: Point,
+        text: (usize, usize),
+        color: Rgb565,
+    },
+}
+
+/// Writes `commands` to `buffer`, the pixels of a `width` wide screen
+fn draw(
+    buffer: &mut [Rgb565],
+    width: usize,
+    commands: &[DrawCommand],
+    text: &str,
+) -> Result<(), Infallible> {
+    let mut target = BufferDrawTarget {
+        height: buffer.len() / width,
+        buffer,
+        width,
+    };
+
+    for command in commands {
+        match *command {
+            DrawCommand::Line { start, end, color } => {
+                Line::new(start, end)
+                    .into_styled(PrimitiveStyle::with_stroke(color, LINE_WIDTH))
+                    .draw(&mut target)?;
+            }
+            DrawCommand::Point { position, color } => {
+                let style = PrimitiveStyleBuilder::new().fill_color(color).build();
+                Rectangle::new(position - Point::new(1, 1), Size::new(3, 3))
+                    .into_styled(style)
+                    .draw(&mut target)?;
+            }
+            DrawCommand::Fill { area, color } => {
+                area.into_styled(PrimitiveStyle::with_fill(color))
+                    .draw(&mut target)?;
+            }
+            DrawCommand::Text {
+                position,
+                text: (start, end),
+                color,
+            } => {
+                Text::with_baseline(
+                    &text[start..end],
+                    position,
+                    MonoTextStyle::new(&FONT, color),
+                    Baseline::Top,
+                )
+                .draw(&mut target)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A recorded frame and the back buffer it is drawn into, rasterized on the
+/// second core while the next frame is recorded
+///
+/// Owns both, so it can move between the cores. See
+/// [`FrameBuffer::take_raster_job`].
+#[cfg(feature = "dual-core")]
+pub struct RasterJob {
+    buffer: Vec<Rgb565>,
+    width: usize,
+    commands: Vec<DrawCommand>,
+    text: String,
+}
+
+#[cfg(feature = "dual-core")]
+impl RasterJob {
+    /// Writes the recorded primitives to the buffer
+    pub fn rasterize(&mut self) -> Result<(), Infallible> {
+        draw(&mut self.buffer, self.width, &self.commands, &self.text)
+    }
+}
+
+/// Double-buffered RGB565 framebuffer with dirty tile tracking
+///
+/// Drawing is recorded and marks the touched tiles dirty right away, the
+/// pixels are written to the back buffer when the frame is rasterized, in
+/// place or by another core with [`FrameBuffer::take_raster_job`]. After
+/// [`FrameBuffer::swap_buffers`] the front buffer holds the finished frame and
+/// [`FrameBuffer::update_dirty_regions`] computes the regions that have to be
+/// sent to the panel.
+pub struct FrameBuffer {
+    geometry: Geometry,
+    front_buffer: Vec<Rgb565>,
+    back_buffer: Vec<Rgb565>,
+    current_tiles: TileTracker, // Tiles drawn this frame
+    prev_tiles: TileTracker,    // Tiles drawn in the previous frame
+    /// Tiles drawn 2 frames ago, what the back buffer still shows of them
+    /// is cleared at the start of the next frame
+    older_tiles: TileTracker,
+    /// Tiles marked dirty again in the next frame, see [`FrameBuffer::erase`]
+    erased_tiles: TileTracker,
+    regions: RegionOptimizer,
+    /// Primitives drawn since the last rasterization
+    commands: Vec<DrawCommand>,
+    /// Text of the recorded text commands
+    text: String,
+    /// Pixels that cleared tiles are restored from instead of black
+    background: Option<Vec<Rgb565>>,
+    /// Color of text and lines
+    ink: Rgb565,
+    /// What the panel shows, only the changed parts of dirty regions are sent
+    #[cfg(feature = "delta-transfer")]
+    sent_buffer: Vec<Rgb565>,
+    /// Dirty regions trimmed to the pixels that differ from `sent_buffer`
+    #[cfg(feature = "delta-transfer")]
+    changed: Vec<DirtyRect>,
+    /// Send the next frame whole, even where it matches `sent_buffer`
+    #[cfg(feature = "delta-transfer")]
+    resend: bool,
+    /// Tiles of the frame with the rasterizer, swapped with those of the
+    /// frame drawn meanwhile when it comes back
+    #[cfg(feature = "dual-core")]
+    raster_tiles: TileTracker,
+    /// The frame drawn while the last one was rasterized is in `raster_tiles`
+    #[cfg(feature = "dual-core")]
+    next_frame_drawn: bool,
+    /// Tiles to clear once the back buffer is back from the rasterizer
+    #[cfg(feature = "dual-core")]
+    pending_clear: TileTracker,
+    /// Recording buffers of the next frame, swapped with the ones of a job
+    #[cfg(feature = "dual-core")]
+    spare_commands: Vec<DrawCommand>,
+    #[cfg(feature = "dual-core")]
+    spare_text: String,
+}
+
+impl FrameBuffer {
+    /// Creates a framebuffer, `transfer_overhead` is the cost of one transfer
+    /// in bytes of pixel data used to merge dirty regions
+    ///
+    /// # Panics
+    ///
+    /// Like [`Geometry::new`], for a geometry built from its fields
+    pub fn new(geometry: Geometry, transfer_overhead: usize) -> Self {
+        geometry.validate();
+        let buffer_size = geometry.pixel_count();
+
+        Self {
+            geometry,
+            front_buffer: vec![Rgb565::BLACK; buffer_size],
+            back_buffer: vec![Rgb565::BLACK; buffer_size],
+            current_tiles: TileTracker::new(&geometry),
+            prev_tiles: TileTracker::new(&geometry),
+            older_tiles: TileTracker::new(&geometry),
+            erased_tiles: TileTracker::new(&geometry),
+            regions: RegionOptimizer::new(transfer_overhead),
+            commands: Vec::new(),
+            text: String::new(),
+            background: None,
+            ink: Rgb565::WHITE,
+            // Third full-size buffer in PSRAM
+            #[cfg(feature = "delta-transfer")]
+            sent_buffer: vec![Rgb565::BLACK; buffer_size],
+            #[cfg(feature = "delta-transfer")]
+            changed: Vec::new(),
+            #[cfg(feature = "delta-transfer")]
+            resend: false,
+            #[cfg(feature = "dual-core")]
+            raster_tiles: TileTracker::new(&geometry),
+            #[cfg(feature = "dual-core")]
+            next_frame_drawn: false,
+            #[cfg(feature = "dual-core")]
+            pending_clear: TileTracker::new(&geometry),
+            #[cfg(feature = "dual-core")]
+            spare_commands: Vec::new(),
+            #[cfg(feature = "dual-core")]
+            spare_text: String::new(),
+        }
+    }
+
+    pub fn size(&self) -> Size {
+        Size::new(self.geometry.width as u32, self.geometry.height as u32)
+    }
+
+    /// Marks the tiles covering the given pixel rectangle as dirty
+    pub fn mark_dirty(&mut self, x1: u16, y1: u16, x2: u16, y2: u16) {
+        self.current_tiles.mark_rect(&self.geometry, x1, y1, x2, y2);
+    }
+
+    /// Sends the next frame whole, for when the panel shows something else
+    /// than the last frame sent or shows it with other colors
+    pub fn invalidate(&mut self) {
+        self.mark_dirty(0, 0, self.geometry.width - 1, self.geometry.height - 1);
+        #[cfg(feature = "delta-transfer")]
+        {
+            self.resend = true;
+        }
+    }
+
+    /// Clears what is left in `area` in both buffers and on the panel
+    ///
+    /// For text drawn with [`FrameBuffer::write_unchanged`] that moved away: its
+    /// tiles were not marked dirty for a while, so they are not cleared on
+    /// their own. They are cleared in the back buffer now, or when it is back
+    /// from the rasterizer, drawing over them still works, and marked dirty in
+    /// this and the next frame.
+    pub fn erase(&mut self, area: Rectangle) {
+        let Some(bottom_right) = area.bottom_right() else {
+            return;
+        };
+        let width = self.geometry.width as i32;
+        let height = self.geometry.height as i32;
+        if bottom_right.x < 0
+            || bottom_right.y < 0
+            || area.top_left.x >= width
+            || area.top_left.y >= height
+        {
+            return;
+        }
+
+        let x = area.top_left.x.max(0) as u16;
+        let y = area.top_left.y.max(0) as u16;
+        let x2 = bottom_right.x.min(width - 1) as u16;
+        let y2 = bottom_right.y.min(height - 1) as u16;
+
+        self.mark_dirty(x, y, x2, y2);
+        self.erased_tiles.mark_rect(&self.geometry, x, y, x2, y2);
+
+        // The back buffer was cleared before, but not these tiles
+        let tile_size = self.geometry.tile_size;
+        self.clear_back_tiles(
+            (x / tile_size) as usize,
+            (y / tile_size) as usize,
+            (x2 / tile_size) as usize,
+            (y2 / tile_size) as usize,
+        );
+    }
+
+    /// Color of the text and lines drawn from now on, white at first
+    pub fn set_ink(&mut self, color: Rgb565) {
+        self.ink = color;
+    }
+
+    pub fn ink(&self) -> Rgb565 {
+        self.ink
+    }
+
+    pub fn write(&mut self, text: &str, position: Point) {
+        let width = self.geometry.width;
+        let height = self.geometry.height;
+
+        // Estimate text bounds (10x20 font)
+        let text_width = (text.len() as u16) * 10;
+        let text_height = 20u16;
+
+        let x = position.x.max(0) as u16;
+        let y = position.y.max(0) as u16;
+        let x2 = x.saturating_add(text_width).min(width - 1);
+        let y2 = y.saturating_add(text_height).min(height - 1);
+
+        // Mark tiles dirty
+        self.mark_dirty(x, y, x2, y2);
+
+        self.write_unchanged(text, position);
+    }
+
+    /// Draws text the panel already shows at this position
+    ///
+    /// The tiles are not marked dirty, so the text is only sent again when
+    /// something else touches them.
+    pub fn write_unchanged(&mut self, text: &str, position: Point) {
+        let start = self.text.len();
+        self.text.push_str(text);
+        self.commands.push(DrawCommand::Text {
+            position,
+            text: (start, self.text.len()),
+            color: self.ink,
+        });
+    }
+
+    pub fn draw_line(&mut self, start: Point, end: Point) {
+        let width = self.geometry.width as i32;
+        let height = self.geometry.height as i32;
+
+        // Mark tiles dirty (add small padding for 2-pixel stroke), the line
+        // may run either way
+        let x1 = start.x.min(end.x).max(0).saturating_sub(2) as u16;
+        let y1 = start.y.min(end.y).max(0).saturating_sub(2) as u16;
+        let x2 = (start.x.max(end.x).max(0) + 2).min(width - 1) as u16;
+        let y2 = (start.y.max(end.y).max(0) + 2).min(height - 1) as u16;
+
+        self.mark_dirty(x1, y1, x2, y2);
+
+        self.commands.push(DrawCommand::Line {
+            start,
+            end,
+            color: self.ink,
+        });
+    }
+
+    /// Draws a small colored point (3x3 pixels) at the specified position
+    pub fn draw_colored_point(&mut self, position: Point, color: Rgb565) {
+        let width = self.geometry.width as i32;
+        let height = self.geometry.height as i32;
+
+        // Draw 3x3 rectangle
+        let x = position.x.saturating_sub(1).max(0) as u16;
+        let y = position.y.saturating_sub(1).max(0) as u16;
+        let x2 = (position.x + 1).min(width - 1) as u16;
+        let y2 = (position.y + 1).min(height - 1) as u16;
+
+        self.mark_dirty(x, y, x2, y2);
+
+        self.commands.push(DrawCommand::Point { position, color });
+    }
+
+    /// Fills a rectangle, covering what was drawn before in this frame
+    pub fn fill_rect(&mut self, area: Rectangle, color: Rgb565) {
+        let Some(bottom_right) = area.bottom_right() else {
+            return;
+        };
+        let width = self.geometry.width as i32;
+        let height = self.geometry.height as i32;
+        if bottom_right.x < 0
+            || bottom_right.y < 0
+            || area.top_left.x >= width
+            || area.top_left.y >= height
+        {
+            return;
+        }
+
+        let x = area.top_left.x.max(0) as u16;
+        let y = area.top_left.y.max(0) as u16;
+        let x2 = bottom_right.x.min(width - 1) as u16;
+        let y2 = bottom_right.y.min(height - 1) as u16;
+
+        self.mark_dirty(x, y, x2, y2);
+
+        self.commands.push(DrawCommand::Fill { area, color });
+    }
+
+    /// Rasterizes the recorded primitives into the back buffer
+    pub fn rasterize(&mut self) -> Result<(), Infallible> {
+        let width = self.geometry.width as usize;
+        draw(&mut self.back_buffer, width, &self.commands, &self.text)?;
+        self.commands.clear();
+        self.text.clear();
+        Ok(())
+    }
+
+    /// Hands the frame drawn so far to a rasterizer, the next frame is drawn
+    /// while it is rasterized
+    ///
+    /// Clears the back buffer like [`FrameBuffer::clear_buffer`] and moves it
+    /// into the job with the recorded primitives. The job has to come back
+    /// with [`FrameBuffer::finish_raster_job`] before the next one is taken,
+    /// meanwhile what has to be cleared in the back buffer is cleared then.
+    ///
+    /// # Panics
+    ///
+    /// If the last job did not come back
+    #[cfg(feature = "dual-core")]
+    pub fn take_raster_job(&mut self) -> RasterJob {
+        assert!(!self.rasterizing(), "the last raster job did not come back");
+        self.clear_buffer();
+
+        // The tiles of the frame are needed for its dirty regions, erased
+        // tiles start the next frame dirty
+        core::mem::swap(&mut self.current_tiles, &mut self.raster_tiles);
+        core::mem::swap(&mut self.current_tiles, &mut self.erased_tiles);
+
+        RasterJob {
+            buffer: core::mem::take(&mut self.back_buffer),
+            width: self.geometry.width as usize,
+            commands: core::mem::replace(
+                &mut self.commands,
+                core::mem::take(&mut self.spare_commands),
+            ),
+            text: core::mem::replace(&mut self.text, core::mem::take(&mut self.spare_text)),
+        }
+    }
+
+    /// Takes a rasterized job back into the back buffer
+    ///
+    /// Its frame is then finished like one rasterized in place, from
+    /// [`FrameBuffer::swap_buffers`] on. What was drawn meanwhile belongs to
+    /// the frame after it.
+    #[cfg(feature = "dual-core")]
+    pub fn finish_raster_job(&mut self, mut job: RasterJob) {
+        self.back_buffer = job.buffer;
+        job.commands.clear();
+        job.text.clear();
+        self.spare_commands = job.commands;
+        self.spare_text = job.text;
+
+        core::mem::swap(&mut self.current_tiles, &mut self.raster_tiles);
+        self.next_frame_drawn = true;
+    }
+
+    /// Whether the back buffer is with a rasterizer
+    fn rasterizing(&self) -> bool {
+        #[cfg(feature = "dual-core")]
+        return self.back_buffer.is_empty();
+        #[cfg(not(feature = "dual-core"))]
+        false
+    }
+
+    /// Keeps a background that stays on screen where nothing else is drawn
+    ///
+    /// It starts black and is drawn on with [`FrameBuffer::set_background_pixel`].
+    /// Dropping it clears the screen.
+    pub fn set_background(&mut self, enabled: bool) {
+        if enabled == self.background.is_some() {
+            return;
+        }
+
+        if enabled {
+            // Full-size buffer in PSRAM
+            self.background = Some(vec![Rgb565::BLACK; self.geometry.pixel_count()]);
+        } else {
+            self.background = None;
+            self.clear_back_tiles(
+                0,
+                0,
+                self.geometry.tiles_x() - 1,
+                self.geometry.tiles_y() - 1,
+            );
+            self.mark_dirty(0, 0, self.geometry.width - 1, self.geometry.height - 1);
+        }
+    }
+
+    pub fn background_pixel(&self, x: u16, y: u16) -> Option<Rgb565> {
+        let index = self.pixel_index(x, y)?;
+        self.background.as_ref().map(|background| background[index])
+    }
+
+    /// Changes a background pixel, it shows from this frame on
+    pub fn set_background_pixel(&mut self, x: u16, y: u16, color: Rgb565) {
+        let Some(index) = self.pixel_index(x, y) else {
+            return;
+        };
+        let Some(background) = self.background.as_mut() else {
+            return;
+        };
+
+        // The other buffer gets it when the tile is cleared in the next frame
+        background[index] = color;
+        if self.rasterizing() {
+            // Restored from the background with the rest of the tile
+            let tile_size = self.geometry.tile_size;
+            let (tile_x, tile_y) = ((x / tile_size) as usize, (y / tile_size) as usize);
+            self.clear_back_tiles(tile_x, tile_y, tile_x, tile_y);
+        } else {
+            self.back_buffer[index] = color;
+        }
+        self.mark_dirty(x, y, x, y);
+    }
+
+    fn pixel_index(&self, x: u16, y: u16) -> Option<usize> {
+        (x < self.geometry.width && y < self.geometry.height)
+            .then(|| y as usize * self.geometry.width as usize + x as usize)
+    }
+
+    /// Clears only the dirty tiles of the back buffer - call this at the start of each frame
+    ///
+    /// With a background the tiles are restored from it. While the back buffer
+    /// is with the rasterizer this is left to [`FrameBuffer::take_raster_job`].
+    pub fn clear_buffer(&mut self) {
+        if self.rasterizing() {
+            return;
+        }
+        let tiles_x = self.geometry.tiles_x();
+
+        // The back buffer was drawn 2 frames ago, clear what it got then.
+        // Tiles of the previous frame hold what erased areas left behind.
+        for tile_idx in 0..self.geometry.total_tiles() {
+            // Erased or painted while the back buffer was with the rasterizer
+            #[cfg(feature = "dual-core")]
+            let pending = self.pending_clear.is_dirty(tile_idx);
+            #[cfg(not(feature = "dual-core"))]
+            let pending = false;
+            if self.older_tiles.is_dirty(tile_idx) || self.prev_tiles.is_dirty(tile_idx) || pending
+            {
+                let tile_x = tile_idx % tiles_x;
+                let tile_y = tile_idx / tiles_x;
+                self.clear_back_tiles(tile_x, tile_y, tile_x, tile_y);
+            }
+        }
+        #[cfg(feature = "dual-core")]
+        self.pending_clear.clear();
+    }
+
+    /// Clears the tiles `tile_x1..=tile_x2`, `tile_y1..=tile_y2` of the back
+    /// buffer, or restores them from the background
+    ///
+    /// While the back buffer is with the rasterizer they are cleared when the
+    /// next job is taken.
+    fn clear_back_tiles(&mut self, tile_x1: usize, tile_y1: usize, tile_x2: usize, tile_y2: usize) {
+        #[cfg(feature = "dual-core")]
+        if self.rasterizing() {
+            let tiles_x = self.geometry.tiles_x();
+            for tile_y in tile_y1..=tile_y2 {
+                for tile_x in tile_x1..=tile_x2 {
+                    self.pending_clear.dirty[tile_y * tiles_x + tile_x] = true;
+                }
+            }
+            return;
+        }
+
+        let width = self.geometry.width as usize;
+        let rect = self.geometry.tile_rect(tile_x1, tile_y1, tile_x2, tile_y2);
+        for y in rect.y_start as usize..=rect.y_end as usize {
+            let row = y * width + rect.x_start as usize..y * width + rect.x_end as usize + 1;
+            match &self.background {
+                Some(background) => self.back_buffer[row.clone()].copy_from_slice(&background[row]),
+                None => self.back_buffer[row].fill(Rgb565::BLACK),
+            }
+        }
+    }
+
+    /// Swaps buffers so the front buffer holds the newly drawn frame
+    pub fn swap_buffers(&mut self) {
+        core::mem::swap(&mut self.front_buffer, &mut self.back_buffer);
+    }
+
+    /// Computes the regions of the front buffer that changed in this or the previous frame
+    ///
+    /// Dirty tiles are merged into rectangles to reduce DMA transfers, call
+    /// after [`FrameBuffer::swap_buffers`].
+    pub fn update_dirty_regions(&mut self) {
+        let batches = DirtyBatches {
+            geometry: &self.geometry,
+            current_tiles: &self.current_tiles,
+            prev_tiles: &self.prev_tiles,
+            tile_x: 0,
+            tile_y: 0,
+        };
+        self.regions.optimize(batches);
+
+        #[cfg(feature = "delta-transfer")]
+        {
+            let width = self.geometry.width as usize;
+
+            self.changed.clear();
+            if core::mem::take(&mut self.resend) {
+                self.changed.extend_from_slice(self.regions.regions());
+            } else {
+                for rect in self.regions.regions() {
+                    self.regions.split_changed(
+                        rect,
+                        &self.front_buffer,
+                        &self.sent_buffer,
+                        width,
+                        &mut self.changed,
+                    );
+                }
+            }
+
+            // The panel shows these pixels once the regions are sent
+            for rect in &self.changed {
+                for y in rect.y_start as usize..=rect.y_end as usize {
+                    let row =
+                        y * width + rect.x_start as usize..y * width + rect.x_end as usize + 1;
+                    self.sent_buffer[row.clone()].copy_from_slice(&self.front_buffer[row]);
+                }
+            }
+        }
+    }
+
+    /// Regions computed by the last [`FrameBuffer::update_dirty_regions`]
+    pub fn dirty_regions(&self) -> &[DirtyRect] {
+        #[cfg(feature = "delta-transfer")]
+        return &self.changed;
+
+        #[cfg(not(feature = "delta-transfer"))]
+        self.regions.regions()
+    }
+
+    /// Regions of the last [`FrameBuffer::update_dirty_regions`] before they
+    /// were split to the pixels that changed
+    #[cfg(feature = "delta-transfer")]
+    pub fn merged_regions(&self) -> &[DirtyRect] {
+        self.regions.regions()
+    }
+
+    /// Number of pixels in the regions of [`FrameBuffer::dirty_regions`]
+    pub fn dirty_pixel_count(&self) -> usize {
+        self.dirty_regions()
+            .iter()
+            .map(DirtyRect::pixel_count)
+            .sum()
+    }
+
+    /// Pixels of the front buffer inside `rect`, in row-major order
+    pub fn rect_pixels(&self, rect: DirtyRect) -> impl Iterator<Item = Rgb565> + '_ {
+        rect.rows(&self.front_buffer, self.geometry.width as usize)
+            .flat_map(|row| row.iter().copied())
+    }
+
+    /// The last finished frame, row by row
+    pub fn front_buffer(&self) -> &[Rgb565] {
+        &self.front_buffer
+    }
+
+    /// Takes the front buffer so it can be sent while the next frame is drawn
+    ///
+    /// The buffer has to be handed back with [`FrameBuffer::restore_front_buffer`]
+    /// before the next [`FrameBuffer::swap_buffers`].
+    #[cfg(feature = "async-flush")]
+    pub fn take_front_buffer(&mut self) -> Vec<Rgb565> {
+        core::mem::take(&mut self.front_buffer)
+    }
+
+    #[cfg(feature = "async-flush")]
+    pub fn restore_front_buffer(&mut self, buffer: Vec<Rgb565>) {
+        self.front_buffer = buffer;
+    }
+
+    /// Finishes the frame once its dirty regions are computed
+    pub fn finish_frame(&mut self) {
+        // Save current tiles for clearing 2 frames later
+        core::mem::swap(&mut self.older_tiles, &mut self.prev_tiles);
+        core::mem::swap(&mut self.prev_tiles, &mut self.current_tiles);
+        // The next frame is drawn already, it got the erased tiles when this
+        // one was handed to the rasterizer
+        #[cfg(feature = "dual-core")]
+        if core::mem::take(&mut self.next_frame_drawn) {
+            core::mem::swap(&mut self.current_tiles, &mut self.raster_tiles);
+            self.raster_tiles.clear();
+            return;
+        }
+        // Erased tiles start the next frame dirty
+        core::mem::swap(&mut self.current_tiles, &mut self.erased_tiles);
+        self.erased_tiles.clear();
+    }
+}
+
+/// Iterator over horizontal runs of adjacent dirty tiles, one tile row high
+///
+/// A tile is dirty if it was drawn in this or the previous frame.
+struct DirtyBatches<'a> {
+    geometry: &'a Geometry,
+    current_tiles: &'a TileTracker,
+    prev_tiles: &'a TileTracker,
+    tile_x: usize,
+    tile_y: usize,
+}
+
+impl DirtyBatches<'_> {
+    fn is_dirty(&self, tile_idx: usize) -> bool {
+        self.current_tiles.is_dirty(tile_idx) || self.prev_tiles.is_dirty(tile_idx)
+    }
+}
+
+impl Iterator for DirtyBatches<'_> {
+    type Item = DirtyRect;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let geometry = self.geometry;
+        let tiles_x = geometry.tiles_x();
+
+        while self.tile_y < geometry.tiles_y() {
+            let row = self.tile_y * tiles_x;
+
+            // Skip clean tiles
+            while self.tile_x < tiles_x && !self.is_dirty(row + self.tile_x) {
+                self.tile_x += 1;
+            }
+
+            if self.tile_x < tiles_x {
+                // Start batch and extend it over adjacent dirty tiles
+                let start_x = self.tile_x;
+                while self.tile_x < tiles_x && self.is_dirty(row + self.tile_x) {
+                    self.tile_x += 1;
+                }
+                return Some(geometry.tile_rect(
+                    start_x,
+                    self.tile_y,
+                    self.tile_x - 1,
+                    self.tile_y,
+                ));
+            }
+
+            self.tile_x = 0;
+            self.tile_y += 1;
+        }
+
+        None
+    }
+}
@@ -0,0 +1,269 @@
+//! Touch gesture recognizer
+//!
+//! Turns timestamped touch samples into drags, flings, taps, double taps,
+//! long presses and swipes. Pure logic without hardware access, the caller
+//! feeds samples with [`GestureRecognizer::update`] and calls
+//! [`GestureRecognizer::poll`] every frame for gestures that only depend on
+//! time (long press).
+
+use embedded_graphics::prelude::Point;
+
+/// Movement in pixels before a touch is a drag instead of a tap
+const TAP_SLOP: i32 = 10;
+/// Longest touch that still counts as a tap
+const TAP_MAX_MS: u64 = 250;
+/// Longest time between two taps of a double tap
+const DOUBLE_TAP_MS: u64 = 300;
+/// Touch duration without movement for a long press
+const LONG_PRESS_MS: u64 = 600;
+/// Release speed in pixels per second above which a drag becomes a fling
+const FLING_MIN_SPEED: f32 = 300.0;
+/// A fling needs a movement within this time before the release
+const FLING_MAX_IDLE_MS: u64 = 100;
+/// Minimum distance of a swipe in pixels
+const SWIPE_MIN_DISTANCE: i32 = 60;
+/// Longest touch that still counts as a swipe
+const SWIPE_MAX_MS: u64 = 500;
+/// Weight of the newest movement in the velocity estimate
+const VELOCITY_SMOOTHING: f32 = 0.5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    Down,
+    Move,
+    Up,
+}
+
+/// A touch controller report
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TouchSample {
+    pub phase: Phase,
+    pub position: Point,
+    pub time_ms: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SwipeDirection {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Gesture {
+    /// Finger moved by `delta` since the last drag, reported while dragging
+    Drag {
+        position: Point,
+        delta: Point,
+    },
+    /// Drag released while moving, velocity in pixels per second
+    Fling {
+        vx: f32,
+        vy: f32,
+    },
+    Tap(Point),
+    /// Second tap shortly after a tap, follows the [`Gesture::Tap`] of the second tap
+    DoubleTap(Point),
+    /// Finger held still, reported once while it is down
+    LongPress(Point),
+    /// Fast straight movement, reported on release
+    Swipe(SwipeDirection),
+}
+
+/// State of the touch in progress
+#[derive(Clone, Copy)]
+struct Contact {
+    start: TouchSample,
+    last: TouchSample,
+    /// Time of the last sample that changed the position
+    last_move_ms: u64,
+    dragging: bool,
+    long_pressed: bool,
+    /// Smoothed velocity in pixels per second
+    vx: f32,
+    vy: f32,
+}
+
+/// Gestures completed by one sample
+type Gestures = [Option<Gesture>; 3];
+
+pub struct GestureRecognizer {
+    contact: Option<Contact>,
+    /// Last tap, for double taps
+    last_tap: Option<TouchSample>,
+}
+
+impl Default for GestureRecognizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GestureRecognizer {
+    pub fn new() -> Self {
+        Self {
+            contact: None,
+            last_tap: None,
+        }
+    }
+
+    /// Processes a sample, returns the gestures it completes
+    pub fn update(&mut self, sample: TouchSample) -> impl Iterator<Item = Gesture> {
+        let mut gestures: Gestures = [None; 3];
+
+        match sample.phase {
+            Phase::Down => {
+                self.contact = Some(Contact {
+                    start: sample,
+                    last: sample,
+                    last_move_ms: sample.time_ms,
+                    dragging: false,
+                    long_pressed: false,
+                    vx: 0.0,
+                    vy: 0.0,
+                });
+            }
+            Phase::Move => {
+                gestures[0] = self.poll(sample.time_ms);
+                if let Some(contact) = self.contact.as_mut() {
+                    gestures[1] = contact.moved(sample);
+                }
+            }
+            Phase::Up => {
+                if let Some(mut contact) = self.contact.take() {
+                    // The release position may differ from the last move
+                    gestures[0] = contact.moved(sample);
+                    if contact.dragging {
+                        [gestures[1], gestures[2]] = contact.released(sample);
+                    } else {
+                        [gestures[0], gestures[1]] = self.tap(&contact, sample);
+                    }
+                }
+            }
+        }
+
+        gestures.into_iter().flatten()
+    }
+
+    /// Reports a long press once the touch has been held long enough
+    pub fn poll(&mut self, now_ms: u64) -> Option<Gesture> {
+        let contact = self.contact.as_mut()?;
+        if contact.dragging
+            || contact.long_pressed
+            || now_ms.saturating_sub(contact.start.time_ms) < LONG_PRESS_MS
+        {
+            return None;
+        }
+
+        contact.long_pressed = true;
+        Some(Gesture::LongPress(contact.start.position))
+    }
+
+    /// Tap and double tap of a release without drag
+    fn tap(&mut self, contact: &Contact, sample: TouchSample) -> [Option<Gesture>; 2] {
+        let duration = sample.time_ms.saturating_sub(contact.start.time_ms);
+        if contact.long_pressed || duration > TAP_MAX_MS {
+            self.last_tap = None;
+            return [None, None];
+        }
+
+        let position = contact.start.position;
+        let double = self.last_tap.take().is_some_and(|tap| {
+            sample.time_ms.saturating_sub(tap.time_ms) <= DOUBLE_TAP_MS
+                && !distance_exceeds(tap.position, position, TAP_SLOP)
+        });
+
+        if double {
+            [
+                Some(Gesture::Tap(position)),
+                Some(Gesture::DoubleTap(position)),
+            ]
+        } else {
+            self.last_tap = Some(sample);
+            [Some(Gesture::Tap(position)), None]
+        }
+    }
+}
+
+impl Contact {
+    /// Tracks a movement, returns a drag once the touch left the tap slop
+    fn moved(&mut self, sample: TouchSample) -> Option<Gesture> {
+        let last = core::mem::replace(&mut self.last, sample);
+        let delta = sample.position - last.position;
+        if delta == Point::zero() {
+            return None;
+        }
+
+        let dt = sample.time_ms.saturating_sub(self.last_move_ms);
+        if dt > 0 {
+            let seconds = dt as f32 / 1000.0;
+            self.vx += (delta.x as f32 / seconds - self.vx) * VELOCITY_SMOOTHING;
+            self.vy += (delta.y as f32 / seconds - self.vy) * VELOCITY_SMOOTHING;
+        }
+        self.last_move_ms = sample.time_ms;
+
+        if self.dragging {
+            return Some(Gesture::Drag {
+                position: sample.position,
+                delta,
+            });
+        }
+        if self.long_pressed || !distance_exceeds(self.start.position, sample.position, TAP_SLOP) {
+            return None;
+        }
+
+        // The first drag covers the movement within the slop as well
+        self.dragging = true;
+        Some(Gesture::Drag {
+            position: sample.position,
+            delta: sample.position - self.start.position,
+        })
+    }
+
+    /// Fling and swipe of a drag released with `sample`
+    fn released(&self, sample: TouchSample) -> [Option<Gesture>; 2] {
+        let idle = sample.time_ms.saturating_sub(self.last_move_ms);
+        let speed_sq = self.vx * self.vx + self.vy * self.vy;
+        let fling = (idle <= FLING_MAX_IDLE_MS && speed_sq >= FLING_MIN_SPEED * FLING_MIN_SPEED)
+            .then_some(Gesture::Fling {
+                vx: self.vx,
+                vy: self.vy,
+            });
+
+        [fling, self.swipe(sample).map(Gesture::Swipe)]
+    }
+
+    /// Direction of a fast movement along one axis
+    fn swipe(&self, sample: TouchSample) -> Option<SwipeDirection> {
+        let delta = sample.position - self.start.position;
+        let (dx, dy) = (delta.x.abs(), delta.y.abs());
+        let duration = sample.time_ms.saturating_sub(self.start.time_ms);
+
+        if duration > SWIPE_MAX_MS || dx.max(dy) < SWIPE_MIN_DISTANCE {
+            return None;
+        }
+
+        // Mostly straight: the main axis is at least twice the other one
+        if dx >= 2 * dy {
+            Some(if delta.x > 0 {
+                SwipeDirection::Right
+            } else {
+                SwipeDirection::Left
+            })
+        } else if dy >= 2 * dx {
+            Some(if delta.y > 0 {
+                SwipeDirection::Down
+            } else {
+                SwipeDirection::Up
+            })
+        } else {
+            None
+        }
+    }
+}
+
+fn distance_exceeds(a: Point, b: Point, limit: i32) -> bool {
+    let d = a - b;
+    d.x * d.x + d.y * d.y > limit * limit
+}
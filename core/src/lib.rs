@@ -0,0 +1,46 @@
+//! Hardware independent logic of the firmware
+//!
+//! Everything that does not touch a peripheral: the framebuffer and its
+//! dirty regions, the scene math, input handling, the settings and the
+//! recovery and power policies. `no_std` like the firmware, which drives the
+//! hardware with it, and built for the host to test it against the
+//! [`mock`] board.
+
+#![no_std]
+// The float methods of std shadow the ones of micromath in unit test builds
+#![cfg_attr(test, allow(unused_imports))]
+
+extern crate alloc;
+
+pub mod arcball;
+pub mod burnin;
+pub mod config;
+pub mod console;
+pub mod crash;
+pub mod dirty_region;
+pub mod display;
+#[cfg(feature = "dual-core")]
+pub mod dual_core;
+#[cfg(feature = "fixed-point")]
+pub mod fixed;
+#[cfg(feature = "async-flush")]
+pub mod flush;
+pub mod fps;
+pub mod framebuffer;
+pub mod gesture;
+pub mod math;
+#[cfg(not(target_os = "none"))]
+pub mod mock;
+pub mod panel;
+pub mod power;
+pub mod profiler;
+pub mod qspi;
+pub mod recovery;
+pub mod replay;
+pub mod rng;
+pub mod scene;
+pub mod settings;
+pub mod storage;
+pub mod text;
+pub mod touch;
+pub mod ui;
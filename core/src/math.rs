@@ -0,0 +1,118 @@
+//! Per-frame math of the scene: rotation, projection and particle integration
+//!
+//! Computed in `f32` through `micromath` or, with the `fixed-point` feature,
+//! in Q16.16 fixed point (see [`crate::fixed`]). Setup values are passed as
+//! `f32` either way.
+
+use embedded_graphics::prelude::Point;
+
+#[cfg(feature = "fixed-point")]
+use crate::fixed::Fixed;
+#[cfg(feature = "fixed-point")]
+pub use crate::fixed::{Quaternion as Rotation, Vector as Vec3};
+#[cfg(not(feature = "fixed-point"))]
+pub use micromath::{vector::F32x3 as Vec3, Quaternion as Rotation};
+
+#[cfg(not(feature = "fixed-point"))]
+type Scalar = f32;
+#[cfg(feature = "fixed-point")]
+type Scalar = Fixed;
+
+const ONE: Scalar = scalar(1.0);
+
+/// Points closer to the camera than this are not projected
+const MIN_DEPTH: Scalar = scalar(0.01);
+
+#[cfg(not(feature = "fixed-point"))]
+const fn scalar(value: f32) -> Scalar {
+    value
+}
+
+#[cfg(feature = "fixed-point")]
+const fn scalar(value: f32) -> Scalar {
+    Fixed::from_f32(value)
+}
+
+/// Integer part, rounded towards zero
+#[cfg(not(feature = "fixed-point"))]
+fn to_int(value: Scalar) -> i32 {
+    value as i32
+}
+
+#[cfg(feature = "fixed-point")]
+fn to_int(value: Scalar) -> i32 {
+    value.to_int()
+}
+
+pub fn vec3(x: f32, y: f32, z: f32) -> Vec3 {
+    Vec3::from((x, y, z))
+}
+
+/// Rotation by `angle` radians around the unit vector `axis`
+pub fn axis_angle(axis: Vec3, angle: f32) -> Rotation {
+    Rotation::axis_angle(axis, angle)
+}
+
+/// Rotation `b` followed by `a`
+pub fn compose(a: Rotation, b: Rotation) -> Rotation {
+    #[cfg(not(feature = "fixed-point"))]
+    return a * b;
+
+    // Keeps the accumulated rotation from drifting off unit length
+    #[cfg(feature = "fixed-point")]
+    (a * b).normalize()
+}
+
+/// Perspective projection onto the screen
+pub struct Projection {
+    fov: Scalar,
+    /// Distance of the camera from the origin
+    distance: Scalar,
+    /// Screen position of the origin
+    center: Point,
+}
+
+impl Projection {
+    pub const fn new(fov: f32, distance: f32, center: Point) -> Self {
+        Self {
+            fov: scalar(fov),
+            distance: scalar(distance),
+            center,
+        }
+    }
+
+    /// Moves the origin on the screen
+    pub fn set_center(&mut self, center: Point) {
+        self.center = center;
+    }
+
+    /// Screen position of `v` rotated by `rotation`, `None` if it is at the camera
+    pub fn project(&self, rotation: Rotation, v: Vec3) -> Option<Point> {
+        let rotated = rotation.rotate(v);
+        let z = rotated.z + self.distance;
+        if (-MIN_DEPTH..=MIN_DEPTH).contains(&z) {
+            return None;
+        }
+
+        let inv_z = ONE / z;
+        Some(Point::new(
+            to_int(rotated.x * self.fov * inv_z) + self.center.x,
+            to_int(rotated.y * self.fov * inv_z) + self.center.y,
+        ))
+    }
+}
+
+/// Moves a particle by its velocity, bouncing off the walls of the unit cube
+pub fn step_particle(pos: &mut Vec3, vel: &mut Vec3) {
+    bounce(&mut pos.x, &mut vel.x);
+    bounce(&mut pos.y, &mut vel.y);
+    bounce(&mut pos.z, &mut vel.z);
+}
+
+fn bounce(pos: &mut Scalar, vel: &mut Scalar) {
+    *pos += *vel;
+    if *pos > ONE || *pos < -ONE {
+        *vel = -*vel;
+        *pos = (*pos).clamp(-ONE, ONE);
+    }
+}
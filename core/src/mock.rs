@@ -0,0 +1,594 @@
+//! Mock board for host builds
+//!
+//! Stands in for a board of the firmware (`src/board`) with the same
+//! constants, a [`MockPanel`] that keeps what a DCS panel is sent, a
+//! [`MockDisplay`] that drives it like the firmware's display driver drives
+//! a real panel and a [`MockFlash`] for the settings partition. A
+//! [`FaultyPanel`] fails when told to, for the error handling. Only built
+//! for targets with an operating system.
+
+use alloc::collections::VecDeque;
+use alloc::rc::Rc;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::convert::Infallible;
+use core::fmt::Debug;
+use embedded_graphics::geometry::{Point, Size};
+use embedded_graphics::pixelcolor::raw::RawU16;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::IntoStorage;
+use embedded_graphics::primitives::Rectangle;
+use embedded_hal::{digital, spi};
+use embedded_storage::nor_flash::{ErrorType, NorFlash, NorFlashErrorKind, ReadNorFlash};
+use mipidsi::interface::{Interface, SpiError};
+use mipidsi::options::{Orientation, Rotation};
+
+use crate::config::{TILE_SIZE, TRANSFER_OVERHEAD_BYTES};
+use crate::display::{Cause, DisplayError, DisplayTrait, Operation};
+use crate::framebuffer::{DirtyRect, FrameBuffer, Geometry};
+use crate::panel::{ColorLut, PanelCommand};
+
+pub const NAME: &str = "Host mock";
+
+/// Resolution after applying `ORIENTATION`
+pub const DISPLAY_WIDTH: u16 = 320;
+pub const DISPLAY_HEIGHT: u16 = 170;
+
+pub const ORIENTATION: Orientation = Orientation {
+    mirrored: false,
+    rotation: Rotation::Deg90,
+};
+pub const OLED: bool = false;
+
+/// DCS "Column Address Set", "Page Address Set" and "Memory Write"
+const CASET: u8 = 0x2A;
+const RASET: u8 = 0x2B;
+const RAMWR: u8 = 0x2C;
+
+/// Panel memory behind a `mipidsi` interface
+///
+/// Pixels written through the address window end up in the memory like on a
+/// DCS panel, every other command is kept with its parameters.
+pub struct MockPanel {
+    width: usize,
+    height: usize,
+    pixels: Vec<Rgb565>,
+    /// Address window, bounds are inclusive
+    window: DirtyRect,
+    /// Next pixel written, relative to the window
+    cursor: usize,
+    /// Commands other than the address window and memory writes, in the
+    /// order they were sent
+    pub commands: Vec<(u8, Vec<u8>)>,
+    /// Memory writes, one per region sent
+    pub transfers: usize,
+    /// Pixel data written
+    pub bytes: usize,
+}
+
+impl MockPanel {
+    pub fn new(width: u16, height: u16) -> Self {
+        Self {
+            width: width as usize,
+            height: height as usize,
+            pixels: vec![Rgb565::new(0, 0, 0); width as usize * height as usize],
+            window: DirtyRect {
+                x_start: 0,
+                y_start: 0,
+                x_end: width - 1,
+                y_end: height - 1,
+            },
+            cursor: 0,
+            commands: Vec::new(),
+            transfers: 0,
+            bytes: 0,
+        }
+    }
+
+    /// What the panel shows, row by row
+    pub fn pixels(&self) -> &[Rgb565] {
+        &self.pixels
+    }
+
+    pub fn pixel(&self, x: u16, y: u16) -> Rgb565 {
+        self.pixels[y as usize * self.width + x as usize]
+    }
+
+    /// Reset through the reset pin, the panel loses what it showed
+    pub fn reset(&mut self) {
+        self.pixels.fill(Rgb565::new(0, 0, 0));
+        self.window = DirtyRect {
+            x_start: 0,
+            y_start: 0,
+            x_end: self.width as u16 - 1,
+            y_end: self.height as u16 - 1,
+        };
+        self.cursor = 0;
+    }
+
+    /// Forgets the commands and counts so far, keeps the pixels
+    pub fn clear_log(&mut self) {
+        self.commands.clear();
+        self.transfers = 0;
+        self.bytes = 0;
+    }
+
+    fn write_pixel(&mut self, color: Rgb565) {
+        let window = self.window;
+        let width = (window.x_end - window.x_start + 1) as usize;
+        let x = window.x_start as usize + self.cursor % width;
+        let y = window.y_start as usize + self.cursor / width;
+        // Past the window or the panel the controller drops the data
+        if y <= window.y_end as usize && x < self.width && y < self.height {
+            self.pixels[y * self.width + x] = color;
+        }
+        self.cursor += 1;
+    }
+}
+
+impl Interface for MockPanel {
+    type Word = u8;
+    type Error = Infallible;
+
+    fn send_command(&mut self, command: u8, args: &[u8]) -> Result<(), Self::Error> {
+        let bounds = |args: &[u8]| match args {
+            &[start_high, start_low, end_high, end_low] => Some((
+                u16::from_be_bytes([start_high, start_low]),
+                u16::from_be_bytes([end_high, end_low]),
+            )),
+            _ => None,
+        };
+
+        match command {
+            CASET => {
+                if let Some((start, end)) = bounds(args) {
+                    self.window.x_start = start;
+                    self.window.x_end = end;
+                }
+            }
+            RASET => {
+                if let Some((start, end)) = bounds(args) {
+                    self.window.y_start = start;
+                    self.window.y_end = end;
+                }
+            }
+            RAMWR => {
+                self.cursor = 0;
+                self.transfers += 1;
+            }
+            _ => self.commands.push((command, args.to_vec())),
+        }
+        Ok(())
+    }
+
+    fn send_pixels<const N: usize>(
+        &mut self,
+        pixels: impl IntoIterator<Item = [Self::Word; N]>,
+    ) -> Result<(), Self::Error> {
+        for pixel in pixels {
+            self.bytes += N;
+            // Two bytes per pixel in RGB565
+            let raw = u16::from_be_bytes([pixel[0], pixel[1]]);
+            self.write_pixel(RawU16::new(raw).into());
+        }
+        Ok(())
+    }
+
+    fn send_repeated_pixel<const N: usize>(
+        &mut self,
+        pixel: [Self::Word; N],
+        count: u32,
+    ) -> Result<(), Self::Error> {
+        self.send_pixels(core::iter::repeat_n(pixel, count as usize))
+    }
+}
+
+/// Error of a [`FaultyPanel`], of the SPI bus or the data/command pin
+pub type MockInterfaceError = SpiError<spi::ErrorKind, digital::ErrorKind>;
+
+/// A [`MockPanel`] whose transfers and re-inits fail when told to
+pub struct FaultyPanel {
+    pub panel: MockPanel,
+    /// Errors of the next commands or pixel transfers, in order
+    faults: VecDeque<MockInterfaceError>,
+    /// Re-inits that fail before one goes through
+    failing_reinits: usize,
+    /// Re-inits so far, good or not
+    pub reinits: usize,
+}
+
+impl FaultyPanel {
+    pub fn new(width: u16, height: u16) -> Self {
+        Self {
+            panel: MockPanel::new(width, height),
+            faults: VecDeque::new(),
+            failing_reinits: 0,
+            reinits: 0,
+        }
+    }
+
+    /// Fails the next command or pixel transfer with `error`, the faults of
+    /// several calls fail the calls after it
+    pub fn fail(&mut self, error: MockInterfaceError) {
+        self.faults.push_back(error);
+    }
+
+    /// The next `count` re-inits fail, the panel does not take its init
+    /// sequence
+    pub fn fail_reinits(&mut self, count: usize) {
+        self.failing_reinits = count;
+    }
+
+    /// Faults left over, the calls they are for did not happen
+    pub fn pending_faults(&self) -> usize {
+        self.faults.len()
+    }
+
+    fn fault(&mut self) -> Result<(), MockInterfaceError> {
+        self.faults.pop_front().map_or(Ok(()), Err)
+    }
+}
+
+impl Interface for FaultyPanel {
+    type Word = u8;
+    type Error = MockInterfaceError;
+
+    fn send_command(&mut self, command: u8, args: &[u8]) -> Result<(), Self::Error> {
+        self.fault()?;
+        let Ok(()) = self.panel.send_command(command, args);
+        Ok(())
+    }
+
+    fn send_pixels<const N: usize>(
+        &mut self,
+        pixels: impl IntoIterator<Item = [Self::Word; N]>,
+    ) -> Result<(), Self::Error> {
+        self.fault()?;
+        let Ok(()) = self.panel.send_pixels(pixels);
+        Ok(())
+    }
+
+    fn send_repeated_pixel<const N: usize>(
+        &mut self,
+        pixel: [Self::Word; N],
+        count: u32,
+    ) -> Result<(), Self::Error> {
+        self.fault()?;
+        let Ok(()) = self.panel.send_repeated_pixel(pixel, count);
+        Ok(())
+    }
+}
+
+/// Panel of a [`MockDisplay`] and the errors the display gets from it
+pub trait MockInterface: Interface<Word = u8> {
+    type DisplayError: Debug;
+
+    /// Error of the display for `error` of the panel during `operation`
+    fn display_error(operation: Operation, error: Self::Error) -> Self::DisplayError;
+
+    /// Reset and init sequence, see [`DisplayTrait::reinit`]
+    fn reinit(&mut self) -> Result<(), Self::DisplayError>;
+}
+
+impl MockInterface for MockPanel {
+    type DisplayError = Infallible;
+
+    fn display_error(_operation: Operation, error: Infallible) -> Infallible {
+        error
+    }
+
+    fn reinit(&mut self) -> Result<(), Infallible> {
+        self.reset();
+        Ok(())
+    }
+}
+
+impl MockInterface for FaultyPanel {
+    type DisplayError = DisplayError<MockInterfaceError>;
+
+    fn display_error(operation: Operation, error: MockInterfaceError) -> Self::DisplayError {
+        DisplayError::new(operation, error)
+    }
+
+    fn reinit(&mut self) -> Result<(), Self::DisplayError> {
+        self.reinits += 1;
+        if self.failing_reinits > 0 {
+            self.failing_reinits -= 1;
+            return Err(DisplayError::new(Operation::Init, Cause::Init));
+        }
+        self.panel.reset();
+        Ok(())
+    }
+}
+
+/// Display of the mock board, a [`FrameBuffer`] sent to a [`MockPanel`]
+/// region by region, like the firmware's blocking flush does
+pub struct MockDisplay<P = MockPanel> {
+    pub frame: FrameBuffer,
+    pub panel: P,
+    /// Color correction of the pixels sent, `None` sends them unchanged
+    lut: Option<ColorLut>,
+}
+
+impl MockDisplay {
+    pub fn new(geometry: Geometry, transfer_overhead: usize) -> Self {
+        Self {
+            frame: FrameBuffer::new(geometry, transfer_overhead),
+            panel: MockPanel::new(geometry.width, geometry.height),
+            lut: None,
+        }
+    }
+
+    /// The display of the mock board, with the tiles of the firmware
+    pub fn board() -> Self {
+        Self::new(
+            Geometry::new(DISPLAY_WIDTH, DISPLAY_HEIGHT, TILE_SIZE),
+            TRANSFER_OVERHEAD_BYTES,
+        )
+    }
+}
+
+impl MockDisplay<FaultyPanel> {
+    /// The display of the mock board on a [`FaultyPanel`]
+    pub fn faulty() -> Self {
+        Self {
+            frame: FrameBuffer::new(
+                Geometry::new(DISPLAY_WIDTH, DISPLAY_HEIGHT, TILE_SIZE),
+                TRANSFER_OVERHEAD_BYTES,
+            ),
+            panel: FaultyPanel::new(DISPLAY_WIDTH, DISPLAY_HEIGHT),
+            lut: None,
+        }
+    }
+}
+
+impl<P: MockInterface> MockDisplay<P> {
+    fn send_command(&mut self, command: PanelCommand) -> Result<(), P::DisplayError> {
+        command
+            .send(&mut self.panel)
+            .map_err(|error| P::display_error(Operation::Command(command), error))
+    }
+}
+
+impl<P: MockInterface> DisplayTrait for MockDisplay<P> {
+    type Error = P::DisplayError;
+
+    fn write(&mut self, text: &str, position: Point) -> Result<(), Self::Error> {
+        self.frame.write(text, position);
+        Ok(())
+    }
+
+    async fn update_with_buffer(&mut self) -> Result<(), Self::Error> {
+        let Ok(()) = self.frame.rasterize();
+        self.frame.swap_buffers();
+        self.frame.update_dirty_regions();
+
+        let lut = self.lut.as_ref();
+        let error = |error| P::display_error(Operation::Flush, error);
+        for &rect in self.frame.dirty_regions() {
+            let (x_start, x_end) = (rect.x_start.to_be_bytes(), rect.x_end.to_be_bytes());
+            let (y_start, y_end) = (rect.y_start.to_be_bytes(), rect.y_end.to_be_bytes());
+            self.panel
+                .send_command(CASET, &[x_start[0], x_start[1], x_end[0], x_end[1]])
+                .map_err(error)?;
+            self.panel
+                .send_command(RASET, &[y_start[0], y_start[1], y_end[0], y_end[1]])
+                .map_err(error)?;
+            self.panel.send_command(RAMWR, &[]).map_err(error)?;
+            let pixels = self.frame.rect_pixels(rect).map(|color| {
+                let color = lut.map_or(color, |lut| lut.apply(color));
+                color.into_storage().to_be_bytes()
+            });
+            self.panel.send_pixels(pixels).map_err(error)?;
+        }
+
+        self.frame.finish_frame();
+        Ok(())
+    }
+
+    fn draw_line(&mut self, start: Point, end: Point) -> Result<(), Self::Error> {
+        self.frame.draw_line(start, end);
+        Ok(())
+    }
+
+    fn fill_rect(&mut self, area: Rectangle, color: Rgb565) -> Result<(), Self::Error> {
+        self.frame.fill_rect(area, color);
+        Ok(())
+    }
+
+    fn size(&self) -> Size {
+        self.frame.size()
+    }
+
+    fn write_unchanged(&mut self, text: &str, position: Point) {
+        self.frame.write_unchanged(text, position);
+    }
+
+    fn erase(&mut self, area: Rectangle) {
+        self.frame.erase(area);
+    }
+
+    fn set_ink(&mut self, color: Rgb565) {
+        self.frame.set_ink(color);
+    }
+
+    fn ink(&self) -> Rgb565 {
+        self.frame.ink()
+    }
+
+    async fn set_brightness(&mut self, level: u8) -> Result<(), Self::Error> {
+        self.send_command(PanelCommand::Brightness(level))
+    }
+
+    async fn sleep(&mut self) -> Result<(), Self::Error> {
+        self.send_command(PanelCommand::DisplayOff)?;
+        self.send_command(PanelCommand::SleepIn)
+    }
+
+    async fn wake(&mut self) -> Result<(), Self::Error> {
+        self.send_command(PanelCommand::SleepOut)?;
+        self.send_command(PanelCommand::DisplayOn)
+    }
+
+    async fn set_inversion(&mut self, inverted: bool) -> Result<(), Self::Error> {
+        let command = if inverted {
+            PanelCommand::InversionOn
+        } else {
+            PanelCommand::InversionOff
+        };
+        self.send_command(command)
+    }
+
+    fn set_color_correction(&mut self, lut: ColorLut) {
+        let lut = (lut != ColorLut::IDENTITY).then_some(lut);
+        if lut != self.lut {
+            self.lut = lut;
+            self.frame.invalidate();
+        }
+    }
+
+    fn invalidate(&mut self) {
+        self.frame.invalidate();
+    }
+
+    async fn reinit(&mut self) -> Result<(), Self::Error> {
+        self.panel.reinit()?;
+        self.frame.invalidate();
+        Ok(())
+    }
+}
+
+/// NOR flash in memory
+///
+/// Like real NOR flash, writes only clear bits and erasing sets a whole
+/// sector back to `0xFF`. Clones share the memory, so a test can keep one to
+/// look at the flash, or to start over after a reset, while another one is
+/// in use.
+#[derive(Clone)]
+pub struct MockFlash {
+    state: Rc<RefCell<FlashState>>,
+}
+
+struct FlashState {
+    data: Vec<u8>,
+    /// Erases of every sector
+    erases: Vec<usize>,
+    /// Bytes written before the power is lost, see [`MockFlash::lose_power_after`]
+    power: Option<usize>,
+}
+
+impl MockFlash {
+    /// Erased flash of `sectors` sectors of [`NorFlash::ERASE_SIZE`] bytes
+    pub fn new(sectors: usize) -> Self {
+        Self {
+            state: Rc::new(RefCell::new(FlashState {
+                data: vec![0xFF; sectors * Self::ERASE_SIZE],
+                erases: vec![0; sectors],
+                power: None,
+            })),
+        }
+    }
+
+    /// Times every sector has been erased
+    pub fn erases(&self) -> Vec<usize> {
+        self.state.borrow().erases.clone()
+    }
+
+    /// Contents of the flash
+    pub fn data(&self) -> Vec<u8> {
+        self.state.borrow().data.clone()
+    }
+
+    /// Flips the bits of `mask` in the byte at `offset`, like a worn cell
+    pub fn corrupt(&self, offset: usize, mask: u8) {
+        self.state.borrow_mut().data[offset] ^= mask;
+    }
+
+    /// Stops writing and erasing after `bytes` more bytes are written, like a
+    /// reset in the middle of a write. Operations fail from then on.
+    pub fn lose_power_after(&self, bytes: usize) {
+        self.state.borrow_mut().power = Some(bytes);
+    }
+
+    /// Lets writes and erases through again
+    pub fn restore_power(&self) {
+        self.state.borrow_mut().power = None;
+    }
+
+    fn check(&self, offset: u32, len: usize, align: usize) -> Result<(), NorFlashErrorKind> {
+        let offset = offset as usize;
+        if !offset.is_multiple_of(align) || !len.is_multiple_of(align) {
+            return Err(NorFlashErrorKind::NotAligned);
+        }
+        if offset + len > self.capacity() {
+            return Err(NorFlashErrorKind::OutOfBounds);
+        }
+        Ok(())
+    }
+}
+
+impl ErrorType for MockFlash {
+    type Error = NorFlashErrorKind;
+}
+
+impl ReadNorFlash for MockFlash {
+    const READ_SIZE: usize = 4;
+
+    fn read(&mut self, offset: u32, bytes: &mut [u8]) -> Result<(), Self::Error> {
+        self.check(offset, bytes.len(), Self::READ_SIZE)?;
+        let offset = offset as usize;
+        bytes.copy_from_slice(&self.state.borrow().data[offset..offset + bytes.len()]);
+        Ok(())
+    }
+
+    fn capacity(&self) -> usize {
+        self.state.borrow().data.len()
+    }
+}
+
+impl NorFlash for MockFlash {
+    const WRITE_SIZE: usize = 4;
+    const ERASE_SIZE: usize = 4096;
+
+    fn erase(&mut self, from: u32, to: u32) -> Result<(), Self::Error> {
+        let len = to.checked_sub(from).ok_or(NorFlashErrorKind::OutOfBounds)?;
+        self.check(from, len as usize, Self::ERASE_SIZE)?;
+
+        let mut state = self.state.borrow_mut();
+        if state.power == Some(0) {
+            return Err(NorFlashErrorKind::Other);
+        }
+        let (from, to) = (from as usize, to as usize);
+        state.data[from..to].fill(0xFF);
+        for sector in from / Self::ERASE_SIZE..to / Self::ERASE_SIZE {
+            state.erases[sector] += 1;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, offset: u32, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.check(offset, bytes.len(), Self::WRITE_SIZE)?;
+
+        let mut state = self.state.borrow_mut();
+        let len = state
+            .power
+            .map_or(bytes.len(), |power| power.min(bytes.len()));
+        let offset = offset as usize;
+        for (cell, byte) in state.data[offset..offset + len].iter_mut().zip(bytes) {
+            *cell &= byte;
+        }
+
+        match state.power.as_mut() {
+            Some(power) if *power < bytes.len() => {
+                *power = 0;
+                Err(NorFlashErrorKind::Other)
+            }
+            Some(power) => {
+                *power -= bytes.len();
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+}
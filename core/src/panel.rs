@@ -0,0 +1,93 @@
+//! Panel controls beyond pixels
+//!
+//! [`PanelCommand`] are the DCS commands behind the brightness, sleep and
+//! inversion methods of [`crate::display::DisplayTrait`]. [`ColorLut`] is the
+//! color correction applied in software to every pixel sent to the panel.
+
+use embedded_graphics::pixelcolor::{Rgb565, RgbColor};
+use micromath::F32Ext;
+use mipidsi::interface::Interface;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PanelCommand {
+    SleepIn,
+    SleepOut,
+    DisplayOff,
+    DisplayOn,
+    InversionOff,
+    InversionOn,
+    /// `0` is off and `255` full, panels with a separate backlight ignore it
+    Brightness(u8),
+}
+
+impl PanelCommand {
+    /// The DCS instruction and its parameters
+    pub fn dcs(&self) -> (u8, &[u8]) {
+        match self {
+            PanelCommand::SleepIn => (0x10, &[]),
+            PanelCommand::SleepOut => (0x11, &[]),
+            PanelCommand::InversionOff => (0x20, &[]),
+            PanelCommand::InversionOn => (0x21, &[]),
+            PanelCommand::DisplayOff => (0x28, &[]),
+            PanelCommand::DisplayOn => (0x29, &[]),
+            PanelCommand::Brightness(level) => (0x51, core::slice::from_ref(level)),
+        }
+    }
+
+    /// Sends the command over a `mipidsi` interface
+    pub fn send<DI: Interface>(&self, interface: &mut DI) -> Result<(), DI::Error> {
+        let (instruction, args) = self.dcs();
+        interface.send_command(instruction, args)
+    }
+}
+
+/// Per-channel lookup tables for RGB565 pixels
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ColorLut {
+    red: [u8; 32],
+    green: [u8; 64],
+    blue: [u8; 32],
+}
+
+impl ColorLut {
+    pub const IDENTITY: Self = Self {
+        red: identity(),
+        green: identity(),
+        blue: identity(),
+    };
+
+    /// Contrast around mid gray, then gamma, `1.0` for both leaves the colors unchanged
+    pub fn new(gamma: f32, contrast: f32) -> Self {
+        Self {
+            red: curve(gamma, contrast),
+            green: curve(gamma, contrast),
+            blue: curve(gamma, contrast),
+        }
+    }
+
+    pub fn apply(&self, color: Rgb565) -> Rgb565 {
+        Rgb565::new(
+            self.red[color.r() as usize],
+            self.green[color.g() as usize],
+            self.blue[color.b() as usize],
+        )
+    }
+}
+
+const fn identity<const N: usize>() -> [u8; N] {
+    let mut table = [0; N];
+    let mut i = 0;
+    while i < N {
+        table[i] = i as u8;
+        i += 1;
+    }
+    table
+}
+
+fn curve<const N: usize>(gamma: f32, contrast: f32) -> [u8; N] {
+    let max = (N - 1) as f32;
+    core::array::from_fn(|i| {
+        let value = ((i as f32 / max - 0.5) * contrast + 0.5).clamp(0.0, 1.0);
+        (value.powf(gamma) * max + 0.5) as u8
+    })
+}
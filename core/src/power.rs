@@ -0,0 +1,69 @@
+//! Idle power policy
+//!
+//! Without input the screen is dimmed, then the frame rate drops and at last
+//! the panel sleeps until the next touch. Pure logic without hardware access,
+//! the caller reports input with [`IdlePolicy::activity`] and applies the
+//! state returned by [`IdlePolicy::update`], called every frame.
+
+/// Time without input before each step, in milliseconds
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IdleTimeouts {
+    pub dim_ms: u64,
+    pub throttle_ms: u64,
+    pub sleep_ms: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PowerState {
+    /// Full brightness and frame rate
+    Active,
+    /// Lower brightness
+    Dimmed,
+    /// Lower brightness and frame rate
+    Throttled,
+    /// Panel asleep, nothing is rendered
+    Asleep,
+}
+
+pub struct IdlePolicy {
+    timeouts: IdleTimeouts,
+    /// State returned by the last update
+    state: PowerState,
+    last_activity_ms: u64,
+}
+
+impl IdlePolicy {
+    pub fn new(timeouts: IdleTimeouts, now_ms: u64) -> Self {
+        Self {
+            timeouts,
+            state: PowerState::Active,
+            last_activity_ms: now_ms,
+        }
+    }
+
+    pub fn state(&self) -> PowerState {
+        self.state
+    }
+
+    /// Input at `now_ms`, the next [`IdlePolicy::update`] returns to active
+    pub fn activity(&mut self, now_ms: u64) {
+        self.last_activity_ms = self.last_activity_ms.max(now_ms);
+    }
+
+    /// The state at `now_ms`, `Some` if it changed since the last call
+    pub fn update(&mut self, now_ms: u64) -> Option<PowerState> {
+        let idle_ms = now_ms.saturating_sub(self.last_activity_ms);
+        let state = if idle_ms >= self.timeouts.sleep_ms {
+            PowerState::Asleep
+        } else if idle_ms >= self.timeouts.throttle_ms {
+            PowerState::Throttled
+        } else if idle_ms >= self.timeouts.dim_ms {
+            PowerState::Dimmed
+        } else {
+            PowerState::Active
+        };
+
+        let previous = core::mem::replace(&mut self.state, state);
+        (state != previous).then_some(state)
+    }
+}
@@ -0,0 +1,262 @@
+//! Frame profile
+//!
+//! Adds up the time spent in every stage of a frame, in microseconds, and
+//! keeps the last [`WINDOW`] frames for averages and min/max. The firmware
+//! times the stages (see `src/profiler.rs`), the statistics are reported
+//! over the console, logged at debug level and drawn on screen as text or
+//! bars.
+
+use core::fmt::{self, Write};
+
+use embedded_graphics::prelude::Point;
+use log::debug;
+
+use crate::display::DisplayTrait;
+use crate::text::TextBuffer;
+
+/// Number of frames the statistics are computed over
+pub const WINDOW: usize = 32;
+
+/// Line height of the overlay (10x20 font)
+const LINE_HEIGHT: i32 = 20;
+/// Width of a bar label, one character and a space
+const LABEL_WIDTH: i32 = 20;
+/// Bar length scale of the overlay
+const MICROS_PER_PIXEL: u32 = 100;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stage {
+    Clear,
+    Input,
+    Simulate,
+    Project,
+    Rasterize,
+    Flush,
+}
+
+impl Stage {
+    const COUNT: usize = 6;
+
+    pub const ALL: [Stage; Self::COUNT] = [
+        Stage::Clear,
+        Stage::Input,
+        Stage::Simulate,
+        Stage::Project,
+        Stage::Rasterize,
+        Stage::Flush,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Stage::Clear => "clear",
+            Stage::Input => "input",
+            Stage::Simulate => "simulate",
+            Stage::Project => "project",
+            Stage::Rasterize => "rasterize",
+            Stage::Flush => "flush",
+        }
+    }
+}
+
+/// How the profile shows up on screen, selected in the firmware's config
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Overlay {
+    Off,
+    /// Average, min and max per stage in microseconds, then bytes sent
+    Text,
+    /// One bar per stage, [`MICROS_PER_PIXEL`] per pixel of the average
+    Bars,
+}
+
+/// Statistics over the last [`WINDOW`] frames
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub avg: u32,
+    pub min: u32,
+    pub max: u32,
+}
+
+impl Stats {
+    fn of(samples: &[u32]) -> Self {
+        if samples.is_empty() {
+            return Self::default();
+        }
+
+        let sum: u64 = samples.iter().map(|&s| s as u64).sum();
+        Self {
+            avg: (sum / samples.len() as u64) as u32,
+            min: samples.iter().copied().min().unwrap_or(0),
+            max: samples.iter().copied().max().unwrap_or(0),
+        }
+    }
+}
+
+pub struct Profile {
+    /// Microseconds per stage of the last frames, a ring buffer
+    samples: [[u32; WINDOW]; Stage::COUNT],
+    /// Bytes sent to the panel in the last frames
+    bytes: [u32; WINDOW],
+    /// Number of frames recorded so far
+    frames: usize,
+    /// Stages of the frame in progress
+    current: [u32; Stage::COUNT],
+}
+
+impl Profile {
+    pub const fn new() -> Self {
+        Self {
+            samples: [[0; WINDOW]; Stage::COUNT],
+            bytes: [0; WINDOW],
+            frames: 0,
+            current: [0; Stage::COUNT],
+        }
+    }
+
+    /// Adds `micros` to `stage` of the frame in progress, a stage can be
+    /// recorded several times per frame
+    pub fn record(&mut self, stage: Stage, micros: u64) {
+        let micros = micros.min(u32::MAX as u64) as u32;
+        self.current[stage as usize] = self.current[stage as usize].saturating_add(micros);
+    }
+
+    /// Completes the frame in progress, `bytes` were sent to the panel
+    pub fn end_frame(&mut self, bytes: usize) {
+        let slot = self.frames % WINDOW;
+        for (stage, samples) in self.samples.iter_mut().enumerate() {
+            samples[slot] = self.current[stage];
+        }
+        self.bytes[slot] = bytes.min(u32::MAX as usize) as u32;
+        self.current = [0; Stage::COUNT];
+        self.frames += 1;
+    }
+
+    /// Number of frames completed so far
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Statistics of `stage` over the recorded frames
+    pub fn stats(&self, stage: Stage) -> Stats {
+        Stats::of(&self.samples[stage as usize][..self.recorded()])
+    }
+
+    /// Statistics of the bytes sent per frame
+    pub fn bytes(&self) -> Stats {
+        Stats::of(&self.bytes[..self.recorded()])
+    }
+
+    fn recorded(&self) -> usize {
+        self.frames.min(WINDOW)
+    }
+
+    /// Logs one line per stage at debug level, then the bytes sent
+    pub fn log(&self) {
+        for stage in Stage::ALL {
+            let stats = self.stats(stage);
+            debug!(
+                "profile frame={} stage={} avg_us={} min_us={} max_us={}",
+                self.frames,
+                stage.name(),
+                stats.avg,
+                stats.min,
+                stats.max
+            );
+        }
+
+        let bytes = self.bytes();
+        debug!(
+            "profile frame={} bytes_avg={} bytes_min={} bytes_max={}",
+            self.frames, bytes.avg, bytes.min, bytes.max
+        );
+    }
+
+    /// Writes the statistics as a table in microseconds, one line per stage,
+    /// then the bytes sent
+    pub fn report<W: Write>(&self, out: &mut W) -> fmt::Result {
+        writeln!(out, "frames={} window={}", self.frames, self.recorded())?;
+        writeln!(out, "{:<10}{:>8}{:>8}{:>8}", "stage", "avg", "min", "max")?;
+        for stage in Stage::ALL {
+            let stats = self.stats(stage);
+            writeln!(
+                out,
+                "{:<10}{:>8}{:>8}{:>8}",
+                stage.name(),
+                stats.avg,
+                stats.min,
+                stats.max
+            )?;
+        }
+
+        let bytes = self.bytes();
+        writeln!(
+            out,
+            "{:<10}{:>8}{:>8}{:>8}",
+            "bytes", bytes.avg, bytes.min, bytes.max
+        )
+    }
+
+    /// Draws the statistics starting at `position`, one line per stage
+    pub fn draw_overlay<D: DisplayTrait>(
+        &self,
+        display: &mut D,
+        overlay: Overlay,
+        position: Point,
+    ) -> Result<(), D::Error> {
+        if overlay == Overlay::Off {
+            return Ok(());
+        }
+
+        let mut text = TextBuffer::<32>::new();
+        let mut line = position;
+        for stage in Stage::ALL {
+            let stats = self.stats(stage);
+            text.clear();
+
+            match overlay {
+                Overlay::Text => {
+                    // Always fits
+                    let _ = write!(
+                        text,
+                        "{:<9}{:>6}{:>6}{:>6}",
+                        stage.name(),
+                        stats.avg,
+                        stats.min,
+                        stats.max
+                    );
+                    display.write(text.as_str(), line)?;
+                }
+                Overlay::Bars => {
+                    let _ = text.write_str(&stage.name()[..1]);
+                    display.write(text.as_str(), line)?;
+
+                    let length = (stats.avg / MICROS_PER_PIXEL) as i32;
+                    if length > 0 {
+                        let y = line.y + LINE_HEIGHT / 2;
+                        display.draw_line(
+                            Point::new(line.x + LABEL_WIDTH, y),
+                            Point::new(line.x + LABEL_WIDTH + length, y),
+                        )?;
+                    }
+                }
+                Overlay::Off => {}
+            }
+
+            line.y += LINE_HEIGHT;
+        }
+
+        if overlay == Overlay::Text {
+            let bytes = self.bytes();
+            text.clear();
+            let _ = write!(text, "bytes{:>8}", bytes.avg);
+            display.write(text.as_str(), line)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Default for Profile {
+    fn default() -> Self {
+        Self::new()
+    }
+}
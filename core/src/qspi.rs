@@ -0,0 +1,143 @@
+//! Quad-SPI display interface
+//!
+//! QSPI panels like the RM67162 wrap every transfer in a 1-byte instruction
+//! and a 24-bit address that carries the DCS command (`command << 8`).
+//! Register writes go out on a single data line, pixel data on all four.
+
+use core::fmt::Debug;
+use mipidsi::interface::Interface;
+
+/// Instruction for a register write, command and parameters on one line
+const WRITE_REGISTER: u8 = 0x02;
+/// Instruction for a pixel write, data on four lines
+const WRITE_PIXELS: u8 = 0x32;
+
+/// DCS "Memory Write" and "Memory Write Continue"
+const RAMWR: u8 = 0x2C;
+const RAMWRC: u8 = 0x3C;
+
+/// Number of data lines used for the data phase of a transfer
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DataLines {
+    Single,
+    Quad,
+}
+
+/// A bus that can send one QSPI write transaction
+///
+/// The instruction and address phases are always sent on a single line,
+/// chip select is asserted for the whole transaction.
+pub trait QspiBus {
+    /// Error type
+    type Error: Debug;
+
+    /// Sends `instruction`, the 24-bit `address` and then `data`
+    fn write(
+        &mut self,
+        instruction: u8,
+        address: u32,
+        data: &[u8],
+        data_lines: DataLines,
+    ) -> Result<(), Self::Error>;
+}
+
+/// `mipidsi` interface that sends commands and pixels over a [`QspiBus`]
+///
+/// A memory write command is not sent on its own but merged into the first
+/// pixel transfer that follows. Pixel data is split into chunks of the
+/// buffer size, chunks after the first continue the memory write.
+pub struct QspiInterface<'a, BUS> {
+    bus: BUS,
+    buffer: &'a mut [u8],
+    memory_write: Option<u8>,
+}
+
+impl<'a, BUS: QspiBus> QspiInterface<'a, BUS> {
+    pub fn new(bus: BUS, buffer: &'a mut [u8]) -> Self {
+        Self {
+            bus,
+            buffer,
+            memory_write: None,
+        }
+    }
+
+    fn flush(&mut self, command: &mut u8, len: usize) -> Result<(), BUS::Error> {
+        if len > 0 {
+            self.bus.write(
+                WRITE_PIXELS,
+                (*command as u32) << 8,
+                &self.buffer[..len],
+                DataLines::Quad,
+            )?;
+            *command = RAMWRC;
+        }
+        Ok(())
+    }
+}
+
+impl<BUS: QspiBus> Interface for QspiInterface<'_, BUS> {
+    type Word = u8;
+    type Error = BUS::Error;
+
+    fn send_command(&mut self, command: u8, args: &[u8]) -> Result<(), Self::Error> {
+        if command == RAMWR || command == RAMWRC {
+            // Sent together with the pixel data
+            self.memory_write = Some(command);
+            return Ok(());
+        }
+
+        self.memory_write = None;
+        self.bus.write(
+            WRITE_REGISTER,
+            (command as u32) << 8,
+            args,
+            DataLines::Single,
+        )
+    }
+
+    fn send_pixels<const N: usize>(
+        &mut self,
+        pixels: impl IntoIterator<Item = [Self::Word; N]>,
+    ) -> Result<(), Self::Error> {
+        let mut command = self.memory_write.take().unwrap_or(RAMWRC);
+        // Only whole pixels per chunk
+        let chunk_len = self.buffer.len() - self.buffer.len() % N;
+        let mut len = 0;
+
+        for pixel in pixels {
+            self.buffer[len..len + N].copy_from_slice(&pixel);
+            len += N;
+
+            if len == chunk_len {
+                self.flush(&mut command, len)?;
+                len = 0;
+            }
+        }
+
+        self.flush(&mut command, len)
+    }
+
+    fn send_repeated_pixel<const N: usize>(
+        &mut self,
+        pixel: [Self::Word; N],
+        count: u32,
+    ) -> Result<(), Self::Error> {
+        let mut command = self.memory_write.take().unwrap_or(RAMWRC);
+        let chunk_pixels = self.buffer.len() / N;
+        let mut remaining = count as usize;
+
+        // The buffer content is the same for every chunk
+        let filled = chunk_pixels.min(remaining);
+        for i in 0..filled {
+            self.buffer[i * N..(i + 1) * N].copy_from_slice(&pixel);
+        }
+
+        while remaining > 0 {
+            let n = chunk_pixels.min(remaining);
+            self.flush(&mut command, n * N)?;
+            remaining -= n;
+        }
+
+        Ok(())
+    }
+}
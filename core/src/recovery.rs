@@ -0,0 +1,148 @@
+//! Recovery from display errors
+//!
+//! Transient errors, like a failed SPI transfer, are retried with a growing
+//! delay. When they keep failing, or the panel stops answering, it is reset
+//! and initialized again, and when that does not help either the caller gives
+//! up. [`Recovery`] is pure logic like [`crate::power`], the caller reports
+//! the outcome of every frame and carries out the returned [`Action`];
+//! [`recover`] does that for a [`DisplayTrait`].
+
+use core::fmt::Debug;
+
+use embedded_hal_async::delay::DelayNs;
+use log::{info, warn};
+
+use crate::display::{DisplayError, DisplayTrait};
+
+/// How hard to try before giving up
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RecoveryLimits {
+    /// Retries of transient errors before the panel is re-initialized
+    pub retries: u32,
+    /// Delay before the first retry, doubled for every further one
+    pub first_delay_ms: u64,
+    pub max_delay_ms: u64,
+    /// Re-inits in a row, without a good frame in between, before giving up
+    pub reinits: u32,
+}
+
+/// How bad an error is
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    /// Retrying the same thing may work
+    Transient,
+    /// The panel has to be reset and initialized again
+    Persistent,
+    /// Nothing left to recover with, e.g. the interface is gone
+    Fatal,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Wait this long, then go on with the next frame
+    Retry {
+        delay_ms: u64,
+    },
+    /// Reset the panel and send its init sequence
+    Reinit,
+    GiveUp,
+}
+
+pub struct Recovery {
+    limits: RecoveryLimits,
+    /// Errors since the last good frame or re-init
+    failures: u32,
+    /// Re-inits since the last good frame
+    reinits: u32,
+}
+
+impl Recovery {
+    pub fn new(limits: RecoveryLimits) -> Self {
+        Self {
+            limits,
+            failures: 0,
+            reinits: 0,
+        }
+    }
+
+    /// A frame went through, the next error starts over
+    pub fn success(&mut self) {
+        self.failures = 0;
+        self.reinits = 0;
+    }
+
+    /// What to do about an error of `severity`
+    pub fn failure(&mut self, severity: Severity) -> Action {
+        if severity == Severity::Fatal {
+            return Action::GiveUp;
+        }
+
+        self.failures += 1;
+        if severity == Severity::Transient && self.failures <= self.limits.retries {
+            let delay_ms = self
+                .limits
+                .first_delay_ms
+                .saturating_mul(1 << (self.failures - 1).min(63))
+                .min(self.limits.max_delay_ms);
+            return Action::Retry { delay_ms };
+        }
+
+        if self.reinits < self.limits.reinits {
+            self.reinits += 1;
+            self.failures = 0;
+            Action::Reinit
+        } else {
+            Action::GiveUp
+        }
+    }
+}
+
+/// Recovers from the display error of a frame, if there was one
+///
+/// Waits before retries and initializes the panel again as [`Recovery`]
+/// says, then puts it back to sleep if it was `asleep`.
+///
+/// # Returns
+/// * `Ok(true)` if the panel was initialized again, it then needs the display
+///   settings again
+/// * `Ok(false)` if the next frame can go on as usual
+/// * `Err(error)` with the last error once nothing helps
+pub async fn recover<D, E>(
+    display: &mut D,
+    recovery: &mut Recovery,
+    delay: &mut impl DelayNs,
+    failed: Option<DisplayError<E>>,
+    asleep: bool,
+) -> Result<bool, DisplayError<E>>
+where
+    D: DisplayTrait<Error = DisplayError<E>>,
+    E: Debug,
+{
+    let Some(mut error) = failed else {
+        recovery.success();
+        return Ok(false);
+    };
+
+    loop {
+        warn!("Display error: {}", error);
+        match recovery.failure(error.severity()) {
+            Action::Retry { delay_ms } => {
+                display.invalidate();
+                delay.delay_ms(delay_ms.min(u32::MAX as u64) as u32).await;
+                return Ok(false);
+            }
+            Action::Reinit => {
+                info!("Initializing the display again");
+                let result = match display.reinit().await {
+                    Ok(()) if asleep => display.sleep().await,
+                    result => result,
+                };
+                match result {
+                    Ok(()) => return Ok(true),
+                    Err(next) => error = next,
+                }
+            }
+            Action::GiveUp => return Err(error),
+        }
+    }
+}
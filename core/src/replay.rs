@@ -0,0 +1,367 @@
+//! Input recording and deterministic replay
+//!
+//! A session is fully determined by the random seed, the settings it started
+//! with, the time of every frame, the raw touch events and the console and
+//! remote input, so that is all a recording holds. Replaying it feeds the main
+//! loop the same inputs frame by frame and reproduces the session exactly.
+//! Pixels drawn over Pixelflut are not recorded.
+//!
+//! Binary format, little endian:
+//!
+//! - magic `PXR2`
+//! - random seed, `u32`
+//! - settings [`VERSION`] (`u8`) and the settings encoded in it
+//! - per frame a LEB128 varint `dt_ms << 2 | touch`, followed by the touch
+//!   phase (`u8`, down/move/up) and raw x and y (`u16`) if `touch` is set
+//! - after its frame, per console or remote command a varint `len << 2 | 2`
+//!   and the `len` bytes of the command line, per remote touch a varint `3`,
+//!   the phase and the x and y on the screen
+//!
+//! Recordings are dumped over serial as hex lines prefixed with `rec:`, e.g.
+//! `sed -n 's/^rec://p' log.txt | xxd -r -p > replay.bin`.
+
+use alloc::vec::Vec;
+use core::fmt::{self, Write};
+use embedded_graphics::prelude::Point;
+use log::{info, warn};
+use pixels_protocol::hex::Hex;
+
+use crate::config::RECORDING_CAPACITY;
+use crate::gesture::{Phase, TouchSample};
+use crate::settings::{Settings, ENCODED_LEN, VERSION};
+
+const MAGIC: &[u8; 4] = b"PXR2";
+const HEADER_LEN: usize = 9 + ENCODED_LEN;
+/// Longest varint of an entry
+const MAX_VARINT_LEN: usize = 5;
+/// Longest frame or remote touch entry: varint, phase and position
+const MAX_FRAME_LEN: usize = MAX_VARINT_LEN + 5;
+
+/// Kinds of entries, in the low two bits of their varint
+const FRAME: u64 = 0;
+const TOUCH_FRAME: u64 = 1;
+const COMMAND: u64 = 2;
+const REMOTE_TOUCH: u64 = 3;
+/// Bytes per dumped line
+const DUMP_LINE_LEN: usize = 32;
+
+/// Where the input of a session comes from
+#[allow(dead_code)]
+#[derive(Clone, Copy, Debug)]
+pub enum InputSource {
+    Live,
+    /// Live input, recorded until [`RECORDING_CAPACITY`] is reached
+    Record,
+    /// A recording, then live input once it ends
+    Replay(&'static [u8]),
+}
+
+/// Input of one frame
+pub struct Frame {
+    /// Milliseconds since the session started
+    pub time_ms: u64,
+    /// Raw touch event, in touch controller coordinates
+    pub touch: Option<TouchSample>,
+}
+
+pub struct Session {
+    seed: u32,
+    settings: Settings,
+    /// Live clock at session time 0
+    start_ms: u64,
+    /// Session time of the last frame
+    time_ms: u64,
+    /// The recording, `Some` while recording
+    recording: Option<Vec<u8>>,
+    /// Length of the recording before the entry of the current frame
+    frame_start: usize,
+    /// The recording ran out of room, it ends with the last whole frame
+    full: bool,
+    /// Rest of the replay, `Some` while replaying
+    replay: Option<&'static [u8]>,
+}
+
+impl Session {
+    /// Starts a session at `now_ms`, `seed` and `settings` are used unless
+    /// replaying
+    pub fn new(source: InputSource, seed: u32, settings: Settings, now_ms: u64) -> Self {
+        let mut session = Self {
+            seed,
+            settings,
+            start_ms: now_ms,
+            time_ms: 0,
+            recording: None,
+            frame_start: 0,
+            full: false,
+            replay: None,
+        };
+
+        match source {
+            InputSource::Live => {}
+            InputSource::Record => {
+                let mut recording = Vec::with_capacity(RECORDING_CAPACITY);
+                recording.extend_from_slice(MAGIC);
+                recording.extend_from_slice(&seed.to_le_bytes());
+                recording.push(VERSION);
+                recording.extend_from_slice(&settings.encode());
+                session.recording = Some(recording);
+                info!("Recording input, seed {}", seed);
+            }
+            InputSource::Replay(data) => match read_header(data) {
+                Some((seed, settings, entries)) => {
+                    session.seed = seed;
+                    session.settings = settings;
+                    session.replay = Some(entries);
+                    info!("Replaying input, seed {}", seed);
+                }
+                None => warn!("Not a recording, using live input"),
+            },
+        }
+
+        session
+    }
+
+    pub fn seed(&self) -> u32 {
+        self.seed
+    }
+
+    /// The settings to start with, the recorded ones when replaying
+    pub fn settings(&self) -> Settings {
+        self.settings
+    }
+
+    /// Input of the next frame: the live clock and `touch`, or the next frame
+    /// of the replay
+    pub fn frame(&mut self, now_ms: u64, touch: Option<TouchSample>) -> Frame {
+        while let Some(data) = self.replay {
+            match read_entry(data) {
+                // Input of the last frame that was not asked for
+                Some((Entry::Command(_) | Entry::RemoteTouch(_), rest)) => {
+                    self.replay = Some(rest);
+                }
+                Some((Entry::Frame(dt_ms, touch), rest)) => {
+                    self.replay = Some(rest);
+                    self.time_ms += dt_ms;
+                    return Frame {
+                        time_ms: self.time_ms,
+                        touch: touch.map(|(phase, position)| TouchSample {
+                            phase,
+                            position,
+                            time_ms: self.time_ms,
+                        }),
+                    };
+                }
+                None => {
+                    info!("Replay finished, using live input");
+                    self.replay = None;
+                    // Continue the session clock from the last replayed frame
+                    self.start_ms = now_ms.saturating_sub(self.time_ms);
+                }
+            }
+        }
+
+        let time_ms = now_ms.saturating_sub(self.start_ms).max(self.time_ms);
+        let dt_ms = time_ms - self.time_ms;
+        self.time_ms = time_ms;
+        let touch = touch.map(|sample| TouchSample { time_ms, ..sample });
+
+        self.frame_start = self.recording.as_ref().map_or(0, Vec::len);
+        self.record(MAX_FRAME_LEN, |recording| {
+            write_frame(recording, dt_ms, touch)
+        });
+
+        Frame { time_ms, touch }
+    }
+
+    /// The next console or remote command line of the frame: the recorded one
+    /// when replaying, which ignores `live`, otherwise `live`
+    pub fn command<'a>(&mut self, live: Option<&'a str>) -> Option<&'a str> {
+        if let Some(data) = self.replay {
+            let (Entry::Command(line), rest) = read_entry(data)? else {
+                return None;
+            };
+            self.replay = Some(rest);
+            return Some(line);
+        }
+
+        if let Some(line) = live {
+            self.record(MAX_VARINT_LEN + line.len(), |recording| {
+                write_varint(recording, (line.len() as u64) << 2 | COMMAND);
+                recording.extend_from_slice(line.as_bytes());
+            });
+        }
+        live
+    }
+
+    /// The touch of a remote client in this frame, in screen coordinates,
+    /// like [`Session::command`]
+    pub fn remote_touch(&mut self, live: Option<(Phase, Point)>) -> Option<(Phase, Point)> {
+        if let Some(data) = self.replay {
+            let (Entry::RemoteTouch(touch), rest) = read_entry(data)? else {
+                return None;
+            };
+            self.replay = Some(rest);
+            return Some(touch);
+        }
+
+        if let Some((phase, position)) = live {
+            self.record(MAX_FRAME_LEN, |recording| {
+                write_varint(recording, REMOTE_TOUCH);
+                write_touch(recording, phase, position);
+            });
+        }
+        live
+    }
+
+    /// Appends an entry of at most `max_len` bytes to the recording
+    ///
+    /// Without room for it the recording is full and ends with the frame
+    /// before this one, a frame missing some of its input would not replay
+    /// exactly.
+    fn record(&mut self, max_len: usize, write: impl FnOnce(&mut Vec<u8>)) {
+        let Some(recording) = self.recording.as_mut().filter(|_| !self.full) else {
+            return;
+        };
+        if recording.len() + max_len > RECORDING_CAPACITY {
+            recording.truncate(self.frame_start);
+            self.full = true;
+        } else {
+            write(recording);
+        }
+    }
+
+    /// The recording once it is full, which ends it, `None` while there is
+    /// room or when not recording. Check after every frame.
+    pub fn take_full_recording(&mut self) -> Option<Vec<u8>> {
+        if !self.full {
+            return None;
+        }
+        let recording = self.recording.take()?;
+        info!("Recording full after {} bytes", recording.len());
+        Some(recording)
+    }
+
+    /// Writes the recording so far like [`dump`], does nothing when not recording
+    pub fn dump<W: Write>(&self, out: &mut W) -> fmt::Result {
+        match self.recording.as_ref() {
+            Some(recording) => dump(recording, out),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Writes a recording as hex lines prefixed with `rec:`
+pub fn dump<W: Write>(recording: &[u8], out: &mut W) -> fmt::Result {
+    info!("Dumping {} bytes of recording", recording.len());
+    for line in recording.chunks(DUMP_LINE_LEN) {
+        writeln!(out, "rec:{}", Hex(line))?;
+    }
+    Ok(())
+}
+
+/// Touch event of a recorded frame, in touch controller coordinates
+type RecordedTouch = Option<(Phase, Point)>;
+
+/// An entry of a recording
+enum Entry<'a> {
+    /// Time since the frame before and its touch event
+    Frame(u64, RecordedTouch),
+    Command(&'a str),
+    RemoteTouch((Phase, Point)),
+}
+
+/// Seed, settings and the entries of a recording, `None` if it is not one
+fn read_header(data: &[u8]) -> Option<(u32, Settings, &[u8])> {
+    let (header, entries) = data.split_first_chunk::<HEADER_LEN>()?;
+    let (magic, header) = header.split_first_chunk::<4>()?;
+    let (seed, header) = header.split_first_chunk::<4>()?;
+    let (&version, settings) = header.split_first()?;
+    if magic != MAGIC {
+        return None;
+    }
+    let settings = Settings::decode(version, settings)?;
+    Some((u32::from_le_bytes(*seed), settings, entries))
+}
+
+fn write_frame(out: &mut Vec<u8>, dt_ms: u64, touch: Option<TouchSample>) {
+    let dt_ms = dt_ms.min(u32::MAX as u64 >> 2);
+    match touch {
+        Some(sample) => {
+            write_varint(out, dt_ms << 2 | TOUCH_FRAME);
+            write_touch(out, sample.phase, sample.position);
+        }
+        None => write_varint(out, dt_ms << 2 | FRAME),
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            break;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn write_touch(out: &mut Vec<u8>, phase: Phase, position: Point) {
+    out.push(match phase {
+        Phase::Down => 0,
+        Phase::Move => 1,
+        Phase::Up => 2,
+    });
+    out.extend_from_slice(&(position.x as u16).to_le_bytes());
+    out.extend_from_slice(&(position.y as u16).to_le_bytes());
+}
+
+/// The next entry and the rest of the data, `None` at the end or on a
+/// truncated or broken entry
+fn read_entry(data: &[u8]) -> Option<(Entry<'_>, &[u8])> {
+    let mut value = 0u64;
+    let mut len = 0;
+    loop {
+        let byte = *data.get(len)?;
+        value |= ((byte & 0x7F) as u64) << (7 * len);
+        len += 1;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        if len == MAX_VARINT_LEN {
+            return None;
+        }
+    }
+
+    let rest = &data[len..];
+    match value & 3 {
+        FRAME => Some((Entry::Frame(value >> 2, None), rest)),
+        TOUCH_FRAME => {
+            let (touch, rest) = read_touch(rest)?;
+            Some((Entry::Frame(value >> 2, Some(touch)), rest))
+        }
+        COMMAND => {
+            let (line, rest) = rest.split_at_checked((value >> 2) as usize)?;
+            Some((Entry::Command(core::str::from_utf8(line).ok()?), rest))
+        }
+        _ => {
+            let (touch, rest) = read_touch(rest)?;
+            Some((Entry::RemoteTouch(touch), rest))
+        }
+    }
+}
+
+fn read_touch(data: &[u8]) -> Option<((Phase, Point), &[u8])> {
+    let (&[phase, x0, x1, y0, y1], rest) = data.split_first_chunk::<5>()?;
+    let phase = match phase {
+        0 => Phase::Down,
+        1 => Phase::Move,
+        2 => Phase::Up,
+        _ => return None,
+    };
+    let position = Point::new(
+        u16::from_le_bytes([x0, x1]) as i32,
+        u16::from_le_bytes([y0, y1]) as i32,
+    );
+    Some(((phase, position), rest))
+}
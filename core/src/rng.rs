@@ -0,0 +1,33 @@
+//! Seeded pseudo-random numbers
+//!
+//! The scene only takes its randomness from here, so a recorded seed
+//! reproduces it exactly (see [`crate::replay`]).
+
+/// Xorshift32 generator
+pub struct Rng {
+    state: u32,
+}
+
+impl Rng {
+    pub fn new(seed: u32) -> Self {
+        // Zero is the one state xorshift never leaves
+        Self {
+            state: if seed == 0 { 0x9E37_79B9 } else { seed },
+        }
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 17;
+        x ^= x << 5;
+        self.state = x;
+        x
+    }
+
+    /// Uniform in `0.0..1.0`
+    pub fn next_f32(&mut self) -> f32 {
+        // 24 bits, all an f32 mantissa holds
+        (self.next_u32() >> 8) as f32 / (1 << 24) as f32
+    }
+}
@@ -0,0 +1,240 @@
+//! What the main loop draws
+
+use embedded_graphics::pixelcolor::{Rgb565, RgbColor};
+use embedded_graphics::prelude::{Point, Size};
+use embedded_graphics::primitives::Rectangle;
+use log::debug;
+use micromath::F32Ext;
+
+use crate::arcball::Arcball;
+use crate::display::DisplayTrait;
+use crate::gesture::Gesture;
+use crate::math::{self, Projection, Rotation, Vec3};
+use crate::rng::Rng;
+use crate::settings::Settings;
+
+/// Most particles alive at once
+const MAX_PARTICLES: usize = 200;
+
+const CUBE_VERTICES: [(f32, f32, f32); 8] = [
+    (-1.0, -1.0, -1.0),
+    (1.0, -1.0, -1.0),
+    (1.0, 1.0, -1.0),
+    (-1.0, 1.0, -1.0),
+    (-1.0, -1.0, 1.0),
+    (1.0, -1.0, 1.0),
+    (1.0, 1.0, 1.0),
+    (-1.0, 1.0, 1.0),
+];
+
+/// Pairs of [`CUBE_VERTICES`]
+const CUBE_EDGES: [(usize, usize); 12] = [
+    (0, 1),
+    (1, 2),
+    (2, 3),
+    (3, 0), // Back face
+    (4, 5),
+    (5, 6),
+    (6, 7),
+    (7, 4), // Front face
+    (0, 4),
+    (1, 5),
+    (2, 6),
+    (3, 7), // Connecting edges
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Scene {
+    /// Cube edges and particles
+    Cube,
+    /// Cube edges only
+    Wireframe,
+    /// Particles only
+    Particles,
+    /// A persistent canvas, drawn on over Pixelflut with the `pixelflut` feature
+    Canvas,
+}
+
+impl Scene {
+    pub const ALL: [Scene; 4] = [
+        Scene::Cube,
+        Scene::Wireframe,
+        Scene::Particles,
+        Scene::Canvas,
+    ];
+
+    /// Names of [`Scene::ALL`], in the same order
+    pub const NAMES: [&'static str; 4] = ["cube", "wireframe", "particles", "canvas"];
+
+    pub fn name(self) -> &'static str {
+        Self::NAMES[self as usize]
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::NAMES
+            .iter()
+            .position(|&n| n == name)
+            .map(|index| Self::ALL[index])
+    }
+
+    pub fn draws_cube(self) -> bool {
+        matches!(self, Scene::Cube | Scene::Wireframe)
+    }
+
+    pub fn draws_particles(self) -> bool {
+        matches!(self, Scene::Cube | Scene::Particles)
+    }
+
+    /// Whether the frame buffer keeps a background for the scene
+    pub fn has_canvas(self) -> bool {
+        self == Scene::Canvas
+    }
+}
+
+#[derive(Clone, Copy)]
+struct Particle {
+    pos: Vec3,
+    vel: Vec3,
+    active: bool,
+    color: Rgb565,
+}
+
+/// The cube and its particles, what the main loop animates and draws
+///
+/// Its randomness comes from a seeded [`Rng`], so a replayed session animates
+/// the same way (see [`crate::replay`]).
+pub struct World {
+    pub arcball: Arcball,
+    rotation: Rotation,
+    particles: [Particle; MAX_PARTICLES],
+    rng: Rng,
+}
+
+impl World {
+    pub fn new(arcball: Arcball, seed: u32) -> Self {
+        Self {
+            arcball,
+            rotation: Rotation::IDENTITY,
+            particles: [Particle {
+                pos: math::vec3(0.0, 0.0, 0.0),
+                vel: math::vec3(0.0, 0.0, 0.0),
+                active: false,
+                color: Rgb565::WHITE,
+            }; MAX_PARTICLES],
+            rng: Rng::new(seed),
+        }
+    }
+
+    /// Rotates the cube with drags and flings, other gestures are only logged
+    pub fn gesture(&mut self, gesture: Gesture) {
+        match gesture {
+            Gesture::Drag { position, delta } => {
+                if let Some(drag) = self.arcball.drag(position - delta, position) {
+                    self.rotation = math::compose(drag, self.rotation);
+                }
+            }
+            Gesture::Fling { vx, vy } => self.arcball.fling(vx, vy),
+            other => debug!("gesture {:?}", other),
+        }
+    }
+
+    /// Animates a frame of `dt` seconds: the spin of a fling or `auto_rotation`,
+    /// new particles from the center and their movement
+    pub fn step(&mut self, dt: f32, settings: &Settings, auto_rotation: Rotation) {
+        if let Some(spin) = self.arcball.step(dt) {
+            self.rotation = math::compose(spin, self.rotation);
+        } else if settings.auto_rotate && !self.arcball.is_active() {
+            self.rotation = math::compose(auto_rotation, self.rotation);
+        }
+
+        for _ in 0..settings.emission_rate {
+            // Find an inactive particle slot
+            if let Some(p) = self.particles.iter_mut().find(|p| !p.active) {
+                let rand_x = self.rng.next_f32() * 2.0 - 1.0;
+                let rand_y = self.rng.next_f32() * 2.0 - 1.0;
+                let rand_z = self.rng.next_f32() * 2.0 - 1.0;
+
+                // Normalize direction and apply speed
+                let speed = settings.particle_speed;
+                let len = (rand_x * rand_x + rand_y * rand_y + rand_z * rand_z).sqrt();
+                let vel = if len > 0.01 {
+                    math::vec3(
+                        rand_x / len * speed,
+                        rand_y / len * speed,
+                        rand_z / len * speed,
+                    )
+                } else {
+                    math::vec3(speed, 0.0, 0.0)
+                };
+
+                // Generate random color
+                let color_seed = self.rng.next_f32();
+                let color = if color_seed < 0.166 {
+                    Rgb565::RED
+                } else if color_seed < 0.333 {
+                    Rgb565::GREEN
+                } else if color_seed < 0.5 {
+                    Rgb565::BLUE
+                } else if color_seed < 0.666 {
+                    Rgb565::YELLOW
+                } else if color_seed < 0.833 {
+                    Rgb565::CYAN
+                } else {
+                    Rgb565::MAGENTA
+                };
+
+                p.pos = math::vec3(0.0, 0.0, 0.0); // Emit from center
+                p.vel = vel;
+                p.active = true;
+                p.color = color;
+            }
+        }
+
+        // Update position, constrained to cube boundaries
+        for p in self.particles.iter_mut().filter(|p| p.active) {
+            math::step_particle(&mut p.pos, &mut p.vel);
+        }
+    }
+
+    /// Removes all particles
+    pub fn reset(&mut self) {
+        self.particles.iter_mut().for_each(|p| p.active = false);
+    }
+
+    /// Draws what `scene` shows of the cube and the particles
+    pub fn draw<D: DisplayTrait>(
+        &self,
+        display: &mut D,
+        projection: &Projection,
+        scene: Scene,
+    ) -> Result<(), D::Error> {
+        if scene.draws_cube() {
+            let cube = CUBE_VERTICES
+                .map(|(x, y, z)| projection.project(self.rotation, math::vec3(x, y, z)));
+            for (start, end) in CUBE_EDGES {
+                if let (Some(start), Some(end)) = (cube[start], cube[end]) {
+                    display.draw_line(start, end)?;
+                }
+            }
+        }
+
+        if scene.draws_particles() {
+            let size = display.size();
+            let (width, height) = (size.width as i32, size.height as i32);
+            for p in self.particles.iter().filter(|p| p.active) {
+                // Drawn as a 3x3 point, away from the screen edges
+                match projection.project(self.rotation, p.pos) {
+                    Some(point)
+                        if (1..width - 1).contains(&point.x)
+                            && (1..height - 1).contains(&point.y) =>
+                    {
+                        let area = Rectangle::new(point - Point::new(1, 1), Size::new(3, 3));
+                        display.fill_rect(area, p.color)?;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        Ok(())
+    }
+}
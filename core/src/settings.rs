@@ -0,0 +1,414 @@
+//! Scene settings that can be changed at runtime
+//!
+//! Settings are validated when loaded and encoded in a versioned binary
+//! format for [`crate::storage`], which keeps them in flash across reboots.
+
+use core::fmt::{self, Write};
+use core::ops::RangeInclusive;
+use embedded_graphics::prelude::{Point, Size};
+use embedded_graphics::primitives::Rectangle;
+
+use crate::config::TOUCH_CALIBRATION;
+use crate::display::DisplayTrait;
+use crate::text::TextBuffer;
+use crate::touch::Calibration;
+use crate::ui::Ui;
+
+/// Width of the settings panel, it sits at the right edge of the screen
+const PANEL_WIDTH: u32 = 230;
+/// Height of a settings row, less on screens too short for all of them
+const ROW_HEIGHT: i32 = 30;
+/// Rows above the close button, the sliders and the auto-rotate toggle
+const ROWS: i32 = 6;
+/// Space around the rows inside the panel
+const MARGIN: i32 = 8;
+/// Width of the value label left of a slider
+const LABEL_WIDTH: i32 = 104;
+const SLIDER_HEIGHT: u32 = 16;
+const TOGGLE_SIZE: u32 = 20;
+const BUTTON_HEIGHT: u32 = 30;
+
+/// Valid ranges, also the ranges of the settings sliders
+const ROTATION_SPEED_RANGE: RangeInclusive<f32> = 0.0..=0.1;
+const EMISSION_RATE_RANGE: RangeInclusive<f32> = 0.0..=10.0;
+const PARTICLE_SPEED_RANGE: RangeInclusive<f32> = 0.005..=0.05;
+const FOV_RANGE: RangeInclusive<f32> = 50.0..=400.0;
+const PROJECTION_DISTANCE_RANGE: RangeInclusive<f32> = 2.0..=10.0;
+/// Never fully dark, the screen could not be found again
+const BRIGHTNESS_RANGE: RangeInclusive<f32> = 16.0..=255.0;
+const GAMMA_RANGE: RangeInclusive<f32> = 0.5..=2.5;
+const CONTRAST_RANGE: RangeInclusive<f32> = 0.5..=1.5;
+
+/// Names of the settings that can be read and set by name, see
+/// [`Settings::write_value`] and [`Settings::set`]
+pub const KEYS: [&str; 11] = [
+    "auto_brightness",
+    "auto_rotate",
+    "brightness",
+    "contrast",
+    "emission_rate",
+    "fov",
+    "gamma",
+    "invert",
+    "particle_speed",
+    "projection_distance",
+    "rotation_speed",
+];
+
+/// Version of the encoding written by [`Settings::encode`]
+pub const VERSION: u8 = 2;
+/// Length of the encoded settings
+pub const ENCODED_LEN: usize = 47;
+/// Length of version 1, without the display settings
+const ENCODED_LEN_V1: usize = 42;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Settings {
+    /// Automatic rotation around the Y axis, radians per frame
+    pub rotation_speed: f32,
+    /// Rotate automatically while the cube is not touched
+    pub auto_rotate: bool,
+    /// Particles emitted per frame
+    pub emission_rate: usize,
+    /// Particle speed in cube units per frame
+    pub particle_speed: f32,
+    /// Field of view of the projection
+    pub fov: f32,
+    /// Distance of the camera from the cube center
+    pub projection_distance: f32,
+    /// Correction of touch positions, measured by the touch calibration
+    pub touch_calibration: Calibration,
+    /// Panel brightness while in use, 0 to 255
+    pub brightness: u8,
+    /// Dim the screen when idle
+    pub auto_brightness: bool,
+    /// Gamma and contrast of the color correction, `1.0` leaves colors unchanged
+    pub gamma: f32,
+    pub contrast: f32,
+    /// Show the colors inverted
+    pub invert: bool,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            rotation_speed: 0.03,
+            auto_rotate: true,
+            emission_rate: 3,
+            particle_speed: 0.02,
+            fov: 200.0,
+            projection_distance: 4.0,
+            touch_calibration: TOUCH_CALIBRATION,
+            brightness: 255,
+            auto_brightness: true,
+            gamma: 1.0,
+            contrast: 1.0,
+            invert: false,
+        }
+    }
+}
+
+impl Settings {
+    /// Settings with every value in its valid range, out of range values are
+    /// clamped and values that are not numbers reset to their default
+    pub fn validated(self) -> Self {
+        let default = Self::default();
+        let calibration = self.touch_calibration;
+        let calibration_finite = [
+            calibration.a,
+            calibration.b,
+            calibration.c,
+            calibration.d,
+            calibration.e,
+            calibration.f,
+        ]
+        .iter()
+        .all(|value| value.is_finite());
+
+        Self {
+            rotation_speed: clamp(
+                self.rotation_speed,
+                default.rotation_speed,
+                ROTATION_SPEED_RANGE,
+            ),
+            auto_rotate: self.auto_rotate,
+            emission_rate: self.emission_rate.min(*EMISSION_RATE_RANGE.end() as usize),
+            particle_speed: clamp(
+                self.particle_speed,
+                default.particle_speed,
+                PARTICLE_SPEED_RANGE,
+            ),
+            fov: clamp(self.fov, default.fov, FOV_RANGE),
+            projection_distance: clamp(
+                self.projection_distance,
+                default.projection_distance,
+                PROJECTION_DISTANCE_RANGE,
+            ),
+            touch_calibration: if calibration_finite {
+                calibration
+            } else {
+                default.touch_calibration
+            },
+            brightness: self.brightness.max(*BRIGHTNESS_RANGE.start() as u8),
+            auto_brightness: self.auto_brightness,
+            gamma: clamp(self.gamma, default.gamma, GAMMA_RANGE),
+            contrast: clamp(self.contrast, default.contrast, CONTRAST_RANGE),
+            invert: self.invert,
+        }
+    }
+
+    /// Encodes the settings in the format of [`VERSION`], little endian
+    pub fn encode(&self) -> [u8; ENCODED_LEN] {
+        let calibration = self.touch_calibration;
+        let floats = [
+            self.rotation_speed,
+            self.particle_speed,
+            self.fov,
+            self.projection_distance,
+            calibration.a,
+            calibration.b,
+            calibration.c,
+            calibration.d,
+            calibration.e,
+            calibration.f,
+        ];
+
+        let mut bytes = [0; ENCODED_LEN];
+        for (value, chunk) in floats.iter().zip(bytes.chunks_exact_mut(4)) {
+            chunk.copy_from_slice(&value.to_le_bytes());
+        }
+        bytes[40] = self.emission_rate.min(u8::MAX as usize) as u8;
+        bytes[41] = self.auto_rotate as u8;
+        bytes[42] = self.brightness;
+        bytes[43] = self.auto_brightness as u8;
+        // Hundredths are plenty for the color correction
+        bytes[44] = (self.gamma * 100.0 + 0.5) as u8;
+        bytes[45] = (self.contrast * 100.0 + 0.5) as u8;
+        bytes[46] = self.invert as u8;
+        bytes
+    }
+
+    /// Decodes settings written in format `version`, `None` for unknown
+    /// versions or a wrong length. The result is validated, settings missing
+    /// in older versions are the defaults.
+    pub fn decode(version: u8, bytes: &[u8]) -> Option<Self> {
+        match (version, bytes.len()) {
+            (1, ENCODED_LEN_V1) | (VERSION, ENCODED_LEN) => {}
+            _ => return None,
+        }
+
+        let mut floats = [0.0; 10];
+        for (value, chunk) in floats.iter_mut().zip(bytes.chunks_exact(4)) {
+            *value = f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+        let [rotation_speed, particle_speed, fov, projection_distance, a, b, c, d, e, f] = floats;
+
+        let mut settings = Self {
+            rotation_speed,
+            auto_rotate: bytes[41] != 0,
+            emission_rate: bytes[40] as usize,
+            particle_speed,
+            fov,
+            projection_distance,
+            touch_calibration: Calibration { a, b, c, d, e, f },
+            ..Self::default()
+        };
+        if version >= 2 {
+            settings.brightness = bytes[42];
+            settings.auto_brightness = bytes[43] != 0;
+            settings.gamma = bytes[44] as f32 / 100.0;
+            settings.contrast = bytes[45] as f32 / 100.0;
+            settings.invert = bytes[46] != 0;
+        }
+        Some(settings.validated())
+    }
+
+    /// Writes the value of the setting named `key`, nothing for unknown keys
+    pub fn write_value<W: Write>(&self, key: &str, out: &mut W) -> fmt::Result {
+        match key {
+            "auto_brightness" => write!(out, "{}", on_off(self.auto_brightness)),
+            "auto_rotate" => write!(out, "{}", on_off(self.auto_rotate)),
+            "brightness" => write!(out, "{}", self.brightness),
+            "contrast" => write!(out, "{}", self.contrast),
+            "emission_rate" => write!(out, "{}", self.emission_rate),
+            "fov" => write!(out, "{}", self.fov),
+            "gamma" => write!(out, "{}", self.gamma),
+            "invert" => write!(out, "{}", on_off(self.invert)),
+            "particle_speed" => write!(out, "{}", self.particle_speed),
+            "projection_distance" => write!(out, "{}", self.projection_distance),
+            "rotation_speed" => write!(out, "{}", self.rotation_speed),
+            _ => Ok(()),
+        }
+    }
+
+    /// Sets the setting named `key` from its text form, clamped to its valid
+    /// range. Returns `false` for unknown keys and values that do not parse.
+    pub fn set(&mut self, key: &str, value: &str) -> bool {
+        let number = value.parse::<f32>().ok().filter(|n| n.is_finite());
+        match (key, number) {
+            ("auto_brightness", _) => match parse_switch(value) {
+                Some(on) => self.auto_brightness = on,
+                None => return false,
+            },
+            ("auto_rotate", _) => match parse_switch(value) {
+                Some(on) => self.auto_rotate = on,
+                None => return false,
+            },
+            ("brightness", Some(level)) if level >= 0.0 => {
+                self.brightness = (level + 0.5).min(255.0) as u8;
+            }
+            ("contrast", Some(contrast)) => self.contrast = contrast,
+            ("emission_rate", Some(rate)) if rate >= 0.0 => {
+                self.emission_rate = (rate + 0.5) as usize;
+            }
+            ("fov", Some(fov)) => self.fov = fov,
+            ("gamma", Some(gamma)) => self.gamma = gamma,
+            ("invert", _) => match parse_switch(value) {
+                Some(on) => self.invert = on,
+                None => return false,
+            },
+            ("particle_speed", Some(speed)) => self.particle_speed = speed,
+            ("projection_distance", Some(distance)) => self.projection_distance = distance,
+            ("rotation_speed", Some(speed)) => self.rotation_speed = speed,
+            _ => return false,
+        }
+
+        *self = self.validated();
+        true
+    }
+
+    /// Draws the settings panel at the right edge of the screen, returns
+    /// `true` if a setting changed. The close button clears `open`.
+    pub fn edit<D: DisplayTrait>(
+        &mut self,
+        ui: &mut Ui,
+        display: &mut D,
+        open: &mut bool,
+    ) -> Result<bool, D::Error> {
+        let screen = display.size();
+        let left = screen.width.saturating_sub(PANEL_WIDTH) as i32;
+        ui.panel(
+            display,
+            Rectangle::new(Point::new(left, 0), Size::new(PANEL_WIDTH, screen.height)),
+        )?;
+
+        let width = PANEL_WIDTH - 2 * MARGIN as u32;
+        // On short screens the rows move closer so the close button fits
+        let bottom = screen.height as i32 - MARGIN;
+        let row_height = ROW_HEIGHT.min((bottom - MARGIN - BUTTON_HEIGHT as i32) / ROWS);
+        let mut row = Point::new(left + MARGIN, MARGIN);
+        let mut changed = false;
+        let mut text = TextBuffer::<16>::new();
+
+        let _ = write!(text, "Spin {:.3}", self.rotation_speed);
+        changed |= slider(
+            ui,
+            display,
+            row,
+            &text,
+            &mut self.rotation_speed,
+            ROTATION_SPEED_RANGE,
+        )?;
+        row.y += row_height;
+
+        text.clear();
+        let _ = write!(text, "Rate {}", self.emission_rate);
+        let mut rate = self.emission_rate as f32;
+        if slider(ui, display, row, &text, &mut rate, EMISSION_RATE_RANGE)? {
+            let rate = (rate + 0.5) as usize;
+            changed |= rate != self.emission_rate;
+            self.emission_rate = rate;
+        }
+        row.y += row_height;
+
+        text.clear();
+        let _ = write!(text, "Vel {:.3}", self.particle_speed);
+        changed |= slider(
+            ui,
+            display,
+            row,
+            &text,
+            &mut self.particle_speed,
+            PARTICLE_SPEED_RANGE,
+        )?;
+        row.y += row_height;
+
+        text.clear();
+        let _ = write!(text, "FOV {:.0}", self.fov);
+        changed |= slider(ui, display, row, &text, &mut self.fov, FOV_RANGE)?;
+        row.y += row_height;
+
+        text.clear();
+        let _ = write!(text, "Bright {}", self.brightness);
+        let mut brightness = self.brightness as f32;
+        if slider(ui, display, row, &text, &mut brightness, BRIGHTNESS_RANGE)? {
+            let brightness = (brightness + 0.5) as u8;
+            changed |= brightness != self.brightness;
+            self.brightness = brightness;
+        }
+        row.y += row_height;
+
+        changed |= ui.toggle(
+            display,
+            Rectangle::new(row, Size::new(width, TOGGLE_SIZE)),
+            "Auto-rotate",
+            &mut self.auto_rotate,
+        )?;
+        row.y = (row.y + row_height + MARGIN).min(bottom - BUTTON_HEIGHT as i32);
+
+        if ui.button(
+            display,
+            Rectangle::new(row, Size::new(width, BUTTON_HEIGHT)),
+            "Close",
+        )? {
+            *open = false;
+        }
+
+        Ok(changed)
+    }
+}
+
+/// Value label with a slider right of it
+fn slider<D: DisplayTrait>(
+    ui: &mut Ui,
+    display: &mut D,
+    row: Point,
+    label: &TextBuffer<16>,
+    value: &mut f32,
+    range: RangeInclusive<f32>,
+) -> Result<bool, D::Error> {
+    ui.label(display, row, label.as_str())?;
+
+    let width = PANEL_WIDTH as i32 - 2 * MARGIN - LABEL_WIDTH;
+    let area = Rectangle::new(
+        Point::new(row.x + LABEL_WIDTH, row.y + 2),
+        Size::new(width as u32, SLIDER_HEIGHT),
+    );
+    ui.slider(display, area, value, *range.start(), *range.end())
+}
+
+fn on_off(on: bool) -> &'static str {
+    if on {
+        "on"
+    } else {
+        "off"
+    }
+}
+
+/// `on` or `off` and the like, `None` for anything else
+fn parse_switch(value: &str) -> Option<bool> {
+    match value {
+        "on" | "true" | "1" => Some(true),
+        "off" | "false" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// `value` clamped to `range`, `default` if it is not a number
+fn clamp(value: f32, default: f32, range: RangeInclusive<f32>) -> f32 {
+    if value.is_nan() {
+        default
+    } else {
+        value.clamp(*range.start(), *range.end())
+    }
+}
@@ -0,0 +1,194 @@
+//! Wear-levelled settings storage in a flash partition
+//!
+//! Works on any [`NorFlash`], the firmware opens the partition with
+//! `storage::open` in its own `src/storage.rs`.
+//!
+//! The partition is split into slots of [`SLOT_SIZE`] bytes. Every save
+//! writes a new record into the slot after the newest one, so writes move
+//! through the whole partition before a sector is erased again. A sector is
+//! erased when the next record starts in it, the newest record is always in
+//! another sector at that point, so the partition needs at least
+//! [`MIN_SECTORS`] sectors. On start the newest valid record is found by its
+//! sequence number.
+//!
+//! Record layout, little endian:
+//!
+//! - magic `PXST`
+//! - settings version (`u8`) and length (`u8`), 2 reserved bytes
+//! - sequence number, `u32`
+//! - encoded settings, see [`Settings::encode`]
+//! - CRC-32 of everything before it
+
+use embedded_storage::nor_flash::NorFlash;
+use log::{info, warn};
+use pixels_protocol::crc::crc32;
+
+use crate::settings::{self, Settings};
+
+const MAGIC: &[u8; 4] = b"PXST";
+const HEADER_LEN: usize = 12;
+const CRC_LEN: usize = 4;
+/// Record size in flash, a multiple of the write size of the flash
+const SLOT_SIZE: usize = 64;
+const RECORD_LEN: usize = HEADER_LEN + settings::ENCODED_LEN + CRC_LEN;
+const _: () = assert!(RECORD_LEN <= SLOT_SIZE);
+/// With one sector the erase before a save would take the newest record
+pub const MIN_SECTORS: usize = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error<E> {
+    /// The partition has fewer than [`MIN_SECTORS`] sectors of at least one
+    /// record each
+    TooSmall,
+    Flash(E),
+}
+
+pub struct SettingsStore<F> {
+    flash: F,
+    /// Slot and sequence number of the newest record
+    newest: Option<(usize, u32)>,
+    /// Settings of the newest record
+    loaded: Option<Settings>,
+}
+
+impl<F: NorFlash> SettingsStore<F> {
+    /// Scans the partition for the newest record
+    pub fn new(mut flash: F) -> Result<Self, Error<F::Error>> {
+        if flash.capacity() / F::ERASE_SIZE < MIN_SECTORS || F::ERASE_SIZE < SLOT_SIZE {
+            return Err(Error::TooSmall);
+        }
+
+        let mut newest = None;
+        let mut loaded = None;
+
+        for slot in 0..slot_count(&flash) {
+            let mut record = [0; SLOT_SIZE];
+            flash
+                .read(slot_offset(slot), &mut record)
+                .map_err(Error::Flash)?;
+
+            if let Some((sequence, settings)) = decode(&record) {
+                if newest.is_none_or(|(_, newest)| is_newer(sequence, newest)) {
+                    newest = Some((slot, sequence));
+                    loaded = Some(settings);
+                }
+            }
+        }
+
+        match newest {
+            Some((slot, sequence)) => info!("Settings loaded from slot {} (#{})", slot, sequence),
+            None => info!("No stored settings, using defaults"),
+        }
+
+        Ok(Self {
+            flash,
+            newest,
+            loaded,
+        })
+    }
+
+    /// Settings of the newest record, `None` if nothing valid is stored
+    pub fn load(&self) -> Option<Settings> {
+        self.loaded
+    }
+
+    /// Writes `settings` as the newest record, unless they are stored already
+    pub fn save(&mut self, settings: &Settings) -> Result<(), F::Error> {
+        if self.loaded.as_ref() == Some(settings) {
+            return Ok(());
+        }
+
+        let slots = slot_count(&self.flash);
+        let slots_per_sector = F::ERASE_SIZE / SLOT_SIZE;
+
+        let (mut slot, sequence) = match self.newest {
+            Some((slot, sequence)) => ((slot + 1) % slots, sequence.wrapping_add(1)),
+            None => (0, 0),
+        };
+
+        // A half written record left from a reset, continue in the next sector
+        if slot % slots_per_sector != 0 && !self.is_blank(slot)? {
+            slot = (slot / slots_per_sector + 1) * slots_per_sector % slots;
+        }
+        if slot % slots_per_sector == 0 {
+            let start = slot_offset(slot);
+            self.flash.erase(start, start + F::ERASE_SIZE as u32)?;
+        }
+
+        self.flash
+            .write(slot_offset(slot), &encode(settings, sequence))?;
+        self.newest = Some((slot, sequence));
+        self.loaded = Some(*settings);
+        info!("Settings saved to slot {} (#{})", slot, sequence);
+        Ok(())
+    }
+
+    fn is_blank(&mut self, slot: usize) -> Result<bool, F::Error> {
+        let mut record = [0; SLOT_SIZE];
+        self.flash.read(slot_offset(slot), &mut record)?;
+        Ok(record.iter().all(|&byte| byte == 0xFF))
+    }
+}
+
+/// Whole sectors only, so every slot can be erased with its sector
+fn slot_count<F: NorFlash>(flash: &F) -> usize {
+    let sectors = flash.capacity() / F::ERASE_SIZE;
+    sectors * F::ERASE_SIZE / SLOT_SIZE
+}
+
+fn slot_offset(slot: usize) -> u32 {
+    (slot * SLOT_SIZE) as u32
+}
+
+/// Sequence numbers wrap around, `a` is newer if it is less than half the
+/// range ahead of `b`
+fn is_newer(a: u32, b: u32) -> bool {
+    a != b && a.wrapping_sub(b) < u32::MAX / 2
+}
+
+fn encode(settings: &Settings, sequence: u32) -> [u8; SLOT_SIZE] {
+    // Unused bytes stay erased
+    let mut record = [0xFF; SLOT_SIZE];
+    record[..4].copy_from_slice(MAGIC);
+    record[4] = settings::VERSION;
+    record[5] = settings::ENCODED_LEN as u8;
+    record[6..8].fill(0);
+    record[8..12].copy_from_slice(&sequence.to_le_bytes());
+
+    let end = HEADER_LEN + settings::ENCODED_LEN;
+    record[HEADER_LEN..end].copy_from_slice(&settings.encode());
+    let crc = crc32(&record[..end]);
+    record[end..end + CRC_LEN].copy_from_slice(&crc.to_le_bytes());
+    record
+}
+
+/// Sequence number and settings of a valid record
+fn decode(record: &[u8; SLOT_SIZE]) -> Option<(u32, Settings)> {
+    if !record.starts_with(MAGIC) {
+        return None;
+    }
+
+    let version = record[4];
+    let len = record[5] as usize;
+    let end = HEADER_LEN + len;
+    if end + CRC_LEN > SLOT_SIZE {
+        return None;
+    }
+
+    let crc = u32::from_le_bytes([
+        record[end],
+        record[end + 1],
+        record[end + 2],
+        record[end + 3],
+    ]);
+    if crc != crc32(&record[..end]) {
+        return None;
+    }
+
+    let sequence = u32::from_le_bytes([record[8], record[9], record[10], record[11]]);
+    let settings = Settings::decode(version, &record[HEADER_LEN..end]);
+    if settings.is_none() {
+        warn!("Stored settings have unknown version {}", version);
+    }
+    Some((sequence, settings?))
+}
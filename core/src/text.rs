@@ -0,0 +1,70 @@
+//! Fixed-capacity text buffer for formatting without allocations
+
+use core::fmt::{self, Write};
+
+/// String of at most `N` bytes, filled with `write!`
+///
+/// Text that does not fit is cut at the last whole character and the write
+/// returns [`fmt::Error`].
+#[derive(Clone)]
+pub struct TextBuffer<const N: usize> {
+    bytes: [u8; N],
+    len: usize,
+}
+
+impl<const N: usize> Default for TextBuffer<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> TextBuffer<N> {
+    pub const fn new() -> Self {
+        Self {
+            bytes: [0; N],
+            len: 0,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        // Only whole characters of `str`s are copied in
+        core::str::from_utf8(&self.bytes[..self.len]).unwrap_or_default()
+    }
+
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    /// Removes the last character
+    pub fn pop(&mut self) -> Option<char> {
+        let last = self.as_str().chars().next_back()?;
+        self.len -= last.len_utf8();
+        Some(last)
+    }
+}
+
+impl<const N: usize> Write for TextBuffer<N> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let free = N - self.len;
+        let (fits, result) = if s.len() <= free {
+            (s.len(), Ok(()))
+        } else {
+            // Largest character boundary that fits
+            let end = (0..=free)
+                .rev()
+                .find(|&i| s.is_char_boundary(i))
+                .unwrap_or(0);
+            (end, Err(fmt::Error))
+        };
+
+        self.bytes[self.len..self.len + fits].copy_from_slice(&s.as_bytes()[..fits]);
+        self.len += fits;
+        result
+    }
+}
+
+impl<const N: usize> PartialEq for TextBuffer<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
@@ -0,0 +1,211 @@
+//! Touch-to-screen coordinate mapping and calibration
+//!
+//! The touch controller reports positions in the native orientation of the
+//! panel, while drawing happens in the rotated coordinates set by the
+//! `ORIENTATION` of the board. [`TouchTransform`]
+//! applies the same rotation to touch positions, followed by an affine
+//! [`Calibration`] for touch layers that are offset, scaled or skewed against
+//! the panel. The calibration is measured on the device with a [`Calibrator`].
+
+use embedded_graphics::prelude::{Point, Size};
+use mipidsi::options::{Orientation, Rotation};
+
+use crate::display::DisplayTrait;
+use crate::gesture::Phase;
+
+/// Half the length of a crosshair line
+const CROSSHAIR_SIZE: i32 = 10;
+
+/// Crosshair positions as fractions of the screen size, not on one line
+const TARGETS: [(f32, f32); 3] = [(0.1, 0.2), (0.9, 0.2), (0.5, 0.8)];
+
+/// Determinants below this mean the taps were (almost) on one line
+const MIN_DETERMINANT: f32 = 1.0;
+
+/// Affine correction `screen = [a b c; d e f] * [x y 1]`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Calibration {
+    pub a: f32,
+    pub b: f32,
+    pub c: f32,
+    pub d: f32,
+    pub e: f32,
+    pub f: f32,
+}
+
+impl Calibration {
+    pub const IDENTITY: Self = Self {
+        a: 1.0,
+        b: 0.0,
+        c: 0.0,
+        d: 0.0,
+        e: 1.0,
+        f: 0.0,
+    };
+
+    /// Correction moving each of `measured` onto the matching `target`,
+    /// `None` if the measured points are on one line
+    pub fn from_points(measured: [Point; 3], target: [Point; 3]) -> Option<Self> {
+        let [(x0, y0), (x1, y1), (x2, y2)] = measured.map(|p| (p.x as f32, p.y as f32));
+        let det = x0 * (y1 - y2) - y0 * (x1 - x2) + (x1 * y2 - x2 * y1);
+        if det.abs() < MIN_DETERMINANT {
+            return None;
+        }
+
+        // Cramer's rule, once per screen axis
+        let solve = |t0: f32, t1: f32, t2: f32| {
+            (
+                (t0 * (y1 - y2) - y0 * (t1 - t2) + (t1 * y2 - t2 * y1)) / det,
+                (x0 * (t1 - t2) - t0 * (x1 - x2) + (x1 * t2 - x2 * t1)) / det,
+                (x0 * (y1 * t2 - y2 * t1) - y0 * (x1 * t2 - x2 * t1) + t0 * (x1 * y2 - x2 * y1))
+                    / det,
+            )
+        };
+        let [tx, ty] = [target.map(|p| p.x as f32), target.map(|p| p.y as f32)];
+        let (a, b, c) = solve(tx[0], tx[1], tx[2]);
+        let (d, e, f) = solve(ty[0], ty[1], ty[2]);
+
+        Some(Self { a, b, c, d, e, f })
+    }
+
+    pub fn apply(&self, point: Point) -> Point {
+        let (x, y) = (point.x as f32, point.y as f32);
+        Point::new(
+            round(self.a * x + self.b * y + self.c),
+            round(self.d * x + self.e * y + self.f),
+        )
+    }
+}
+
+/// Maps raw touch positions to screen coordinates
+pub struct TouchTransform {
+    /// Native panel size, width and height before rotation
+    native: (u16, u16),
+    orientation: Orientation,
+    calibration: Calibration,
+}
+
+impl TouchTransform {
+    pub fn new(native: (u16, u16), orientation: Orientation) -> Self {
+        Self {
+            native,
+            orientation,
+            calibration: Calibration::IDENTITY,
+        }
+    }
+
+    pub fn set_calibration(&mut self, calibration: Calibration) {
+        self.calibration = calibration;
+    }
+
+    /// Screen position of a raw touch position
+    pub fn to_screen(&self, raw: Point) -> Point {
+        self.calibration.apply(self.orient(raw))
+    }
+
+    /// Raw touch position rotated like the display, without calibration
+    pub fn orient(&self, raw: Point) -> Point {
+        let (width, height) = (self.native.0 as i32, self.native.1 as i32);
+        // Clockwise rotation of the image, as done by the panel
+        let rotated = match self.orientation.rotation {
+            Rotation::Deg0 => raw,
+            Rotation::Deg90 => Point::new(height - 1 - raw.y, raw.x),
+            Rotation::Deg180 => Point::new(width - 1 - raw.x, height - 1 - raw.y),
+            Rotation::Deg270 => Point::new(raw.y, width - 1 - raw.x),
+        };
+
+        if self.orientation.mirrored {
+            let rotated_width = match self.orientation.rotation {
+                Rotation::Deg0 | Rotation::Deg180 => width,
+                Rotation::Deg90 | Rotation::Deg270 => height,
+            };
+            Point::new(rotated_width - 1 - rotated.x, rotated.y)
+        } else {
+            rotated
+        }
+    }
+}
+
+/// On-screen calibration: tap the crosshairs one after another
+pub struct Calibrator {
+    targets: [Point; 3],
+    /// Oriented touch positions of the targets tapped so far
+    measured: [Point; 3],
+    step: usize,
+    /// A touch started after the calibration did, only its release counts
+    pressed: bool,
+}
+
+impl Calibrator {
+    pub fn new(screen: Size) -> Self {
+        let targets = TARGETS.map(|(x, y)| {
+            Point::new(
+                (screen.width as f32 * x) as i32,
+                (screen.height as f32 * y) as i32,
+            )
+        });
+
+        Self {
+            targets,
+            measured: [Point::zero(); 3],
+            step: 0,
+            pressed: false,
+        }
+    }
+
+    /// Feeds a touch at an oriented (not calibrated) position, see
+    /// [`TouchTransform::orient`], a release taps the current crosshair
+    ///
+    /// Returns the calibration after the last crosshair. If the taps cannot
+    /// be used the calibration starts over.
+    pub fn touch(&mut self, phase: Phase, oriented: Point) -> Option<Calibration> {
+        match phase {
+            Phase::Down => self.pressed = true,
+            Phase::Up if self.pressed => {
+                self.pressed = false;
+                return self.tap(oriented);
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn tap(&mut self, oriented: Point) -> Option<Calibration> {
+        self.measured[self.step] = oriented;
+        self.step += 1;
+        if self.step < self.targets.len() {
+            return None;
+        }
+
+        self.step = 0;
+        Calibration::from_points(self.measured, self.targets)
+    }
+
+    /// Draws the crosshair to tap and a hint
+    pub fn draw<D: DisplayTrait>(&self, display: &mut D) -> Result<(), D::Error> {
+        let target = self.targets[self.step];
+        display.draw_line(
+            target - Point::new(CROSSHAIR_SIZE, 0),
+            target + Point::new(CROSSHAIR_SIZE, 0),
+        )?;
+        display.draw_line(
+            target - Point::new(0, CROSSHAIR_SIZE),
+            target + Point::new(0, CROSSHAIR_SIZE),
+        )?;
+
+        let size = display.size();
+        display.write(
+            "Tap the cross",
+            Point::new(size.width as i32 / 2 - 65, size.height as i32 / 2 - 10),
+        )
+    }
+}
+
+/// Nearest integer, `f32::round` is not available without `std`
+fn round(value: f32) -> i32 {
+    if value < 0.0 {
+        (value - 0.5) as i32
+    } else {
+        (value + 0.5) as i32
+    }
+}
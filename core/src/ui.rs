@@ -0,0 +1,265 @@
+//! Immediate-mode touch widgets
+//!
+//! Widgets are functions called every frame they are shown: each one handles
+//! the touch input of the frame and draws itself through [`DisplayTrait`], so
+//! the tiles it covers are marked dirty like any other drawing. A widget is
+//! identified by its call order within the frame; it holds the touch from the
+//! press on it until the release.
+//!
+//! Touches that start on the widgets of the last frame are captured, so the
+//! scene can ignore them before this frame's widgets are drawn on top of it.
+//!
+//! ```ignore
+//! ui.begin(touch);
+//! let scene_touch = touch.filter(|_| !ui.captures_touch());
+//! // ... draw the scene
+//! ui.panel(display, area)?;
+//! if ui.button(display, area, "Close")? { ... }
+//! ui.end();
+//! ```
+
+use alloc::vec::Vec;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::{Point, Size};
+use embedded_graphics::primitives::Rectangle;
+
+use crate::display::DisplayTrait;
+use crate::gesture::{Phase, TouchSample};
+
+/// Character size of the 10x20 font
+const CHAR_WIDTH: i32 = 10;
+const CHAR_HEIGHT: i32 = 20;
+/// Space between a toggle box and its label
+const LABEL_GAP: i32 = 8;
+
+const PANEL_COLOR: Rgb565 = Rgb565::new(3, 6, 3);
+const PRESSED_COLOR: Rgb565 = Rgb565::new(0, 20, 16);
+const VALUE_COLOR: Rgb565 = Rgb565::new(0, 40, 24);
+
+pub struct Ui {
+    /// Touch position while the finger is down
+    pointer: Option<Point>,
+    /// The finger went down in this frame
+    pressed: bool,
+    /// The finger went up in this frame, at this position
+    released: Option<Point>,
+    /// Widget holding the touch
+    active: Option<usize>,
+    /// Id of the next widget in this frame
+    next_id: usize,
+    /// The touch started on a panel or widget and is not for the scene
+    captured: bool,
+    /// Areas of the panels and widgets drawn in this and in the last frame
+    areas: Vec<Rectangle>,
+    last_areas: Vec<Rectangle>,
+}
+
+impl Default for Ui {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Ui {
+    pub fn new() -> Self {
+        Self {
+            pointer: None,
+            pressed: false,
+            released: None,
+            active: None,
+            next_id: 0,
+            captured: false,
+            areas: Vec::new(),
+            last_areas: Vec::new(),
+        }
+    }
+
+    /// Starts a frame with its touch event, in screen coordinates
+    pub fn begin(&mut self, touch: Option<TouchSample>) {
+        self.next_id = 0;
+        self.pressed = false;
+        self.released = None;
+
+        if let Some(sample) = touch {
+            match sample.phase {
+                Phase::Down => {
+                    self.pointer = Some(sample.position);
+                    self.pressed = true;
+                    self.captured = self
+                        .last_areas
+                        .iter()
+                        .any(|area| area.contains(sample.position));
+                }
+                Phase::Move => self.pointer = Some(sample.position),
+                Phase::Up => {
+                    self.pointer = None;
+                    self.released = Some(sample.position);
+                }
+            }
+        }
+    }
+
+    /// Ends the frame, a released touch frees its widget
+    pub fn end(&mut self) {
+        if self.released.is_some() {
+            self.active = None;
+        }
+
+        core::mem::swap(&mut self.areas, &mut self.last_areas);
+        self.areas.clear();
+    }
+
+    /// The current touch belongs to the widgets, not to the scene below them
+    pub fn captures_touch(&self) -> bool {
+        self.captured
+    }
+
+    /// Background of a group of widgets, touches starting on it are captured
+    pub fn panel<D: DisplayTrait>(
+        &mut self,
+        display: &mut D,
+        area: Rectangle,
+    ) -> Result<(), D::Error> {
+        self.areas.push(area);
+
+        display.fill_rect(area, PANEL_COLOR)?;
+        outline(display, area)
+    }
+
+    pub fn label<D: DisplayTrait>(
+        &mut self,
+        display: &mut D,
+        position: Point,
+        text: &str,
+    ) -> Result<(), D::Error> {
+        display.write(text, position)
+    }
+
+    /// Returns `true` when tapped: pressed and released inside `area`
+    pub fn button<D: DisplayTrait>(
+        &mut self,
+        display: &mut D,
+        area: Rectangle,
+        text: &str,
+    ) -> Result<bool, D::Error> {
+        let (active, clicked) = self.interact(area);
+
+        if active && self.pointer.is_some_and(|p| area.contains(p)) {
+            display.fill_rect(area, PRESSED_COLOR)?;
+        }
+        outline(display, area)?;
+
+        let text_width = text.chars().count() as i32 * CHAR_WIDTH;
+        let center = area.center();
+        display.write(
+            text,
+            Point::new(center.x - text_width / 2, center.y - CHAR_HEIGHT / 2),
+        )?;
+
+        Ok(clicked)
+    }
+
+    /// Box with a label on its right, a tap flips `value`. Returns `true` when
+    /// `value` changed.
+    pub fn toggle<D: DisplayTrait>(
+        &mut self,
+        display: &mut D,
+        area: Rectangle,
+        text: &str,
+        value: &mut bool,
+    ) -> Result<bool, D::Error> {
+        let (_, clicked) = self.interact(area);
+        if clicked {
+            *value = !*value;
+        }
+
+        // Square box at the left of the area
+        let side = area.size.height;
+        let check = Rectangle::new(area.top_left, Size::new(side, side));
+        if *value {
+            display.fill_rect(check.offset(-3), VALUE_COLOR)?;
+        }
+        outline(display, check)?;
+        display.write(
+            text,
+            Point::new(
+                area.top_left.x + side as i32 + LABEL_GAP,
+                check.center().y - CHAR_HEIGHT / 2,
+            ),
+        )?;
+
+        Ok(clicked)
+    }
+
+    /// Horizontal slider setting `value` within `min..=max` while dragged.
+    /// Returns `true` when `value` changed.
+    pub fn slider<D: DisplayTrait>(
+        &mut self,
+        display: &mut D,
+        area: Rectangle,
+        value: &mut f32,
+        min: f32,
+        max: f32,
+    ) -> Result<bool, D::Error> {
+        let (active, _) = self.interact(area);
+
+        let mut changed = false;
+        if let (true, Some(pointer)) = (active, self.pointer) {
+            let width = area.size.width.max(2) as f32 - 1.0;
+            let t = ((pointer.x - area.top_left.x) as f32 / width).clamp(0.0, 1.0);
+            let new = min + t * (max - min);
+            changed = new != *value;
+            *value = new;
+        }
+
+        let t = if max > min {
+            ((*value - min) / (max - min)).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let filled = (t * area.size.width as f32) as u32;
+        if filled > 0 {
+            display.fill_rect(
+                Rectangle::new(area.top_left, Size::new(filled, area.size.height)),
+                VALUE_COLOR,
+            )?;
+        }
+        outline(display, area)?;
+
+        Ok(changed)
+    }
+
+    /// Whether the widget holds the touch and whether it was tapped
+    fn interact(&mut self, area: Rectangle) -> (bool, bool) {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.areas.push(area);
+
+        if self.pressed_in(area) && self.active.is_none() {
+            self.active = Some(id);
+        }
+
+        let active = self.active == Some(id);
+        let clicked = active && self.released.is_some_and(|p| area.contains(p));
+        (active, clicked)
+    }
+
+    fn pressed_in(&self, area: Rectangle) -> bool {
+        self.pressed && self.pointer.is_some_and(|p| area.contains(p))
+    }
+}
+
+/// Draws the border of `area`
+fn outline<D: DisplayTrait>(display: &mut D, area: Rectangle) -> Result<(), D::Error> {
+    let Some(bottom_right) = area.bottom_right() else {
+        return Ok(());
+    };
+    let top_left = area.top_left;
+    let top_right = Point::new(bottom_right.x, top_left.y);
+    let bottom_left = Point::new(top_left.x, bottom_right.y);
+
+    display.draw_line(top_left, top_right)?;
+    display.draw_line(top_right, bottom_right)?;
+    display.draw_line(bottom_left, bottom_right)?;
+    display.draw_line(top_left, bottom_left)
+}
@@ -0,0 +1,175 @@
+use embedded_graphics::prelude::Point;
+use micromath::F32Ext;
+use pixels_core::arcball::Arcball;
+use pixels_core::math::{self, Rotation};
+
+const CENTER: Point = Point::new(160, 85);
+const RADIUS: f32 = 80.0;
+/// Frame time of the steps, 60 FPS
+const DT: f32 = 1.0 / 60.0;
+/// The arcball works with the approximate `sqrt`, `sin` and `cos` of micromath
+const TOLERANCE: f32 = 0.02;
+
+type V = (f32, f32, f32);
+
+fn rotate(rotation: Rotation, v: V) -> V {
+    let r = rotation.rotate(math::vec3(v.0, v.1, v.2));
+    #[cfg(not(feature = "fixed-point"))]
+    return (r.x, r.y, r.z);
+    #[cfg(feature = "fixed-point")]
+    (r.x.to_f32(), r.y.to_f32(), r.z.to_f32())
+}
+
+fn dot(a: V, b: V) -> f32 {
+    a.0 * b.0 + a.1 * b.1 + a.2 * b.2
+}
+
+/// Angle between two unit vectors
+fn angle(a: V, b: V) -> f32 {
+    dot(a, b).clamp(-1.0, 1.0).acos()
+}
+
+fn assert_near(a: V, b: V) {
+    let d = (a.0 - b.0)
+        .abs()
+        .max((a.1 - b.1).abs())
+        .max((a.2 - b.2).abs());
+    assert!(d < TOLERANCE, "{a:?} != {b:?}");
+}
+
+/// Direction of the sphere point under a screen position inside it, the
+/// front is at -z
+///
+/// With the approximate `sqrt` of the arcball, so only the rotation is
+/// compared.
+fn sphere(position: Point) -> V {
+    let x = (position.x - CENTER.x) as f32 / RADIUS;
+    let y = (position.y - CENTER.y) as f32 / RADIUS;
+    normalize((x, y, -F32Ext::sqrt(1.0 - x * x - y * y)))
+}
+
+fn normalize(v: V) -> V {
+    let length = dot(v, v).sqrt();
+    (v.0 / length, v.1 / length, v.2 / length)
+}
+
+fn finite(v: V) -> bool {
+    v.0.is_finite() && v.1.is_finite() && v.2.is_finite()
+}
+
+#[test]
+fn drag_rotates_by_the_angle_on_the_sphere() {
+    let arcball = Arcball::new(CENTER, RADIUS);
+
+    // Half a radius right of the center is 30 degrees around the vertical axis
+    let to = CENTER + Point::new(40, 0);
+    let rotation = arcball.drag(CENTER, to).unwrap();
+    let front = (0.0, 0.0, -1.0);
+    assert!((angle(front, rotate(rotation, front)) - 30f32.to_radians()).abs() < TOLERANCE);
+    assert_near(rotate(rotation, front), sphere(to));
+    assert_near(rotate(rotation, (0.0, 1.0, 0.0)), (0.0, 1.0, 0.0));
+}
+
+#[test]
+fn dragged_point_follows_the_finger() {
+    let arcball = Arcball::new(CENTER, RADIUS);
+    let drags = [
+        (Point::new(-30, -20), Point::new(10, 25)),
+        (Point::new(50, 10), Point::new(-40, -40)),
+        (Point::new(0, 70), Point::new(0, -70)),
+        (Point::new(-60, 30), Point::new(-66, 28)),
+        (Point::new(5, 5), Point::new(6, 5)),
+    ];
+    for (from, to) in drags {
+        let (from, to) = (CENTER + from, CENTER + to);
+        let rotation = arcball.drag(from, to).unwrap();
+        assert_near(normalize(rotate(rotation, sphere(from))), sphere(to));
+    }
+}
+
+#[test]
+fn zero_length_drag_does_nothing() {
+    let arcball = Arcball::new(CENTER, RADIUS);
+    for position in [CENTER, CENTER + Point::new(30, -10), Point::new(0, 0)] {
+        assert!(arcball.drag(position, position).is_none());
+    }
+
+    // Outside the sphere positions in the same direction map to the same
+    // point of the rim
+    let rim = arcball.drag(CENTER + Point::new(100, 0), CENTER + Point::new(200, 0));
+    assert!(rim.is_none());
+}
+
+#[test]
+fn tiny_drags_are_finite() {
+    let arcball = Arcball::new(CENTER, RADIUS);
+    let from = CENTER + Point::new(79, 0);
+    if let Some(rotation) = arcball.drag(from, from + Point::new(1, 0)) {
+        assert!(finite(rotate(rotation, (0.0, 0.0, -1.0))));
+    }
+    let rotation = arcball.drag(CENTER, CENTER + Point::new(1, 0)).unwrap();
+    assert!(finite(rotate(rotation, (0.0, 0.0, -1.0))));
+}
+
+#[test]
+fn fling_decays_to_a_stop() {
+    let mut arcball = Arcball::new(CENTER, RADIUS);
+    arcball.hold();
+    arcball.release();
+    assert!(!arcball.is_active());
+
+    // 400 px/s to the right at 80 px per radian is 5 rad/s around the vertical axis
+    arcball.fling(400.0, 0.0);
+    assert!(arcball.is_active());
+
+    let front = (0.0, 0.0, -1.0);
+    let mut angles = Vec::new();
+    while let Some(rotation) = arcball.step(DT) {
+        let rotated = rotate(rotation, front);
+        assert!(finite(rotated));
+        // Turns to the right like the drag in the same direction
+        assert!(rotated.0 > 0.0);
+        // Around the vertical axis the front turns by asin(x), which is exact
+        // to more digits than the acos of the z near 1
+        angles.push(rotated.0.asin());
+        assert!(angles.len() < 600, "still spinning after 10 s");
+    }
+
+    assert!((angles[0] - 5.0 * DT).abs() < TOLERANCE, "{}", angles[0]);
+    assert!(angles.windows(2).all(|pair| pair[1] < pair[0]));
+    // Stops below 0.5 rad/s, at a tenth of the speed after ln(10) / 2 s
+    let stop = (10f32.ln() / 2.0 / DT) as usize;
+    assert!(angles.len().abs_diff(stop) <= 1, "{} steps", angles.len());
+    assert!(!arcball.is_active());
+    assert!(arcball.step(DT).is_none());
+}
+
+#[test]
+fn fling_speed_is_limited() {
+    let mut arcball = Arcball::new(CENTER, RADIUS);
+    arcball.fling(0.0, 1e6);
+    let rotation = arcball.step(DT).unwrap();
+    let front = (0.0, 0.0, -1.0);
+    assert!((angle(front, rotate(rotation, front)) - 20.0 * DT).abs() < TOLERANCE);
+}
+
+#[test]
+fn touch_stops_the_spin() {
+    let mut arcball = Arcball::new(CENTER, RADIUS);
+    arcball.fling(400.0, 300.0);
+    assert!(arcball.step(DT).is_some());
+
+    arcball.hold();
+    assert!(arcball.step(DT).is_none());
+    assert!(arcball.is_active());
+    arcball.release();
+    assert!(!arcball.is_active());
+}
+
+#[test]
+fn still_release_does_not_spin() {
+    let mut arcball = Arcball::new(CENTER, RADIUS);
+    arcball.fling(0.0, 0.0);
+    assert!(!arcball.is_active());
+    assert!(arcball.step(DT).is_none());
+}
@@ -0,0 +1,155 @@
+use embedded_graphics::pixelcolor::{Rgb565, RgbColor};
+use embedded_graphics::prelude::{Point, Size};
+use embedded_graphics::primitives::Rectangle;
+use pixels_core::burnin::{BurnIn, Hud, Mitigation};
+
+const SCREEN: Size = Size::new(536, 240);
+const MITIGATION: Mitigation = Mitigation {
+    orbit_radius: 4,
+    orbit_period_ms: 60_000,
+    refresh_interval_ms: 3_600_000,
+    refresh_step_ms: 5_000,
+    relocate_margin_ms: 60_000,
+    dim_after_ms: 600_000,
+};
+/// The FPS counter in the top left corner
+const FPS: Rectangle = Rectangle::new(Point::new(0, 0), Size::new(80, 20));
+
+#[test]
+fn orbit_stays_within_the_radius_and_moves_slowly() {
+    let burn_in = BurnIn::new(MITIGATION, SCREEN);
+    assert_eq!(burn_in.orbit(0), Point::zero());
+
+    let mut seen_x = [false; 9];
+    let mut seen_y = [false; 9];
+    let mut last = burn_in.orbit(0);
+    for now_ms in (0..4 * MITIGATION.orbit_period_ms).step_by(100) {
+        let orbit = burn_in.orbit(now_ms);
+        assert!(orbit.x.abs() <= 4 && orbit.y.abs() <= 4, "{orbit:?}");
+        assert!((orbit - last).x.abs() <= 1 && (orbit - last).y.abs() <= 1);
+        seen_x[(orbit.x + 4) as usize] = true;
+        seen_y[(orbit.y + 4) as usize] = true;
+        last = orbit;
+    }
+    assert!(seen_x.iter().all(|&seen| seen));
+    assert!(seen_y.iter().all(|&seen| seen));
+}
+
+#[test]
+fn hud_moves_to_the_less_worn_place() {
+    let mut burn_in = BurnIn::new(MITIGATION, SCREEN);
+    let mut hud = Hud::new(FPS, SCREEN);
+    assert_eq!(hud.offset(Point::zero()), Point::zero());
+
+    burn_in.track(&mut hud, 59_000);
+    assert_eq!(hud.offset(Point::zero()), Point::zero());
+    burn_in.track(&mut hud, 1_000);
+    assert_eq!(hud.offset(Point::zero()), Point::new(0, 220));
+
+    // And back once the mirrored place is worn more
+    burn_in.track(&mut hud, 119_000);
+    assert_eq!(hud.offset(Point::zero()), Point::new(0, 220));
+    burn_in.track(&mut hud, 1_000);
+    assert_eq!(hud.offset(Point::zero()), Point::zero());
+}
+
+#[test]
+fn hud_orbits_towards_the_inside() {
+    let mut burn_in = BurnIn::new(MITIGATION, SCREEN);
+    let mut hud = Hud::new(FPS, SCREEN);
+    assert_eq!(hud.offset(Point::new(-3, 2)), Point::new(3, 2));
+    assert_eq!(hud.offset(Point::new(3, -2)), Point::new(3, 2));
+
+    burn_in.track(&mut hud, MITIGATION.relocate_margin_ms as u64);
+    assert_eq!(hud.offset(Point::new(-3, 2)), Point::new(3, 218));
+}
+
+#[test]
+fn hud_is_dimmed_once_both_places_are_worn() {
+    let mut burn_in = BurnIn::new(MITIGATION, SCREEN);
+    let mut hud = Hud::new(FPS, SCREEN);
+    let mut on_ms = 0;
+    while !burn_in.dimmed(&hud) {
+        burn_in.track(&mut hud, 1_000);
+        on_ms += 1_000;
+        assert!(on_ms < 10 * MITIGATION.dim_after_ms);
+    }
+    // Both places took their share
+    assert!(on_ms >= 2 * MITIGATION.dim_after_ms - MITIGATION.relocate_margin_ms);
+}
+
+#[test]
+fn elements_elsewhere_do_not_wear_each_other() {
+    let mut burn_in = BurnIn::new(MITIGATION, SCREEN);
+    let mut fps = Hud::new(FPS, SCREEN);
+    let mut other = Hud::new(
+        Rectangle::new(Point::new(400, 100), Size::new(40, 20)),
+        SCREEN,
+    );
+    burn_in.track(&mut fps, MITIGATION.dim_after_ms as u64);
+    assert!(!burn_in.dimmed(&other));
+    burn_in.track(&mut other, 1_000);
+    assert_eq!(other.offset(Point::zero()), Point::zero());
+}
+
+/// Frames of 100 ms from `start_ms` for `duration_ms`, returns the refresh
+/// color of each
+fn refresh_frames(
+    burn_in: &mut BurnIn,
+    start_ms: u64,
+    duration_ms: u64,
+    idle: bool,
+) -> Vec<Option<Rgb565>> {
+    (start_ms..start_ms + duration_ms)
+        .step_by(100)
+        .map(|now_ms| burn_in.refresh(now_ms, 100, idle))
+        .collect()
+}
+
+/// Colors in the order shown, each once
+fn sequence(colors: &[Option<Rgb565>]) -> Vec<Option<Rgb565>> {
+    let mut sequence = colors.to_vec();
+    sequence.dedup();
+    sequence
+}
+
+#[test]
+fn idle_screen_refreshes_all_pixels_when_due() {
+    let mut burn_in = BurnIn::new(MITIGATION, SCREEN);
+    let interval = MITIGATION.refresh_interval_ms;
+    assert!(refresh_frames(&mut burn_in, 0, interval, false)
+        .iter()
+        .all(Option::is_none));
+
+    let colors = refresh_frames(&mut burn_in, interval, 30_000, true);
+    assert_eq!(
+        sequence(&colors),
+        [
+            Some(Rgb565::RED),
+            Some(Rgb565::GREEN),
+            Some(Rgb565::BLUE),
+            Some(Rgb565::WHITE),
+            None
+        ]
+    );
+    assert_eq!(colors.iter().filter(|color| color.is_some()).count(), 200);
+
+    // Not again until the next interval
+    let colors = refresh_frames(&mut burn_in, interval + 30_000, 600_000, true);
+    assert!(colors.iter().all(Option::is_none));
+}
+
+#[test]
+fn input_stops_the_refresh() {
+    let mut burn_in = BurnIn::new(MITIGATION, SCREEN);
+    let interval = MITIGATION.refresh_interval_ms;
+    refresh_frames(&mut burn_in, 0, interval, false);
+
+    let colors = refresh_frames(&mut burn_in, interval, 7_000, true);
+    assert_eq!(sequence(&colors), [Some(Rgb565::RED), Some(Rgb565::GREEN)]);
+    assert_eq!(burn_in.refresh(interval + 7_000, 100, false), None);
+
+    // Still due, it starts over when idle again
+    let colors = refresh_frames(&mut burn_in, interval + 60_000, 1_000, true);
+    assert_eq!(sequence(&colors), [Some(Rgb565::RED)]);
+}
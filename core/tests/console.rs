@@ -0,0 +1,207 @@
+use pixels_core::console::{self, Command, Error, LineEditor};
+use pixels_core::scene::Scene;
+use pixels_core::settings::{Settings, KEYS};
+
+/// Feeds `input` to `editor`, returns the completed lines and the echo
+fn type_bytes(editor: &mut LineEditor, input: &[u8]) -> (Vec<String>, String) {
+    let mut echo = String::new();
+    let mut lines = Vec::new();
+    for &byte in input {
+        if let Some(line) = editor.push(byte, &mut echo) {
+            lines.push(line.to_string());
+        }
+    }
+    (lines, echo)
+}
+
+fn completions(line: &str) -> (usize, Vec<&'static str>) {
+    let (start, candidates) = console::complete(line);
+    (start, candidates.collect())
+}
+
+#[test]
+fn parses_every_command() {
+    let commands = [
+        ("help", Command::Help(None)),
+        ("help set", Command::Help(Some("set"))),
+        ("get", Command::Get(None)),
+        ("get fov", Command::Get(Some("fov"))),
+        ("set fov 90", Command::Set("fov", "90")),
+        ("save", Command::Save),
+        ("scene", Command::Scene(None)),
+        ("scene particles", Command::Scene(Some(Scene::Particles))),
+        ("pause", Command::Pause),
+        ("resume", Command::Resume),
+        ("step", Command::Step(1)),
+        ("step 10", Command::Step(10)),
+        ("profile", Command::Profile),
+        ("screenshot", Command::Screenshot),
+        ("crash", Command::Crash),
+        ("reset", Command::Reset),
+    ];
+    for (line, command) in commands {
+        assert_eq!(console::parse(line), Ok(command), "{line}");
+    }
+}
+
+#[test]
+fn words_can_be_shortened() {
+    assert_eq!(console::parse("h"), Ok(Command::Help(None)));
+    assert_eq!(
+        console::parse("  se   rot 0.05 "),
+        Ok(Command::Set("rotation_speed", "0.05"))
+    );
+    assert_eq!(
+        console::parse("sce w"),
+        Ok(Command::Scene(Some(Scene::Wireframe)))
+    );
+    assert_eq!(console::parse("g i"), Ok(Command::Get(Some("invert"))));
+}
+
+#[test]
+fn reports_what_is_wrong() {
+    assert_eq!(console::parse(""), Err(Error::Missing("command")));
+    assert!(matches!(
+        console::parse("jump"),
+        Err(Error::Unknown("jump", _))
+    ));
+    assert!(matches!(console::parse("s"), Err(Error::Ambiguous("s", _))));
+    assert!(matches!(
+        console::parse("set p 1"),
+        Err(Error::Ambiguous("p", choices)) if choices == KEYS
+    ));
+    assert!(matches!(
+        console::parse("scene cubes"),
+        Err(Error::Unknown("cubes", _))
+    ));
+    assert_eq!(console::parse("set"), Err(Error::Missing("key")));
+    assert_eq!(console::parse("set fov"), Err(Error::Missing("value")));
+    assert_eq!(console::parse("step -1"), Err(Error::InvalidNumber("-1")));
+    assert_eq!(console::parse("pause now"), Err(Error::Extra("now")));
+    assert_eq!(console::parse("set fov 90 100"), Err(Error::Extra("100")));
+}
+
+#[test]
+fn errors_list_the_choices() {
+    let error = console::parse("sc").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "ambiguous 'sc', candidates:\n  scene\n  screenshot"
+    );
+
+    let error = console::parse("scene x").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "unknown 'x', expected one of:\n  cube\n  wireframe\n  particles\n  canvas"
+    );
+}
+
+#[test]
+fn help_lists_the_commands() {
+    let mut all = String::new();
+    console::help(None, &mut all).unwrap();
+    assert_eq!(all.lines().count(), 12);
+    assert!(all.lines().all(|line| line.len() > 19));
+
+    let mut one = String::new();
+    console::help(Some("step"), &mut one).unwrap();
+    assert_eq!(
+        one,
+        "step [frames]      animate one or more frames while paused\n"
+    );
+}
+
+#[test]
+fn writes_settings_as_key_value_lines() {
+    let settings = Settings::default();
+    let mut one = String::new();
+    console::write_settings(&settings, Some("fov"), &mut one).unwrap();
+    assert_eq!(one, "fov=200\n");
+
+    let mut all = String::new();
+    console::write_settings(&settings, None, &mut all).unwrap();
+    let keys: Vec<_> = all
+        .lines()
+        .map(|line| line.split('=').next().unwrap())
+        .collect();
+    assert_eq!(keys, KEYS);
+}
+
+#[test]
+fn completes_the_last_word() {
+    assert_eq!(completions("").1.len(), 12);
+    assert_eq!(completions("re"), (0, vec!["resume", "reset"]));
+    assert_eq!(completions("sce"), (0, vec!["scene"]));
+    assert_eq!(
+        completions("set p"),
+        (4, vec!["particle_speed", "projection_distance"])
+    );
+    assert_eq!(completions("sce  w"), (5, vec!["wireframe"]));
+    assert_eq!(completions("help st"), (5, vec!["step"]));
+
+    // Nothing after the first argument or of commands without arguments
+    assert_eq!(completions("set fov 1"), (8, vec![]));
+    assert_eq!(completions("pause "), (6, vec![]));
+    assert_eq!(completions("x "), (2, vec![]));
+}
+
+#[test]
+fn editor_returns_completed_lines() {
+    let mut editor = LineEditor::new();
+    let (lines, echo) = type_bytes(&mut editor, b"get\r\n\nstep 2\n");
+    assert_eq!(lines, ["get", "step 2"]);
+    assert_eq!(echo, "get\nstep 2\n");
+
+    // Blank lines are not returned
+    let (lines, _) = type_bytes(&mut editor, b"   \r\n");
+    assert!(lines.is_empty());
+}
+
+#[test]
+fn editor_erases_characters() {
+    let mut editor = LineEditor::new();
+    let (lines, echo) = type_bytes(&mut editor, b"pausx\x08e\x7F\x7F\x7F\x7F\x7F\x7Fhelp\n");
+    assert_eq!(lines, ["help"]);
+    assert_eq!(
+        echo,
+        "pausx\x08 \x08e\x08 \x08\x08 \x08\x08 \x08\x08 \x08\x08 \x08help\n"
+    );
+}
+
+#[test]
+fn editor_ignores_control_characters_and_cuts_long_lines() {
+    let mut editor = LineEditor::new();
+    let (_, echo) = type_bytes(&mut editor, b"he\x1b\x00lp");
+    assert_eq!(echo, "help");
+    assert_eq!(editor.line(), "help");
+
+    let mut editor = LineEditor::new();
+    let (lines, _) = type_bytes(&mut editor, &[b'a'; 100]);
+    assert!(lines.is_empty());
+    assert_eq!(editor.line().len(), 64);
+}
+
+#[test]
+fn tab_completes_a_unique_word() {
+    let mut editor = LineEditor::new();
+    let (lines, echo) = type_bytes(&mut editor, b"sce\tpa\t\n");
+    assert_eq!(echo, "scene particles \n");
+    assert_eq!(lines, ["scene particles "]);
+    assert_eq!(
+        console::parse(&lines[0]),
+        Ok(Command::Scene(Some(Scene::Particles)))
+    );
+}
+
+#[test]
+fn tab_lists_the_candidates() {
+    let mut editor = LineEditor::new();
+    let (_, echo) = type_bytes(&mut editor, b"set p\t");
+    assert_eq!(echo, "set p\nparticle_speed\nprojection_distance\nset p");
+    assert_eq!(editor.line(), "set p");
+
+    // Without candidates nothing happens
+    let (_, echo) = type_bytes(&mut editor, b"x\t");
+    assert_eq!(echo, "x");
+    assert_eq!(editor.line(), "set px");
+}
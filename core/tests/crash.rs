@@ -0,0 +1,145 @@
+use core::fmt::Write;
+
+use embassy_futures::block_on;
+use embedded_graphics::pixelcolor::Rgb565;
+use pixels_core::crash::{Cause, CrashReport, BACKTRACE_LEN, MESSAGE_LEN, RECORD_LEN};
+use pixels_core::display::DisplayTrait;
+use pixels_core::mock::{MockDisplay, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+
+const BACKGROUND: Rgb565 = Rgb565::new(10, 0, 0);
+
+fn panic_report(message: &str, frames: u32) -> CrashReport {
+    let mut report = CrashReport::new(Cause::Panic);
+    let _ = report.message.write_str(message);
+    for frame in 0..frames {
+        report.push_frame(0x4200_0000 + 4 * frame);
+    }
+    report
+}
+
+fn encode(report: &CrashReport) -> [u8; RECORD_LEN] {
+    let mut record = [0xA5; RECORD_LEN];
+    report.encode(&mut record);
+    record
+}
+
+fn console_text(report: &CrashReport) -> String {
+    let mut text = String::new();
+    report.write(&mut text).unwrap();
+    text
+}
+
+/// Screen after drawing `report` with `hint`
+fn draw(report: &CrashReport, hint: &str) -> MockDisplay {
+    let mut display = MockDisplay::board();
+    display.frame.clear_buffer();
+    let Ok(()) = report.draw(&mut display, hint);
+    let Ok(()) = block_on(display.update_with_buffer());
+    display
+}
+
+/// Pixels of the rows `rows` that are not the background
+fn drawn_in_rows(display: &MockDisplay, rows: std::ops::Range<u16>) -> usize {
+    rows.flat_map(|y| (0..DISPLAY_WIDTH).map(move |x| (x, y)))
+        .filter(|&(x, y)| display.panel.pixel(x, y) != BACKGROUND)
+        .count()
+}
+
+#[test]
+fn report_survives_the_record() {
+    let report = panic_report("panicked at src/main.rs:42:5:\nindex out of bounds", 5);
+    let decoded = CrashReport::decode(&encode(&report)).unwrap();
+    assert!(decoded == report);
+    assert_eq!(decoded.cause, Cause::Panic);
+    assert_eq!(decoded.backtrace().len(), 5);
+    assert_eq!(decoded.backtrace()[4], 0x4200_0010);
+
+    let watchdog = CrashReport::new(Cause::Watchdog);
+    let decoded = CrashReport::decode(&encode(&watchdog)).unwrap();
+    assert!(decoded == watchdog);
+    assert!(decoded.message.as_str().is_empty());
+
+    let mut display = CrashReport::new(Cause::Display);
+    let _ = display.message.write_str("Display init failed: SPI error");
+    let decoded = CrashReport::decode(&encode(&display)).unwrap();
+    assert!(decoded == display);
+    assert_eq!(
+        console_text(&decoded),
+        "cause=display\nDisplay init failed: SPI error\n"
+    );
+}
+
+#[test]
+fn long_messages_and_backtraces_are_cut() {
+    let message = "é".repeat(MESSAGE_LEN);
+    let report = panic_report(&message, 40);
+    assert_eq!(report.message.as_str().len(), MESSAGE_LEN);
+    assert_eq!(report.backtrace().len(), BACKTRACE_LEN);
+
+    let decoded = CrashReport::decode(&encode(&report)).unwrap();
+    assert!(decoded == report);
+}
+
+#[test]
+fn anything_but_a_record_is_ignored() {
+    assert!(CrashReport::decode(&[0; RECORD_LEN]).is_none());
+    assert!(CrashReport::decode(&[0xFF; RECORD_LEN]).is_none());
+
+    // Memory that changed over a power cycle
+    let record = encode(&panic_report("boom", 3));
+    for i in (0..RECORD_LEN).step_by(7) {
+        let mut damaged = record;
+        damaged[i] ^= 0x40;
+        assert!(CrashReport::decode(&damaged).is_none(), "byte {i}");
+    }
+}
+
+#[test]
+fn console_shows_cause_message_and_backtrace() {
+    let report = panic_report("panicked at src/main.rs:42:5:\nboom", 2);
+    assert_eq!(
+        console_text(&report),
+        "cause=panic\n\
+         panicked at src/main.rs:42:5:\n\
+         boom\n\
+         backtrace:\n\
+         0x42000000\n\
+         0x42000004\n"
+    );
+    assert_eq!(
+        console_text(&CrashReport::new(Cause::Watchdog)),
+        "cause=watchdog\n"
+    );
+}
+
+#[test]
+fn screen_shows_the_report_and_the_hint() {
+    let report = panic_report("panicked at src/main.rs:42:5:\nboom", 8);
+    let display = draw(&report, "Tap to close");
+    assert_eq!(display.panel.pixel(0, 0), BACKGROUND);
+    assert_eq!(
+        display.panel.pixel(DISPLAY_WIDTH - 1, DISPLAY_HEIGHT - 1),
+        BACKGROUND
+    );
+
+    // The title at the top and the hint on the last line
+    assert!(drawn_in_rows(&display, 8..28) > 0);
+    let last_line = (DISPLAY_HEIGHT - 16) / 20 - 1;
+    let hint_rows = 8 + 20 * last_line..8 + 20 * (last_line + 1);
+    let without_hint = draw(&report, "");
+    assert!(drawn_in_rows(&display, hint_rows.clone()) > 0);
+    assert_eq!(drawn_in_rows(&without_hint, hint_rows), 0);
+}
+
+#[test]
+fn long_reports_leave_the_hint_line_free() {
+    let message = "x".repeat(MESSAGE_LEN);
+    let report = panic_report(&message, BACKTRACE_LEN as u32);
+    let display = draw(&report, "");
+
+    // Wrapped and cut before the hint line, nothing below the text area
+    let last_line = (DISPLAY_HEIGHT - 16) / 20 - 1;
+    let text_end = 8 + 20 * last_line;
+    assert!(drawn_in_rows(&display, text_end - 20..text_end) > 0);
+    assert_eq!(drawn_in_rows(&display, text_end..DISPLAY_HEIGHT), 0);
+}
@@ -0,0 +1,164 @@
+#![cfg(feature = "delta-transfer")]
+
+use embassy_futures::block_on;
+use embedded_graphics::pixelcolor::{Rgb565, RgbColor};
+use embedded_graphics::prelude::{Point, Size};
+use embedded_graphics::primitives::Rectangle;
+use pixels_core::config::TRANSFER_OVERHEAD_BYTES;
+use pixels_core::dirty_region::{RegionOptimizer, BYTES_PER_PIXEL};
+use pixels_core::display::DisplayTrait;
+use pixels_core::framebuffer::DirtyRect;
+use pixels_core::mock::{MockDisplay, MockPanel, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use pixels_core::rng::Rng;
+
+const WIDTH: usize = 96;
+const HEIGHT: usize = 48;
+const RECT: DirtyRect = DirtyRect {
+    x_start: 16,
+    y_start: 8,
+    x_end: 79,
+    y_end: 39,
+};
+
+/// A frame that differs from `sent` in about `density` of its pixels, also
+/// outside of [`RECT`]
+fn changed_frame(sent: &[Rgb565], rng: &mut Rng, density: f32) -> Vec<Rgb565> {
+    sent.iter()
+        .map(|&color| {
+            if rng.next_f32() < density {
+                Rgb565::new(color.r() ^ 1, color.g(), color.b())
+            } else {
+                color
+            }
+        })
+        .collect()
+}
+
+fn split(overhead: usize, frame: &[Rgb565], sent: &[Rgb565]) -> Vec<DirtyRect> {
+    let mut spans = Vec::new();
+    RegionOptimizer::new(overhead).split_changed(&RECT, frame, sent, WIDTH, &mut spans);
+    spans
+}
+
+fn contains(rect: &DirtyRect, x: usize, y: usize) -> bool {
+    (rect.x_start as usize..=rect.x_end as usize).contains(&x)
+        && (rect.y_start as usize..=rect.y_end as usize).contains(&y)
+}
+
+/// Draws a frame like the main loop: clear, draw, send
+fn frame(display: &mut MockDisplay, draw: impl FnOnce(&mut MockDisplay)) {
+    display.panel.clear_log();
+    display.frame.clear_buffer();
+    draw(display);
+    let Ok(()) = block_on(display.update_with_buffer());
+}
+
+fn scene(display: &mut MockDisplay) {
+    let Ok(()) = display.fill_rect(
+        Rectangle::new(Point::new(40, 40), Size::new(50, 30)),
+        Rgb565::GREEN,
+    );
+    let Ok(()) = display.draw_line(Point::new(100, 20), Point::new(300, 150));
+}
+
+#[test]
+fn spans_cover_exactly_the_changed_pixels() {
+    let mut rng = Rng::new(0xDE17A);
+    let sent = vec![Rgb565::BLUE; WIDTH * HEIGHT];
+    for density in [0.0, 0.002, 0.02, 0.2, 1.0] {
+        let frame = changed_frame(&sent, &mut rng, density);
+
+        // Without a transfer overhead no rows are merged
+        let spans = split(0, &frame, &sent);
+        for y in 0..HEIGHT {
+            let changed: Vec<usize> = (RECT.x_start as usize..=RECT.x_end as usize)
+                .filter(|&x| frame[y * WIDTH + x] != sent[y * WIDTH + x])
+                .collect();
+            let covered: Vec<usize> = (0..WIDTH)
+                .filter(|&x| spans.iter().any(|span| contains(span, x, y)))
+                .collect();
+
+            // A row is sent from its first to its last change
+            let expected = match (changed.first(), changed.last()) {
+                (Some(&first), Some(&last)) if contains(&RECT, first, y) => {
+                    (first..=last).collect()
+                }
+                _ => Vec::new(),
+            };
+            assert_eq!(covered, expected, "row {y} at density {density}");
+        }
+    }
+}
+
+#[test]
+fn merged_spans_cover_every_change() {
+    let mut rng = Rng::new(0x5EA7);
+    let sent = vec![Rgb565::BLUE; WIDTH * HEIGHT];
+    let whole = RECT.pixel_count() * BYTES_PER_PIXEL + TRANSFER_OVERHEAD_BYTES;
+    for density in [0.002, 0.02, 0.2, 1.0] {
+        let frame = changed_frame(&sent, &mut rng, density);
+        let spans = split(TRANSFER_OVERHEAD_BYTES, &frame, &sent);
+
+        for (i, a) in spans.iter().enumerate() {
+            assert!(contains(&RECT, a.x_start as usize, a.y_start as usize));
+            assert!(contains(&RECT, a.x_end as usize, a.y_end as usize));
+            for b in &spans[i + 1..] {
+                assert!(
+                    a.x_end < b.x_start
+                        || b.x_end < a.x_start
+                        || a.y_end < b.y_start
+                        || b.y_end < a.y_start,
+                    "{a:?} overlaps {b:?}"
+                );
+            }
+        }
+        for y in RECT.y_start as usize..=RECT.y_end as usize {
+            for x in RECT.x_start as usize..=RECT.x_end as usize {
+                if frame[y * WIDTH + x] != sent[y * WIDTH + x] {
+                    assert!(spans.iter().any(|span| contains(span, x, y)), "({x}, {y})");
+                }
+            }
+        }
+
+        // Splitting never costs more than sending the region whole
+        let cost: usize = spans
+            .iter()
+            .map(|span| span.pixel_count() * BYTES_PER_PIXEL + TRANSFER_OVERHEAD_BYTES)
+            .sum();
+        assert!(cost <= whole, "{cost} > {whole} at density {density}");
+    }
+}
+
+#[test]
+fn identical_frames_send_nothing() {
+    let mut display = MockDisplay::board();
+    frame(&mut display, scene);
+    assert!(display.panel.bytes > 0);
+
+    // The tiles are dirty again, but the pixels are the same
+    for _ in 0..3 {
+        frame(&mut display, scene);
+        assert_eq!(display.panel.transfers, 0);
+        assert_eq!(display.panel.bytes, 0);
+    }
+    assert!(display.panel.pixels() == display.frame.front_buffer());
+}
+
+#[test]
+fn invalidated_frame_is_resent_whole() {
+    let mut display = MockDisplay::board();
+    frame(&mut display, scene);
+
+    // Like after the panel was reset: it lost what it showed
+    display.panel = MockPanel::new(DISPLAY_WIDTH, DISPLAY_HEIGHT);
+    display.frame.invalidate();
+    frame(&mut display, scene);
+    let screen = DISPLAY_WIDTH as usize * DISPLAY_HEIGHT as usize;
+    assert_eq!(display.panel.bytes, screen * BYTES_PER_PIXEL);
+    assert!(display.panel.pixels() == display.frame.front_buffer());
+    assert_eq!(display.panel.pixel(60, 50), Rgb565::GREEN);
+
+    // Only once
+    frame(&mut display, scene);
+    assert_eq!(display.panel.bytes, 0);
+}
@@ -0,0 +1,165 @@
+use pixels_core::config::{TILE_SIZE, TRANSFER_OVERHEAD_BYTES};
+use pixels_core::dirty_region::{RegionOptimizer, BYTES_PER_PIXEL};
+use pixels_core::framebuffer::{DirtyRect, Geometry};
+use pixels_core::mock::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use pixels_core::rng::Rng;
+
+const GEOMETRY: Geometry = Geometry::new(DISPLAY_WIDTH, DISPLAY_HEIGHT, TILE_SIZE);
+
+/// Dirty tiles of a frame, row by row
+struct Tiles {
+    dirty: Vec<bool>,
+}
+
+impl Tiles {
+    fn new(dirty: impl Fn(usize, usize) -> bool) -> Self {
+        let dirty = (0..GEOMETRY.tiles_y())
+            .flat_map(|ty| (0..GEOMETRY.tiles_x()).map(move |tx| (tx, ty)))
+            .map(|(tx, ty)| dirty(tx, ty))
+            .collect();
+        Self { dirty }
+    }
+
+    fn is_dirty(&self, tx: usize, ty: usize) -> bool {
+        self.dirty[ty * GEOMETRY.tiles_x() + tx]
+    }
+
+    fn rects(&self) -> impl Iterator<Item = DirtyRect> + '_ {
+        (0..GEOMETRY.tiles_y())
+            .flat_map(|ty| (0..GEOMETRY.tiles_x()).map(move |tx| (tx, ty)))
+            .filter(|&(tx, ty)| self.is_dirty(tx, ty))
+            .map(|(tx, ty)| GEOMETRY.tile_rect(tx, ty, tx, ty))
+    }
+
+    /// Runs of adjacent dirty tiles, like the framebuffer hands them to the optimizer
+    fn batches(&self) -> Vec<DirtyRect> {
+        let mut batches = Vec::new();
+        for ty in 0..GEOMETRY.tiles_y() {
+            let mut tx = 0;
+            while tx < GEOMETRY.tiles_x() {
+                if !self.is_dirty(tx, ty) {
+                    tx += 1;
+                    continue;
+                }
+                let start = tx;
+                while tx + 1 < GEOMETRY.tiles_x() && self.is_dirty(tx + 1, ty) {
+                    tx += 1;
+                }
+                batches.push(GEOMETRY.tile_rect(start, ty, tx, ty));
+                tx += 1;
+            }
+        }
+        batches
+    }
+}
+
+fn cost(rects: &[DirtyRect], overhead: usize) -> usize {
+    rects
+        .iter()
+        .map(|rect| rect.pixel_count() * BYTES_PER_PIXEL + overhead)
+        .sum()
+}
+
+fn overlap(a: &DirtyRect, b: &DirtyRect) -> bool {
+    a.x_start <= b.x_end && b.x_start <= a.x_end && a.y_start <= b.y_end && b.y_start <= a.y_end
+}
+
+fn contains(rect: &DirtyRect, x: u16, y: u16) -> bool {
+    (rect.x_start..=rect.x_end).contains(&x) && (rect.y_start..=rect.y_end).contains(&y)
+}
+
+/// Optimizes `tiles` and checks the regions against them
+fn check(tiles: &Tiles, overhead: usize) -> Vec<DirtyRect> {
+    let mut optimizer = RegionOptimizer::new(overhead);
+    optimizer.optimize(tiles.batches().into_iter());
+    let regions = optimizer.regions().to_vec();
+
+    for (i, a) in regions.iter().enumerate() {
+        assert!(a.x_end < DISPLAY_WIDTH && a.y_end < DISPLAY_HEIGHT, "{a:?}");
+        for b in &regions[i + 1..] {
+            assert!(!overlap(a, b), "{a:?} overlaps {b:?}");
+        }
+    }
+
+    for tile in tiles.rects() {
+        for y in tile.y_start..=tile.y_end {
+            for x in tile.x_start..=tile.x_end {
+                assert!(
+                    regions.iter().any(|rect| contains(rect, x, y)),
+                    "({x}, {y}) of {tile:?} is not sent"
+                );
+            }
+        }
+    }
+
+    let tiles: Vec<_> = tiles.rects().collect();
+    assert!(
+        cost(&regions, overhead) <= cost(&tiles, overhead),
+        "{} regions cost more than {} tiles",
+        regions.len(),
+        tiles.len()
+    );
+    regions
+}
+
+#[test]
+fn all_dirty_is_one_region() {
+    let tiles = Tiles::new(|_, _| true);
+    let regions = check(&tiles, TRANSFER_OVERHEAD_BYTES);
+    assert_eq!(
+        regions,
+        [DirtyRect {
+            x_start: 0,
+            y_start: 0,
+            x_end: DISPLAY_WIDTH - 1,
+            y_end: DISPLAY_HEIGHT - 1,
+        }]
+    );
+}
+
+#[test]
+fn single_tile_is_sent_alone() {
+    for (tx, ty) in [
+        (0, 0),
+        (5, 3),
+        (GEOMETRY.tiles_x() - 1, GEOMETRY.tiles_y() - 1),
+    ] {
+        let tiles = Tiles::new(|x, y| (x, y) == (tx, ty));
+        let regions = check(&tiles, TRANSFER_OVERHEAD_BYTES);
+        assert_eq!(regions, [GEOMETRY.tile_rect(tx, ty, tx, ty)]);
+    }
+}
+
+#[test]
+fn checkerboard() {
+    let tiles = Tiles::new(|tx, ty| (tx + ty) % 2 == 0);
+    let dirty = tiles.rects().count();
+
+    // Without overhead nothing clean is worth sending
+    let regions = check(&tiles, 0);
+    assert_eq!(regions.len(), dirty);
+
+    // With the overhead of the board the clean tiles in between are cheaper
+    let regions = check(&tiles, TRANSFER_OVERHEAD_BYTES);
+    assert!(regions.len() < dirty, "{} regions", regions.len());
+}
+
+#[test]
+fn nothing_dirty_sends_nothing() {
+    let tiles = Tiles::new(|_, _| false);
+    assert!(check(&tiles, TRANSFER_OVERHEAD_BYTES).is_empty());
+}
+
+#[test]
+fn random_frames() {
+    let mut rng = Rng::new(0x7115);
+    for _ in 0..200 {
+        let density = rng.next_f32();
+        let dirty: Vec<bool> = (0..GEOMETRY.total_tiles())
+            .map(|_| rng.next_f32() < density)
+            .collect();
+        let tiles = Tiles::new(|tx, ty| dirty[ty * GEOMETRY.tiles_x() + tx]);
+        let overhead = rng.next_u32() as usize % (4 * TRANSFER_OVERHEAD_BYTES);
+        check(&tiles, overhead);
+    }
+}
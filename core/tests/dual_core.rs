@@ -0,0 +1,262 @@
+#![cfg(feature = "dual-core")]
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use embedded_graphics::pixelcolor::{Rgb565, RgbColor};
+use embedded_graphics::prelude::{Point, Size};
+use embedded_graphics::primitives::Rectangle;
+use pixels_core::config::{TILE_SIZE, TRANSFER_OVERHEAD_BYTES};
+use pixels_core::dual_core::Handoff;
+use pixels_core::framebuffer::{DirtyRect, FrameBuffer, Geometry, RasterJob};
+use pixels_core::mock::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+
+#[test]
+fn full_and_empty() {
+    let queue: Handoff<u32, 2> = Handoff::new();
+    assert_eq!(queue.pop(), None);
+
+    assert_eq!(queue.push(1), Ok(()));
+    assert_eq!(queue.push(2), Ok(()));
+    assert_eq!(queue.push(3), Err(3));
+
+    assert_eq!(queue.pop(), Some(1));
+    assert_eq!(queue.push(3), Ok(()));
+    assert_eq!(queue.pop(), Some(2));
+    assert_eq!(queue.pop(), Some(3));
+    assert_eq!(queue.pop(), None);
+}
+
+/// Values arrive in the order they were pushed, with the producer and the
+/// consumer on two threads
+#[test]
+fn order_across_threads() {
+    const COUNT: u64 = 200_000;
+    let queue: Arc<Handoff<u64, 2>> = Arc::new(Handoff::new());
+
+    let producer = {
+        let queue = queue.clone();
+        thread::spawn(move || {
+            for value in 0..COUNT {
+                let mut value = value;
+                while let Err(rejected) = queue.push(value) {
+                    value = rejected;
+                    thread::yield_now();
+                }
+            }
+        })
+    };
+
+    let mut expected = 0;
+    while expected < COUNT {
+        match queue.pop() {
+            Some(value) => {
+                assert_eq!(value, expected);
+                expected += 1;
+            }
+            None => thread::yield_now(),
+        }
+    }
+    producer.join().unwrap();
+    assert_eq!(queue.pop(), None);
+}
+
+/// Jobs and results like the rasterizer: every job writes a
+/// buffer, the result only arrives after the write
+#[test]
+fn results_follow_the_work() {
+    const JOBS: usize = 10_000;
+    let jobs: Arc<Handoff<(usize, Box<[u32; 64]>), 1>> = Arc::new(Handoff::new());
+    let done: Arc<Handoff<Box<[u32; 64]>, 1>> = Arc::new(Handoff::new());
+
+    let worker = {
+        let (jobs, done) = (jobs.clone(), done.clone());
+        thread::spawn(move || {
+            for _ in 0..JOBS {
+                let (index, mut buffer) = loop {
+                    match jobs.pop() {
+                        Some(job) => break job,
+                        None => thread::yield_now(),
+                    }
+                };
+                buffer.fill(index as u32);
+                // One job at a time, the result slot is always free
+                assert!(done.push(buffer).is_ok());
+            }
+        })
+    };
+
+    let mut buffer = Box::new([0; 64]);
+    for index in 0..JOBS {
+        assert!(jobs.push((index, buffer)).is_ok());
+        buffer = loop {
+            match done.pop() {
+                Some(buffer) => break buffer,
+                None => thread::yield_now(),
+            }
+        };
+        assert!(buffer.iter().all(|&value| value == index as u32));
+    }
+    worker.join().unwrap();
+}
+
+fn framebuffer() -> FrameBuffer {
+    FrameBuffer::new(
+        Geometry::new(DISPLAY_WIDTH, DISPLAY_HEIGHT, TILE_SIZE),
+        TRANSFER_OVERHEAD_BYTES,
+    )
+}
+
+/// Starts frame `n` like the main loop and draws something that moves, text
+/// that stays, erased areas and a canvas for a while
+fn draw(frame: &mut FrameBuffer, n: usize) {
+    let canvas = (10..20).contains(&n);
+    frame.set_background(canvas);
+    frame.clear_buffer();
+
+    let x = (n * 13 % 280) as i32;
+    frame.draw_line(Point::new(x, 10), Point::new(300 - x, 160));
+    frame.write(&n.to_string(), Point::new(x / 2, 40));
+    frame.write_unchanged("FPS", Point::new(250, 140));
+    if n.is_multiple_of(4) {
+        frame.fill_rect(
+            Rectangle::new(Point::new(x, 100), Size::new(30, 20)),
+            Rgb565::GREEN,
+        );
+    }
+    if n.is_multiple_of(5) {
+        frame.erase(Rectangle::new(Point::new(240, 130), Size::new(60, 30)));
+    }
+    if canvas {
+        for i in 0..20 {
+            frame.set_background_pixel((n * 20 + i) as u16 % 320, 80, Rgb565::RED);
+        }
+    }
+}
+
+/// What the panel is sent for a frame
+type Shown = (Vec<Rgb565>, Vec<DirtyRect>);
+
+/// Swaps the rasterized frame in and computes its regions like the display
+fn present(frame: &mut FrameBuffer) -> Shown {
+    frame.swap_buffers();
+    frame.update_dirty_regions();
+    let shown = (
+        frame.front_buffer().to_vec(),
+        frame.dirty_regions().to_vec(),
+    );
+    frame.finish_frame();
+    shown
+}
+
+/// Rasterizes the jobs of `jobs` on another thread and hands them back
+/// through `done`, like core 1
+fn rasterizer(
+    jobs: Arc<Handoff<RasterJob, 1>>,
+    done: Arc<Handoff<RasterJob, 1>>,
+    count: usize,
+    before: impl Fn(usize) + Send + 'static,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        for n in 0..count {
+            let mut job = loop {
+                match jobs.pop() {
+                    Some(job) => break job,
+                    None => thread::yield_now(),
+                }
+            };
+            before(n);
+            let Ok(()) = job.rasterize();
+            // One job at a time, the result slot is always free
+            assert!(done.push(job).is_ok());
+        }
+    })
+}
+
+fn collect(done: &Handoff<RasterJob, 1>) -> RasterJob {
+    loop {
+        match done.pop() {
+            Some(job) => break job,
+            None => thread::yield_now(),
+        }
+    }
+}
+
+/// A frame rasterized on the other thread while the next one is drawn ends
+/// up like one rasterized in place, a frame later
+#[test]
+fn pipelined_frames_match_in_place_ones() {
+    const FRAMES: usize = 40;
+
+    let mut in_place = framebuffer();
+    let expected: Vec<Shown> = (0..FRAMES)
+        .map(|n| {
+            draw(&mut in_place, n);
+            let Ok(()) = in_place.rasterize();
+            present(&mut in_place)
+        })
+        .collect();
+
+    let jobs = Arc::new(Handoff::new());
+    let done = Arc::new(Handoff::new());
+    let worker = rasterizer(jobs.clone(), done.clone(), FRAMES, |_| {});
+
+    let mut frame = framebuffer();
+    let mut shown = Vec::new();
+    for n in 0..FRAMES {
+        draw(&mut frame, n);
+        if n > 0 {
+            frame.finish_raster_job(collect(&done));
+            shown.push(present(&mut frame));
+        }
+        assert!(jobs.push(frame.take_raster_job()).is_ok());
+    }
+    frame.finish_raster_job(collect(&done));
+    shown.push(present(&mut frame));
+    worker.join().unwrap();
+
+    for (n, (shown, expected)) in shown.iter().zip(&expected).enumerate() {
+        assert!(shown.0 == expected.0, "pixels of frame {n}");
+        assert_eq!(shown.1, expected.1, "regions of frame {n}");
+    }
+}
+
+/// The next frame is drawn while the rasterizer holds the one before, the
+/// stages overlap instead of taking turns
+#[test]
+fn drawing_overlaps_rasterizing() {
+    const FRAMES: usize = 20;
+    // Frames drawn by the main thread
+    let drawn = Arc::new(AtomicUsize::new(0));
+
+    let jobs = Arc::new(Handoff::new());
+    let done = Arc::new(Handoff::new());
+    let worker = {
+        let drawn = drawn.clone();
+        // Every job is held until the frame after it is drawn, which never
+        // happens if drawing waits for the rasterizer
+        rasterizer(jobs.clone(), done.clone(), FRAMES, move |n| {
+            let start = Instant::now();
+            while drawn.load(Ordering::Acquire) < (n + 2).min(FRAMES) {
+                assert!(start.elapsed() < Duration::from_secs(10), "frame {n}");
+                thread::yield_now();
+            }
+        })
+    };
+
+    let mut frame = framebuffer();
+    for n in 0..FRAMES {
+        draw(&mut frame, n);
+        drawn.store(n + 1, Ordering::Release);
+        if n > 0 {
+            frame.finish_raster_job(collect(&done));
+            present(&mut frame);
+        }
+        assert!(jobs.push(frame.take_raster_job()).is_ok());
+    }
+    frame.finish_raster_job(collect(&done));
+    present(&mut frame);
+    worker.join().unwrap();
+}
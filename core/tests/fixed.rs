@@ -0,0 +1,262 @@
+#![cfg(feature = "fixed-point")]
+
+use embedded_graphics::prelude::Point;
+use micromath::vector::F32x3;
+use micromath::{F32Ext, Quaternion};
+use pixels_core::fixed::{Fixed, Vector};
+use pixels_core::math::{self, Projection, Rotation};
+
+/// One step of Q16.16
+const ULP: f64 = 1.0 / 65536.0;
+
+/// Furthest a projected point may be off, in pixels
+const MAX_PIXEL_ERROR: i32 = 1;
+/// Same after the per-frame rotation was accumulated for 600 frames
+const MAX_ACCUMULATED_PIXEL_ERROR: i32 = 6;
+/// Furthest a rotated point may be off, per component
+const MAX_ROTATION_ERROR: f64 = 0.001;
+
+const FOV: f32 = 200.0;
+const DISTANCE: f32 = 4.0;
+const CENTER: Point = Point::new(268, 120);
+
+/// Values from -8 to 8 that are not on the Q16.16 grid
+fn samples() -> impl Iterator<Item = f32> + Clone {
+    (-400..=400).map(|i| i as f32 * 0.0201 + 0.00003)
+}
+
+#[test]
+fn scalar_error_is_within_one_step() {
+    let mut max_error = [0.0f64; 4];
+    for a in samples() {
+        let fa = Fixed::from_f32(a);
+        // Truncated towards zero
+        max_error[0] = max_error[0].max((fa.to_f32() as f64 - a as f64).abs());
+        max_error[3] = max_error[3]
+            .max((fa.sqrt().to_f32() as f64 - (fa.to_f32() as f64).max(0.0).sqrt()).abs());
+
+        for b in samples().step_by(7) {
+            let fb = Fixed::from_f32(b);
+            let (x, y) = (fa.to_f32() as f64, fb.to_f32() as f64);
+            max_error[1] = max_error[1].max(((fa * fb).to_f32() as f64 - x * y).abs());
+            if y.abs() >= 0.5 {
+                max_error[2] = max_error[2].max(((fa / fb).to_f32() as f64 - x / y).abs());
+            }
+        }
+    }
+
+    for (error, operation) in max_error.iter().zip(["from_f32", "mul", "div", "sqrt"]) {
+        assert!(*error <= ULP, "{operation}: {error} > {ULP}");
+    }
+}
+
+/// Cube corners and a few other points of the scene
+const POINTS: [(f32, f32, f32); 10] = [
+    (-1.0, -1.0, -1.0),
+    (1.0, -1.0, -1.0),
+    (1.0, 1.0, -1.0),
+    (-1.0, 1.0, -1.0),
+    (-1.0, -1.0, 1.0),
+    (1.0, -1.0, 1.0),
+    (1.0, 1.0, 1.0),
+    (-1.0, 1.0, 1.0),
+    (0.3, -0.7, 0.9),
+    (0.0, 0.0, 0.0),
+];
+
+/// Rotation in `f64` to measure both against
+#[derive(Clone, Copy)]
+struct Reference([f64; 4]);
+
+impl Reference {
+    /// Rotation by the angle both representations are built from: sine and
+    /// cosine come from `micromath` for them, which is not exact
+    fn axis_angle((x, y, z): (f32, f32, f32), angle: f32) -> Self {
+        let half = angle * 0.5;
+        let (sin, cos) = (F32Ext::sin(half) as f64, F32Ext::cos(half) as f64);
+        let length = (sin * sin + cos * cos).sqrt();
+        let sin = sin / length;
+        Self([cos / length, x as f64 * sin, y as f64 * sin, z as f64 * sin])
+    }
+
+    fn compose(self, other: Self) -> Self {
+        let [w1, x1, y1, z1] = self.0;
+        let [w2, x2, y2, z2] = other.0;
+        Self([
+            w1 * w2 - x1 * x2 - y1 * y2 - z1 * z2,
+            w1 * x2 + x1 * w2 + y1 * z2 - z1 * y2,
+            w1 * y2 - x1 * z2 + y1 * w2 + z1 * x2,
+            w1 * z2 + x1 * y2 - y1 * x2 + z1 * w2,
+        ])
+    }
+
+    fn rotate(self, (px, py, pz): (f32, f32, f32)) -> [f64; 3] {
+        let [w, x, y, z] = self.0;
+        let (px, py, pz) = (px as f64, py as f64, pz as f64);
+        [
+            (w * w + x * x - y * y - z * z) * px
+                + 2.0 * (x * y - w * z) * py
+                + 2.0 * (x * z + w * y) * pz,
+            2.0 * (x * y + w * z) * px
+                + (w * w - x * x + y * y - z * z) * py
+                + 2.0 * (y * z - w * x) * pz,
+            2.0 * (x * z - w * y) * px
+                + 2.0 * (y * z + w * x) * py
+                + (w * w - x * x - y * y + z * z) * pz,
+        ]
+    }
+}
+
+/// A rotation in all three representations
+#[derive(Clone, Copy)]
+struct Rotations {
+    reference: Reference,
+    float: Quaternion,
+    fixed: Rotation,
+}
+
+impl Rotations {
+    const IDENTITY: Self = Self {
+        reference: Reference([1.0, 0.0, 0.0, 0.0]),
+        float: Quaternion::IDENTITY,
+        fixed: Rotation::IDENTITY,
+    };
+
+    fn axis_angle(axis: (f32, f32, f32), angle: f32) -> Self {
+        Self {
+            reference: Reference::axis_angle(axis, angle),
+            float: Quaternion::axis_angle(F32x3::from(axis), angle),
+            fixed: math::axis_angle(Vector::from(axis), angle),
+        }
+    }
+
+    /// `self` followed by `other`
+    fn then(self, other: Self) -> Self {
+        Self {
+            reference: other.reference.compose(self.reference),
+            float: other.float * self.float,
+            fixed: math::compose(other.fixed, self.fixed),
+        }
+    }
+
+    /// Largest pixel and per-component error of the points rotated and
+    /// projected in `f32` and in fixed point
+    fn errors(&self) -> Errors {
+        let projection = Projection::new(FOV, DISTANCE, CENTER);
+        let project = |[x, y, z]: [f64; 3]| {
+            let scale = FOV as f64 / (z + DISTANCE as f64);
+            Point::new((x * scale) as i32, (y * scale) as i32) + CENTER
+        };
+        let mut errors = Errors::default();
+
+        for point in POINTS {
+            let expected = self.reference.rotate(point);
+            let float = self.float.rotate(F32x3::from(point));
+            let float = [float.x, float.y, float.z].map(|c| c as f64);
+            let fixed = self.fixed.rotate(Vector::from(point));
+            let fixed = [fixed.x, fixed.y, fixed.z].map(|c| c.to_f32() as f64);
+
+            for ((reference, float), fixed) in expected.iter().zip(float).zip(fixed) {
+                errors.float.1 = errors.float.1.max((float - reference).abs());
+                errors.fixed.1 = errors.fixed.1.max((fixed - reference).abs());
+            }
+
+            let offset = |point: Point| {
+                let offset = point - project(expected);
+                offset.x.abs().max(offset.y.abs())
+            };
+            let fixed_point = projection.project(self.fixed, Vector::from(point)).unwrap();
+            errors.float.0 = errors.float.0.max(offset(project(float)));
+            errors.fixed.0 = errors.fixed.0.max(offset(fixed_point));
+        }
+        errors
+    }
+}
+
+/// Pixel and per-component errors, against the exact rotation
+#[derive(Default, Debug)]
+struct Errors {
+    float: (i32, f64),
+    fixed: (i32, f64),
+}
+
+impl Errors {
+    fn max(self, other: Self) -> Self {
+        let max = |a: (i32, f64), b: (i32, f64)| (a.0.max(b.0), a.1.max(b.1));
+        Self {
+            float: max(self.float, other.float),
+            fixed: max(self.fixed, other.fixed),
+        }
+    }
+}
+
+/// Rotations of the main loop's range, each built from two axis rotations
+#[test]
+fn projection_is_as_accurate_as_f32() {
+    let mut errors = Errors::default();
+    for i in 0..2000 {
+        let rotation = Rotations::axis_angle((1.0, 0.0, 0.0), i as f32 * 0.0071)
+            .then(Rotations::axis_angle((0.0, 1.0, 0.0), i as f32 * 0.0123));
+        errors = errors.max(rotation.errors());
+    }
+
+    assert!(errors.float.0 <= MAX_PIXEL_ERROR, "{errors:?}");
+    assert!(errors.fixed.0 <= MAX_PIXEL_ERROR, "{errors:?}");
+    assert!(errors.fixed.1 <= MAX_ROTATION_ERROR, "{errors:?}");
+    // f32 is off more, its rotations are not normalized
+    assert!(errors.fixed.1 <= errors.float.1, "{errors:?}");
+}
+
+/// The rotation of the main loop, accumulated frame by frame for 10 seconds
+/// at 60 FPS
+///
+/// The per-frame step is rounded to Q16.16, so the fixed-point cube turns
+/// about 0.3% slower or faster and the error grows with the frames. `f32`
+/// stays within a pixel.
+#[test]
+fn accumulated_rotation_stays_close() {
+    let step = Rotations::axis_angle((1.0, 0.0, 0.0), 0.011)
+        .then(Rotations::axis_angle((0.0, 1.0, 0.0), 0.03));
+
+    let mut rotation = Rotations::IDENTITY;
+    let mut errors = Errors::default();
+    for _ in 0..600 {
+        rotation = rotation.then(step);
+        errors = errors.max(rotation.errors());
+    }
+
+    assert!(errors.float.0 <= MAX_PIXEL_ERROR, "{errors:?}");
+    assert!(errors.fixed.0 <= MAX_ACCUMULATED_PIXEL_ERROR, "{errors:?}");
+}
+
+/// Normalizing keeps the accumulated rotation from growing or shrinking
+/// the cube
+#[test]
+fn accumulated_rotation_keeps_unit_length() {
+    let step = math::axis_angle(math::vec3(0.6, 0.8, 0.0), 0.05);
+    let mut rotation = Rotation::IDENTITY;
+    for _ in 0..100_000 {
+        rotation = math::compose(step, rotation);
+    }
+
+    for point in POINTS {
+        let length =
+            |v: Vector| (v.x.to_f32().powi(2) + v.y.to_f32().powi(2) + v.z.to_f32().powi(2)).sqrt();
+        let rotated = rotation.rotate(Vector::from(point));
+        let error = (length(rotated) - length(Vector::from(point))).abs();
+        assert!(error as f64 <= MAX_ROTATION_ERROR, "{point:?}: {error}");
+    }
+}
+
+#[test]
+fn particles_bounce_off_the_walls() {
+    let mut position = math::vec3(0.9, -0.95, 0.0);
+    let mut velocity = math::vec3(0.02, -0.02, 0.01);
+    for _ in 0..10 {
+        math::step_particle(&mut position, &mut velocity);
+    }
+    assert!(velocity.x < Fixed::ZERO);
+    assert!(velocity.y > Fixed::ZERO);
+    assert_eq!(velocity.z, Fixed::from_f32(0.01));
+    assert!(position.x <= Fixed::ONE && position.y >= -Fixed::ONE);
+}
@@ -0,0 +1,209 @@
+#![cfg(feature = "async-flush")]
+
+use std::cell::Cell;
+use std::convert::Infallible;
+use std::rc::Rc;
+
+use embassy_futures::block_on;
+use embedded_graphics::pixelcolor::{Rgb565, RgbColor};
+use embedded_graphics::prelude::{Point, Size};
+use embedded_graphics::primitives::Rectangle;
+use embedded_hal::digital::{ErrorType, OutputPin};
+use embedded_hal_async::spi::{self, Operation, SpiDevice};
+use mipidsi::interface::Interface;
+use pixels_core::config::{TILE_SIZE, TRANSFER_OVERHEAD_BYTES};
+use pixels_core::flush::{flush, FlushJob};
+use pixels_core::framebuffer::{DirtyRect, FrameBuffer, Geometry};
+use pixels_core::mock::{MockPanel, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use pixels_core::panel::PanelCommand;
+
+/// SPI device that keeps every write with the level of the DC pin
+struct MockSpi {
+    dc: Rc<Cell<bool>>,
+    writes: Vec<(bool, Vec<u8>)>,
+}
+
+struct MockDc(Rc<Cell<bool>>);
+
+impl spi::ErrorType for MockSpi {
+    type Error = Infallible;
+}
+
+impl SpiDevice for MockSpi {
+    async fn transaction(
+        &mut self,
+        operations: &mut [Operation<'_, u8>],
+    ) -> Result<(), Infallible> {
+        for operation in operations {
+            if let Operation::Write(data) = operation {
+                self.writes.push((self.dc.get(), data.to_vec()));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ErrorType for MockDc {
+    type Error = Infallible;
+}
+
+impl OutputPin for MockDc {
+    fn set_low(&mut self) -> Result<(), Infallible> {
+        self.0.set(false);
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Infallible> {
+        self.0.set(true);
+        Ok(())
+    }
+}
+
+fn job(buffer: Vec<Rgb565>, rects: Vec<DirtyRect>) -> FlushJob<()> {
+    FlushJob {
+        buffer,
+        width: DISPLAY_WIDTH as usize,
+        rects,
+        commands: Vec::new(),
+        lut: None,
+        reinit: false,
+        result: Ok(()),
+    }
+}
+
+/// Sends `job` with a staging buffer of `staging_len` bytes and returns the
+/// writes, `true` for data
+fn writes(job: &FlushJob<()>, staging_len: usize, offset: (u16, u16)) -> Vec<(bool, Vec<u8>)> {
+    let level = Rc::new(Cell::new(true));
+    let mut spi = MockSpi {
+        dc: level.clone(),
+        writes: Vec::new(),
+    };
+    let mut dc = MockDc(level);
+    let mut staging = vec![0; staging_len];
+    block_on(flush(&mut spi, &mut dc, &mut staging, job, offset)).unwrap();
+    spi.writes
+}
+
+/// Plays the writes back to `panel`, a command with DC low and everything
+/// with DC high up to the next command as its parameters or pixels
+fn play(writes: &[(bool, Vec<u8>)], panel: &mut MockPanel) {
+    let mut writes = writes.iter().peekable();
+    while let Some((data, bytes)) = writes.next() {
+        assert!(!data, "data without a command");
+        let command = bytes[0];
+        let mut args = Vec::new();
+        while let Some((_, bytes)) = writes.next_if(|(data, _)| *data) {
+            args.extend_from_slice(bytes);
+        }
+
+        let Ok(()) = if command == 0x2C {
+            let Ok(()) = panel.send_command(command, &[]);
+            panel.send_pixels(args.chunks_exact(2).map(|pixel| [pixel[0], pixel[1]]))
+        } else {
+            panel.send_command(command, &args)
+        };
+    }
+}
+
+fn rect(x_start: u16, y_start: u16, x_end: u16, y_end: u16) -> DirtyRect {
+    DirtyRect {
+        x_start,
+        y_start,
+        x_end,
+        y_end,
+    }
+}
+
+#[test]
+fn commands_go_before_the_regions() {
+    let mut job = job(vec![Rgb565::WHITE; 320 * 170], vec![rect(0, 0, 0, 0)]);
+    job.commands = vec![PanelCommand::Brightness(0x80), PanelCommand::InversionOn];
+
+    let writes = writes(&job, 64, (0, 0));
+    assert_eq!(
+        writes,
+        [
+            (false, vec![0x51]),
+            (true, vec![0x80]),
+            (false, vec![0x21]),
+            (false, vec![0x2A]),
+            (true, vec![0, 0, 0, 0]),
+            (false, vec![0x2B]),
+            (true, vec![0, 0, 0, 0]),
+            (false, vec![0x2C]),
+            (true, vec![0xFF, 0xFF]),
+        ]
+    );
+}
+
+#[test]
+fn offset_moves_the_address_window() {
+    let job = job(vec![Rgb565::BLACK; 320 * 170], vec![rect(2, 3, 4, 5)]);
+
+    let writes = writes(&job, 64, (0x100, 35));
+    assert_eq!(writes[1], (true, vec![0x01, 0x02, 0x01, 0x04]));
+    assert_eq!(writes[3], (true, vec![0, 38, 0, 40]));
+}
+
+#[test]
+fn pixels_are_sent_in_whole_pixel_chunks() {
+    // 7 bytes of staging hold 3 pixels, 5 pixels take two writes
+    let job = job(vec![Rgb565::RED; 320 * 170], vec![rect(10, 10, 14, 10)]);
+
+    let pixels: Vec<_> = writes(&job, 7, (0, 0))
+        .into_iter()
+        .skip(5)
+        .map(|(data, bytes)| {
+            assert!(data);
+            bytes
+        })
+        .collect();
+    assert_eq!(
+        pixels,
+        [vec![0xF8, 0, 0xF8, 0, 0xF8, 0], vec![0xF8, 0, 0xF8, 0]]
+    );
+}
+
+/// Renders the frames like the firmware with a flush task: the front buffer
+/// of a frame is in a job while the next frame is drawn, and comes back
+/// before the next swap
+#[test]
+fn panel_follows_frames_in_flight() {
+    let geometry = Geometry::new(DISPLAY_WIDTH, DISPLAY_HEIGHT, TILE_SIZE);
+    let mut frame = FrameBuffer::new(geometry, TRANSFER_OVERHEAD_BYTES);
+    let mut panel = MockPanel::new(DISPLAY_WIDTH, DISPLAY_HEIGHT);
+    let mut in_flight: Option<FlushJob<()>> = None;
+
+    for step in 0..20 {
+        frame.clear_buffer();
+        let x = 10 + 13 * step;
+        frame.draw_line(Point::new(x, 5), Point::new(300 - x, 160));
+        if step % 3 == 0 {
+            frame.fill_rect(
+                Rectangle::new(Point::new(x, 60), Size::new(40, 40)),
+                Rgb565::GREEN,
+            );
+        }
+        let Ok(()) = frame.rasterize();
+
+        // Fence: the previous frame is sent and its buffer handed back
+        if let Some(job) = in_flight.take() {
+            play(&writes(&job, 4096, (0, 0)), &mut panel);
+            frame.restore_front_buffer(job.buffer);
+            assert!(panel.pixels() == frame.front_buffer(), "step {step}");
+        }
+
+        frame.swap_buffers();
+        frame.update_dirty_regions();
+        in_flight = Some(job(
+            frame.take_front_buffer(),
+            frame.dirty_regions().to_vec(),
+        ));
+        frame.finish_frame();
+    }
+
+    let job = in_flight.unwrap();
+    play(&writes(&job, 4096, (0, 0)), &mut panel);
+    assert!(panel.pixels() == job.buffer.as_slice());
+}
@@ -0,0 +1,145 @@
+use embassy_futures::block_on;
+use embedded_graphics::pixelcolor::{Rgb565, RgbColor};
+use embedded_graphics::prelude::Point;
+use pixels_core::display::DisplayTrait;
+use pixels_core::fps::FpsMeter;
+use pixels_core::mock::MockDisplay;
+
+const POSITION: Point = Point::new(8, 8);
+/// Bottom of the graph below the counter at [`POSITION`]
+const GRAPH_BOTTOM: u16 = 8 + 20 + 32;
+
+/// Ticks `frames` frames of `frame_ms` each after `now_ms`, returns the time
+/// of the last one
+fn run(fps: &mut FpsMeter, now_ms: u64, frame_ms: u64, frames: usize) -> u64 {
+    let mut now = now_ms;
+    for _ in 0..frames {
+        now += frame_ms;
+        fps.tick(now);
+    }
+    now
+}
+
+/// Draws a frame with the counter like the main loop: clear, draw, send
+fn frame(display: &mut MockDisplay, fps: &mut FpsMeter, position: Point, graph: bool) {
+    display.panel.clear_log();
+    display.frame.clear_buffer();
+    let Ok(()) = fps.draw(display, position, graph);
+    let Ok(()) = block_on(display.update_with_buffer());
+}
+
+/// What a display shows with only the counter of `fps` drawn on it once
+fn drawn_once(fps: &FpsMeter, position: Point) -> MockDisplay {
+    let mut display = MockDisplay::board();
+    display.frame.clear_buffer();
+    let Ok(()) = display.write(&format!("FPS: {:>3}", fps.fps()), position);
+    let Ok(()) = block_on(display.update_with_buffer());
+    display
+}
+
+#[test]
+fn steady_frames_give_their_rate() {
+    let mut fps = FpsMeter::new();
+    assert_eq!(fps.fps(), 0);
+    fps.tick(1000);
+    assert_eq!(fps.fps(), 0);
+
+    run(&mut fps, 1000, 20, 1);
+    assert_eq!(fps.fps(), 50);
+    run(&mut fps, 1020, 20, 100);
+    assert_eq!(fps.fps(), 50);
+}
+
+#[test]
+fn rate_changes_are_smoothed() {
+    let mut fps = FpsMeter::new();
+    let now = run(&mut fps, 0, 20, 10);
+
+    // A tenth of the way from 20 ms to 10 ms: 19 ms
+    let now = run(&mut fps, now, 10, 1);
+    assert_eq!(fps.fps(), 53);
+    // One slow frame moves it by a tenth: 27.1 ms
+    let now = run(&mut fps, now, 100, 1);
+    assert_eq!(fps.fps(), 37);
+
+    run(&mut fps, now, 10, 100);
+    assert_eq!(fps.fps(), 100);
+}
+
+#[test]
+fn clock_going_back_counts_as_no_time() {
+    let mut fps = FpsMeter::new();
+    run(&mut fps, 0, 20, 10);
+    fps.tick(100);
+    fps.tick(120);
+    assert!(fps.fps() > 0);
+}
+
+#[test]
+fn graph_shows_the_last_frame_times() {
+    let mut fps = FpsMeter::new();
+    let now = run(&mut fps, 0, 10, 40);
+    run(&mut fps, now, 100, 1);
+
+    let mut display = MockDisplay::board();
+    frame(&mut display, &mut fps, POSITION, true);
+
+    // Oldest frame on the left, 3 pixels apart, one pixel per millisecond
+    let bar = |i: u16, height: u16| display.panel.pixel(8 + 3 * i, GRAPH_BOTTOM - height);
+    assert_eq!(bar(0, 5), Rgb565::WHITE);
+    assert_eq!(bar(0, 15), Rgb565::BLACK);
+    // The slow frame is cut at the height of the graph
+    assert_eq!(bar(31, 30), Rgb565::WHITE);
+    assert_eq!(bar(31, 35), Rgb565::BLACK);
+
+    // Without the graph only the counter is drawn
+    let mut display = MockDisplay::board();
+    frame(&mut display, &mut fps, POSITION, false);
+    assert_eq!(display.panel.pixel(8, GRAPH_BOTTOM - 5), Rgb565::BLACK);
+}
+
+#[test]
+fn unchanged_counter_is_not_sent() {
+    let mut fps = FpsMeter::new();
+    let mut now = run(&mut fps, 0, 20, 2);
+    let mut display = MockDisplay::board();
+    frame(&mut display, &mut fps, POSITION, false);
+    assert!(display.panel.transfers > 0);
+
+    // Sent once more from the other buffer, then no longer
+    now = run(&mut fps, now, 20, 1);
+    frame(&mut display, &mut fps, POSITION, false);
+    for _ in 0..3 {
+        now = run(&mut fps, now, 20, 1);
+        frame(&mut display, &mut fps, POSITION, false);
+        assert_eq!(display.panel.transfers, 0);
+    }
+    assert!(display.panel.pixels() == drawn_once(&fps, POSITION).panel.pixels());
+
+    // A new rate covers the old text
+    run(&mut fps, now, 5, 20);
+    frame(&mut display, &mut fps, POSITION, false);
+    assert!(display.panel.transfers > 0);
+    let expected = drawn_once(&fps, POSITION);
+    assert!(display.panel.pixels() == expected.panel.pixels());
+    frame(&mut display, &mut fps, POSITION, false);
+    assert!(display.panel.pixels() == expected.panel.pixels());
+}
+
+#[test]
+fn moved_counter_leaves_nothing_behind() {
+    let mut fps = FpsMeter::new();
+    run(&mut fps, 0, 20, 2);
+    let mut display = MockDisplay::board();
+    for _ in 0..3 {
+        frame(&mut display, &mut fps, POSITION, false);
+    }
+
+    // Like the HUD orbiting against burn-in
+    let moved = POSITION + Point::new(3, 2);
+    for _ in 0..3 {
+        frame(&mut display, &mut fps, moved, false);
+        let expected = drawn_once(&fps, moved);
+        assert!(display.panel.pixels() == expected.panel.pixels());
+    }
+}
@@ -0,0 +1,64 @@
+use embassy_futures::block_on;
+use embedded_graphics::pixelcolor::{Rgb565, RgbColor};
+use embedded_graphics::prelude::{Point, Size};
+use embedded_graphics::primitives::Rectangle;
+use pixels_core::display::DisplayTrait;
+use pixels_core::framebuffer::{DirtyRect, FrameBuffer, Geometry};
+use pixels_core::mock::MockDisplay;
+
+#[test]
+fn last_tiles_may_be_partial() {
+    let geometry = Geometry::new(536, 240, 16);
+    assert_eq!(geometry.tiles_x(), 34);
+    assert_eq!(geometry.tiles_y(), 15);
+    assert_eq!(
+        geometry.tile_rect(33, 14, 33, 14),
+        DirtyRect {
+            x_start: 528,
+            y_start: 224,
+            x_end: 535,
+            y_end: 239,
+        }
+    );
+}
+
+#[test]
+#[should_panic(expected = "tile size of 0")]
+fn tile_size_must_not_be_0() {
+    Geometry::new(240, 240, 0);
+}
+
+#[test]
+#[should_panic(expected = "without pixels")]
+fn width_must_not_be_0() {
+    Geometry::new(0, 240, 16);
+}
+
+#[test]
+#[should_panic(expected = "without pixels")]
+fn height_must_not_be_0() {
+    Geometry::new(240, 0, 16);
+}
+
+#[test]
+#[should_panic(expected = "tile size of 0")]
+fn geometry_from_fields_is_checked_too() {
+    let geometry = Geometry {
+        width: 240,
+        height: 240,
+        tile_size: 0,
+    };
+    FrameBuffer::new(geometry, 0);
+}
+
+#[test]
+fn single_pixel_screen() {
+    let mut display = MockDisplay::new(Geometry::new(1, 1, 16), 0);
+    display.frame.invalidate();
+    let Ok(()) = display.fill_rect(
+        Rectangle::new(Point::new(-2, -2), Size::new(4, 4)),
+        Rgb565::RED,
+    );
+    let Ok(()) = block_on(display.update_with_buffer());
+    assert_eq!(display.panel.pixels(), [Rgb565::RED]);
+}
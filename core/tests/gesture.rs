@@ -0,0 +1,238 @@
+use embedded_graphics::prelude::Point;
+use pixels_core::gesture::{Gesture, GestureRecognizer, Phase, SwipeDirection, TouchSample};
+
+fn sample(phase: Phase, x: i32, y: i32, time_ms: u64) -> TouchSample {
+    TouchSample {
+        phase,
+        position: Point::new(x, y),
+        time_ms,
+    }
+}
+
+/// Gestures of all `samples`, in order
+fn gestures(recognizer: &mut GestureRecognizer, samples: &[TouchSample]) -> Vec<Gesture> {
+    samples
+        .iter()
+        .flat_map(|&sample| recognizer.update(sample).collect::<Vec<_>>())
+        .collect()
+}
+
+/// A straight movement from `from` by `step` every 16 ms, released `idle_ms`
+/// after the last move
+fn stroke(from: Point, step: Point, steps: i32, idle_ms: u64) -> Vec<TouchSample> {
+    let mut samples = vec![sample(Phase::Down, from.x, from.y, 0)];
+    for i in 1..=steps {
+        let position = from + step * i;
+        samples.push(sample(Phase::Move, position.x, position.y, i as u64 * 16));
+    }
+    let end = from + step * steps;
+    samples.push(sample(Phase::Up, end.x, end.y, steps as u64 * 16 + idle_ms));
+    samples
+}
+
+#[test]
+fn tap_within_the_slop() {
+    let mut recognizer = GestureRecognizer::new();
+    let samples = [
+        sample(Phase::Down, 100, 100, 0),
+        sample(Phase::Move, 106, 104, 30),
+        sample(Phase::Up, 106, 104, 80),
+    ];
+    assert_eq!(
+        gestures(&mut recognizer, &samples),
+        [Gesture::Tap(Point::new(100, 100))]
+    );
+}
+
+#[test]
+fn held_too_long_is_no_tap() {
+    let mut recognizer = GestureRecognizer::new();
+    let samples = [
+        sample(Phase::Down, 100, 100, 0),
+        sample(Phase::Up, 100, 100, 400),
+    ];
+    assert!(gestures(&mut recognizer, &samples).is_empty());
+}
+
+#[test]
+fn double_tap() {
+    let mut recognizer = GestureRecognizer::new();
+    let first = [
+        sample(Phase::Down, 100, 100, 0),
+        sample(Phase::Up, 100, 100, 80),
+    ];
+    let second = [
+        sample(Phase::Down, 103, 100, 200),
+        sample(Phase::Up, 103, 100, 260),
+    ];
+    gestures(&mut recognizer, &first);
+    assert_eq!(
+        gestures(&mut recognizer, &second),
+        [
+            Gesture::Tap(Point::new(103, 100)),
+            Gesture::DoubleTap(Point::new(103, 100))
+        ]
+    );
+
+    // A third tap starts over
+    let third = [
+        sample(Phase::Down, 103, 100, 400),
+        sample(Phase::Up, 103, 100, 450),
+    ];
+    assert_eq!(
+        gestures(&mut recognizer, &third),
+        [Gesture::Tap(Point::new(103, 100))]
+    );
+}
+
+#[test]
+fn taps_too_late_or_too_far_apart_are_no_double_tap() {
+    let taps = |second: Point, time_ms: u64| {
+        let mut recognizer = GestureRecognizer::new();
+        gestures(
+            &mut recognizer,
+            &[
+                sample(Phase::Down, 100, 100, 0),
+                sample(Phase::Up, 100, 100, 50),
+                sample(Phase::Down, second.x, second.y, time_ms),
+                sample(Phase::Up, second.x, second.y, time_ms + 50),
+            ],
+        )
+    };
+
+    let late = taps(Point::new(100, 100), 400);
+    assert!(!late.iter().any(|g| matches!(g, Gesture::DoubleTap(_))));
+    let far = taps(Point::new(140, 100), 150);
+    assert!(!far.iter().any(|g| matches!(g, Gesture::DoubleTap(_))));
+}
+
+#[test]
+fn long_press_is_reported_once() {
+    let mut recognizer = GestureRecognizer::new();
+    gestures(&mut recognizer, &[sample(Phase::Down, 10, 10, 0)]);
+    assert_eq!(recognizer.poll(300), None);
+    assert_eq!(
+        recognizer.poll(700),
+        Some(Gesture::LongPress(Point::new(10, 10)))
+    );
+    assert_eq!(recognizer.poll(800), None);
+
+    // Neither a tap nor a drag afterwards
+    let rest = [
+        sample(Phase::Move, 60, 10, 850),
+        sample(Phase::Up, 60, 10, 900),
+    ];
+    assert!(gestures(&mut recognizer, &rest).is_empty());
+}
+
+#[test]
+fn long_press_is_reported_by_moves_too() {
+    let mut recognizer = GestureRecognizer::new();
+    let samples = [
+        sample(Phase::Down, 10, 10, 0),
+        sample(Phase::Move, 12, 10, 650),
+    ];
+    assert_eq!(
+        gestures(&mut recognizer, &samples),
+        [Gesture::LongPress(Point::new(10, 10))]
+    );
+}
+
+#[test]
+fn drags_add_up_to_the_movement() {
+    let mut recognizer = GestureRecognizer::new();
+    let samples = stroke(Point::new(100, 100), Point::new(3, 1), 20, 0);
+    let drags: Vec<_> = gestures(&mut recognizer, &samples)
+        .into_iter()
+        .filter_map(|gesture| match gesture {
+            Gesture::Drag { position, delta } => Some((position, delta)),
+            _ => None,
+        })
+        .collect();
+
+    // The first drag once the slop is left, then one per move
+    assert_eq!(drags.len(), 17);
+    let total = drags
+        .iter()
+        .fold(Point::zero(), |sum, &(_, delta)| sum + delta);
+    assert_eq!(total, Point::new(60, 20));
+    assert_eq!(drags.last().unwrap().0, Point::new(160, 120));
+}
+
+#[test]
+fn fast_release_flings() {
+    let mut recognizer = GestureRecognizer::new();
+    // 15 px every 16 ms, about 940 px/s
+    let samples = stroke(Point::new(100, 100), Point::new(15, 0), 10, 10);
+    let fling = gestures(&mut recognizer, &samples)
+        .into_iter()
+        .find_map(|gesture| match gesture {
+            Gesture::Fling { vx, vy } => Some((vx, vy)),
+            _ => None,
+        });
+    let (vx, vy) = fling.expect("fling");
+    assert!((900.0..=1000.0).contains(&vx), "{vx}");
+    assert_eq!(vy, 0.0);
+}
+
+#[test]
+fn no_fling_after_holding_still() {
+    let mut recognizer = GestureRecognizer::new();
+    let samples = stroke(Point::new(100, 100), Point::new(15, 0), 10, 150);
+    assert!(!gestures(&mut recognizer, &samples)
+        .iter()
+        .any(|g| matches!(g, Gesture::Fling { .. })));
+}
+
+#[test]
+fn no_fling_when_slow() {
+    let mut recognizer = GestureRecognizer::new();
+    // 2 px every 16 ms, about 125 px/s
+    let samples = stroke(Point::new(100, 100), Point::new(2, 0), 40, 0);
+    assert!(!gestures(&mut recognizer, &samples)
+        .iter()
+        .any(|g| matches!(g, Gesture::Fling { .. })));
+}
+
+#[test]
+fn swipe_directions() {
+    let swipe = |step: Point| {
+        let mut recognizer = GestureRecognizer::new();
+        let samples = stroke(Point::new(160, 85), step, 8, 0);
+        gestures(&mut recognizer, &samples)
+            .into_iter()
+            .find_map(|gesture| match gesture {
+                Gesture::Swipe(direction) => Some(direction),
+                _ => None,
+            })
+    };
+
+    assert_eq!(swipe(Point::new(10, 1)), Some(SwipeDirection::Right));
+    assert_eq!(swipe(Point::new(-10, 2)), Some(SwipeDirection::Left));
+    assert_eq!(swipe(Point::new(0, 10)), Some(SwipeDirection::Down));
+    assert_eq!(swipe(Point::new(-3, -10)), Some(SwipeDirection::Up));
+    // Diagonal
+    assert_eq!(swipe(Point::new(10, 8)), None);
+    // Too short
+    assert_eq!(swipe(Point::new(5, 0)), None);
+}
+
+#[test]
+fn slow_stroke_is_no_swipe() {
+    let mut recognizer = GestureRecognizer::new();
+    let samples = stroke(Point::new(100, 100), Point::new(10, 0), 40, 0);
+    assert!(!gestures(&mut recognizer, &samples)
+        .iter()
+        .any(|g| matches!(g, Gesture::Swipe(_))));
+}
+
+#[test]
+fn samples_without_touch_down_are_ignored() {
+    let mut recognizer = GestureRecognizer::new();
+    let samples = [
+        sample(Phase::Move, 10, 10, 0),
+        sample(Phase::Up, 10, 10, 20),
+    ];
+    assert!(gestures(&mut recognizer, &samples).is_empty());
+    assert_eq!(recognizer.poll(1000), None);
+}
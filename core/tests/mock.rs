@@ -0,0 +1,88 @@
+use embassy_futures::block_on;
+use embedded_graphics::pixelcolor::{Rgb565, RgbColor};
+use embedded_graphics::prelude::{Point, Size};
+use embedded_graphics::primitives::Rectangle;
+use pixels_core::display::DisplayTrait;
+use pixels_core::mock::{MockDisplay, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+
+/// Draws a frame like the main loop: clear, draw, send
+fn frame(display: &mut MockDisplay, draw: impl FnOnce(&mut MockDisplay)) {
+    display.frame.clear_buffer();
+    draw(display);
+    let Ok(()) = block_on(display.update_with_buffer());
+}
+
+fn square(display: &mut MockDisplay) {
+    let Ok(()) = display.fill_rect(
+        Rectangle::new(Point::new(40, 40), Size::new(20, 20)),
+        Rgb565::GREEN,
+    );
+}
+
+#[test]
+fn panel_shows_the_frame() {
+    let mut display = MockDisplay::board();
+    assert_eq!(
+        display.size(),
+        Size::new(DISPLAY_WIDTH as u32, DISPLAY_HEIGHT as u32)
+    );
+
+    frame(&mut display, |display| {
+        square(display);
+        let Ok(()) = display.draw_line(Point::new(100, 20), Point::new(300, 150));
+        let Ok(()) = display.write("60 FPS", Point::new(0, 0));
+    });
+    assert!(display.panel.pixels() == display.frame.front_buffer());
+    assert_eq!(display.panel.pixel(50, 50), Rgb565::GREEN);
+    assert_eq!(display.panel.pixel(200, 85), Rgb565::WHITE);
+}
+
+#[test]
+fn cleared_tiles_are_sent_once_more() {
+    let mut display = MockDisplay::board();
+    frame(&mut display, square);
+    assert_eq!(display.panel.transfers, 1);
+
+    // The square is gone, its tile is sent once more to clear it
+    display.panel.clear_log();
+    frame(&mut display, |_| {});
+    assert_eq!(display.panel.transfers, 1);
+    assert_eq!(display.panel.pixel(50, 50), Rgb565::BLACK);
+
+    display.panel.clear_log();
+    frame(&mut display, |_| {});
+    assert_eq!(display.panel.transfers, 0);
+    assert_eq!(display.panel.bytes, 0);
+}
+
+#[test]
+fn moving_lines_leave_nothing_behind() {
+    let mut display = MockDisplay::board();
+    for step in 0..20 {
+        let x = 10 + 13 * step;
+        frame(&mut display, |display| {
+            let Ok(()) = display.draw_line(Point::new(x, 5), Point::new(300 - x, 160));
+        });
+        assert!(
+            display.panel.pixels() == display.frame.front_buffer(),
+            "step {step}"
+        );
+    }
+
+    // Nothing is drawn, both buffers and the panel are cleared
+    for _ in 0..2 {
+        frame(&mut display, |_| {});
+    }
+    assert!(display.panel.pixels().iter().all(|&p| p == Rgb565::BLACK));
+}
+
+#[test]
+fn panel_commands() {
+    let mut display = MockDisplay::board();
+    let Ok(()) = block_on(display.set_brightness(0x80));
+    let Ok(()) = block_on(display.sleep());
+    assert_eq!(
+        display.panel.commands,
+        [(0x51, vec![0x80]), (0x28, vec![]), (0x10, vec![])]
+    );
+}
@@ -0,0 +1,133 @@
+use embassy_futures::block_on;
+use embedded_graphics::pixelcolor::{Rgb565, RgbColor};
+use embedded_graphics::prelude::{Point, Size};
+use embedded_graphics::primitives::Rectangle;
+use pixels_core::display::DisplayTrait;
+use pixels_core::mock::MockDisplay;
+use pixels_core::panel::{ColorLut, PanelCommand};
+
+const SQUARE: Rectangle = Rectangle::new(Point::new(40, 40), Size::new(20, 20));
+const COLOR: Rgb565 = Rgb565::new(20, 40, 10);
+/// Pixel data of a whole frame of the mock board
+const FRAME_BYTES: usize = 2 * 320 * 170;
+
+/// Draws a square and sends the frame
+fn frame(display: &mut MockDisplay) {
+    display.frame.clear_buffer();
+    let Ok(()) = display.fill_rect(SQUARE, COLOR);
+    let Ok(()) = block_on(display.update_with_buffer());
+}
+
+/// Gray levels from black to white
+fn grays() -> impl Iterator<Item = Rgb565> {
+    (0..32).map(|level| Rgb565::new(level, 2 * level, level))
+}
+
+/// No channel of `a` is lighter than in `b`
+fn not_lighter(a: Rgb565, b: Rgb565) -> bool {
+    a.r() <= b.r() && a.g() <= b.g() && a.b() <= b.b()
+}
+
+#[test]
+fn dcs_commands() {
+    let commands = [
+        (PanelCommand::SleepIn, 0x10, &[][..]),
+        (PanelCommand::SleepOut, 0x11, &[]),
+        (PanelCommand::InversionOff, 0x20, &[]),
+        (PanelCommand::InversionOn, 0x21, &[]),
+        (PanelCommand::DisplayOff, 0x28, &[]),
+        (PanelCommand::DisplayOn, 0x29, &[]),
+        (PanelCommand::Brightness(0), 0x51, &[0]),
+        (PanelCommand::Brightness(255), 0x51, &[255]),
+    ];
+    for (command, instruction, args) in commands {
+        assert_eq!(command.dcs(), (instruction, args), "{command:?}");
+    }
+}
+
+#[test]
+fn display_sends_the_panel_commands() {
+    let mut display = MockDisplay::board();
+    let Ok(()) = block_on(display.sleep());
+    let Ok(()) = block_on(display.wake());
+    let Ok(()) = block_on(display.set_inversion(true));
+    let Ok(()) = block_on(display.set_inversion(false));
+    let Ok(()) = block_on(display.set_brightness(0));
+    let Ok(()) = block_on(display.set_brightness(255));
+    assert_eq!(
+        display.panel.commands,
+        [
+            (0x28, vec![]),
+            (0x10, vec![]),
+            (0x11, vec![]),
+            (0x29, vec![]),
+            (0x21, vec![]),
+            (0x20, vec![]),
+            (0x51, vec![0]),
+            (0x51, vec![255]),
+        ]
+    );
+    // No pixels are sent for them
+    assert_eq!(display.panel.transfers, 0);
+}
+
+#[test]
+fn identity_leaves_colors_unchanged() {
+    for color in grays().chain([COLOR, Rgb565::RED, Rgb565::CYAN]) {
+        assert_eq!(ColorLut::IDENTITY.apply(color), color);
+    }
+}
+
+#[test]
+fn gamma_darkens_mid_tones() {
+    let lut = ColorLut::new(2.2, 1.0);
+    let mid = lut.apply(Rgb565::new(16, 32, 16));
+    assert!(mid.r() < 10 && mid.g() < 20 && mid.b() < 10, "{mid:?}");
+
+    let corrected: Vec<_> = grays().map(|gray| lut.apply(gray)).collect();
+    assert!(corrected
+        .windows(2)
+        .all(|pair| not_lighter(pair[0], pair[1])));
+    assert!(corrected[31].r() >= 30);
+}
+
+#[test]
+fn contrast_below_one_lifts_black_and_dims_white() {
+    let lut = ColorLut::new(1.0, 0.5);
+    let black = lut.apply(Rgb565::BLACK);
+    let white = lut.apply(Rgb565::WHITE);
+    assert!((6..=10).contains(&black.r()), "{black:?}");
+    assert!((21..=25).contains(&white.r()), "{white:?}");
+    assert!(black.g() > 0 && white.g() < 63);
+}
+
+#[test]
+fn color_correction_is_applied_when_sending() {
+    let mut display = MockDisplay::board();
+    frame(&mut display);
+    assert_eq!(display.panel.pixel(50, 50), COLOR);
+
+    // A new correction resends the whole frame
+    let lut = ColorLut::new(1.0, 0.5);
+    display.set_color_correction(lut);
+    display.panel.clear_log();
+    frame(&mut display);
+    assert!(display.panel.bytes >= FRAME_BYTES);
+    assert_eq!(display.panel.pixel(50, 50), lut.apply(COLOR));
+    assert_eq!(display.panel.pixel(0, 0), lut.apply(Rgb565::BLACK));
+    assert_eq!(display.panel.pixel(319, 169), lut.apply(Rgb565::BLACK));
+
+    // The same one again does not, once the buffers no longer hold tiles
+    // of the resent frame
+    frame(&mut display);
+    display.set_color_correction(lut);
+    display.panel.clear_log();
+    frame(&mut display);
+    assert!(display.panel.bytes < FRAME_BYTES);
+
+    // Back to unchanged colors
+    display.set_color_correction(ColorLut::IDENTITY);
+    frame(&mut display);
+    assert_eq!(display.panel.pixel(50, 50), COLOR);
+    assert_eq!(display.panel.pixel(0, 0), Rgb565::BLACK);
+}
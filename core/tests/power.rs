@@ -0,0 +1,102 @@
+use pixels_core::power::{IdlePolicy, IdleTimeouts, PowerState};
+
+const TIMEOUTS: IdleTimeouts = IdleTimeouts {
+    dim_ms: 10_000,
+    throttle_ms: 30_000,
+    sleep_ms: 60_000,
+};
+
+/// Frame clock of a test, in milliseconds
+struct Clock {
+    now_ms: u64,
+}
+
+impl Clock {
+    /// Runs frames every `frame_ms` for `duration_ms`, returns the state
+    /// changes and their times
+    fn run(
+        &mut self,
+        policy: &mut IdlePolicy,
+        duration_ms: u64,
+        frame_ms: u64,
+    ) -> Vec<(u64, PowerState)> {
+        let end = self.now_ms + duration_ms;
+        let mut changes = Vec::new();
+        while self.now_ms < end {
+            self.now_ms += frame_ms;
+            if let Some(state) = policy.update(self.now_ms) {
+                changes.push((self.now_ms, state));
+            }
+        }
+        changes
+    }
+}
+
+#[test]
+fn steps_down_while_idle() {
+    let mut clock = Clock { now_ms: 5_000 };
+    let mut policy = IdlePolicy::new(TIMEOUTS, clock.now_ms);
+    assert_eq!(policy.state(), PowerState::Active);
+
+    assert_eq!(
+        clock.run(&mut policy, 120_000, 1_000),
+        [
+            (15_000, PowerState::Dimmed),
+            (35_000, PowerState::Throttled),
+            (65_000, PowerState::Asleep),
+        ]
+    );
+    assert_eq!(policy.state(), PowerState::Asleep);
+}
+
+#[test]
+fn input_keeps_the_screen_active() {
+    let mut clock = Clock { now_ms: 0 };
+    let mut policy = IdlePolicy::new(TIMEOUTS, clock.now_ms);
+    for _ in 0..10 {
+        assert!(clock.run(&mut policy, 9_000, 16).is_empty());
+        policy.activity(clock.now_ms);
+    }
+    assert_eq!(policy.state(), PowerState::Active);
+}
+
+#[test]
+fn input_wakes_from_every_state() {
+    for (idle_ms, state) in [
+        (10_000, PowerState::Dimmed),
+        (30_000, PowerState::Throttled),
+        (60_000, PowerState::Asleep),
+    ] {
+        let mut clock = Clock { now_ms: 0 };
+        let mut policy = IdlePolicy::new(TIMEOUTS, clock.now_ms);
+        let changes = clock.run(&mut policy, idle_ms, 100);
+        assert_eq!(changes.last(), Some(&(idle_ms, state)));
+
+        policy.activity(clock.now_ms);
+        assert_eq!(policy.update(clock.now_ms), Some(PowerState::Active));
+        assert_eq!(policy.update(clock.now_ms + 100), None);
+    }
+}
+
+#[test]
+fn long_frames_skip_steps() {
+    let mut policy = IdlePolicy::new(TIMEOUTS, 0);
+    assert_eq!(policy.update(5_000), None);
+    assert_eq!(policy.update(70_000), Some(PowerState::Asleep));
+    assert_eq!(policy.update(80_000), None);
+}
+
+#[test]
+fn old_input_does_not_move_the_clock_back() {
+    let mut policy = IdlePolicy::new(TIMEOUTS, 0);
+    policy.activity(20_000);
+    policy.activity(1_000);
+    assert_eq!(policy.update(29_000), None);
+    assert_eq!(policy.update(30_000), Some(PowerState::Dimmed));
+
+    // Input reported ahead of the frame clock keeps it active until then
+    policy.activity(100_000);
+    assert_eq!(policy.update(40_000), Some(PowerState::Active));
+    assert_eq!(policy.update(109_999), None);
+    assert_eq!(policy.update(110_000), Some(PowerState::Dimmed));
+}
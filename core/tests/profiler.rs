@@ -0,0 +1,131 @@
+use embassy_futures::block_on;
+use embedded_graphics::pixelcolor::{Rgb565, RgbColor};
+use embedded_graphics::prelude::Point;
+use pixels_core::display::DisplayTrait;
+use pixels_core::mock::{MockDisplay, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use pixels_core::profiler::{Overlay, Profile, Stage, Stats, WINDOW};
+
+/// A frame with `micros` in every stage, `bytes` sent
+fn frame(profile: &mut Profile, micros: u64, bytes: usize) {
+    for stage in Stage::ALL {
+        profile.record(stage, micros);
+    }
+    profile.end_frame(bytes);
+}
+
+fn report(profile: &Profile) -> String {
+    let mut text = String::new();
+    profile.report(&mut text).unwrap();
+    text
+}
+
+/// Screen after drawing the overlay of `profile` at the top-left corner
+fn overlay(profile: &Profile, overlay: Overlay) -> MockDisplay {
+    let mut display = MockDisplay::board();
+    display.frame.clear_buffer();
+    let Ok(()) = profile.draw_overlay(&mut display, overlay, Point::zero());
+    let Ok(()) = block_on(display.update_with_buffer());
+    display
+}
+
+fn drawn_in_rows(display: &MockDisplay, rows: std::ops::Range<u16>) -> usize {
+    rows.flat_map(|y| (0..DISPLAY_WIDTH).map(move |x| (x, y)))
+        .filter(|&(x, y)| display.panel.pixel(x, y) != Rgb565::BLACK)
+        .count()
+}
+
+#[test]
+fn laps_of_a_stage_add_up() {
+    let mut profile = Profile::new();
+    profile.record(Stage::Input, 100);
+    profile.record(Stage::Input, 250);
+    profile.record(Stage::Flush, u64::MAX);
+    profile.record(Stage::Flush, 1);
+    profile.end_frame(4096);
+
+    let input = Stats {
+        avg: 350,
+        min: 350,
+        max: 350,
+    };
+    assert_eq!(profile.stats(Stage::Input), input);
+    assert_eq!(profile.stats(Stage::Flush).max, u32::MAX);
+    assert_eq!(profile.stats(Stage::Clear), Stats::default());
+    assert_eq!(profile.bytes().avg, 4096);
+
+    // The next frame starts from zero
+    profile.end_frame(0);
+    assert_eq!(profile.stats(Stage::Input).min, 0);
+    assert_eq!(profile.stats(Stage::Input).avg, 175);
+}
+
+#[test]
+fn statistics_cover_the_last_window() {
+    let mut profile = Profile::new();
+    assert_eq!(profile.stats(Stage::Clear), Stats::default());
+
+    for i in 0..WINDOW as u64 {
+        frame(&mut profile, 1000 + i, 10);
+    }
+    let stats = profile.stats(Stage::Clear);
+    assert_eq!(stats.min, 1000);
+    assert_eq!(stats.max, 1000 + WINDOW as u32 - 1);
+    assert_eq!(stats.avg, 1000 + (WINDOW as u32 - 1) / 2);
+
+    // Older frames drop out
+    for _ in 0..WINDOW {
+        frame(&mut profile, 20, 30);
+    }
+    let stats = Stats {
+        avg: 20,
+        min: 20,
+        max: 20,
+    };
+    assert_eq!(profile.stats(Stage::Rasterize), stats);
+    assert_eq!(profile.bytes().max, 30);
+    assert_eq!(profile.frames(), 2 * WINDOW);
+}
+
+#[test]
+fn report_is_a_table() {
+    let mut profile = Profile::new();
+    frame(&mut profile, 100, 2000);
+    frame(&mut profile, 300, 4000);
+    assert_eq!(
+        report(&profile),
+        "frames=2 window=2\n\
+         stage          avg     min     max\n\
+         clear          200     100     300\n\
+         input          200     100     300\n\
+         simulate       200     100     300\n\
+         project        200     100     300\n\
+         rasterize      200     100     300\n\
+         flush          200     100     300\n\
+         bytes         3000    2000    4000\n"
+    );
+}
+
+#[test]
+fn overlay_draws_a_line_per_stage() {
+    let mut profile = Profile::new();
+    frame(&mut profile, 5000, 1000);
+
+    assert_eq!(
+        drawn_in_rows(&overlay(&profile, Overlay::Off), 0..DISPLAY_HEIGHT),
+        0
+    );
+
+    // Six stages and the bytes
+    let text = overlay(&profile, Overlay::Text);
+    assert!(drawn_in_rows(&text, 120..140) > 0);
+    assert_eq!(drawn_in_rows(&text, 140..DISPLAY_HEIGHT), 0);
+
+    // Six bars of 50 pixels after their labels
+    let bars = overlay(&profile, Overlay::Bars);
+    for line in 0..6 {
+        let y = line * 20 + 10;
+        assert_eq!(bars.panel.pixel(45, y), Rgb565::WHITE, "line {line}");
+        assert_eq!(bars.panel.pixel(75, y), Rgb565::BLACK, "line {line}");
+    }
+    assert_eq!(drawn_in_rows(&bars, 120..DISPLAY_HEIGHT), 0);
+}
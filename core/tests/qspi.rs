@@ -0,0 +1,122 @@
+use mipidsi::interface::Interface;
+use pixels_core::qspi::{DataLines, QspiBus, QspiInterface};
+
+/// One transaction: instruction, address, data and data lines
+type Write = (u8, u32, Vec<u8>, DataLines);
+
+/// Bus that keeps every transaction
+#[derive(Default)]
+struct MockBus {
+    writes: Vec<Write>,
+}
+
+impl QspiBus for &mut MockBus {
+    type Error = ();
+
+    fn write(
+        &mut self,
+        instruction: u8,
+        address: u32,
+        data: &[u8],
+        data_lines: DataLines,
+    ) -> Result<(), Self::Error> {
+        self.writes
+            .push((instruction, address, data.to_vec(), data_lines));
+        Ok(())
+    }
+}
+
+/// Runs `send` on an interface with a buffer of `buffer_len` bytes
+fn transactions(
+    buffer_len: usize,
+    send: impl FnOnce(&mut QspiInterface<&mut MockBus>) -> Result<(), ()>,
+) -> Vec<Write> {
+    let mut bus = MockBus::default();
+    let mut buffer = vec![0; buffer_len];
+    send(&mut QspiInterface::new(&mut bus, &mut buffer)).unwrap();
+    bus.writes
+}
+
+#[test]
+fn register_writes_use_one_line() {
+    let writes = transactions(8, |qspi| {
+        qspi.send_command(0x2A, &[0x00, 0x10, 0x01, 0x3F])?;
+        qspi.send_command(0x29, &[])
+    });
+    assert_eq!(
+        writes,
+        [
+            (
+                0x02,
+                0x2A00,
+                vec![0x00, 0x10, 0x01, 0x3F],
+                DataLines::Single
+            ),
+            (0x02, 0x2900, vec![], DataLines::Single),
+        ]
+    );
+}
+
+#[test]
+fn memory_write_is_merged_into_the_pixels() {
+    // 5 bytes hold two whole pixels, the third pixel continues the write
+    let writes = transactions(5, |qspi| {
+        qspi.send_command(0x2C, &[])?;
+        qspi.send_pixels([[1, 2], [3, 4], [5, 6]])
+    });
+    assert_eq!(
+        writes,
+        [
+            (0x32, 0x2C00, vec![1, 2, 3, 4], DataLines::Quad),
+            (0x32, 0x3C00, vec![5, 6], DataLines::Quad),
+        ]
+    );
+}
+
+#[test]
+fn pixels_without_memory_write_continue() {
+    let writes = transactions(8, |qspi| qspi.send_pixels([[1, 2]]));
+    assert_eq!(writes, [(0x32, 0x3C00, vec![1, 2], DataLines::Quad)]);
+}
+
+#[test]
+fn command_drops_pending_memory_write() {
+    let writes = transactions(8, |qspi| {
+        qspi.send_command(0x2C, &[])?;
+        qspi.send_command(0x28, &[])?;
+        qspi.send_pixels([[1, 2]])
+    });
+    assert_eq!(
+        writes,
+        [
+            (0x02, 0x2800, vec![], DataLines::Single),
+            (0x32, 0x3C00, vec![1, 2], DataLines::Quad),
+        ]
+    );
+}
+
+#[test]
+fn repeated_pixel_is_sent_in_chunks() {
+    let writes = transactions(4, |qspi| {
+        qspi.send_command(0x2C, &[])?;
+        qspi.send_repeated_pixel([7, 8], 5)
+    });
+    assert_eq!(
+        writes,
+        [
+            (0x32, 0x2C00, vec![7, 8, 7, 8], DataLines::Quad),
+            (0x32, 0x3C00, vec![7, 8, 7, 8], DataLines::Quad),
+            (0x32, 0x3C00, vec![7, 8], DataLines::Quad),
+        ]
+    );
+}
+
+#[test]
+fn nothing_is_sent_for_no_pixels() {
+    let writes = transactions(4, |qspi| {
+        qspi.send_command(0x2C, &[])?;
+        qspi.send_pixels(core::iter::empty::<[u8; 2]>())?;
+        qspi.send_repeated_pixel([7, 8], 0)
+    });
+    assert!(writes.is_empty());
+}
@@ -0,0 +1,531 @@
+use embassy_futures::block_on;
+use embedded_graphics::pixelcolor::{Rgb565, RgbColor};
+use embedded_graphics::prelude::{Point, Size};
+use embedded_graphics::primitives::Rectangle;
+use embedded_hal::{digital, spi};
+use embedded_hal_async::delay::DelayNs;
+use mipidsi::interface::SpiError;
+use pixels_core::display::{Cause, DisplayError, DisplayTrait, Operation};
+use pixels_core::mock::{FaultyPanel, MockDisplay, MockInterface, MockInterfaceError};
+use pixels_core::panel::PanelCommand;
+use pixels_core::recovery::{recover, Action, Recovery, RecoveryLimits, Severity};
+
+/// The limits of the firmware
+const LIMITS: RecoveryLimits = RecoveryLimits {
+    retries: 3,
+    first_delay_ms: 10,
+    max_delay_ms: 200,
+    reinits: 3,
+};
+
+/// A panel that fails the frames and re-inits it is told to, by severity
+#[derive(Default)]
+struct ScriptedPanel {
+    /// Error of every frame, `None` for a good one, all good after the end
+    frames: Vec<Option<Severity>>,
+    /// Error of every re-init, all good after the end
+    reinits: Vec<Option<Severity>>,
+    frame: usize,
+    reinit: usize,
+}
+
+impl ScriptedPanel {
+    fn flush(&mut self) -> Result<(), Severity> {
+        let fault = self.frames.get(self.frame).copied().flatten();
+        self.frame += 1;
+        fault.map_or(Ok(()), Err)
+    }
+
+    fn reinit(&mut self) -> Result<(), Severity> {
+        let fault = self.reinits.get(self.reinit).copied().flatten();
+        self.reinit += 1;
+        fault.map_or(Ok(()), Err)
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Outcome {
+    /// Frames drawn, good or not
+    frames: usize,
+    /// Time spent waiting before retries
+    waited_ms: u64,
+    reinits: usize,
+    gave_up: bool,
+}
+
+/// Draws up to `frames` frames on `panel`, recovering from its errors like the
+/// render loop of the firmware
+fn run(panel: &mut ScriptedPanel, frames: usize) -> Outcome {
+    let mut recovery = Recovery::new(LIMITS);
+    let mut outcome = Outcome::default();
+    'frames: while outcome.frames < frames {
+        outcome.frames += 1;
+        let Err(mut severity) = panel.flush() else {
+            recovery.success();
+            continue;
+        };
+
+        loop {
+            match recovery.failure(severity) {
+                Action::Retry { delay_ms } => {
+                    outcome.waited_ms += delay_ms;
+                    continue 'frames;
+                }
+                Action::Reinit => {
+                    outcome.reinits += 1;
+                    match panel.reinit() {
+                        Ok(()) => continue 'frames,
+                        Err(next) => severity = next,
+                    }
+                }
+                Action::GiveUp => {
+                    outcome.gave_up = true;
+                    return outcome;
+                }
+            }
+        }
+    }
+    outcome
+}
+
+#[test]
+fn transient_errors_are_retried_with_backoff() {
+    let mut recovery = Recovery::new(LIMITS);
+    let actions: Vec<_> = (0..4)
+        .map(|_| recovery.failure(Severity::Transient))
+        .collect();
+    assert_eq!(
+        actions,
+        [
+            Action::Retry { delay_ms: 10 },
+            Action::Retry { delay_ms: 20 },
+            Action::Retry { delay_ms: 40 },
+            Action::Reinit,
+        ]
+    );
+}
+
+#[test]
+fn delay_is_capped() {
+    let mut recovery = Recovery::new(RecoveryLimits {
+        retries: 100,
+        ..LIMITS
+    });
+    let delays: Vec<_> = (0..100)
+        .map(|_| match recovery.failure(Severity::Transient) {
+            Action::Retry { delay_ms } => delay_ms,
+            action => panic!("{action:?}"),
+        })
+        .collect();
+    assert_eq!(delays[..6], [10, 20, 40, 80, 160, 200]);
+    assert!(delays[6..].iter().all(|&delay| delay == 200));
+}
+
+#[test]
+fn good_frame_starts_over() {
+    let mut recovery = Recovery::new(LIMITS);
+    recovery.failure(Severity::Transient);
+    recovery.failure(Severity::Transient);
+    recovery.success();
+    assert_eq!(
+        recovery.failure(Severity::Transient),
+        Action::Retry { delay_ms: 10 }
+    );
+
+    // Also for re-inits
+    for _ in 0..3 {
+        assert_eq!(recovery.failure(Severity::Persistent), Action::Reinit);
+    }
+    recovery.success();
+    assert_eq!(recovery.failure(Severity::Persistent), Action::Reinit);
+}
+
+#[test]
+fn persistent_errors_reinit_until_the_limit() {
+    let mut recovery = Recovery::new(LIMITS);
+    for _ in 0..3 {
+        assert_eq!(recovery.failure(Severity::Persistent), Action::Reinit);
+    }
+    assert_eq!(recovery.failure(Severity::Persistent), Action::GiveUp);
+}
+
+#[test]
+fn reinit_allows_new_retries() {
+    let mut recovery = Recovery::new(LIMITS);
+    assert_eq!(recovery.failure(Severity::Persistent), Action::Reinit);
+    assert_eq!(
+        recovery.failure(Severity::Transient),
+        Action::Retry { delay_ms: 10 }
+    );
+}
+
+#[test]
+fn fatal_errors_give_up_at_once() {
+    let mut recovery = Recovery::new(LIMITS);
+    assert_eq!(recovery.failure(Severity::Fatal), Action::GiveUp);
+}
+
+#[test]
+fn occasional_transfer_errors_are_retried() {
+    // Every seventh frame fails
+    let mut panel = ScriptedPanel {
+        frames: (0..1000)
+            .map(|frame| (frame % 7 == 6).then_some(Severity::Transient))
+            .collect(),
+        ..ScriptedPanel::default()
+    };
+    let outcome = run(&mut panel, 1000);
+    assert_eq!(
+        outcome,
+        Outcome {
+            frames: 1000,
+            waited_ms: 142 * 10,
+            reinits: 0,
+            gave_up: false,
+        }
+    );
+}
+
+#[test]
+fn burst_of_transfer_errors_resets_the_panel() {
+    let mut panel = ScriptedPanel {
+        frames: [vec![None; 10], vec![Some(Severity::Transient); 7]].concat(),
+        ..ScriptedPanel::default()
+    };
+    let outcome = run(&mut panel, 100);
+    // Three retries, a reset, and three more retries before a good frame
+    assert_eq!(outcome.waited_ms, 2 * (10 + 20 + 40));
+    assert_eq!(outcome.reinits, 1);
+    assert!(!outcome.gave_up);
+}
+
+#[test]
+fn panel_that_stops_answering_is_given_up() {
+    let mut panel = ScriptedPanel {
+        frames: [vec![None; 10], vec![Some(Severity::Persistent); 100]].concat(),
+        ..ScriptedPanel::default()
+    };
+    let outcome = run(&mut panel, 100);
+    assert_eq!(outcome.frames, 14);
+    assert_eq!(outcome.reinits, 3);
+    assert!(outcome.gave_up);
+}
+
+#[test]
+fn failed_reinit_is_retried_at_once() {
+    // The first two resets fail, the third brings the panel back
+    let mut panel = ScriptedPanel {
+        frames: vec![Some(Severity::Persistent)],
+        reinits: vec![Some(Severity::Persistent), Some(Severity::Persistent)],
+        ..ScriptedPanel::default()
+    };
+    let outcome = run(&mut panel, 10);
+    assert_eq!(
+        outcome,
+        Outcome {
+            frames: 10,
+            waited_ms: 0,
+            reinits: 3,
+            gave_up: false,
+        }
+    );
+
+    // One more failure and it gives up
+    let mut panel = ScriptedPanel {
+        frames: vec![Some(Severity::Persistent)],
+        reinits: vec![Some(Severity::Persistent); 3],
+        ..ScriptedPanel::default()
+    };
+    let outcome = run(&mut panel, 10);
+    assert_eq!(outcome.frames, 1);
+    assert_eq!(outcome.reinits, 3);
+    assert!(outcome.gave_up);
+}
+
+#[test]
+fn reinit_without_a_panel_gives_up() {
+    let mut panel = ScriptedPanel {
+        frames: vec![Some(Severity::Persistent)],
+        reinits: vec![Some(Severity::Fatal)],
+        ..ScriptedPanel::default()
+    };
+    let outcome = run(&mut panel, 10);
+    assert_eq!(outcome.reinits, 1);
+    assert!(outcome.gave_up);
+}
+
+const SPI_ERROR: MockInterfaceError = SpiError::Spi(spi::ErrorKind::Other);
+const DC_ERROR: MockInterfaceError = SpiError::Dc(digital::ErrorKind::Other);
+
+/// Delay that only adds up the time waited
+#[derive(Default)]
+struct Waited {
+    ns: u64,
+}
+
+impl Waited {
+    fn ms(&self) -> u64 {
+        self.ns / 1_000_000
+    }
+}
+
+impl DelayNs for Waited {
+    async fn delay_ns(&mut self, ns: u32) {
+        self.ns += ns as u64;
+    }
+}
+
+fn square(display: &mut MockDisplay<impl MockInterface>) {
+    let _ = display.fill_rect(
+        Rectangle::new(Point::new(40, 40), Size::new(20, 20)),
+        Rgb565::GREEN,
+    );
+}
+
+/// Draws a square and sends the frame, then recovers like the render loop of
+/// the firmware
+///
+/// # Returns
+/// What [`recover`] returned
+fn frame_and_recover(
+    display: &mut MockDisplay<FaultyPanel>,
+    recovery: &mut Recovery,
+    waited: &mut Waited,
+) -> Result<bool, DisplayError<MockInterfaceError>> {
+    display.frame.clear_buffer();
+    square(display);
+    let failed = block_on(display.update_with_buffer()).err();
+    block_on(recover(display, recovery, waited, failed, false))
+}
+
+#[test]
+fn severity_of_display_errors() {
+    let mut display = MockDisplay::faulty();
+    for fault in [SPI_ERROR, DC_ERROR] {
+        // Unchanged tiles are not sent with delta transfers
+        display.invalidate();
+        display.panel.fail(fault);
+        square(&mut display);
+        let error = block_on(display.update_with_buffer()).unwrap_err();
+        assert_eq!(error.operation, Operation::Flush);
+        assert_eq!(
+            format!("{:?}", error.cause),
+            format!("{:?}", Cause::Interface(fault))
+        );
+        assert_eq!(error.severity(), Severity::Transient);
+
+        display.panel.fail(fault);
+        let error = block_on(display.set_brightness(0x40)).unwrap_err();
+        assert_eq!(
+            error.operation,
+            Operation::Command(PanelCommand::Brightness(0x40))
+        );
+        assert_eq!(error.severity(), Severity::Persistent);
+    }
+
+    let fatal: [DisplayError<MockInterfaceError>; 3] = [
+        DisplayError::setup("DMA buffers"),
+        DisplayError::new(Operation::Init, Cause::Init),
+        DisplayError::new(Operation::Flush, Cause::NoPanel),
+    ];
+    for error in fatal {
+        assert_eq!(error.severity(), Severity::Fatal, "{error}");
+    }
+}
+
+#[test]
+fn failed_frames_are_retried_then_the_panel_is_reset() {
+    let mut display = MockDisplay::faulty();
+    let mut recovery = Recovery::new(LIMITS);
+    let mut waited = Waited::default();
+    for _ in 0..4 {
+        display.panel.fail(SPI_ERROR);
+    }
+
+    for delay in [10, 20, 40] {
+        let before = waited.ms();
+        assert!(matches!(
+            frame_and_recover(&mut display, &mut recovery, &mut waited),
+            Ok(false)
+        ));
+        assert_eq!(waited.ms() - before, delay);
+    }
+    assert_eq!(display.panel.reinits, 0);
+    assert!(matches!(
+        frame_and_recover(&mut display, &mut recovery, &mut waited),
+        Ok(true)
+    ));
+    assert_eq!(display.panel.reinits, 1);
+    assert_eq!(waited.ms(), 70);
+
+    // The reset panel lost the frame, the next one is sent whole
+    assert_eq!(display.panel.panel.pixel(50, 50), Rgb565::BLACK);
+    assert!(matches!(
+        frame_and_recover(&mut display, &mut recovery, &mut waited),
+        Ok(false)
+    ));
+    assert!(display.panel.panel.pixels() == display.frame.front_buffer());
+    assert_eq!(display.panel.panel.pixel(50, 50), Rgb565::GREEN);
+}
+
+#[test]
+fn retried_frame_is_sent_whole() {
+    let mut display = MockDisplay::faulty();
+    let mut recovery = Recovery::new(LIMITS);
+    let mut waited = Waited::default();
+    assert!(matches!(
+        frame_and_recover(&mut display, &mut recovery, &mut waited),
+        Ok(false)
+    ));
+
+    // The panel lost the unchanged square, and the frame that would send it
+    // again fails
+    display.panel.panel.reset();
+    display.panel.fail(DC_ERROR);
+    display.frame.invalidate();
+    assert!(matches!(
+        frame_and_recover(&mut display, &mut recovery, &mut waited),
+        Ok(false)
+    ));
+    assert_eq!(display.panel.panel.pixel(50, 50), Rgb565::BLACK);
+
+    assert!(matches!(
+        frame_and_recover(&mut display, &mut recovery, &mut waited),
+        Ok(false)
+    ));
+    assert_eq!(display.panel.panel.pixel(50, 50), Rgb565::GREEN);
+}
+
+#[test]
+fn failed_command_resets_the_panel() {
+    let mut display = MockDisplay::faulty();
+    let mut recovery = Recovery::new(LIMITS);
+    let mut waited = Waited::default();
+
+    display.panel.fail(DC_ERROR);
+    let failed = block_on(display.sleep()).err();
+    assert!(failed.is_some());
+    display.panel.panel.clear_log();
+    assert!(matches!(
+        block_on(recover(
+            &mut display,
+            &mut recovery,
+            &mut waited,
+            failed,
+            true
+        )),
+        Ok(true)
+    ));
+    assert_eq!(waited.ms(), 0);
+    assert_eq!(display.panel.reinits, 1);
+    // Asleep before, asleep after
+    assert_eq!(
+        display.panel.panel.commands,
+        [(0x28, vec![]), (0x10, vec![])]
+    );
+}
+
+#[test]
+fn failed_reinits_give_up_with_the_last_error() {
+    let mut display = MockDisplay::faulty();
+    let mut recovery = Recovery::new(LIMITS);
+    let mut waited = Waited::default();
+
+    display.panel.fail(DC_ERROR);
+    display.panel.fail_reinits(1);
+    let failed = block_on(display.set_brightness(0x80)).err();
+    let error = block_on(recover(
+        &mut display,
+        &mut recovery,
+        &mut waited,
+        failed,
+        false,
+    ))
+    .unwrap_err();
+    assert_eq!(display.panel.reinits, 1);
+    assert_eq!(error.operation, Operation::Init);
+    assert!(matches!(error.cause, Cause::Init));
+}
+
+#[test]
+fn failed_sleep_after_a_reinit_is_recovered_too() {
+    let mut display = MockDisplay::faulty();
+    let mut recovery = Recovery::new(LIMITS);
+    let mut waited = Waited::default();
+
+    // The brightness fails, then the first sleep after the reset
+    display.panel.fail(SPI_ERROR);
+    let failed = block_on(display.set_brightness(0x80)).err();
+    display.panel.fail(SPI_ERROR);
+    assert!(matches!(
+        block_on(recover(
+            &mut display,
+            &mut recovery,
+            &mut waited,
+            failed,
+            true
+        )),
+        Ok(true)
+    ));
+    assert_eq!(display.panel.reinits, 2);
+    assert_eq!(display.panel.pending_faults(), 0);
+}
+
+#[test]
+fn panel_that_keeps_failing_is_given_up() {
+    let mut display = MockDisplay::faulty();
+    let mut recovery = Recovery::new(LIMITS);
+    let mut waited = Waited::default();
+
+    display.panel.fail(SPI_ERROR);
+    let failed = block_on(display.set_brightness(0x80)).err();
+    // Every re-init is followed by another failed command
+    for _ in 0..LIMITS.reinits {
+        display.panel.fail(SPI_ERROR);
+    }
+    let mut result = block_on(recover(
+        &mut display,
+        &mut recovery,
+        &mut waited,
+        failed,
+        false,
+    ));
+    for _ in 0..LIMITS.reinits {
+        assert!(matches!(result, Ok(true)));
+        let failed = block_on(display.set_brightness(0x80)).err();
+        result = block_on(recover(
+            &mut display,
+            &mut recovery,
+            &mut waited,
+            failed,
+            false,
+        ));
+    }
+    let error = result.unwrap_err();
+    assert_eq!(
+        error.operation,
+        Operation::Command(PanelCommand::Brightness(0x80))
+    );
+    assert_eq!(display.panel.reinits, LIMITS.reinits as usize);
+}
+
+#[test]
+fn good_frame_resets_the_recovery() {
+    let mut display = MockDisplay::faulty();
+    let mut recovery = Recovery::new(LIMITS);
+    let mut waited = Waited::default();
+    for _ in 0..10 {
+        display.invalidate();
+        display.panel.fail(SPI_ERROR);
+        assert!(matches!(
+            frame_and_recover(&mut display, &mut recovery, &mut waited),
+            Ok(false)
+        ));
+        assert!(matches!(
+            frame_and_recover(&mut display, &mut recovery, &mut waited),
+            Ok(false)
+        ));
+    }
+    assert_eq!(waited.ms(), 10 * 10);
+    assert_eq!(display.panel.reinits, 0);
+}
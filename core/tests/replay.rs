@@ -0,0 +1,387 @@
+use embassy_futures::block_on;
+use embedded_graphics::pixelcolor::raw::RawU16;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::{IntoStorage, Point};
+use pixels_core::arcball::Arcball;
+use pixels_core::config::RECORDING_CAPACITY;
+use pixels_core::console::{self, Command};
+use pixels_core::display::DisplayTrait;
+use pixels_core::fps::FpsMeter;
+use pixels_core::gesture::{GestureRecognizer, Phase, TouchSample};
+use pixels_core::math::{self, Projection};
+use pixels_core::mock::MockDisplay;
+use pixels_core::replay::{InputSource, Session};
+use pixels_core::scene::{Scene, World};
+use pixels_core::settings::{Settings, ENCODED_LEN};
+use pixels_protocol::rle;
+
+fn touch(phase: Phase, x: i32, y: i32) -> Option<TouchSample> {
+    Some(TouchSample {
+        phase,
+        position: Point::new(x, y),
+        time_ms: 0,
+    })
+}
+
+/// Live clock and touch of every frame of a short drag
+fn session_input() -> Vec<(u64, Option<TouchSample>)> {
+    vec![
+        (1_016, None),
+        (1_033, touch(Phase::Down, 10, 20)),
+        (1_049, touch(Phase::Move, 60, 25)),
+        (1_049, None),
+        (1_066, touch(Phase::Move, 535, 239)),
+        (1_320, touch(Phase::Up, 535, 239)),
+        // A long pause needs a multi byte frame time
+        (101_320, None),
+    ]
+}
+
+/// The recording so far, decoded from its hex dump
+fn recording(session: &Session) -> &'static [u8] {
+    let mut dump = String::new();
+    session.dump(&mut dump).unwrap();
+
+    let mut bytes = Vec::new();
+    for line in dump.lines() {
+        let hex = line.strip_prefix("rec:").unwrap();
+        assert!(hex.len() <= 64);
+        for i in (0..hex.len()).step_by(2) {
+            bytes.push(u8::from_str_radix(&hex[i..i + 2], 16).unwrap());
+        }
+    }
+    bytes.leak()
+}
+
+#[test]
+fn replay_reproduces_the_session() {
+    let mut recorder = Session::new(InputSource::Record, 42, Settings::default(), 1_000);
+    let recorded: Vec<_> = session_input()
+        .into_iter()
+        .map(|(now_ms, touch)| {
+            let frame = recorder.frame(now_ms, touch);
+            (frame.time_ms, frame.touch)
+        })
+        .collect();
+    assert_eq!(recorded[0].0, 16);
+    assert_eq!(recorded[1].1.unwrap().time_ms, 33);
+    assert_eq!(recorded.last().unwrap().0, 100_320);
+    assert!(recorder.take_full_recording().is_none());
+
+    // The replay ignores the live clock, touch and seed
+    let mut player = Session::new(
+        InputSource::Replay(recording(&recorder)),
+        7,
+        Settings::default(),
+        50_000,
+    );
+    assert_eq!(player.seed(), 42);
+    for (i, &(time_ms, sample)) in recorded.iter().enumerate() {
+        let frame = player.frame(90_000 + i as u64, touch(Phase::Down, 1, 1));
+        assert_eq!(frame.time_ms, time_ms);
+        assert_eq!(frame.touch, sample);
+    }
+
+    // Then live input continues the session clock
+    let frame = player.frame(200_000, None);
+    assert_eq!(frame.time_ms, 100_320);
+    assert_eq!(frame.touch, None);
+    let frame = player.frame(200_016, touch(Phase::Down, 5, 5));
+    assert_eq!(frame.time_ms, 100_336);
+    assert_eq!(frame.touch.unwrap().time_ms, 100_336);
+}
+
+#[test]
+fn truncated_replay_ends_early() {
+    let mut recorder = Session::new(InputSource::Record, 42, Settings::default(), 0);
+    recorder.frame(16, touch(Phase::Down, 10, 20));
+    recorder.frame(32, touch(Phase::Up, 10, 20));
+    let data = recording(&recorder);
+
+    // The touch position of the second frame is cut off
+    let mut player = Session::new(
+        InputSource::Replay(&data[..data.len() - 2]),
+        7,
+        Settings::default(),
+        0,
+    );
+    let frame = player.frame(0, None);
+    assert_eq!(frame.time_ms, 16);
+    assert_eq!(frame.touch.unwrap().position, Point::new(10, 20));
+    let frame = player.frame(1_000, None);
+    assert_eq!(frame.time_ms, 16);
+    assert_eq!(frame.touch, None);
+}
+
+#[test]
+fn not_a_recording_uses_live_input() {
+    let mut session = Session::new(
+        InputSource::Replay(b"PXR0\0\0\0\0"),
+        7,
+        Settings::default(),
+        1_000,
+    );
+    assert_eq!(session.seed(), 7);
+    let frame = session.frame(1_016, touch(Phase::Down, 10, 20));
+    assert_eq!(frame.time_ms, 16);
+    assert_eq!(frame.touch.unwrap().position, Point::new(10, 20));
+
+    let mut session = Session::new(InputSource::Replay(b"PX"), 7, Settings::default(), 1_000);
+    assert_eq!(session.seed(), 7);
+    assert_eq!(session.frame(1_016, None).time_ms, 16);
+}
+
+#[test]
+fn live_clock_never_runs_backwards() {
+    let mut session = Session::new(InputSource::Live, 7, Settings::default(), 1_000);
+    assert_eq!(session.frame(1_100, None).time_ms, 100);
+    assert_eq!(session.frame(1_050, None).time_ms, 100);
+    assert_eq!(session.frame(900, None).time_ms, 100);
+}
+
+#[test]
+fn recording_ends_when_full() {
+    let mut session = Session::new(InputSource::Record, 42, Settings::default(), 0);
+    let mut now_ms = 0;
+    let full = loop {
+        now_ms += 16;
+        session.frame(now_ms, touch(Phase::Move, 100, 100));
+        if let Some(recording) = session.take_full_recording() {
+            break recording;
+        }
+        assert!(now_ms < 16 * RECORDING_CAPACITY as u64);
+    };
+    assert!(full.len() <= RECORDING_CAPACITY);
+    assert!(full.starts_with(b"PXR2"));
+
+    // Nothing more is recorded or dumped
+    session.frame(now_ms + 16, touch(Phase::Up, 100, 100));
+    assert!(session.take_full_recording().is_none());
+    let mut dump = String::new();
+    session.dump(&mut dump).unwrap();
+    assert!(dump.is_empty());
+
+    // Every recorded frame is replayed
+    let frames = (full.len() - 9 - ENCODED_LEN) / 6;
+    let mut player = Session::new(InputSource::Replay(full.leak()), 7, Settings::default(), 0);
+    for i in 1..=frames as u64 {
+        let frame = player.frame(0, None);
+        assert_eq!(frame.time_ms, 16 * i);
+        assert_eq!(frame.touch.unwrap().position, Point::new(100, 100));
+    }
+    assert_eq!(player.frame(0, None).touch, None);
+}
+
+/// Console and remote input and the settings are replayed with their frames
+#[test]
+fn replay_reproduces_commands_and_settings() {
+    let settings = Settings {
+        emission_rate: 9,
+        auto_rotate: false,
+        ..Settings::default()
+    };
+    let remote = (Phase::Down, Point::new(300, 160));
+    let mut recorder = Session::new(InputSource::Record, 42, settings, 0);
+    recorder.frame(16, None);
+    assert_eq!(
+        recorder.command(Some("scene wireframe")),
+        Some("scene wireframe")
+    );
+    assert_eq!(recorder.command(Some("pause")), Some("pause"));
+    assert_eq!(recorder.command(None), None);
+    recorder.frame(32, None);
+    assert_eq!(recorder.remote_touch(Some(remote)), Some(remote));
+    recorder.remote_touch(Some((Phase::Up, Point::new(300, 160))));
+    recorder.frame(48, touch(Phase::Down, 1, 2));
+
+    // Live commands and touches are ignored while replaying
+    let mut player = Session::new(
+        InputSource::Replay(recording(&recorder)),
+        7,
+        Settings::default(),
+        0,
+    );
+    assert_eq!(player.settings(), settings);
+    player.frame(0, None);
+    assert_eq!(player.command(Some("resume")), Some("scene wireframe"));
+    assert_eq!(player.remote_touch(Some(remote)), None);
+    assert_eq!(player.command(None), Some("pause"));
+    assert_eq!(player.command(Some("resume")), None);
+    player.frame(0, None);
+    assert_eq!(player.command(Some("resume")), None);
+    assert_eq!(player.remote_touch(None), Some(remote));
+
+    // Input that is not asked for is skipped with its frame
+    let frame = player.frame(0, None);
+    assert_eq!(frame.time_ms, 48);
+    assert_eq!(frame.touch.unwrap().position, Point::new(1, 2));
+    assert_eq!(player.command(Some("resume")), None);
+
+    // Then live input continues
+    player.frame(0, None);
+    assert_eq!(player.command(Some("resume")), Some("resume"));
+    assert_eq!(player.remote_touch(Some(remote)), Some(remote));
+}
+
+/// A frame whose input does not fit is left out of the recording whole
+#[test]
+fn full_recording_keeps_whole_frames() {
+    let line = "set rotation_speed 0.05";
+    let mut session = Session::new(InputSource::Record, 42, Settings::default(), 0);
+    let mut now_ms = 0;
+    let full = loop {
+        now_ms += 16;
+        session.frame(now_ms, None);
+        session.command(Some(line));
+        if let Some(recording) = session.take_full_recording() {
+            break recording;
+        }
+    };
+    assert!(full.len() <= RECORDING_CAPACITY);
+
+    let mut player = Session::new(InputSource::Replay(full.leak()), 7, Settings::default(), 0);
+    let mut frames = 0;
+    while player.frame(0, None).time_ms == 16 * (frames + 1) {
+        assert_eq!(player.command(None), Some(line));
+        assert_eq!(player.command(None), None);
+        frames += 1;
+    }
+    assert_eq!(frames, now_ms / 16 - 1);
+}
+
+/// Frames of the session rendered for the golden image
+const GOLDEN_FRAMES: u64 = 90;
+
+/// A drag and a fling, with console commands on the way
+fn golden_recording() -> &'static [u8] {
+    let settings = Settings {
+        emission_rate: 2,
+        ..Settings::default()
+    };
+    let mut recorder = Session::new(InputSource::Record, 0x5EED, settings, 0);
+    for i in 1..=GOLDEN_FRAMES {
+        let x = 100 + 12 * i as i32;
+        let touch = match i {
+            10 => touch(Phase::Down, 100, 60),
+            11..=19 => touch(Phase::Move, x - 120, 60 + i as i32),
+            20 => touch(Phase::Up, 100, 80),
+            40 => touch(Phase::Down, 160, 40),
+            41..=44 => touch(Phase::Move, 160 + 25 * (i as i32 - 40), 40),
+            45 => touch(Phase::Up, 260, 40),
+            _ => None,
+        };
+        recorder.frame(16 * i, touch);
+        let command = match i {
+            15 => "scene particles",
+            30 => "set particle_speed 0.03",
+            50 => "scene cube",
+            60 => "set emission_rate 5",
+            _ => continue,
+        };
+        assert_eq!(recorder.command(Some(command)), Some(command));
+    }
+    recording(&recorder)
+}
+
+/// Runs a replay through the frame logic of the main loop, touches are in
+/// screen coordinates
+fn render(replay: &'static [u8]) -> MockDisplay {
+    let mut display = MockDisplay::board();
+    let screen = display.size();
+    let center = Point::new(screen.width as i32 / 2, screen.height as i32 / 2);
+
+    // The live clock, seed and settings are not used
+    let mut session = Session::new(InputSource::Replay(replay), 7, Settings::default(), 0);
+    let mut settings = session.settings();
+    let arcball = Arcball::new(center, screen.width.min(screen.height) as f32 / 2.0);
+    let mut world = World::new(arcball, session.seed());
+    let mut gestures = GestureRecognizer::new();
+    let mut fps = FpsMeter::new();
+    let mut scene = Scene::Cube;
+    let mut last_time = 0;
+
+    for _ in 0..GOLDEN_FRAMES {
+        display.frame.set_background(scene.has_canvas());
+        display.frame.clear_buffer();
+
+        let frame = session.frame(0, None);
+        fps.tick(frame.time_ms);
+        let dt = (frame.time_ms - last_time) as f32 / 1000.0;
+        last_time = frame.time_ms;
+
+        match frame.touch.map(|sample| sample.phase) {
+            Some(Phase::Down) => world.arcball.hold(),
+            Some(Phase::Up) => world.arcball.release(),
+            _ => {}
+        }
+        let polled = gestures.poll(frame.time_ms);
+        for gesture in frame
+            .touch
+            .into_iter()
+            .flat_map(|sample| gestures.update(sample))
+            .chain(polled)
+        {
+            world.gesture(gesture);
+        }
+
+        while let Some(line) = session.command(None) {
+            match console::parse(line) {
+                Ok(Command::Set(key, value)) => assert!(settings.set(key, value)),
+                Ok(Command::Scene(Some(next))) => scene = next,
+                other => panic!("unexpected command {:?}", other),
+            }
+        }
+
+        let auto_rotation = math::axis_angle(math::vec3(0.0, 1.0, 0.0), settings.rotation_speed);
+        world.step(dt, &settings, auto_rotation);
+        let projection = Projection::new(settings.fov, settings.projection_distance, center);
+        let Ok(()) = world.draw(&mut display, &projection, scene);
+        let Ok(()) = fps.draw(&mut display, Point::new(4, 4), true);
+        let Ok(()) = block_on(display.update_with_buffer());
+    }
+    display
+}
+
+/// The last frame of a replayed session matches the stored image
+///
+/// `UPDATE_GOLDEN=1 cargo test` writes the image again after an intended
+/// change of what is drawn.
+#[test]
+fn replay_matches_the_golden_image() {
+    // Points are projected a little differently in fixed point
+    let path = if cfg!(feature = "fixed-point") {
+        "tests/golden/replay_fixed.rle"
+    } else {
+        "tests/golden/replay.rle"
+    };
+    let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join(path);
+
+    let display = render(golden_recording());
+    let pixels = display.frame.front_buffer();
+    // What was sent is what the frame buffer holds
+    assert!(display.panel.pixels() == pixels);
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        let mut encoded = Vec::new();
+        let mut encoder = rle::Encoder::new();
+        let mut out = |bytes: &[u8]| encoded.extend_from_slice(bytes);
+        for &pixel in pixels {
+            encoder.push(pixel.into_storage(), &mut out);
+        }
+        encoder.finish(&mut out);
+        std::fs::write(&path, encoded).unwrap();
+    }
+
+    let mut golden = Vec::new();
+    let stored = std::fs::read(&path).unwrap();
+    rle::decode(&stored, |pixel| {
+        golden.push(Rgb565::from(RawU16::new(pixel)))
+    })
+    .unwrap();
+    assert_eq!(golden.len(), pixels.len());
+    let mismatch = pixels.iter().zip(&golden).position(|(a, b)| a != b);
+    assert_eq!(mismatch, None, "first differing pixel, row by row");
+
+    // A second replay draws the same
+    assert!(render(golden_recording()).frame.front_buffer() == pixels);
+}
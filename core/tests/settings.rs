@@ -0,0 +1,171 @@
+use embassy_futures::block_on;
+use embedded_graphics::prelude::Point;
+use pixels_core::config::{TILE_SIZE, TRANSFER_OVERHEAD_BYTES};
+use pixels_core::display::DisplayTrait;
+use pixels_core::framebuffer::Geometry;
+use pixels_core::gesture::{Phase, TouchSample};
+use pixels_core::mock::{MockDisplay, DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use pixels_core::settings::{Settings, ENCODED_LEN, KEYS, VERSION};
+use pixels_core::ui::Ui;
+
+fn value(settings: &Settings, key: &str) -> String {
+    let mut text = String::new();
+    settings.write_value(key, &mut text).unwrap();
+    text
+}
+
+#[test]
+fn encoding_round_trips() {
+    let settings = Settings {
+        rotation_speed: 0.05,
+        auto_rotate: false,
+        emission_rate: 7,
+        particle_speed: 0.01,
+        fov: 150.0,
+        projection_distance: 6.5,
+        brightness: 128,
+        auto_brightness: false,
+        gamma: 2.2,
+        contrast: 0.8,
+        invert: true,
+        ..Settings::default()
+    };
+    let bytes = settings.encode();
+    assert_eq!(bytes.len(), ENCODED_LEN);
+    assert_eq!(Settings::decode(VERSION, &bytes), Some(settings));
+}
+
+#[test]
+fn version_1_gets_default_display_settings() {
+    let settings = Settings {
+        fov: 150.0,
+        brightness: 128,
+        invert: true,
+        ..Settings::default()
+    };
+    let bytes = settings.encode();
+    assert_eq!(
+        Settings::decode(1, &bytes[..42]),
+        Some(Settings {
+            fov: 150.0,
+            ..Settings::default()
+        })
+    );
+}
+
+#[test]
+fn unknown_versions_and_lengths_are_rejected() {
+    let bytes = Settings::default().encode();
+    assert_eq!(Settings::decode(VERSION + 1, &bytes), None);
+    assert_eq!(Settings::decode(0, &bytes), None);
+    assert_eq!(Settings::decode(VERSION, &bytes[..42]), None);
+    assert_eq!(Settings::decode(1, &bytes), None);
+}
+
+#[test]
+fn decoded_settings_are_validated() {
+    let settings = Settings {
+        rotation_speed: f32::NAN,
+        emission_rate: 200,
+        fov: 10_000.0,
+        particle_speed: -1.0,
+        brightness: 0,
+        ..Settings::default()
+    };
+    let decoded = Settings::decode(VERSION, &settings.encode()).unwrap();
+    assert_eq!(decoded.rotation_speed, Settings::default().rotation_speed);
+    assert_eq!(decoded.emission_rate, 10);
+    assert_eq!(decoded.fov, 400.0);
+    assert_eq!(decoded.particle_speed, 0.005);
+    assert_eq!(decoded.brightness, 16);
+}
+
+#[test]
+fn non_finite_calibration_is_reset() {
+    let mut settings = Settings::default();
+    settings.touch_calibration.c = f32::INFINITY;
+    assert_eq!(settings.validated(), Settings::default());
+}
+
+#[test]
+fn every_key_can_be_read_and_set() {
+    for key in KEYS {
+        let mut settings = Settings::default();
+        let text = value(&settings, key);
+        assert!(!text.is_empty(), "{key}");
+        assert!(settings.set(key, &text), "{key}");
+        assert_eq!(settings, Settings::default(), "{key}");
+    }
+    assert_eq!(value(&Settings::default(), "nope"), "");
+}
+
+#[test]
+fn set_parses_and_clamps() {
+    let mut settings = Settings::default();
+    assert!(settings.set("fov", "90"));
+    assert_eq!(settings.fov, 90.0);
+    assert!(settings.set("fov", "1e9"));
+    assert_eq!(settings.fov, 400.0);
+    assert!(settings.set("emission_rate", "4.6"));
+    assert_eq!(settings.emission_rate, 5);
+    assert!(settings.set("brightness", "300"));
+    assert_eq!(settings.brightness, 255);
+    assert!(settings.set("auto_rotate", "off"));
+    assert!(!settings.auto_rotate);
+    assert!(settings.set("invert", "1"));
+    assert!(settings.invert);
+
+    let before = settings;
+    assert!(!settings.set("fov", "wide"));
+    assert!(!settings.set("fov", "NaN"));
+    assert!(!settings.set("emission_rate", "-1"));
+    assert!(!settings.set("invert", "maybe"));
+    assert!(!settings.set("nope", "1"));
+    assert_eq!(settings, before);
+}
+
+/// Taps `position` on the open settings panel, one frame per touch phase
+fn tap_panel(display: &mut MockDisplay, settings: &mut Settings, position: Point) -> bool {
+    let mut ui = Ui::new();
+    let mut open = true;
+    for phase in [Some(Phase::Down), None, Some(Phase::Up)] {
+        display.frame.clear_buffer();
+        ui.begin(phase.map(|phase| TouchSample {
+            phase,
+            position,
+            time_ms: 0,
+        }));
+        let Ok(_) = settings.edit(&mut ui, display, &mut open);
+        ui.end();
+        let Ok(()) = block_on(display.update_with_buffer());
+    }
+    open
+}
+
+#[test]
+fn close_button_is_on_screen() {
+    for height in [DISPLAY_HEIGHT, 240] {
+        let geometry = Geometry::new(DISPLAY_WIDTH, height, TILE_SIZE);
+        let mut display = MockDisplay::new(geometry, TRANSFER_OVERHEAD_BYTES);
+        let mut settings = Settings::default();
+
+        // Right above the bottom margin of the panel
+        let close = Point::new(DISPLAY_WIDTH as i32 - 115, height as i32 - 20);
+        assert!(
+            !tap_panel(&mut display, &mut settings, close),
+            "{height} px"
+        );
+        assert_eq!(settings, Settings::default());
+    }
+}
+
+#[test]
+fn rows_fit_on_a_short_screen() {
+    let mut display = MockDisplay::board();
+    let mut settings = Settings::default();
+
+    // The auto-rotate toggle is the last row, 20 px apart on 170 px
+    let toggle = Point::new(DISPLAY_WIDTH as i32 - 200, 8 + 5 * 20 + 10);
+    assert!(tap_panel(&mut display, &mut settings, toggle));
+    assert!(!settings.auto_rotate);
+}
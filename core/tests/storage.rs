@@ -0,0 +1,136 @@
+use pixels_core::mock::MockFlash;
+use pixels_core::settings::Settings;
+use pixels_core::storage::{Error, SettingsStore, MIN_SECTORS};
+
+/// Slots of 64 bytes in a sector of the mock flash
+const SLOTS_PER_SECTOR: usize = 64;
+
+/// Settings that differ from those of the saves before and after
+fn nth_settings(n: usize) -> Settings {
+    Settings {
+        brightness: 16 + (n % 240) as u8,
+        ..Settings::default()
+    }
+}
+
+fn open(flash: &MockFlash) -> SettingsStore<MockFlash> {
+    SettingsStore::new(flash.clone()).unwrap()
+}
+
+#[test]
+fn nothing_stored_on_erased_flash() {
+    let flash = MockFlash::new(2);
+    assert_eq!(open(&flash).load(), None);
+}
+
+#[test]
+fn saved_settings_survive_a_reset() {
+    let flash = MockFlash::new(2);
+    let mut store = open(&flash);
+    let settings = Settings {
+        fov: 120.0,
+        auto_rotate: false,
+        ..Settings::default()
+    };
+    store.save(&settings).unwrap();
+    assert_eq!(store.load(), Some(settings));
+    assert_eq!(open(&flash).load(), Some(settings));
+}
+
+#[test]
+fn unchanged_settings_are_not_written() {
+    let flash = MockFlash::new(2);
+    let mut store = open(&flash);
+    store.save(&Settings::default()).unwrap();
+    let data = flash.data();
+
+    store.save(&Settings::default()).unwrap();
+    assert!(flash.data() == data);
+    let mut store = open(&flash);
+    store.save(&Settings::default()).unwrap();
+    assert!(flash.data() == data);
+}
+
+#[test]
+fn saves_wear_all_sectors_evenly() {
+    let flash = MockFlash::new(4);
+    let mut store = open(&flash);
+    let saves = 10 * 4 * SLOTS_PER_SECTOR;
+    for n in 0..saves {
+        store.save(&nth_settings(n)).unwrap();
+    }
+    assert_eq!(flash.erases(), [10, 10, 10, 10]);
+
+    // The newest record is found wherever the writes wrapped around to
+    store.save(&nth_settings(saves)).unwrap();
+    assert_eq!(open(&flash).load(), Some(nth_settings(saves)));
+}
+
+#[test]
+fn corrupted_record_falls_back_to_the_one_before() {
+    let flash = MockFlash::new(2);
+    let mut store = open(&flash);
+    for n in 0..3 {
+        store.save(&nth_settings(n)).unwrap();
+    }
+
+    // In the settings of the newest record, in the third slot
+    flash.corrupt(2 * 64 + 20, 0x10);
+    assert_eq!(open(&flash).load(), Some(nth_settings(1)));
+
+    // The next save does not reuse the corrupted slot
+    let mut store = open(&flash);
+    store.save(&nth_settings(3)).unwrap();
+    assert_eq!(open(&flash).load(), Some(nth_settings(3)));
+}
+
+#[test]
+fn interrupted_save_keeps_the_previous_settings() {
+    let flash = MockFlash::new(2);
+    let mut store = open(&flash);
+    store.save(&nth_settings(0)).unwrap();
+
+    flash.lose_power_after(20);
+    assert!(store.save(&nth_settings(1)).is_err());
+    flash.restore_power();
+    assert_eq!(open(&flash).load(), Some(nth_settings(0)));
+
+    // The half written slot is skipped, the save starts the next sector
+    let mut store = open(&flash);
+    store.save(&nth_settings(2)).unwrap();
+    assert_eq!(flash.erases(), [1, 1]);
+    assert_eq!(open(&flash).load(), Some(nth_settings(2)));
+}
+
+#[test]
+fn interrupted_erase_keeps_the_previous_settings() {
+    let flash = MockFlash::new(2);
+    let mut store = open(&flash);
+    for n in 0..SLOTS_PER_SECTOR {
+        store.save(&nth_settings(n)).unwrap();
+    }
+
+    // The next save erases the second sector first
+    flash.lose_power_after(0);
+    assert!(store.save(&nth_settings(100)).is_err());
+    flash.restore_power();
+    assert_eq!(
+        open(&flash).load(),
+        Some(nth_settings(SLOTS_PER_SECTOR - 1))
+    );
+}
+
+#[test]
+fn partition_of_less_than_two_sectors_is_rejected() {
+    // With one sector every save would erase the record before it
+    for sectors in 0..MIN_SECTORS {
+        let flash = MockFlash::new(sectors);
+        assert!(matches!(
+            SettingsStore::new(flash.clone()),
+            Err(Error::TooSmall)
+        ));
+        assert_eq!(flash.erases(), vec![0; sectors]);
+    }
+    let flash = MockFlash::new(MIN_SECTORS);
+    assert!(SettingsStore::new(flash).is_ok());
+}
@@ -0,0 +1,213 @@
+use embedded_graphics::prelude::{Point, Size};
+use mipidsi::options::{MemoryMapping, Orientation, Rotation};
+use pixels_core::gesture::Phase;
+use pixels_core::touch::{Calibration, Calibrator, TouchTransform};
+
+/// Native size of the ST7789 on the 320x170 board, before rotation
+const NATIVE: (u16, u16) = (170, 320);
+
+/// Crosshairs of the calibration on the 320x170 board, 10 % and 90 % across,
+/// 20 % and 80 % down
+const TARGETS: [Point; 3] = [
+    Point::new(32, 34),
+    Point::new(288, 34),
+    Point::new(160, 136),
+];
+
+const ROTATIONS: [Rotation; 4] = [
+    Rotation::Deg0,
+    Rotation::Deg90,
+    Rotation::Deg180,
+    Rotation::Deg270,
+];
+
+/// Panel memory position of the pixel mipidsi draws at `(x, y)`
+///
+/// The panel mirrors the drawing coordinates as the memory mapping of the
+/// orientation says, then swaps rows and columns.
+fn native(orientation: Orientation, x: i32, y: i32) -> Point {
+    let mapping = MemoryMapping::from(orientation);
+    let (width, height) = if mapping.swap_rows_and_columns {
+        (NATIVE.1 as i32, NATIVE.0 as i32)
+    } else {
+        (NATIVE.0 as i32, NATIVE.1 as i32)
+    };
+
+    let x = if mapping.reverse_columns {
+        width - 1 - x
+    } else {
+        x
+    };
+    let y = if mapping.reverse_rows {
+        height - 1 - y
+    } else {
+        y
+    };
+    if mapping.swap_rows_and_columns {
+        Point::new(y, x)
+    } else {
+        Point::new(x, y)
+    }
+}
+
+fn assert_close(calibration: Calibration, expected: Calibration) {
+    let pairs = [
+        (calibration.a, expected.a),
+        (calibration.b, expected.b),
+        (calibration.c, expected.c),
+        (calibration.d, expected.d),
+        (calibration.e, expected.e),
+        (calibration.f, expected.f),
+    ];
+    for (value, expected) in pairs {
+        assert!((value - expected).abs() < 1e-3, "{calibration:?}");
+    }
+}
+
+#[test]
+fn from_points_recovers_an_affine_map() {
+    // Scaled, skewed and offset, with exact results for the points below
+    let map = Calibration {
+        a: 1.25,
+        b: -0.25,
+        c: 3.0,
+        d: 0.5,
+        e: 0.75,
+        f: -6.0,
+    };
+    let measured = [
+        Point::new(20, 32),
+        Point::new(300, 40),
+        Point::new(160, 148),
+    ];
+    let target = measured.map(|point| map.apply(point));
+
+    let calibration = Calibration::from_points(measured, target).unwrap();
+    assert_close(calibration, map);
+    for point in [Point::new(0, 0), Point::new(319, 169), Point::new(123, 45)] {
+        assert_eq!(calibration.apply(point), map.apply(point));
+    }
+
+    // The same points give the identity
+    let identity = Calibration::from_points(measured, measured).unwrap();
+    assert_close(identity, Calibration::IDENTITY);
+}
+
+#[test]
+fn from_points_rejects_collinear_taps() {
+    let lines = [
+        [
+            Point::new(10, 10),
+            Point::new(100, 100),
+            Point::new(200, 200),
+        ],
+        [Point::new(10, 50), Point::new(150, 50), Point::new(300, 50)],
+        [Point::new(40, 0), Point::new(40, 80), Point::new(40, 169)],
+        [Point::new(60, 60); 3],
+    ];
+    for measured in lines {
+        assert!(
+            Calibration::from_points(measured, TARGETS).is_none(),
+            "{measured:?}"
+        );
+    }
+}
+
+#[test]
+fn orient_matches_the_display_rotation() {
+    for rotation in ROTATIONS {
+        for mirrored in [false, true] {
+            let orientation = Orientation { rotation, mirrored };
+            let transform = TouchTransform::new(NATIVE, orientation);
+            let (width, height) = match rotation {
+                Rotation::Deg0 | Rotation::Deg180 => NATIVE,
+                Rotation::Deg90 | Rotation::Deg270 => (NATIVE.1, NATIVE.0),
+            };
+
+            // A touch on a pixel maps to where the display drew it
+            for y in 0..height as i32 {
+                for x in 0..width as i32 {
+                    let raw = native(orientation, x, y);
+                    assert_eq!(
+                        transform.orient(raw),
+                        Point::new(x, y),
+                        "{orientation:?} raw {raw:?}"
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn to_screen_calibrates_the_oriented_position() {
+    let orientation = Orientation {
+        rotation: Rotation::Deg90,
+        mirrored: false,
+    };
+    let mut transform = TouchTransform::new(NATIVE, orientation);
+    let raw = native(orientation, 100, 60);
+    assert_eq!(transform.to_screen(raw), Point::new(100, 60));
+
+    transform.set_calibration(Calibration {
+        c: 5.0,
+        f: -3.0,
+        ..Calibration::IDENTITY
+    });
+    assert_eq!(transform.to_screen(raw), Point::new(105, 57));
+}
+
+/// Taps at `positions`, returns the result of the last one
+fn tap_all(calibrator: &mut Calibrator, positions: &[Point]) -> Option<Calibration> {
+    let mut result = None;
+    for &position in positions {
+        assert!(calibrator.touch(Phase::Down, position).is_none());
+        assert!(calibrator.touch(Phase::Move, position).is_none());
+        result = calibrator.touch(Phase::Up, position);
+    }
+    result
+}
+
+#[test]
+fn calibrator_measures_the_offset_of_the_taps() {
+    let mut calibrator = Calibrator::new(Size::new(320, 170));
+    let offset = Point::new(-4, 6);
+
+    assert!(tap_all(&mut calibrator, &[TARGETS[0] + offset, TARGETS[1] + offset]).is_none());
+    let calibration = tap_all(&mut calibrator, &[TARGETS[2] + offset]).unwrap();
+    assert_close(
+        calibration,
+        Calibration {
+            c: 4.0,
+            f: -6.0,
+            ..Calibration::IDENTITY
+        },
+    );
+}
+
+#[test]
+fn calibrator_ignores_a_release_without_a_press() {
+    let mut calibrator = Calibrator::new(Size::new(320, 170));
+    // The release of the long press that started the calibration
+    assert!(calibrator.touch(Phase::Up, Point::new(5, 5)).is_none());
+    assert!(calibrator.touch(Phase::Move, Point::new(7, 7)).is_none());
+    assert!(calibrator.touch(Phase::Up, Point::new(9, 9)).is_none());
+
+    // Had it counted, the stray position would skew the result
+    let calibration = tap_all(&mut calibrator, &TARGETS).unwrap();
+    assert_close(calibration, Calibration::IDENTITY);
+}
+
+#[test]
+fn calibrator_starts_over_after_collinear_taps() {
+    let mut calibrator = Calibrator::new(Size::new(320, 170));
+    let line = [
+        Point::new(20, 20),
+        Point::new(100, 100),
+        Point::new(180, 180),
+    ];
+    assert!(tap_all(&mut calibrator, &line).is_none());
+
+    let calibration = tap_all(&mut calibrator, &TARGETS).unwrap();
+    assert_close(calibration, Calibration::IDENTITY);
+}
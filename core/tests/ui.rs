@@ -0,0 +1,237 @@
+use embassy_futures::block_on;
+use embedded_graphics::pixelcolor::{Rgb565, RgbColor};
+use embedded_graphics::prelude::{Point, Size};
+use embedded_graphics::primitives::Rectangle;
+use pixels_core::display::DisplayTrait;
+use pixels_core::gesture::{Phase, TouchSample};
+use pixels_core::mock::MockDisplay;
+use pixels_core::ui::Ui;
+
+const BUTTON: Rectangle = Rectangle::new(Point::new(20, 20), Size::new(100, 40));
+const SLIDER: Rectangle = Rectangle::new(Point::new(20, 100), Size::new(201, 20));
+const TOGGLE: Rectangle = Rectangle::new(Point::new(200, 20), Size::new(100, 30));
+
+fn touch(phase: Phase, x: i32, y: i32) -> Option<TouchSample> {
+    Some(TouchSample {
+        phase,
+        position: Point::new(x, y),
+        time_ms: 0,
+    })
+}
+
+/// Runs one frame of widgets with `touch` and sends it to the panel
+fn frame<R>(
+    ui: &mut Ui,
+    display: &mut MockDisplay,
+    touch: Option<TouchSample>,
+    widgets: impl FnOnce(&mut Ui, &mut MockDisplay) -> R,
+) -> R {
+    display.frame.clear_buffer();
+    ui.begin(touch);
+    let result = widgets(ui, display);
+    ui.end();
+    let Ok(()) = block_on(display.update_with_buffer());
+    result
+}
+
+fn button(ui: &mut Ui, display: &mut MockDisplay) -> bool {
+    let Ok(clicked) = ui.button(display, BUTTON, "OK");
+    clicked
+}
+
+/// Frames of a touch going down at `from`, moving to `to` and lifting there
+fn press(from: Point, to: Point) -> [Option<TouchSample>; 4] {
+    [
+        touch(Phase::Down, from.x, from.y),
+        touch(Phase::Move, to.x, to.y),
+        None,
+        touch(Phase::Up, to.x, to.y),
+    ]
+}
+
+#[test]
+fn button_clicks_on_release() {
+    let mut ui = Ui::new();
+    let mut display = MockDisplay::board();
+    let clicks: Vec<_> = press(Point::new(30, 30), Point::new(100, 50))
+        .into_iter()
+        .map(|touch| frame(&mut ui, &mut display, touch, button))
+        .collect();
+    assert_eq!(clicks, [false, false, false, true]);
+
+    // Nothing without a touch
+    assert!(!frame(&mut ui, &mut display, None, button));
+}
+
+#[test]
+fn pressed_button_is_highlighted() {
+    let mut ui = Ui::new();
+    let mut display = MockDisplay::board();
+    // Left of the text
+    let (x, y) = (40, 40);
+    frame(&mut ui, &mut display, None, button);
+    assert_eq!(display.panel.pixel(x, y), Rgb565::BLACK);
+
+    frame(&mut ui, &mut display, touch(Phase::Down, 70, 40), button);
+    assert_ne!(display.panel.pixel(x, y), Rgb565::BLACK);
+
+    frame(&mut ui, &mut display, touch(Phase::Up, 70, 40), button);
+    assert_eq!(display.panel.pixel(x, y), Rgb565::BLACK);
+}
+
+#[test]
+fn no_click_when_released_outside() {
+    let mut ui = Ui::new();
+    let mut display = MockDisplay::board();
+    for touch in press(Point::new(30, 30), Point::new(300, 150)) {
+        assert!(!frame(&mut ui, &mut display, touch, button));
+    }
+}
+
+#[test]
+fn no_click_when_pressed_outside() {
+    let mut ui = Ui::new();
+    let mut display = MockDisplay::board();
+    for touch in press(Point::new(300, 150), Point::new(30, 30)) {
+        assert!(!frame(&mut ui, &mut display, touch, button));
+    }
+}
+
+#[test]
+fn toggle_flips_on_tap() {
+    let mut ui = Ui::new();
+    let mut display = MockDisplay::board();
+    let mut value = false;
+    let mut toggle = |ui: &mut Ui, display: &mut MockDisplay| {
+        let Ok(changed) = ui.toggle(display, TOGGLE, "On", &mut value);
+        changed
+    };
+
+    let inside = Point::new(210, 30);
+    let changes: Vec<_> = press(inside, inside)
+        .into_iter()
+        .map(|touch| frame(&mut ui, &mut display, touch, &mut toggle))
+        .collect();
+    assert_eq!(changes, [false, false, false, true]);
+    for touch in press(inside, inside) {
+        frame(&mut ui, &mut display, touch, &mut toggle);
+    }
+    assert!(!value);
+}
+
+#[test]
+fn slider_follows_the_drag() {
+    let mut ui = Ui::new();
+    let mut display = MockDisplay::board();
+    let mut value = 0.0;
+    let mut slider = |touch, value: &mut f32| {
+        frame(&mut ui, &mut display, touch, |ui, display| {
+            let Ok(changed) = ui.slider(display, SLIDER, value, 10.0, 20.0);
+            changed
+        })
+    };
+
+    // The left edge is `min`, the right edge `max`
+    assert!(slider(touch(Phase::Down, 20, 110), &mut value));
+    assert_eq!(value, 10.0);
+    assert!(slider(touch(Phase::Move, 120, 110), &mut value));
+    assert_eq!(value, 15.0);
+    assert!(!slider(touch(Phase::Move, 120, 200), &mut value));
+    assert_eq!(value, 15.0);
+
+    // Clamped when dragged past the ends, even off the slider
+    assert!(slider(touch(Phase::Move, 400, 10), &mut value));
+    assert_eq!(value, 20.0);
+    assert!(slider(touch(Phase::Move, -50, 110), &mut value));
+    assert_eq!(value, 10.0);
+
+    // Keeps its value after the release
+    assert!(!slider(touch(Phase::Up, 170, 110), &mut value));
+    assert!(!slider(touch(Phase::Move, 170, 110), &mut value));
+    assert_eq!(value, 10.0);
+}
+
+#[test]
+fn slider_shows_its_value() {
+    let mut ui = Ui::new();
+    let mut display = MockDisplay::board();
+    let mut value = 0.5;
+    frame(&mut ui, &mut display, None, |ui, display| {
+        let Ok(_) = ui.slider(display, SLIDER, &mut value, 0.0, 1.0);
+    });
+    assert_ne!(display.panel.pixel(60, 110), Rgb565::BLACK);
+    assert_eq!(display.panel.pixel(180, 110), Rgb565::BLACK);
+}
+
+#[test]
+fn held_widget_keeps_the_touch() {
+    let mut ui = Ui::new();
+    let mut display = MockDisplay::board();
+    let mut value = 0.0;
+
+    // Dragging from the slider over the button and releasing there
+    let touches = [
+        touch(Phase::Down, 120, 110),
+        touch(Phase::Move, 60, 40),
+        touch(Phase::Up, 60, 40),
+    ];
+    for touch in touches {
+        let clicked = frame(&mut ui, &mut display, touch, |ui, display| {
+            let Ok(_) = ui.slider(display, SLIDER, &mut value, 0.0, 1.0);
+            button(ui, display)
+        });
+        assert!(!clicked);
+    }
+    assert_eq!(value, 0.2);
+}
+
+#[test]
+fn panel_captures_touches_started_on_it() {
+    let mut ui = Ui::new();
+    let mut display = MockDisplay::board();
+    let area = Rectangle::new(Point::new(0, 0), Size::new(160, 170));
+    let mut panel = |touch| {
+        frame(&mut ui, &mut display, touch, |ui, display| {
+            let Ok(()) = ui.panel(display, area);
+            ui.captures_touch()
+        })
+    };
+
+    // Outside of the panel, and on the panel before it was drawn
+    assert!(!panel(touch(Phase::Down, 200, 100)));
+    assert!(!panel(touch(Phase::Move, 100, 100)));
+    assert!(!panel(touch(Phase::Up, 100, 100)));
+
+    // Still captured when the touch leaves the panel, until the next one
+    assert!(panel(touch(Phase::Down, 100, 100)));
+    assert!(panel(touch(Phase::Move, 250, 100)));
+    assert!(panel(touch(Phase::Up, 250, 100)));
+    assert!(!panel(touch(Phase::Down, 250, 100)));
+
+    // Nothing is captured once the panel is gone
+    let mut ui = Ui::new();
+    frame(&mut ui, &mut display, None, |ui, display| {
+        let Ok(()) = ui.panel(display, area);
+    });
+    frame(&mut ui, &mut display, None, |_, _| {});
+    frame(
+        &mut ui,
+        &mut display,
+        touch(Phase::Down, 100, 100),
+        |_, _| {},
+    );
+    assert!(!ui.captures_touch());
+}
+
+#[test]
+fn panel_is_drawn() {
+    let mut ui = Ui::new();
+    let mut display = MockDisplay::board();
+    let area = Rectangle::new(Point::new(0, 0), Size::new(160, 170));
+    frame(&mut ui, &mut display, None, |ui, display| {
+        let Ok(()) = ui.panel(display, area);
+        let Ok(()) = ui.label(display, Point::new(10, 10), "Settings");
+    });
+    assert_ne!(display.panel.pixel(80, 100), Rgb565::BLACK);
+    assert_eq!(display.panel.pixel(200, 100), Rgb565::BLACK);
+}
@@ -0,0 +1,27 @@
+//! Hex lines of the serial dumps
+//!
+//! Recordings and screenshots are printed as one line of lowercase hex per
+//! chunk behind a prefix, `xxd -r -p` turns the lines back into bytes.
+
+use core::fmt;
+
+const DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+/// Formats the bytes as lowercase hex, two digits per byte
+#[derive(Clone, Copy, Debug)]
+pub struct Hex<'a>(pub &'a [u8]);
+
+impl fmt::Display for Hex<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut digits = [0; 64];
+        for chunk in self.0.chunks(digits.len() / 2) {
+            for (byte, pair) in chunk.iter().zip(digits.chunks_exact_mut(2)) {
+                pair[0] = DIGITS[(byte >> 4) as usize];
+                pair[1] = DIGITS[(byte & 0xF) as usize];
+            }
+            // Only ASCII digits were written
+            f.write_str(core::str::from_utf8(&digits[..2 * chunk.len()]).unwrap_or_default())?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,13 @@
+//! Wire formats shared by the firmware and the host tools
+//!
+//! `no_std` and without allocations, so the firmware encodes with the same
+//! code the host tools decode with.
+
+#![no_std]
+
+pub mod crc;
+pub mod hex;
+pub mod pixelflut;
+pub mod rle;
+pub mod screenshot;
+pub mod stream;
@@ -0,0 +1,257 @@
+//! Pixelflut, a text protocol for drawing on a shared canvas over TCP
+//!
+//! Every command is one line ending in `\n`, a `\r` before it is ignored:
+//!
+//! - `PX x y rrggbb` sets a pixel, `PX x y rrggbbaa` blends it in with alpha
+//!   `aa` and `PX x y ww` sets a gray
+//! - `PX x y` replies `PX x y rrggbb`
+//! - `SIZE` replies `SIZE width height`
+//! - `HELP` replies a summary of the commands
+//!
+//! Pixels outside the canvas are ignored, malformed lines reply `ERROR` and
+//! the reason. A [`Session`] runs the commands of one connection with a
+//! budget, so that a server can share its time between clients.
+
+use core::fmt::{self, Write};
+
+/// TCP port of the device, the one most Pixelflut tools default to
+pub const PORT: u16 = 1337;
+
+/// Longest line, longer ones are answered with an error
+pub const MAX_LINE_LEN: usize = 32;
+
+const HELP: &str = "\
+HELP commands:
+PX x y rrggbb     set a pixel, rrggbbaa blends it, ww sets a gray
+PX x y            read a pixel
+SIZE              canvas size
+HELP              this text
+";
+
+/// Longest reply
+pub const MAX_REPLY_LEN: usize = HELP.len();
+
+/// Pixels a [`Session`] draws on, colors are `0xRRGGBB`
+pub trait Canvas {
+    fn size(&self) -> (u16, u16);
+    fn pixel(&self, x: u16, y: u16) -> u32;
+    fn set_pixel(&mut self, x: u16, y: u16, color: u32);
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    /// Blend `color` into the pixel, opaque with `alpha` 255
+    Set {
+        x: u16,
+        y: u16,
+        color: u32,
+        alpha: u8,
+    },
+    Get {
+        x: u16,
+        y: u16,
+    },
+    Size,
+    Help,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    Unknown,
+    Coordinate,
+    Color,
+    TooLong,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Unknown => write!(f, "unknown command, try HELP"),
+            Error::Coordinate => write!(f, "coordinates are two numbers"),
+            Error::Color => write!(f, "colors are ww, rrggbb or rrggbbaa in hex"),
+            Error::TooLong => write!(f, "line too long"),
+        }
+    }
+}
+
+pub fn parse(line: &[u8]) -> Result<Command, Error> {
+    let mut words = line
+        .split(|byte| byte.is_ascii_whitespace())
+        .filter(|word| !word.is_empty());
+
+    let command = match words.next() {
+        Some(b"PX") => {
+            let mut coordinate = || {
+                words
+                    .next()
+                    .and_then(|word| core::str::from_utf8(word).ok())
+                    .and_then(|word| word.parse().ok())
+                    .ok_or(Error::Coordinate)
+            };
+            let (x, y) = (coordinate()?, coordinate()?);
+            match words.next() {
+                None => Command::Get { x, y },
+                Some(color) => {
+                    let (color, alpha) = parse_color(color).ok_or(Error::Color)?;
+                    Command::Set { x, y, color, alpha }
+                }
+            }
+        }
+        Some(b"SIZE") => Command::Size,
+        Some(b"HELP") => Command::Help,
+        _ => return Err(Error::Unknown),
+    };
+
+    if words.next().is_some() {
+        return Err(Error::Unknown);
+    }
+    Ok(command)
+}
+
+/// `ww`, `rrggbb` or `rrggbbaa`, returns the color and alpha
+fn parse_color(hex: &[u8]) -> Option<(u32, u8)> {
+    if !hex.iter().all(u8::is_ascii_hexdigit) {
+        return None;
+    }
+    let value = u32::from_str_radix(core::str::from_utf8(hex).ok()?, 16).ok()?;
+    match hex.len() {
+        2 => Some((value * 0x010101, 0xFF)),
+        6 => Some((value, 0xFF)),
+        8 => Some((value >> 8, value as u8)),
+        _ => None,
+    }
+}
+
+/// `color` over `background` with `alpha`
+fn blend(background: u32, color: u32, alpha: u8) -> u32 {
+    if alpha == 0xFF {
+        return color;
+    }
+
+    let alpha = alpha as u32;
+    [16, 8, 0].iter().fold(0, |blended, shift| {
+        let front = (color >> shift) & 0xFF;
+        let back = (background >> shift) & 0xFF;
+        blended | ((front * alpha + back * (255 - alpha)) / 255) << shift
+    })
+}
+
+/// What [`Session::feed`] did
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Progress {
+    /// Input bytes used, the rest has to be fed again
+    pub consumed: usize,
+    /// Reply bytes written
+    pub replied: usize,
+    /// Lines run
+    pub commands: u32,
+}
+
+/// One connection: collects lines and runs them
+pub struct Session {
+    line: [u8; MAX_LINE_LEN],
+    len: usize,
+    too_long: bool,
+}
+
+impl Session {
+    pub const fn new() -> Self {
+        Self {
+            line: [0; MAX_LINE_LEN],
+            len: 0,
+            too_long: false,
+        }
+    }
+
+    /// Runs the lines in `input` on `canvas`, writing replies to `reply`
+    ///
+    /// Stops after `budget` lines or when `reply` has no room for another
+    /// reply, the bytes not consumed are left for the next call.
+    pub fn feed(
+        &mut self,
+        input: &[u8],
+        canvas: &mut impl Canvas,
+        budget: u32,
+        reply: &mut [u8],
+    ) -> Progress {
+        let mut progress = Progress::default();
+        for &byte in input {
+            if progress.commands == budget || reply.len() - progress.replied < MAX_REPLY_LEN {
+                break;
+            }
+            progress.consumed += 1;
+
+            match byte {
+                b'\n' => {
+                    let mut out = Cursor {
+                        buffer: &mut reply[progress.replied..],
+                        len: 0,
+                    };
+                    self.run(canvas, &mut out);
+                    progress.replied += out.len;
+                    progress.commands += 1;
+                    self.len = 0;
+                    self.too_long = false;
+                }
+                _ if self.len < MAX_LINE_LEN => {
+                    self.line[self.len] = byte;
+                    self.len += 1;
+                }
+                _ => self.too_long = true,
+            }
+        }
+        progress
+    }
+
+    fn run(&self, canvas: &mut impl Canvas, out: &mut Cursor<'_>) {
+        let line = &self.line[..self.len];
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        let command = match parse(line) {
+            _ if self.too_long => Err(Error::TooLong),
+            // Empty lines are harmless
+            Err(Error::Unknown) if line.trim_ascii().is_empty() => return,
+            result => result,
+        };
+
+        let (width, height) = canvas.size();
+        // Replies always fit, `feed` keeps room for the longest
+        let _ = match command {
+            Ok(Command::Set { x, y, color, alpha }) => {
+                if x < width && y < height {
+                    let color = blend(canvas.pixel(x, y), color, alpha);
+                    canvas.set_pixel(x, y, color);
+                }
+                Ok(())
+            }
+            Ok(Command::Get { x, y }) if x < width && y < height => {
+                writeln!(out, "PX {} {} {:06x}", x, y, canvas.pixel(x, y))
+            }
+            Ok(Command::Get { .. }) => writeln!(out, "ERROR outside the canvas"),
+            Ok(Command::Size) => writeln!(out, "SIZE {} {}", width, height),
+            Ok(Command::Help) => out.write_str(HELP),
+            Err(error) => writeln!(out, "ERROR {}", error),
+        };
+    }
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Writes into a byte buffer, cuts off what does not fit
+struct Cursor<'a> {
+    buffer: &'a mut [u8],
+    len: usize,
+}
+
+impl Write for Cursor<'_> {
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        let room = self.buffer.len() - self.len;
+        let bytes = &text.as_bytes()[..text.len().min(room)];
+        self.buffer[self.len..self.len + bytes.len()].copy_from_slice(bytes);
+        self.len += bytes.len();
+        Ok(())
+    }
+}
@@ -0,0 +1,114 @@
+//! Run-length encoding of 16-bit pixels
+//!
+//! The data is a sequence of packets, each starting with a control byte `c`:
+//!
+//! - `c < 128`: `c + 1` literal pixels follow
+//! - `c >= 128`: one pixel follows, repeated `c - 126` times
+//!
+//! Pixels are little endian. Mostly black frames shrink to a few percent.
+
+/// Most pixels in a literal packet
+const MAX_LITERALS: usize = 128;
+/// Most repetitions in a repeat packet
+const MAX_REPEAT: usize = 129;
+/// Shortest run sent as a repeat packet, a single pixel is a literal
+const MIN_REPEAT: usize = 2;
+
+/// Streaming encoder, bytes are passed to `out` as packets complete
+pub struct Encoder {
+    literals: [u16; MAX_LITERALS],
+    literal_count: usize,
+    /// Pixel of the current run and its length
+    run: Option<(u16, usize)>,
+}
+
+impl Encoder {
+    pub const fn new() -> Self {
+        Self {
+            literals: [0; MAX_LITERALS],
+            literal_count: 0,
+            run: None,
+        }
+    }
+
+    pub fn push(&mut self, pixel: u16, out: &mut impl FnMut(&[u8])) {
+        match self.run {
+            Some((run_pixel, count)) if run_pixel == pixel && count < MAX_REPEAT => {
+                self.run = Some((pixel, count + 1));
+            }
+            _ => {
+                self.end_run(out);
+                self.run = Some((pixel, 1));
+            }
+        }
+    }
+
+    /// Writes the pending pixels
+    pub fn finish(mut self, out: &mut impl FnMut(&[u8])) {
+        self.end_run(out);
+        self.flush_literals(out);
+    }
+
+    fn end_run(&mut self, out: &mut impl FnMut(&[u8])) {
+        match self.run.take() {
+            Some((pixel, count)) if count >= MIN_REPEAT => {
+                self.flush_literals(out);
+                let [low, high] = pixel.to_le_bytes();
+                out(&[(count + 126) as u8, low, high]);
+            }
+            Some((pixel, _)) => {
+                if self.literal_count == MAX_LITERALS {
+                    self.flush_literals(out);
+                }
+                self.literals[self.literal_count] = pixel;
+                self.literal_count += 1;
+            }
+            None => {}
+        }
+    }
+
+    fn flush_literals(&mut self, out: &mut impl FnMut(&[u8])) {
+        if self.literal_count == 0 {
+            return;
+        }
+
+        out(&[(self.literal_count - 1) as u8]);
+        for pixel in &self.literals[..self.literal_count] {
+            out(&pixel.to_le_bytes());
+        }
+        self.literal_count = 0;
+    }
+}
+
+impl Default for Encoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The data ends inside a packet
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Truncated;
+
+/// Decodes `data`, passing every pixel to `pixel`
+pub fn decode(mut data: &[u8], mut pixel: impl FnMut(u16)) -> Result<(), Truncated> {
+    while let Some((&control, rest)) = data.split_first() {
+        // Number of pixels that follow and how often each is repeated
+        let (pixels, repeat) = if control < 128 {
+            (control as usize + 1, 1)
+        } else {
+            (1, control as usize - 126)
+        };
+        let (values, rest) = rest.split_at_checked(2 * pixels).ok_or(Truncated)?;
+
+        for value in values.chunks_exact(2) {
+            let value = u16::from_le_bytes([value[0], value[1]]);
+            for _ in 0..repeat {
+                pixel(value);
+            }
+        }
+        data = rest;
+    }
+
+    Ok(())
+}
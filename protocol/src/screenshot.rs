@@ -0,0 +1,262 @@
+//! Screenshot stream
+//!
+//! A screenshot is a sequence of frames: a header, the pixel data in chunks of
+//! at most [`CHUNK_LEN`] bytes, then an end frame with the length and CRC-32 of
+//! the whole pixel data. Frame layout, little endian:
+//!
+//! - kind (`u8`): 0 header, 1 data, 2 end
+//! - sequence number (`u16`), the header is 0
+//! - payload length (`u16`) and payload
+//! - CRC-32 of everything before it
+//!
+//! The header payload is the magic `PXSC`, the [`VERSION`], the [`Encoding`]
+//! (`u8`) and the width and height (`u16`). Pixels are RGB565, row by row,
+//! either raw little endian or run-length encoded with [`crate::rle`].
+//!
+//! Over serial every frame is one line: [`LINE_PREFIX`] and the frame in hex.
+
+use core::fmt;
+
+use crate::crc::{crc32, Crc32};
+use crate::rle;
+
+pub const MAGIC: &[u8; 4] = b"PXSC";
+pub const VERSION: u8 = 1;
+/// Prefix of the serial lines holding a frame
+pub const LINE_PREFIX: &str = "scr:";
+/// Most pixel data bytes in a data frame
+pub const CHUNK_LEN: usize = 240;
+/// Kind, sequence number and payload length
+const HEADER_LEN: usize = 5;
+const CRC_LEN: usize = 4;
+const INFO_LEN: usize = 10;
+const END_LEN: usize = 8;
+/// Longest encoded frame
+pub const MAX_FRAME_LEN: usize = HEADER_LEN + CHUNK_LEN + CRC_LEN;
+
+const KIND_HEADER: u8 = 0;
+const KIND_DATA: u8 = 1;
+const KIND_END: u8 = 2;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    /// Two bytes per pixel
+    Raw,
+    /// See [`crate::rle`]
+    Rle,
+}
+
+/// Size and encoding of the screenshot
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Info {
+    pub width: u16,
+    pub height: u16,
+    pub encoding: Encoding,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Frame<'a> {
+    Header(Info),
+    /// Next chunk of the pixel data
+    Data(&'a [u8]),
+    /// Length and CRC-32 of the pixel data
+    End {
+        len: u32,
+        crc: u32,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The frame is shorter or longer than its payload length says
+    Length,
+    Checksum,
+    Kind(u8),
+    /// The header does not start with [`MAGIC`]
+    Magic,
+    Version(u8),
+    Encoding(u8),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Length => write!(f, "frame length does not match"),
+            Error::Checksum => write!(f, "frame checksum does not match"),
+            Error::Kind(kind) => write!(f, "unknown frame kind {}", kind),
+            Error::Magic => write!(f, "not a screenshot header"),
+            Error::Version(version) => write!(f, "unsupported version {}", version),
+            Error::Encoding(encoding) => write!(f, "unknown encoding {}", encoding),
+        }
+    }
+}
+
+impl Frame<'_> {
+    /// Encodes the frame with sequence number `sequence` into `out`, returns
+    /// its length
+    pub fn encode(&self, sequence: u16, out: &mut [u8; MAX_FRAME_LEN]) -> usize {
+        let mut payload = [0; CHUNK_LEN];
+        let (kind, payload) = match *self {
+            Frame::Header(info) => {
+                payload[..4].copy_from_slice(MAGIC);
+                payload[4] = VERSION;
+                payload[5] = match info.encoding {
+                    Encoding::Raw => 0,
+                    Encoding::Rle => 1,
+                };
+                payload[6..8].copy_from_slice(&info.width.to_le_bytes());
+                payload[8..10].copy_from_slice(&info.height.to_le_bytes());
+                (KIND_HEADER, &payload[..INFO_LEN])
+            }
+            Frame::Data(data) => {
+                let len = data.len().min(CHUNK_LEN);
+                payload[..len].copy_from_slice(&data[..len]);
+                (KIND_DATA, &payload[..len])
+            }
+            Frame::End { len, crc } => {
+                payload[..4].copy_from_slice(&len.to_le_bytes());
+                payload[4..8].copy_from_slice(&crc.to_le_bytes());
+                (KIND_END, &payload[..END_LEN])
+            }
+        };
+
+        out[0] = kind;
+        out[1..3].copy_from_slice(&sequence.to_le_bytes());
+        out[3..5].copy_from_slice(&(payload.len() as u16).to_le_bytes());
+        let end = HEADER_LEN + payload.len();
+        out[HEADER_LEN..end].copy_from_slice(payload);
+        let crc = crc32(&out[..end]);
+        out[end..end + CRC_LEN].copy_from_slice(&crc.to_le_bytes());
+        end + CRC_LEN
+    }
+
+    /// Decodes a frame, returns its sequence number and the frame
+    pub fn decode(bytes: &[u8]) -> Result<(u16, Frame<'_>), Error> {
+        let (header, rest) = bytes
+            .split_first_chunk::<HEADER_LEN>()
+            .ok_or(Error::Length)?;
+        let [kind, s0, s1, l0, l1] = *header;
+        let len = u16::from_le_bytes([l0, l1]) as usize;
+        if rest.len() != len + CRC_LEN {
+            return Err(Error::Length);
+        }
+
+        let (payload, crc) = rest.split_at(len);
+        let end = HEADER_LEN + len;
+        if crc32(&bytes[..end]).to_le_bytes() != crc {
+            return Err(Error::Checksum);
+        }
+
+        let frame = match kind {
+            KIND_HEADER => {
+                let info: &[u8; INFO_LEN] = payload.try_into().map_err(|_| Error::Length)?;
+                if !info.starts_with(MAGIC) {
+                    return Err(Error::Magic);
+                }
+                if info[4] != VERSION {
+                    return Err(Error::Version(info[4]));
+                }
+                let encoding = match info[5] {
+                    0 => Encoding::Raw,
+                    1 => Encoding::Rle,
+                    other => return Err(Error::Encoding(other)),
+                };
+                Frame::Header(Info {
+                    width: u16::from_le_bytes([info[6], info[7]]),
+                    height: u16::from_le_bytes([info[8], info[9]]),
+                    encoding,
+                })
+            }
+            KIND_DATA => Frame::Data(payload),
+            KIND_END => {
+                let end: &[u8; END_LEN] = payload.try_into().map_err(|_| Error::Length)?;
+                Frame::End {
+                    len: u32::from_le_bytes([end[0], end[1], end[2], end[3]]),
+                    crc: u32::from_le_bytes([end[4], end[5], end[6], end[7]]),
+                }
+            }
+            other => return Err(Error::Kind(other)),
+        };
+
+        Ok((u16::from_le_bytes([s0, s1]), frame))
+    }
+}
+
+/// Encodes a screenshot of `pixels`, row by row, and passes every encoded
+/// frame to `out`. Returns the length of the pixel data.
+pub fn write(info: Info, pixels: impl IntoIterator<Item = u16>, mut out: impl FnMut(&[u8])) -> u32 {
+    let mut writer = Writer {
+        frame: [0; MAX_FRAME_LEN],
+        chunk: [0; CHUNK_LEN],
+        chunk_len: 0,
+        sequence: 0,
+        len: 0,
+        crc: Crc32::new(),
+        out: &mut out,
+    };
+    writer.send(Frame::Header(info));
+
+    match info.encoding {
+        Encoding::Raw => {
+            for pixel in pixels {
+                writer.data(&pixel.to_le_bytes());
+            }
+        }
+        Encoding::Rle => {
+            let mut encoder = rle::Encoder::new();
+            for pixel in pixels {
+                encoder.push(pixel, &mut |bytes| writer.data(bytes));
+            }
+            encoder.finish(&mut |bytes| writer.data(bytes));
+        }
+    }
+
+    writer.flush();
+    let (len, crc) = (writer.len, writer.crc.finish());
+    writer.send(Frame::End { len, crc });
+    len
+}
+
+/// Collects the pixel data into data frames
+struct Writer<'a, F: FnMut(&[u8])> {
+    frame: [u8; MAX_FRAME_LEN],
+    chunk: [u8; CHUNK_LEN],
+    chunk_len: usize,
+    sequence: u16,
+    /// Pixel data so far and its CRC
+    len: u32,
+    crc: Crc32,
+    out: &'a mut F,
+}
+
+impl<F: FnMut(&[u8])> Writer<'_, F> {
+    fn data(&mut self, mut bytes: &[u8]) {
+        self.len += bytes.len() as u32;
+        self.crc.update(bytes);
+
+        while !bytes.is_empty() {
+            let fits = bytes.len().min(CHUNK_LEN - self.chunk_len);
+            self.chunk[self.chunk_len..self.chunk_len + fits].copy_from_slice(&bytes[..fits]);
+            self.chunk_len += fits;
+            bytes = &bytes[fits..];
+
+            if self.chunk_len == CHUNK_LEN {
+                self.flush();
+            }
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.chunk_len > 0 {
+            let chunk = self.chunk;
+            self.send(Frame::Data(&chunk[..self.chunk_len]));
+            self.chunk_len = 0;
+        }
+    }
+
+    fn send(&mut self, frame: Frame<'_>) {
+        let len = frame.encode(self.sequence, &mut self.frame);
+        (self.out)(&self.frame[..len]);
+        self.sequence = self.sequence.wrapping_add(1);
+    }
+}
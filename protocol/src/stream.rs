@@ -0,0 +1,399 @@
+//! Remote display stream over UDP
+//!
+//! The device streams the regions of every frame it sends to the panel to one
+//! subscribed client and takes touches and console commands from it. Every
+//! datagram is one packet, little endian:
+//!
+//! - kind (`u8`)
+//! - sequence number (`u16`), counted per sender; a gap means lost packets
+//! - payload
+//!
+//! Device to client:
+//!
+//! - `Rows` (0): frame number, x, y, width and row count (`u16`), then the
+//!   rows run-length encoded with [`crate::rle`]
+//! - `Present` (1): frame number, width and height; all rows of the frame
+//!   were sent
+//!
+//! Client to device:
+//!
+//! - `Hello` (2): subscribes, repeated at least every [`TIMEOUT_MS`]; a new
+//!   client is sent the whole frame
+//! - `Refresh` (3): asks for the whole frame, e.g. after lost packets
+//! - `Touch` (4): phase (`u8`, down/move/up), x and y (`u16`) in frame
+//!   coordinates
+//! - `Command` (5): a console command line, UTF-8
+//!
+//! Pixels only change where they were drawn, so a lost `Rows` packet leaves
+//! stale pixels until the client asks for a refresh.
+
+use core::fmt;
+
+use crate::rle;
+
+/// UDP port the device listens on
+pub const PORT: u16 = 7878;
+/// A client that sent nothing for this long is dropped
+pub const TIMEOUT_MS: u32 = 3000;
+/// Longest packet, fits an Ethernet MTU with IP and UDP headers
+pub const MAX_PACKET_LEN: usize = 1400;
+/// Kind and sequence number
+const HEADER_LEN: usize = 3;
+const ROWS_HEADER_LEN: usize = 10;
+/// Most run-length encoded row data in a `Rows` packet
+const MAX_DATA_LEN: usize = MAX_PACKET_LEN - HEADER_LEN - ROWS_HEADER_LEN;
+/// Widest row in a `Rows` packet, wider regions are split into columns
+pub const MAX_ROW_PIXELS: usize = 640;
+/// Longest run-length encoded row, all literals
+const MAX_ROW_LEN: usize = 2 * MAX_ROW_PIXELS + MAX_ROW_PIXELS.div_ceil(128);
+const _: () = assert!(MAX_ROW_LEN <= MAX_DATA_LEN);
+
+const KIND_ROWS: u8 = 0;
+const KIND_PRESENT: u8 = 1;
+const KIND_HELLO: u8 = 2;
+const KIND_REFRESH: u8 = 3;
+const KIND_TOUCH: u8 = 4;
+const KIND_COMMAND: u8 = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    Down,
+    Move,
+    Up,
+}
+
+/// Region of a frame, in pixels
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Rect {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Packet<'a> {
+    /// `rows` rows of `width` pixels starting at `x`, `y`, run-length encoded
+    Rows {
+        frame: u16,
+        x: u16,
+        y: u16,
+        width: u16,
+        rows: u16,
+        data: &'a [u8],
+    },
+    Present {
+        frame: u16,
+        width: u16,
+        height: u16,
+    },
+    Hello,
+    Refresh,
+    Touch {
+        phase: Phase,
+        x: u16,
+        y: u16,
+    },
+    Command(&'a str),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The packet is too short or too long for its kind
+    Length,
+    Kind(u8),
+    Phase(u8),
+    /// The command is not UTF-8
+    Text,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Length => write!(f, "packet length does not match"),
+            Error::Kind(kind) => write!(f, "unknown packet kind {}", kind),
+            Error::Phase(phase) => write!(f, "unknown touch phase {}", phase),
+            Error::Text => write!(f, "command is not UTF-8"),
+        }
+    }
+}
+
+impl Packet<'_> {
+    /// Encodes the packet with sequence number `sequence` into `out`, returns
+    /// its length. Row data and commands that do not fit are cut off.
+    pub fn encode(&self, sequence: u16, out: &mut [u8; MAX_PACKET_LEN]) -> usize {
+        let mut fields = [0u8; ROWS_HEADER_LEN];
+        let (kind, fields, tail): (u8, &[u8], &[u8]) = match *self {
+            Packet::Rows {
+                frame,
+                x,
+                y,
+                width,
+                rows,
+                data,
+            } => {
+                for (field, value) in fields.chunks_mut(2).zip([frame, x, y, width, rows]) {
+                    field.copy_from_slice(&value.to_le_bytes());
+                }
+                (KIND_ROWS, &fields[..], data)
+            }
+            Packet::Present {
+                frame,
+                width,
+                height,
+            } => {
+                for (field, value) in fields.chunks_mut(2).zip([frame, width, height]) {
+                    field.copy_from_slice(&value.to_le_bytes());
+                }
+                (KIND_PRESENT, &fields[..6], &[])
+            }
+            Packet::Hello => (KIND_HELLO, &[], &[]),
+            Packet::Refresh => (KIND_REFRESH, &[], &[]),
+            Packet::Touch { phase, x, y } => {
+                fields[0] = match phase {
+                    Phase::Down => 0,
+                    Phase::Move => 1,
+                    Phase::Up => 2,
+                };
+                fields[1..3].copy_from_slice(&x.to_le_bytes());
+                fields[3..5].copy_from_slice(&y.to_le_bytes());
+                (KIND_TOUCH, &fields[..5], &[])
+            }
+            Packet::Command(line) => (KIND_COMMAND, &[], line.as_bytes()),
+        };
+
+        out[0] = kind;
+        out[1..3].copy_from_slice(&sequence.to_le_bytes());
+        let end = HEADER_LEN + fields.len();
+        out[HEADER_LEN..end].copy_from_slice(fields);
+        let tail = &tail[..tail.len().min(MAX_PACKET_LEN - end)];
+        out[end..end + tail.len()].copy_from_slice(tail);
+        end + tail.len()
+    }
+
+    /// Decodes a packet, returns its sequence number and the packet
+    pub fn decode(bytes: &[u8]) -> Result<(u16, Packet<'_>), Error> {
+        let (header, payload) = bytes
+            .split_first_chunk::<HEADER_LEN>()
+            .ok_or(Error::Length)?;
+        let [kind, s0, s1] = *header;
+        let field = |index: usize| u16::from_le_bytes([payload[2 * index], payload[2 * index + 1]]);
+
+        let packet = match kind {
+            KIND_ROWS => {
+                if payload.len() < ROWS_HEADER_LEN {
+                    return Err(Error::Length);
+                }
+                Packet::Rows {
+                    frame: field(0),
+                    x: field(1),
+                    y: field(2),
+                    width: field(3),
+                    rows: field(4),
+                    data: &payload[ROWS_HEADER_LEN..],
+                }
+            }
+            KIND_PRESENT => {
+                if payload.len() != 6 {
+                    return Err(Error::Length);
+                }
+                Packet::Present {
+                    frame: field(0),
+                    width: field(1),
+                    height: field(2),
+                }
+            }
+            KIND_HELLO | KIND_REFRESH => {
+                if !payload.is_empty() {
+                    return Err(Error::Length);
+                }
+                if kind == KIND_HELLO {
+                    Packet::Hello
+                } else {
+                    Packet::Refresh
+                }
+            }
+            KIND_TOUCH => {
+                let &[phase, x0, x1, y0, y1] = payload else {
+                    return Err(Error::Length);
+                };
+                let phase = match phase {
+                    0 => Phase::Down,
+                    1 => Phase::Move,
+                    2 => Phase::Up,
+                    other => return Err(Error::Phase(other)),
+                };
+                Packet::Touch {
+                    phase,
+                    x: u16::from_le_bytes([x0, x1]),
+                    y: u16::from_le_bytes([y0, y1]),
+                }
+            }
+            KIND_COMMAND => {
+                Packet::Command(core::str::from_utf8(payload).map_err(|_| Error::Text)?)
+            }
+            other => return Err(Error::Kind(other)),
+        };
+
+        Ok((u16::from_le_bytes([s0, s1]), packet))
+    }
+}
+
+/// Packs the regions of a frame into `Rows` packets, followed by `Present`
+///
+/// Packets are pulled one at a time with [`Encoder::next`], so they can be
+/// sent asynchronously between calls.
+pub struct Encoder {
+    packet: [u8; MAX_PACKET_LEN],
+    /// Rows of the next `Rows` packet
+    data: [u8; MAX_DATA_LEN],
+    data_len: usize,
+    rows: u16,
+    /// x, y and width of the rows in `data`
+    start: (u16, u16, u16),
+    /// Encoded row that did not fit into the last packet
+    row: [u8; MAX_ROW_LEN],
+    row_len: usize,
+    sequence: u16,
+    frame: u16,
+    size: (u16, u16),
+    /// Region, column offset and row of the next row to encode
+    rect: usize,
+    column: u16,
+    y: u16,
+    presented: bool,
+}
+
+impl Encoder {
+    pub const fn new() -> Self {
+        Self {
+            packet: [0; MAX_PACKET_LEN],
+            data: [0; MAX_DATA_LEN],
+            data_len: 0,
+            rows: 0,
+            start: (0, 0, 0),
+            row: [0; MAX_ROW_LEN],
+            row_len: 0,
+            sequence: 0,
+            frame: 0,
+            size: (0, 0),
+            rect: 0,
+            column: 0,
+            y: 0,
+            presented: true,
+        }
+    }
+
+    /// Starts the next frame of `width` x `height` pixels
+    pub fn begin(&mut self, width: u16, height: u16) {
+        self.frame = self.frame.wrapping_add(1);
+        self.size = (width, height);
+        self.data_len = 0;
+        self.rows = 0;
+        self.row_len = 0;
+        self.rect = 0;
+        self.column = 0;
+        self.y = 0;
+        self.presented = false;
+    }
+
+    /// Next packet of the frame, `None` after `Present`
+    ///
+    /// `rects` are the regions to send and have to be the same on every call
+    /// for a frame, `pixel(x, y)` is the RGB565 pixel at frame coordinates.
+    pub fn next(&mut self, rects: &[Rect], pixel: impl Fn(u16, u16) -> u16) -> Option<&[u8]> {
+        while let Some(&rect) = rects.get(self.rect) {
+            if rect.width == 0 || rect.height == 0 {
+                self.rect += 1;
+                continue;
+            }
+
+            let x = rect.x + self.column;
+            let width = (rect.width - self.column).min(MAX_ROW_PIXELS as u16);
+            if self.y == rect.height {
+                // Column done, a packet only holds rows of one column
+                self.column += width;
+                self.y = 0;
+                if self.column == rect.width {
+                    self.rect += 1;
+                    self.column = 0;
+                }
+                if self.rows > 0 {
+                    return Some(self.flush());
+                }
+                continue;
+            }
+
+            if self.row_len == 0 {
+                self.encode_row(x, rect.y + self.y, width, &pixel);
+            }
+            if self.data_len + self.row_len > MAX_DATA_LEN {
+                return Some(self.flush());
+            }
+            if self.rows == 0 {
+                self.start = (x, rect.y + self.y, width);
+            }
+            self.data[self.data_len..self.data_len + self.row_len]
+                .copy_from_slice(&self.row[..self.row_len]);
+            self.data_len += self.row_len;
+            self.row_len = 0;
+            self.rows += 1;
+            self.y += 1;
+        }
+
+        if self.presented {
+            return None;
+        }
+        self.presented = true;
+        let (width, height) = self.size;
+        let packet = Packet::Present {
+            frame: self.frame,
+            width,
+            height,
+        };
+        Some(self.send(packet))
+    }
+
+    fn encode_row(&mut self, x: u16, y: u16, width: u16, pixel: &impl Fn(u16, u16) -> u16) {
+        let (row, row_len) = (&mut self.row, &mut self.row_len);
+        let mut out = |bytes: &[u8]| {
+            row[*row_len..*row_len + bytes.len()].copy_from_slice(bytes);
+            *row_len += bytes.len();
+        };
+        let mut encoder = rle::Encoder::new();
+        for column in x..x + width {
+            encoder.push(pixel(column, y), &mut out);
+        }
+        encoder.finish(&mut out);
+    }
+
+    /// Encodes the collected rows into a `Rows` packet
+    fn flush(&mut self) -> &[u8] {
+        let (x, y, width) = self.start;
+        let packet = Packet::Rows {
+            frame: self.frame,
+            x,
+            y,
+            width,
+            rows: self.rows,
+            data: &self.data[..self.data_len],
+        };
+        let len = packet.encode(self.sequence, &mut self.packet);
+        self.sequence = self.sequence.wrapping_add(1);
+        self.data_len = 0;
+        self.rows = 0;
+        &self.packet[..len]
+    }
+
+    fn send(&mut self, packet: Packet<'_>) -> &[u8] {
+        let len = packet.encode(self.sequence, &mut self.packet);
+        self.sequence = self.sequence.wrapping_add(1);
+        &self.packet[..len]
+    }
+}
+
+impl Default for Encoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
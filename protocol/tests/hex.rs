@@ -0,0 +1,18 @@
+use pixels_protocol::hex::Hex;
+
+#[test]
+fn bytes_are_written_as_lowercase_hex() {
+    assert_eq!(Hex(&[]).to_string(), "");
+    assert_eq!(Hex(&[0x00, 0x0F, 0xA5, 0xFF]).to_string(), "000fa5ff");
+
+    // Longer than the digits formatted at once
+    let bytes: Vec<u8> = (0..=255).collect();
+    let hex = Hex(&bytes).to_string();
+    assert_eq!(hex.len(), 512);
+    for (i, pair) in hex.as_bytes().chunks(2).enumerate() {
+        assert_eq!(
+            u8::from_str_radix(std::str::from_utf8(pair).unwrap(), 16),
+            Ok(i as u8)
+        );
+    }
+}
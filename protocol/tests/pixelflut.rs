@@ -0,0 +1,287 @@
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+
+use pixels_protocol::pixelflut::{
+    self, Canvas, Command, Error, Progress, Session, MAX_LINE_LEN, MAX_REPLY_LEN,
+};
+
+const WIDTH: u16 = 64;
+const HEIGHT: u16 = 32;
+
+struct TestCanvas {
+    pixels: Vec<u32>,
+}
+
+impl TestCanvas {
+    fn new() -> Self {
+        Self {
+            pixels: vec![0; WIDTH as usize * HEIGHT as usize],
+        }
+    }
+}
+
+impl Canvas for TestCanvas {
+    fn size(&self) -> (u16, u16) {
+        (WIDTH, HEIGHT)
+    }
+
+    fn pixel(&self, x: u16, y: u16) -> u32 {
+        self.pixels[y as usize * WIDTH as usize + x as usize]
+    }
+
+    fn set_pixel(&mut self, x: u16, y: u16, color: u32) {
+        self.pixels[y as usize * WIDTH as usize + x as usize] = color;
+    }
+}
+
+/// Runs all of `input`, returns the replies
+fn run(session: &mut Session, canvas: &mut TestCanvas, input: &[u8]) -> String {
+    let mut reply = [0; 4 * MAX_REPLY_LEN];
+    let progress = session.feed(input, canvas, u32::MAX, &mut reply);
+    assert_eq!(progress.consumed, input.len());
+    String::from_utf8(reply[..progress.replied].to_vec()).unwrap()
+}
+
+#[test]
+fn parses_commands() {
+    let commands = [
+        (
+            &b"PX 1 2 ff8000"[..],
+            Command::Set {
+                x: 1,
+                y: 2,
+                color: 0xFF8000,
+                alpha: 0xFF,
+            },
+        ),
+        (
+            b"PX 1 2 FF800080",
+            Command::Set {
+                x: 1,
+                y: 2,
+                color: 0xFF8000,
+                alpha: 0x80,
+            },
+        ),
+        (
+            b"PX  3\t4 7f ",
+            Command::Set {
+                x: 3,
+                y: 4,
+                color: 0x7F7F7F,
+                alpha: 0xFF,
+            },
+        ),
+        (b"PX 65535 0", Command::Get { x: 65535, y: 0 }),
+        (b"SIZE", Command::Size),
+        (b"HELP", Command::Help),
+    ];
+    for (line, command) in commands {
+        assert_eq!(pixelflut::parse(line), Ok(command));
+    }
+}
+
+#[test]
+fn rejects_malformed_lines() {
+    let lines = [
+        (&b""[..], Error::Unknown),
+        (b"px 1 2 ffffff", Error::Unknown),
+        (b"SIZE 1", Error::Unknown),
+        (b"PX 1 2 ffffff 1", Error::Unknown),
+        (b"PX", Error::Coordinate),
+        (b"PX 1", Error::Coordinate),
+        (b"PX -1 2", Error::Coordinate),
+        (b"PX 1 65536", Error::Coordinate),
+        (b"PX 1 2 fff", Error::Color),
+        (b"PX 1 2 +fffff", Error::Color),
+        (b"PX 1 2 gg0000", Error::Color),
+        (b"PX 1 2 ffffffffff", Error::Color),
+    ];
+    for (line, error) in lines {
+        assert_eq!(
+            pixelflut::parse(line),
+            Err(error),
+            "{}",
+            String::from_utf8_lossy(line)
+        );
+    }
+}
+
+#[test]
+fn draws_and_reads_pixels() {
+    let mut session = Session::new();
+    let mut canvas = TestCanvas::new();
+    let replies = run(
+        &mut session,
+        &mut canvas,
+        b"PX 1 2 ff8000\nPX 1 2\r\nPX 63 31 40\nPX 63 31\nSIZE\n",
+    );
+    assert_eq!(replies, "PX 1 2 ff8000\nPX 63 31 404040\nSIZE 64 32\n");
+    assert_eq!(canvas.pixel(1, 2), 0xFF8000);
+}
+
+#[test]
+fn blends_with_alpha() {
+    let mut session = Session::new();
+    let mut canvas = TestCanvas::new();
+    canvas.set_pixel(0, 0, 0x0000FF);
+    run(
+        &mut session,
+        &mut canvas,
+        b"PX 0 0 ff000080\nPX 1 0 ffffff00\n",
+    );
+    assert_eq!(canvas.pixel(0, 0), 0x80007F);
+    assert_eq!(canvas.pixel(1, 0), 0);
+}
+
+#[test]
+fn ignores_pixels_outside_the_canvas() {
+    let mut session = Session::new();
+    let mut canvas = TestCanvas::new();
+    let replies = run(&mut session, &mut canvas, b"PX 64 0 ffffff\nPX 0 32\n");
+    assert_eq!(replies, "ERROR outside the canvas\n");
+    assert!(canvas.pixels.iter().all(|&pixel| pixel == 0));
+}
+
+#[test]
+fn reports_errors_and_skips_empty_lines() {
+    let mut session = Session::new();
+    let mut canvas = TestCanvas::new();
+    let long = format!("PX 1 1 {}\n", "f".repeat(MAX_LINE_LEN));
+    let input = [
+        &b"\n \r\nDRAW\nPX 1 2 fff\n"[..],
+        long.as_bytes(),
+        b"SIZE\n",
+    ]
+    .concat();
+    assert_eq!(
+        run(&mut session, &mut canvas, &input),
+        "ERROR unknown command, try HELP\n\
+         ERROR colors are ww, rrggbb or rrggbbaa in hex\n\
+         ERROR line too long\n\
+         SIZE 64 32\n"
+    );
+}
+
+#[test]
+fn help_fits_a_reply() {
+    let mut session = Session::new();
+    let mut canvas = TestCanvas::new();
+    let help = run(&mut session, &mut canvas, b"HELP\n");
+    assert!(help.starts_with("HELP"));
+    assert!(help.len() <= MAX_REPLY_LEN);
+    assert!(help.contains("PX x y rrggbb"));
+}
+
+#[test]
+fn lines_continue_across_feeds() {
+    let mut session = Session::new();
+    let mut canvas = TestCanvas::new();
+    assert_eq!(run(&mut session, &mut canvas, b"PX 5 "), "");
+    assert_eq!(run(&mut session, &mut canvas, b"6 00ff"), "");
+    assert_eq!(
+        run(&mut session, &mut canvas, b"00\nPX 5 6\n"),
+        "PX 5 6 00ff00\n"
+    );
+}
+
+#[test]
+fn stops_at_the_budget() {
+    let mut session = Session::new();
+    let mut canvas = TestCanvas::new();
+    let input = b"PX 0 0 01\nPX 1 0 02\nPX 2 0 03\n";
+    let mut reply = [0; MAX_REPLY_LEN];
+
+    let progress = session.feed(input, &mut canvas, 2, &mut reply);
+    assert_eq!(
+        progress,
+        Progress {
+            consumed: 20,
+            replied: 0,
+            commands: 2
+        }
+    );
+    assert_eq!(canvas.pixel(2, 0), 0);
+
+    let progress = session.feed(&input[20..], &mut canvas, 2, &mut reply);
+    assert_eq!(progress.consumed, 10);
+    assert_eq!(progress.commands, 1);
+    assert_eq!(canvas.pixel(2, 0), 0x030303);
+}
+
+#[test]
+fn stops_when_replies_do_not_fit() {
+    let mut session = Session::new();
+    let mut canvas = TestCanvas::new();
+    let mut reply = [0; MAX_REPLY_LEN + 5];
+
+    let progress = session.feed(b"SIZE\nSIZE\n", &mut canvas, 10, &mut reply);
+    assert_eq!(progress.commands, 1);
+    assert_eq!(progress.consumed, 5);
+    assert_eq!(&reply[..progress.replied], b"SIZE 64 32\n");
+
+    // Nothing is run without room for a reply
+    let progress = session.feed(b"SIZE\n", &mut canvas, 10, &mut reply[..10]);
+    assert_eq!(progress, Progress::default());
+}
+
+/// Serves one connection like the firmware does, returns the canvas once the
+/// client hangs up
+fn serve(mut stream: TcpStream) -> TestCanvas {
+    let mut canvas = TestCanvas::new();
+    let mut session = Session::new();
+    let mut input = [0; 256];
+    let mut reply = [0; 2 * MAX_REPLY_LEN];
+    loop {
+        let len = stream.read(&mut input).unwrap();
+        if len == 0 {
+            return canvas;
+        }
+
+        // A small budget, so the input takes several rounds
+        let mut pending = &input[..len];
+        while !pending.is_empty() {
+            let progress = session.feed(pending, &mut canvas, 3, &mut reply);
+            stream.write_all(&reply[..progress.replied]).unwrap();
+            pending = &pending[progress.consumed..];
+        }
+    }
+}
+
+#[test]
+fn serves_a_client_over_tcp() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let server = thread::spawn(move || serve(listener.accept().unwrap().0));
+
+    let mut stream = TcpStream::connect(address).unwrap();
+    let mut replies = BufReader::new(stream.try_clone().unwrap());
+    let mut line = String::new();
+
+    stream.write_all(b"SIZE\n").unwrap();
+    replies.read_line(&mut line).unwrap();
+    assert_eq!(line, "SIZE 64 32\n");
+
+    let mut commands = String::new();
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            commands += &format!("PX {x} {y} {:02x}{:02x}00\n", x * 4, y * 8);
+        }
+    }
+    stream.write_all(commands.as_bytes()).unwrap();
+
+    for (x, y) in [(0, 0), (63, 0), (17, 23), (63, 31)] {
+        stream
+            .write_all(format!("PX {x} {y}\n").as_bytes())
+            .unwrap();
+        line.clear();
+        replies.read_line(&mut line).unwrap();
+        assert_eq!(line, format!("PX {x} {y} {:02x}{:02x}00\n", x * 4, y * 8));
+    }
+
+    drop(replies);
+    drop(stream);
+    let canvas = server.join().unwrap();
+    assert_eq!(canvas.pixel(40, 10), 0xA05000);
+}
@@ -0,0 +1,229 @@
+use pixels_protocol::crc::{crc32, Crc32};
+use pixels_protocol::rle;
+use pixels_protocol::screenshot::{self, Encoding, Error, Frame, Info, CHUNK_LEN, MAX_FRAME_LEN};
+
+const WIDTH: u16 = 536;
+const HEIGHT: u16 = 240;
+
+fn rle_encode(pixels: &[u16]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut encoder = rle::Encoder::new();
+    for &pixel in pixels {
+        encoder.push(pixel, &mut |bytes| out.extend_from_slice(bytes));
+    }
+    encoder.finish(&mut |bytes| out.extend_from_slice(bytes));
+    out
+}
+
+fn rle_decode(data: &[u8]) -> Result<Vec<u16>, rle::Truncated> {
+    let mut pixels = Vec::new();
+    rle::decode(data, |pixel| pixels.push(pixel))?;
+    Ok(pixels)
+}
+
+/// A frame with a few shapes on black, like the cube scene
+fn frame_pixels() -> Vec<u16> {
+    let mut pixels = vec![0; WIDTH as usize * HEIGHT as usize];
+    for y in 40..200 {
+        for x in 200..340 {
+            pixels[y * WIDTH as usize + x] = 0x07E0;
+        }
+    }
+    for (i, pixel) in pixels.iter_mut().enumerate().step_by(97) {
+        *pixel = i as u16;
+    }
+    pixels
+}
+
+/// Encoded frames of a screenshot
+fn screenshot_frames(info: Info, pixels: &[u16]) -> (Vec<Vec<u8>>, u32) {
+    let mut frames = Vec::new();
+    let len = screenshot::write(info, pixels.iter().copied(), |frame| {
+        frames.push(frame.to_vec())
+    });
+    (frames, len)
+}
+
+/// Pixels of a received screenshot, checked like the host tool does
+fn receive(frames: &[Vec<u8>]) -> (Info, Vec<u16>) {
+    let mut info = None;
+    let mut data = Vec::new();
+    for (i, bytes) in frames.iter().enumerate() {
+        assert!(bytes.len() <= MAX_FRAME_LEN);
+        let (sequence, frame) = Frame::decode(bytes).unwrap();
+        assert_eq!(sequence as usize, i);
+
+        match frame {
+            Frame::Header(header) => info = Some(header),
+            Frame::Data(chunk) => {
+                assert!(chunk.len() <= CHUNK_LEN);
+                data.extend_from_slice(chunk);
+            }
+            Frame::End { len, crc } => {
+                assert_eq!(i, frames.len() - 1);
+                assert_eq!(len as usize, data.len());
+                assert_eq!(crc, crc32(&data));
+            }
+        }
+    }
+
+    let info = info.unwrap();
+    let pixels = match info.encoding {
+        Encoding::Raw => data
+            .chunks_exact(2)
+            .map(|pixel| u16::from_le_bytes([pixel[0], pixel[1]]))
+            .collect(),
+        Encoding::Rle => rle_decode(&data).unwrap(),
+    };
+    (info, pixels)
+}
+
+#[test]
+fn crc_matches_the_check_value() {
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    assert_eq!(crc32(b""), 0);
+
+    let mut crc = Crc32::new();
+    crc.update(b"1234");
+    crc.update(b"56789");
+    assert_eq!(crc.finish(), 0xCBF4_3926);
+}
+
+#[test]
+fn rle_round_trips() {
+    let mut pixels = Vec::new();
+    // Runs around the longest repeat packet
+    for (value, len) in [(0, 1), (1, 2), (2, 128), (3, 129), (4, 130), (5, 300)] {
+        pixels.extend(std::iter::repeat_n(value, len));
+    }
+    // Literals around the longest literal packet
+    pixels.extend(0..300);
+    pixels.extend([7, 8, 8, 9]);
+
+    assert_eq!(rle_decode(&rle_encode(&pixels)).unwrap(), pixels);
+    assert_eq!(rle_decode(&rle_encode(&[])).unwrap(), []);
+}
+
+#[test]
+fn rle_packets() {
+    // One literal, then a run of three
+    assert_eq!(rle_encode(&[1, 2, 2, 2]), [0, 1, 0, 129, 2, 0]);
+    // The longest run fits one packet
+    assert_eq!(rle_encode(&[0xABCD; 129]), [255, 0xCD, 0xAB]);
+    assert_eq!(rle_encode(&[0xABCD; 130]).len(), 6);
+}
+
+#[test]
+fn rle_shrinks_black_frames() {
+    let pixels = vec![0; WIDTH as usize * HEIGHT as usize];
+    let encoded = rle_encode(&pixels);
+    assert!(encoded.len() * 50 < 2 * pixels.len());
+}
+
+#[test]
+fn rle_reports_truncated_data() {
+    // Two literals, then a run
+    let encoded = rle_encode(&[1, 2, 3, 3, 3]);
+    assert_eq!(encoded.len(), 8);
+    let complete: Vec<_> = (1..encoded.len())
+        .filter(|&len| rle_decode(&encoded[..len]).is_ok())
+        .collect();
+    assert_eq!(complete, [5]);
+    assert_eq!(rle_decode(&[200]), Err(rle::Truncated));
+}
+
+#[test]
+fn screenshot_round_trips() {
+    let pixels = frame_pixels();
+    for encoding in [Encoding::Raw, Encoding::Rle] {
+        let info = Info {
+            width: WIDTH,
+            height: HEIGHT,
+            encoding,
+        };
+        let (frames, len) = screenshot_frames(info, &pixels);
+        assert_eq!(receive(&frames), (info, pixels.clone()));
+
+        if encoding == Encoding::Raw {
+            assert_eq!(len as usize, 2 * pixels.len());
+            assert_eq!(frames.len(), 2 + (len as usize).div_ceil(CHUNK_LEN));
+        } else {
+            assert!((len as usize) < pixels.len() / 2);
+        }
+    }
+}
+
+#[test]
+fn empty_screenshot_has_header_and_end() {
+    let info = Info {
+        width: 0,
+        height: 0,
+        encoding: Encoding::Rle,
+    };
+    let (frames, len) = screenshot_frames(info, &[]);
+    assert_eq!(len, 0);
+    assert_eq!(frames.len(), 2);
+    assert_eq!(receive(&frames), (info, vec![]));
+}
+
+#[test]
+fn damaged_frames_are_rejected() {
+    let mut out = [0; MAX_FRAME_LEN];
+    let len = Frame::Data(&[1, 2, 3]).encode(7, &mut out);
+    let frame = &out[..len];
+    assert_eq!(Frame::decode(frame), Ok((7, Frame::Data(&[1, 2, 3]))));
+
+    for i in 0..len {
+        let mut damaged = frame.to_vec();
+        damaged[i] ^= 0x01;
+        assert!(Frame::decode(&damaged).is_err(), "byte {i}");
+    }
+    assert_eq!(Frame::decode(&frame[..len - 1]), Err(Error::Length));
+    assert_eq!(Frame::decode(&[frame, &[0]].concat()), Err(Error::Length));
+    assert_eq!(Frame::decode(&frame[..3]), Err(Error::Length));
+}
+
+/// `payload` as a frame of `kind` with a valid checksum
+fn raw_frame(kind: u8, payload: &[u8]) -> Vec<u8> {
+    let mut frame = vec![kind, 0, 0];
+    frame.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+    frame.extend_from_slice(payload);
+    let crc = crc32(&frame);
+    frame.extend_from_slice(&crc.to_le_bytes());
+    frame
+}
+
+#[test]
+fn unknown_frames_are_rejected() {
+    let header = *b"PXSC\x01\x01\x18\x02\xF0\x00";
+    assert_eq!(
+        Frame::decode(&raw_frame(0, &header)),
+        Ok((
+            0,
+            Frame::Header(Info {
+                width: WIDTH,
+                height: HEIGHT,
+                encoding: Encoding::Rle
+            })
+        ))
+    );
+
+    assert_eq!(Frame::decode(&raw_frame(3, &[])), Err(Error::Kind(3)));
+    assert_eq!(
+        Frame::decode(&raw_frame(0, b"PXSQ\x01\x01\x18\x02\xF0\x00")),
+        Err(Error::Magic)
+    );
+    assert_eq!(
+        Frame::decode(&raw_frame(0, b"PXSC\x02\x01\x18\x02\xF0\x00")),
+        Err(Error::Version(2))
+    );
+    assert_eq!(
+        Frame::decode(&raw_frame(0, b"PXSC\x01\x05\x18\x02\xF0\x00")),
+        Err(Error::Encoding(5))
+    );
+    assert_eq!(
+        Frame::decode(&raw_frame(0, &header[..9])),
+        Err(Error::Length)
+    );
+    assert_eq!(Frame::decode(&raw_frame(2, &[0; 7])), Err(Error::Length));
+}
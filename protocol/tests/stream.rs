@@ -0,0 +1,269 @@
+use std::net::UdpSocket;
+use std::time::Duration;
+
+use pixels_protocol::rle;
+use pixels_protocol::stream::{
+    Encoder, Error, Packet, Phase, Rect, MAX_PACKET_LEN, MAX_ROW_PIXELS,
+};
+
+const WIDTH: u16 = 800;
+const HEIGHT: u16 = 240;
+
+/// A different value for every pixel, with runs of equal pixels
+fn pattern(x: u16, y: u16) -> u16 {
+    if (x / 16 + y / 16).is_multiple_of(2) {
+        0
+    } else {
+        x ^ (y << 6)
+    }
+}
+
+/// What a client shows, built from the received packets
+struct Screen {
+    pixels: Vec<u16>,
+    /// Frame number and size of the last `Present`
+    presented: Option<(u16, u16, u16)>,
+    /// Sequence number of the next packet
+    next: u16,
+}
+
+impl Screen {
+    fn new() -> Self {
+        Self {
+            pixels: vec![0xFFFF; WIDTH as usize * HEIGHT as usize],
+            presented: None,
+            next: 0,
+        }
+    }
+
+    fn receive(&mut self, bytes: &[u8]) {
+        assert!(bytes.len() <= MAX_PACKET_LEN);
+        let (sequence, packet) = Packet::decode(bytes).unwrap();
+        assert_eq!(sequence, self.next, "packet lost");
+        self.next = sequence.wrapping_add(1);
+
+        match packet {
+            Packet::Rows {
+                x,
+                y,
+                width,
+                rows,
+                data,
+                ..
+            } => {
+                assert!(width as usize <= MAX_ROW_PIXELS);
+                let mut decoded = Vec::new();
+                rle::decode(data, |pixel| decoded.push(pixel)).unwrap();
+                assert_eq!(decoded.len(), width as usize * rows as usize);
+                for (i, pixel) in decoded.into_iter().enumerate() {
+                    let column = x as usize + i % width as usize;
+                    let row = y as usize + i / width as usize;
+                    self.pixels[row * WIDTH as usize + column] = pixel;
+                }
+            }
+            Packet::Present {
+                frame,
+                width,
+                height,
+            } => self.presented = Some((frame, width, height)),
+            other => panic!("unexpected {other:?}"),
+        }
+    }
+
+    fn pixel(&self, x: u16, y: u16) -> u16 {
+        self.pixels[y as usize * WIDTH as usize + x as usize]
+    }
+}
+
+/// Packets of one frame of `rects`
+fn frame_packets(encoder: &mut Encoder, rects: &[Rect]) -> Vec<Vec<u8>> {
+    encoder.begin(WIDTH, HEIGHT);
+    let mut packets = Vec::new();
+    while let Some(packet) = encoder.next(rects, pattern) {
+        packets.push(packet.to_vec());
+    }
+    packets
+}
+
+const FULL: Rect = Rect {
+    x: 0,
+    y: 0,
+    width: WIDTH,
+    height: HEIGHT,
+};
+
+#[test]
+fn packets_round_trip() {
+    let packets = [
+        Packet::Rows {
+            frame: 3,
+            x: 10,
+            y: 20,
+            width: 30,
+            rows: 2,
+            data: &[1, 2, 3],
+        },
+        Packet::Present {
+            frame: 3,
+            width: WIDTH,
+            height: HEIGHT,
+        },
+        Packet::Hello,
+        Packet::Refresh,
+        Packet::Touch {
+            phase: Phase::Move,
+            x: 535,
+            y: 239,
+        },
+        Packet::Command("set fov 90"),
+    ];
+
+    let mut out = [0; MAX_PACKET_LEN];
+    for (sequence, packet) in packets.into_iter().enumerate() {
+        let len = packet.encode((sequence as u16).wrapping_add(0xFFFE), &mut out);
+        assert_eq!(
+            Packet::decode(&out[..len]),
+            Ok(((sequence as u16).wrapping_add(0xFFFE), packet))
+        );
+    }
+}
+
+#[test]
+fn long_commands_are_cut() {
+    let line = "x".repeat(2 * MAX_PACKET_LEN);
+    let mut out = [0; MAX_PACKET_LEN];
+    let len = Packet::Command(&line).encode(0, &mut out);
+    assert_eq!(len, MAX_PACKET_LEN);
+    assert_eq!(
+        Packet::decode(&out[..len]),
+        Ok((0, Packet::Command(&line[..MAX_PACKET_LEN - 3])))
+    );
+}
+
+#[test]
+fn malformed_packets_are_rejected() {
+    assert_eq!(Packet::decode(&[]), Err(Error::Length));
+    assert_eq!(Packet::decode(&[0, 0]), Err(Error::Length));
+    assert_eq!(Packet::decode(&[0, 0, 0, 1, 2]), Err(Error::Length));
+    assert_eq!(
+        Packet::decode(&[1, 0, 0, 1, 2, 3, 4, 5]),
+        Err(Error::Length)
+    );
+    assert_eq!(Packet::decode(&[2, 0, 0, 1]), Err(Error::Length));
+    assert_eq!(Packet::decode(&[4, 0, 0, 0, 1, 2, 3]), Err(Error::Length));
+    assert_eq!(
+        Packet::decode(&[4, 0, 0, 3, 1, 2, 3, 4]),
+        Err(Error::Phase(3))
+    );
+    assert_eq!(Packet::decode(&[5, 0, 0, 0xFF]), Err(Error::Text));
+    assert_eq!(Packet::decode(&[9, 0, 0]), Err(Error::Kind(9)));
+}
+
+#[test]
+fn whole_frame_reaches_the_client() {
+    let mut encoder = Encoder::new();
+    let mut screen = Screen::new();
+    for packet in frame_packets(&mut encoder, &[FULL]) {
+        screen.receive(&packet);
+    }
+
+    assert_eq!(screen.presented, Some((1, WIDTH, HEIGHT)));
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            assert_eq!(screen.pixel(x, y), pattern(x, y), "{x}, {y}");
+        }
+    }
+}
+
+#[test]
+fn only_the_regions_are_sent() {
+    let mut encoder = Encoder::new();
+    let mut screen = Screen::new();
+    let rects = [
+        Rect {
+            x: 100,
+            y: 50,
+            width: 32,
+            height: 16,
+        },
+        Rect {
+            x: 0,
+            y: 0,
+            width: 0,
+            height: 10,
+        },
+        Rect {
+            x: 600,
+            y: 200,
+            width: 200,
+            height: 40,
+        },
+    ];
+    for packet in frame_packets(&mut encoder, &rects) {
+        screen.receive(&packet);
+    }
+
+    assert_eq!(screen.pixel(100, 50), pattern(100, 50));
+    assert_eq!(screen.pixel(131, 65), pattern(131, 65));
+    assert_eq!(screen.pixel(799, 239), pattern(799, 239));
+    assert_eq!(screen.pixel(99, 50), 0xFFFF);
+    assert_eq!(screen.pixel(132, 50), 0xFFFF);
+    assert_eq!(screen.pixel(599, 239), 0xFFFF);
+}
+
+#[test]
+fn frames_without_regions_are_presented() {
+    let mut encoder = Encoder::new();
+    let mut screen = Screen::new();
+    for frame in 1..=3 {
+        let packets = frame_packets(&mut encoder, &[]);
+        assert_eq!(packets.len(), 1);
+        screen.receive(&packets[0]);
+        assert_eq!(screen.presented, Some((frame, WIDTH, HEIGHT)));
+    }
+    assert!(encoder.next(&[], pattern).is_none());
+}
+
+#[test]
+fn frames_stream_over_loopback() {
+    let device = UdpSocket::bind("127.0.0.1:0").unwrap();
+    let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+    for socket in [&device, &client] {
+        socket
+            .set_read_timeout(Some(Duration::from_secs(5)))
+            .unwrap();
+    }
+    let device_addr = device.local_addr().unwrap();
+    let mut buffer = [0; MAX_PACKET_LEN];
+
+    // The client subscribes and touches the screen
+    let mut out = [0; MAX_PACKET_LEN];
+    let len = Packet::Hello.encode(0, &mut out);
+    client.send_to(&out[..len], device_addr).unwrap();
+    let touch = Packet::Touch {
+        phase: Phase::Down,
+        x: 12,
+        y: 34,
+    };
+    let len = touch.encode(1, &mut out);
+    client.send_to(&out[..len], device_addr).unwrap();
+
+    let (len, subscriber) = device.recv_from(&mut buffer).unwrap();
+    assert_eq!(Packet::decode(&buffer[..len]), Ok((0, Packet::Hello)));
+    let (len, _) = device.recv_from(&mut buffer).unwrap();
+    assert_eq!(Packet::decode(&buffer[..len]), Ok((1, touch)));
+
+    // The device answers with the whole frame, the client takes every packet
+    // before the next is sent so none are dropped
+    let mut encoder = Encoder::new();
+    let mut screen = Screen::new();
+    encoder.begin(WIDTH, HEIGHT);
+    while let Some(packet) = encoder.next(&[FULL], pattern) {
+        device.send_to(packet, subscriber).unwrap();
+        let len = client.recv(&mut buffer).unwrap();
+        screen.receive(&buffer[..len]);
+    }
+    assert_eq!(screen.presented, Some((1, WIDTH, HEIGHT)));
+    assert_eq!(screen.pixel(700, 100), pattern(700, 100));
+    assert_eq!(screen.pixel(10, 239), pattern(10, 239));
+}
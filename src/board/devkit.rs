@@ -0,0 +1,30 @@
+//! Shared wiring for SPI panels on a generic ESP32-S3 DevKit
+//!
+//! | Signal    | GPIO |
+//! |-----------|------|
+//! | SCK       | 12   |
+//! | MOSI      | 11   |
+//! | CS        | 10   |
+//! | DC        | 9    |
+//! | RST       | 8    |
+//! | Backlight | 7    |
+
+macro_rules! devkit_board_peripherals {
+    ($p:ident) => {
+        $crate::board::BoardPeripherals {
+            display: $crate::board::DisplayPeripherals {
+                sck: esp_hal::gpio::Pin::degrade($p.GPIO12),
+                mosi: esp_hal::gpio::Pin::degrade($p.GPIO11),
+                cs: esp_hal::gpio::Pin::degrade($p.GPIO10),
+                dc: esp_hal::gpio::Pin::degrade($p.GPIO9),
+                rst: esp_hal::gpio::Pin::degrade($p.GPIO8),
+                quad: None,
+                spi: $p.SPI2,
+                dma: $p.DMA_CH0,
+            },
+            touch: None,
+            power_enable: Some(esp_hal::gpio::Pin::degrade($p.GPIO7)),
+        }
+    };
+}
+pub(crate) use devkit_board_peripherals;
@@ -0,0 +1,33 @@
+//! ESP32-S3 DevKit with ILI9341 320x240 LCD
+
+use mipidsi::models::ILI9341Rgb565;
+use mipidsi::options::{ColorInversion, ColorOrder, Orientation, Rotation};
+
+pub(crate) use super::devkit::devkit_board_peripherals as board_peripherals;
+
+pub const NAME: &str = "ESP32-S3 DevKit with ILI9341 320x240 LCD";
+
+pub type PanelModel = ILI9341Rgb565;
+pub const PANEL_MODEL: PanelModel = ILI9341Rgb565;
+
+/// Resolution after applying `ORIENTATION`
+pub const DISPLAY_WIDTH: u16 = 320;
+pub const DISPLAY_HEIGHT: u16 = 240;
+
+/// Native panel size and offset as expected by the controller
+pub const PANEL_SIZE: (u16, u16) = (240, 320);
+pub const PANEL_OFFSET: (u16, u16) = (0, 0);
+
+/// Offset of the address window in rotated coordinates, used by the async flush
+#[cfg(feature = "async-flush")]
+pub const WINDOW_OFFSET: (u16, u16) = (0, 0);
+
+pub const ORIENTATION: Orientation = Orientation {
+    mirrored: false,
+    rotation: Rotation::Deg90,
+};
+pub const COLOR_INVERSION: ColorInversion = ColorInversion::Normal;
+pub const COLOR_ORDER: ColorOrder = ColorOrder::Bgr;
+pub const OLED: bool = false;
+
+pub const SPI_FREQUENCY_MHZ: u32 = 40;
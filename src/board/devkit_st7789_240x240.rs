@@ -0,0 +1,33 @@
+//! ESP32-S3 DevKit with ST7789 240x240 LCD
+
+use mipidsi::models::ST7789;
+use mipidsi::options::{ColorInversion, ColorOrder, Orientation, Rotation};
+
+pub(crate) use super::devkit::devkit_board_peripherals as board_peripherals;
+
+pub const NAME: &str = "ESP32-S3 DevKit with ST7789 240x240 LCD";
+
+pub type PanelModel = ST7789;
+pub const PANEL_MODEL: PanelModel = ST7789;
+
+/// Resolution after applying `ORIENTATION`
+pub const DISPLAY_WIDTH: u16 = 240;
+pub const DISPLAY_HEIGHT: u16 = 240;
+
+/// Native panel size and offset as expected by the controller
+pub const PANEL_SIZE: (u16, u16) = (240, 240);
+pub const PANEL_OFFSET: (u16, u16) = (0, 0);
+
+/// Offset of the address window in rotated coordinates, used by the async flush
+#[cfg(feature = "async-flush")]
+pub const WINDOW_OFFSET: (u16, u16) = (0, 0);
+
+pub const ORIENTATION: Orientation = Orientation {
+    mirrored: false,
+    rotation: Rotation::Deg0,
+};
+pub const COLOR_INVERSION: ColorInversion = ColorInversion::Inverted;
+pub const COLOR_ORDER: ColorOrder = ColorOrder::Rgb;
+pub const OLED: bool = false;
+
+pub const SPI_FREQUENCY_MHZ: u32 = 62;
@@ -0,0 +1,117 @@
+//! Board support
+//!
+//! Each supported board maps its pins and panel model in its own module,
+//! selected by exactly one `board-*` cargo feature. The rest of the firmware
+//! only uses the items re-exported from here.
+
+use esp_hal::gpio::AnyPin;
+use esp_hal::peripherals::{DMA_CH0, I2C0, SPI2};
+
+#[cfg(any(
+    feature = "board-devkit-st7789-240x240",
+    feature = "board-devkit-st7789-320x170",
+    feature = "board-devkit-ili9341",
+    feature = "board-devkit-gc9a01",
+))]
+mod devkit;
+
+#[cfg(feature = "board-t-display-s3-amoled")]
+mod t_display_s3_amoled;
+#[cfg(feature = "board-t-display-s3-amoled")]
+pub use t_display_s3_amoled::*;
+
+#[cfg(feature = "board-devkit-st7789-240x240")]
+mod devkit_st7789_240x240;
+#[cfg(feature = "board-devkit-st7789-240x240")]
+pub use devkit_st7789_240x240::*;
+
+#[cfg(feature = "board-devkit-st7789-320x170")]
+mod devkit_st7789_320x170;
+#[cfg(feature = "board-devkit-st7789-320x170")]
+pub use devkit_st7789_320x170::*;
+
+#[cfg(feature = "board-devkit-ili9341")]
+mod devkit_ili9341;
+#[cfg(feature = "board-devkit-ili9341")]
+pub use devkit_ili9341::*;
+
+#[cfg(feature = "board-devkit-gc9a01")]
+mod devkit_gc9a01;
+#[cfg(feature = "board-devkit-gc9a01")]
+pub use devkit_gc9a01::*;
+
+#[cfg(not(any(
+    feature = "board-t-display-s3-amoled",
+    feature = "board-devkit-st7789-240x240",
+    feature = "board-devkit-st7789-320x170",
+    feature = "board-devkit-ili9341",
+    feature = "board-devkit-gc9a01",
+)))]
+compile_error!("No board selected, enable one of the `board-*` features");
+
+#[cfg(any(
+    all(
+        feature = "board-t-display-s3-amoled",
+        any(
+            feature = "board-devkit-st7789-240x240",
+            feature = "board-devkit-st7789-320x170",
+            feature = "board-devkit-ili9341",
+            feature = "board-devkit-gc9a01",
+        )
+    ),
+    all(
+        feature = "board-devkit-st7789-240x240",
+        any(
+            feature = "board-devkit-st7789-320x170",
+            feature = "board-devkit-ili9341",
+            feature = "board-devkit-gc9a01",
+        )
+    ),
+    all(
+        feature = "board-devkit-st7789-320x170",
+        any(feature = "board-devkit-ili9341", feature = "board-devkit-gc9a01")
+    ),
+    all(feature = "board-devkit-ili9341", feature = "board-devkit-gc9a01"),
+))]
+compile_error!(
+    "More than one board selected, the default board needs `--no-default-features` to pick another"
+);
+
+#[cfg(all(feature = "qspi", not(feature = "board-t-display-s3-amoled")))]
+compile_error!("The `qspi` feature needs a board with a quad-SPI panel");
+
+#[cfg(all(feature = "qspi", feature = "async-flush"))]
+compile_error!("The `async-flush` feature only supports single-line SPI");
+
+/// Pins and peripherals used by the display
+pub struct DisplayPeripherals {
+    pub sck: AnyPin<'static>,
+    /// SIO0 in quad-SPI mode
+    pub mosi: AnyPin<'static>,
+    pub cs: AnyPin<'static>,
+    /// SIO1 in quad-SPI mode
+    pub dc: AnyPin<'static>,
+    pub rst: AnyPin<'static>,
+    /// SIO2 and SIO3, `None` if the panel is only wired for single-line SPI
+    #[cfg_attr(not(feature = "qspi"), allow(dead_code))]
+    pub quad: Option<(AnyPin<'static>, AnyPin<'static>)>,
+    pub spi: SPI2<'static>,
+    pub dma: DMA_CH0<'static>,
+}
+
+/// Pins and peripherals used by the CST816 touch controller
+pub struct TouchPeripherals {
+    pub i2c: I2C0<'static>,
+    pub sda: AnyPin<'static>,
+    pub scl: AnyPin<'static>,
+    pub int: AnyPin<'static>,
+}
+
+/// Everything the firmware needs from a board, built by `board_peripherals!`
+pub struct BoardPeripherals {
+    pub display: DisplayPeripherals,
+    /// `None` on boards without a touch controller
+    pub touch: Option<TouchPeripherals>,
+    /// Driven high at boot (PMIC enable on AMOLED boards, backlight on LCD boards)
+    pub power_enable: Option<AnyPin<'static>>,
+}
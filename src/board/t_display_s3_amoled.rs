@@ -0,0 +1,60 @@
+//! LilyGo T-Display-S3 AMOLED: RM67162 536x240 panel, CST816 touch
+
+use mipidsi::models::RM67162;
+use mipidsi::options::{ColorInversion, ColorOrder, Orientation, Rotation};
+
+pub const NAME: &str = "LilyGo T-Display-S3 AMOLED";
+
+pub type PanelModel = RM67162;
+pub const PANEL_MODEL: PanelModel = RM67162;
+
+/// Resolution after applying `ORIENTATION`
+pub const DISPLAY_WIDTH: u16 = 536;
+pub const DISPLAY_HEIGHT: u16 = 240;
+
+/// Native panel size and offset as expected by the controller
+pub const PANEL_SIZE: (u16, u16) = (240, 536);
+pub const PANEL_OFFSET: (u16, u16) = (0, 0);
+
+/// Offset of the address window in rotated coordinates, used by the async flush
+#[cfg(feature = "async-flush")]
+pub const WINDOW_OFFSET: (u16, u16) = (0, 0);
+
+pub const ORIENTATION: Orientation = Orientation {
+    mirrored: false,
+    rotation: Rotation::Deg270,
+};
+pub const COLOR_INVERSION: ColorInversion = ColorInversion::Normal;
+pub const COLOR_ORDER: ColorOrder = ColorOrder::Rgb;
+/// Self-emitting pixels that burn in, enables the mitigation in `src/burnin.rs`
+pub const OLED: bool = true;
+
+pub const SPI_FREQUENCY_MHZ: u32 = 80;
+
+macro_rules! board_peripherals {
+    ($p:ident) => {
+        $crate::board::BoardPeripherals {
+            display: $crate::board::DisplayPeripherals {
+                sck: esp_hal::gpio::Pin::degrade($p.GPIO47),
+                mosi: esp_hal::gpio::Pin::degrade($p.GPIO18),
+                cs: esp_hal::gpio::Pin::degrade($p.GPIO6),
+                dc: esp_hal::gpio::Pin::degrade($p.GPIO7),
+                rst: esp_hal::gpio::Pin::degrade($p.GPIO17),
+                quad: Some((
+                    esp_hal::gpio::Pin::degrade($p.GPIO48),
+                    esp_hal::gpio::Pin::degrade($p.GPIO5),
+                )),
+                spi: $p.SPI2,
+                dma: $p.DMA_CH0,
+            },
+            touch: Some($crate::board::TouchPeripherals {
+                i2c: $p.I2C0,
+                sda: esp_hal::gpio::Pin::degrade($p.GPIO3),
+                scl: esp_hal::gpio::Pin::degrade($p.GPIO2),
+                int: esp_hal::gpio::Pin::degrade($p.GPIO21),
+            }),
+            power_enable: Some(esp_hal::gpio::Pin::degrade($p.GPIO38)),
+        }
+    };
+}
+pub(crate) use board_peripherals;
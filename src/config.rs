@@ -1,2 +1,101 @@
-pub const DISPLAY_HEIGHT: u16 = 240;
-pub const DISPLAY_WIDTH: u16 = 536;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::Point;
+use pixels_core::burnin::Mitigation;
+use pixels_core::power::IdleTimeouts;
+use pixels_core::recovery::RecoveryLimits;
+use pixels_core::replay::InputSource;
+use pixels_protocol::screenshot::Encoding;
+
+use crate::profiler::Overlay;
+
+// The tunables of the host-buildable logic, like the tile size and the touch
+// calibration, are in core/src/config.rs
+
+/// Top-left corner of the FPS counter
+pub const FPS_POSITION: Point = Point::new(0, 0);
+
+/// Draw the frame times of the last frames below the FPS counter
+pub const FPS_GRAPH: bool = false;
+
+/// On-screen frame profiler, see [`Overlay`]
+pub const PROFILER_OVERLAY: Overlay = Overlay::Off;
+
+/// Top-left corner of the profiler overlay, below the FPS counter and its graph
+pub const PROFILER_POSITION: Point = Point::new(0, 60);
+
+/// Frames between profiler log lines, logged at debug level
+pub const PROFILER_LOG_INTERVAL: usize = 120;
+
+/// Where touch input, commands, frame times, the random seed and the first
+/// settings come from. Set to
+/// `InputSource::Replay(include_bytes!("../replay.bin"))` to replay a dumped
+/// recording.
+pub const INPUT_SOURCE: InputSource = InputSource::Live;
+
+/// Label of the data partition holding the settings, see partitions.csv
+pub const SETTINGS_PARTITION: &str = "settings";
+
+/// Pixel data of screenshots sent over serial, run-length encoding shrinks
+/// typical frames to a few percent of raw
+pub const SCREENSHOT_ENCODING: Encoding = Encoding::Rle;
+
+/// Time without input before the screen is dimmed, then drawn at
+/// [`THROTTLED_FRAME_TIME_MS`] and at last put to sleep until it is touched.
+/// Boards without a touch controller stay on.
+pub const IDLE_TIMEOUTS: IdleTimeouts = IdleTimeouts {
+    dim_ms: 30_000,
+    throttle_ms: 60_000,
+    sleep_ms: 180_000,
+};
+
+/// Panel brightness while dimmed with the `auto_brightness` setting, 0 to 255
+pub const DIM_BRIGHTNESS: u8 = 40;
+
+/// Shortest frame time while throttled, 5 frames per second
+pub const THROTTLED_FRAME_TIME_MS: u64 = 200;
+
+/// Burn-in mitigation on OLED panels (`board::OLED`): the scene and the
+/// overlays orbit within 3 pixels, worn overlays move after half an hour and
+/// are dimmed after four hours, an idle screen gets a pixel refresh every hour
+pub const BURN_IN: Mitigation = Mitigation {
+    orbit_radius: 3,
+    orbit_period_ms: 8 * 60_000,
+    refresh_interval_ms: 60 * 60_000,
+    refresh_step_ms: 2_000,
+    relocate_margin_ms: 30 * 60_000,
+    dim_after_ms: 4 * 60 * 60_000,
+};
+
+/// Text and lines of worn overlays
+pub const DIMMED_INK: Rgb565 = Rgb565::new(12, 24, 12);
+
+/// Display errors: a failed transfer is retried after 10, 20 and 40 ms, then
+/// the panel is reset, giving up after three resets without a good frame
+pub const DISPLAY_RECOVERY: RecoveryLimits = RecoveryLimits {
+    retries: 3,
+    first_delay_ms: 10,
+    max_delay_ms: 200,
+    reinits: 3,
+};
+
+/// The device resets when the render loop has not finished a frame for this
+/// long, longer than display recovery and settings saves take
+pub const WATCHDOG_TIMEOUT_MS: u64 = 5_000;
+
+/// The crash report after a reset closes on a tap or after this long
+pub const CRASH_SCREEN_MS: u64 = 60_000;
+
+/// With Wi-Fi the CPU stays awake while the panel sleeps, checking for input this often
+#[cfg(feature = "wifi")]
+pub const ASLEEP_POLL_MS: u64 = 100;
+
+/// Wi-Fi network joined with the `wifi` feature, taken from the environment
+/// at build time: `WIFI_SSID=... WIFI_PASSWORD=... cargo run --features wifi`
+#[cfg(feature = "wifi")]
+pub const WIFI_SSID: &str = env!("WIFI_SSID");
+#[cfg(feature = "wifi")]
+pub const WIFI_PASSWORD: &str = env!("WIFI_PASSWORD");
+
+/// Pixelflut commands run per frame, shared by the connected clients
+#[cfg(feature = "pixelflut")]
+pub const PIXELFLUT_BUDGET: u32 = 16 * 1024;
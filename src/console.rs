@@ -0,0 +1,35 @@
+//! Serial console on the USB serial port, the commands and the line editing
+//! are in [`pixels_core::console`]
+
+use esp_hal::usb_serial_jtag::UsbSerialJtagRx;
+use esp_hal::Blocking;
+use esp_println::Printer;
+
+pub use pixels_core::console::*;
+
+/// Console on the USB serial port
+pub struct Console {
+    rx: UsbSerialJtagRx<'static, Blocking>,
+    editor: LineEditor,
+}
+
+impl Console {
+    pub fn new(rx: UsbSerialJtagRx<'static, Blocking>) -> Self {
+        Self {
+            rx,
+            editor: LineEditor::new(),
+        }
+    }
+
+    /// Reads what has been received so far, returns a line once one is
+    /// complete. Call again for the next line.
+    pub fn read_line(&mut self) -> Option<&str> {
+        let mut byte = [0];
+        while self.rx.drain_rx_fifo(&mut byte) == 1 {
+            if self.editor.push(byte[0], &mut Printer).is_some() {
+                return Some(self.editor.line());
+            }
+        }
+        None
+    }
+}
@@ -1,253 +1,354 @@
+#[cfg(feature = "async-flush")]
 use alloc::vec::Vec;
 use core::convert::Infallible;
-use core::fmt::Debug;
-use embedded_graphics::draw_target::DrawTarget;
-use embedded_graphics::geometry::{OriginDimensions, Point, Size};
-use embedded_graphics::mono_font::iso_8859_1::FONT_10X20 as FONT;
-use embedded_graphics::mono_font::MonoTextStyle;
-use embedded_graphics::pixelcolor::{Rgb565, RgbColor};
-use embedded_graphics::prelude::Primitive;
-use embedded_graphics::primitives::{Line, PrimitiveStyle};
-use embedded_graphics::text::{Baseline, Text};
-use embedded_graphics::{Drawable, Pixel};
-use embedded_hal_bus::spi::{DeviceError, ExclusiveDevice};
+#[cfg(feature = "async-flush")]
+use embassy_executor::Spawner;
+#[cfg(feature = "async-flush")]
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+#[cfg(feature = "async-flush")]
+use embassy_sync::channel::Channel;
+use embedded_graphics::geometry::{Point, Size};
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::primitives::Rectangle;
+use embedded_hal_bus::spi::DeviceError;
+#[cfg(not(feature = "qspi"))]
+use embedded_hal_bus::spi::ExclusiveDevice;
 use esp_hal::delay::Delay;
 use esp_hal::dma::DmaTxBuf;
 use esp_hal::dma_buffers;
-use esp_hal::gpio::{Level, Output, OutputConfig};
-use esp_hal::peripherals::{DMA_CH0, GPIO17, GPIO18, GPIO47, GPIO6, GPIO7, SPI2};
+use esp_hal::gpio::{AnyPin, Level, Output, OutputConfig};
+#[cfg(feature = "qspi")]
+use esp_hal::spi::master::{Address, Command, DataMode};
 use esp_hal::spi::master::{Config as SpiConfig, Spi, SpiDmaBus};
 use esp_hal::spi::{Error, Mode};
-use esp_hal::time::Rate;
-use mipidsi::interface::{SpiError, SpiInterface};
-use mipidsi::models::RM67162;
-use mipidsi::options::{Orientation, Rotation};
+use esp_hal::time::{Duration, Instant, Rate};
+#[cfg(not(feature = "qspi"))]
+use mipidsi::interface::SpiInterface;
+use mipidsi::interface::{Interface, InterfacePixelFormat, SpiError};
+use mipidsi::models::Model;
+use mipidsi::options::ColorInversion;
 use mipidsi::{Builder, Display as MipiDisplay};
+use pixels_core::config::TRANSFER_OVERHEAD_BYTES;
+use pixels_core::dirty_region::BYTES_PER_PIXEL;
+use pixels_core::display::{Cause, Operation};
+#[cfg(feature = "async-flush")]
+use pixels_core::flush::{flush, FlushJob};
+use pixels_core::framebuffer::{DirtyRect, FrameBuffer, Geometry};
+use pixels_core::panel::{ColorLut, PanelCommand};
+#[cfg(feature = "qspi")]
+use pixels_core::qspi::{DataLines, QspiBus, QspiInterface};
 use static_cell::StaticCell;
 
-use crate::config::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
-
-const TEXT_STYLE: MonoTextStyle<Rgb565> = MonoTextStyle::new(&FONT, Rgb565::WHITE);
-const LINE_STYLE: PrimitiveStyle<Rgb565> = PrimitiveStyle::with_stroke(RgbColor::WHITE, 2);
-
-pub type MipiDisplayWrapper<'a> = MipiDisplay<
-    SpiInterface<
-        'a,
-        ExclusiveDevice<
-            SpiDmaBus<'a, esp_hal::Blocking>,
-            Output<'a>,
-            embedded_hal_bus::spi::NoDelay,
-        >,
-        Output<'a>,
-    >,
-    RM67162,
-    Output<'a>,
->;
-
-const TILE_SIZE: u16 = 32; // 32x32 pixel tiles
-const TILES_X: usize = DISPLAY_WIDTH.div_ceil(TILE_SIZE) as usize; // 17 tiles wide
-const TILES_Y: usize = DISPLAY_HEIGHT.div_ceil(TILE_SIZE) as usize; // 8 tiles high
-const TOTAL_TILES: usize = TILES_X * TILES_Y; // 136 tiles total
+use crate::board::{self, DisplayPeripherals, PanelModel};
+#[cfg(feature = "dual-core")]
+use crate::dual_core;
+
+pub use pixels_core::display::DisplayTrait;
+
+/// The panel needs this long after sleep out before it takes more commands
+/// (and before it may sleep again), 5 ms after sleep in
+const SLEEP_OUT_DELAY_MS: u64 = 120;
+const SLEEP_IN_DELAY_MS: u64 = 5;
+
+/// Driver mode of the SPI bus, the async flush task needs an async bus
+#[cfg(not(feature = "async-flush"))]
+type BusMode = esp_hal::Blocking;
+#[cfg(feature = "async-flush")]
+type BusMode = esp_hal::Async;
+
+#[cfg(not(feature = "qspi"))]
+type DisplaySpiDevice<'a> =
+    ExclusiveDevice<SpiDmaBus<'a, BusMode>, Output<'a>, embedded_hal_bus::spi::NoDelay>;
+
+#[cfg(not(feature = "qspi"))]
+type DisplayInterface<'a> = SpiInterface<'a, DisplaySpiDevice<'a>, Output<'a>>;
+
+#[cfg(feature = "qspi")]
+type DisplayInterface<'a> = QspiInterface<'a, QspiDmaBus<'a>>;
+
+pub type MipiDisplayWrapper<'a> = MipiDisplay<DisplayInterface<'a>, PanelModel, Output<'a>>;
 
 pub struct Display {
-    display: MipiDisplayWrapper<'static>,
-    front_buffer: Vec<Rgb565>,
-    back_buffer: Vec<Rgb565>,
-    current_tiles: TileTracker, // Tiles drawn this frame
-    prev_tiles: TileTracker,    // Tiles to clear (from 2 frames ago)
+    /// `None` once a failed re-init took the interface with it
+    #[cfg(not(feature = "async-flush"))]
+    display: Option<MipiDisplayWrapper<'static>>,
+    /// Whether a frame has been handed to the flush task and not returned yet
+    #[cfg(feature = "async-flush")]
+    in_flight: bool,
+    /// Reused list of dirty regions for the next flush job
+    #[cfg(feature = "async-flush")]
+    rects: Vec<DirtyRect>,
+    frame: FrameBuffer,
+    /// Rasterizes the frames on core 1
+    #[cfg(feature = "dual-core")]
+    rasterizer: dual_core::Rasterizer,
+    /// Color correction of the pixels sent, `None` sends them unchanged
+    lut: Option<ColorLut>,
+    last_update: UpdateStats,
 }
 
-#[derive(Clone, Copy)]
-struct TileTracker {
-    dirty: [bool; TOTAL_TILES],
+/// What the last [`DisplayTrait::update_with_buffer`] did
+#[derive(Clone, Copy, Debug)]
+pub struct UpdateStats {
+    /// Time spent writing the drawn primitives to the back buffer, on core 1
+    /// with `dual-core`
+    pub rasterize: Duration,
+    /// Time spent sending the dirty regions, with `async-flush` the time spent
+    /// waiting for the previous frame and queueing this one
+    pub flush: Duration,
+    /// Pixel data sent (or queued) for the frame
+    pub bytes: usize,
 }
 
-impl TileTracker {
-    fn new() -> Self {
+impl Default for UpdateStats {
+    fn default() -> Self {
         Self {
-            dirty: [false; TOTAL_TILES],
+            rasterize: Duration::ZERO,
+            flush: Duration::ZERO,
+            bytes: 0,
         }
     }
+}
 
-    fn mark_rect(&mut self, x1: u16, y1: u16, x2: u16, y2: u16) {
-        let min_x = x1.min(x2).min(DISPLAY_WIDTH - 1);
-        let max_x = x1.max(x2).min(DISPLAY_WIDTH - 1);
-        let min_y = y1.min(y2).min(DISPLAY_HEIGHT - 1);
-        let max_y = y1.max(y2).min(DISPLAY_HEIGHT - 1);
+#[cfg(feature = "async-flush")]
+type Job = FlushJob<DisplayError>;
 
-        let tile_x1 = (min_x / TILE_SIZE) as usize;
-        let tile_x2 = (max_x / TILE_SIZE) as usize;
-        let tile_y1 = (min_y / TILE_SIZE) as usize;
-        let tile_y2 = (max_y / TILE_SIZE) as usize;
+/// Frames waiting to be sent by the flush task
+#[cfg(feature = "async-flush")]
+static FLUSH_JOBS: Channel<CriticalSectionRawMutex, Job, 1> = Channel::new();
+/// Frames the flush task has finished, with their buffers
+#[cfg(feature = "async-flush")]
+static FLUSH_DONE: Channel<CriticalSectionRawMutex, Job, 1> = Channel::new();
 
-        for ty in tile_y1..=tile_y2 {
-            for tx in tile_x1..=tile_x2 {
-                let tile_idx = ty * TILES_X + tx;
-                if tile_idx < TOTAL_TILES {
-                    self.dirty[tile_idx] = true;
-                }
+impl Display {
+    pub fn new(
+        p: DisplayPeripherals,
+        geometry: Geometry,
+        #[cfg(feature = "async-flush")] spawner: &Spawner,
+    ) -> Result<Self, DisplayError> {
+        let (di, rst_pin) = create_interface(p)?;
+        let rst = Output::new(rst_pin, Level::High, OutputConfig::default());
+        let display = init_panel(di, rst)?;
+
+        // Both buffers in PSRAM (256KB each at 536x240 - too large for DRAM)
+        let frame = FrameBuffer::new(geometry, TRANSFER_OVERHEAD_BYTES);
+
+        #[cfg(not(feature = "async-flush"))]
+        let display = Self {
+            display: Some(display),
+            frame,
+            #[cfg(feature = "dual-core")]
+            rasterizer: dual_core::Rasterizer::new(),
+            lut: None,
+            last_update: UpdateStats::default(),
+        };
+
+        #[cfg(feature = "async-flush")]
+        let display = {
+            // Panel is initialized, the flush task takes over the interface
+            let (di, _model, rst) = display.release();
+            let (spi_device, dc) = di.release();
+            let rst = rst.ok_or(DisplayError::new(
+                Operation::Setup,
+                Cause::Setup("reset pin"),
+            ))?;
+            spawner.must_spawn(flush_task(spi_device, dc, rst));
+
+            Self {
+                in_flight: false,
+                rects: Vec::new(),
+                frame,
+                #[cfg(feature = "dual-core")]
+                rasterizer: dual_core::Rasterizer::new(),
+                lut: None,
+                last_update: UpdateStats::default(),
             }
-        }
-    }
+        };
 
-    fn clear(&mut self) {
-        self.dirty.fill(false);
+        Ok(display)
     }
+}
 
-    fn is_dirty(&self, tile_idx: usize) -> bool {
-        tile_idx < TOTAL_TILES && self.dirty[tile_idx]
-    }
+/// Resets the panel through `rst` and sends its init sequence
+///
+/// The interface is gone if this fails, there is no way to get it back.
+fn init_panel<DI>(
+    di: DI,
+    rst: Output<'static>,
+) -> Result<MipiDisplay<DI, PanelModel, Output<'static>>, DisplayError>
+where
+    DI: Interface,
+    <PanelModel as Model>::ColorFormat: InterfacePixelFormat<DI::Word>,
+{
+    Builder::new(board::PANEL_MODEL, di)
+        .display_size(board::PANEL_SIZE.0, board::PANEL_SIZE.1)
+        .display_offset(board::PANEL_OFFSET.0, board::PANEL_OFFSET.1)
+        .orientation(board::ORIENTATION)
+        .invert_colors(board::COLOR_INVERSION)
+        .color_order(board::COLOR_ORDER)
+        .reset_pin(rst)
+        .init(&mut Delay::new())
+        .map_err(|_| DisplayError::new(Operation::Init, Cause::Init))
 }
 
-struct BufferDrawTarget<'a> {
-    buffer: &'a mut [Rgb565],
-    width: usize,
-    height: usize,
+/// Creates the single-line SPI interface, returns it with the unused reset pin
+#[cfg(not(feature = "qspi"))]
+fn create_interface(
+    p: DisplayPeripherals,
+) -> Result<(DisplayInterface<'static>, AnyPin<'static>), DisplayError> {
+    // SPI pins
+    let dc = Output::new(p.dc, Level::Low, OutputConfig::default());
+    let sck = Output::new(p.sck, Level::Low, OutputConfig::default());
+    let mosi = Output::new(p.mosi, Level::Low, OutputConfig::default());
+    let cs = Output::new(p.cs, Level::High, OutputConfig::default());
+
+    #[allow(clippy::manual_div_ceil)]
+    let (rx_buffer, rx_descriptors, tx_buffer, tx_descriptors) = dma_buffers!(32000);
+    let dma_rx_buf = esp_hal::dma::DmaRxBuf::new(rx_descriptors, rx_buffer)
+        .map_err(|_| DisplayError::setup("DMA receive buffer"))?;
+    let dma_tx_buf = DmaTxBuf::new(tx_descriptors, tx_buffer)
+        .map_err(|_| DisplayError::setup("DMA transmit buffer"))?;
+
+    // Configure SPI
+    let spi_dma = Spi::new(
+        p.spi,
+        SpiConfig::default()
+            .with_frequency(Rate::from_mhz(board::SPI_FREQUENCY_MHZ))
+            .with_mode(Mode::_0),
+    )
+    .map_err(|_| DisplayError::setup("SPI configuration"))?
+    .with_sck(sck)
+    .with_mosi(mosi)
+    .with_dma(p.dma);
+
+    // Create the SPI DMA bus with the configured buffers
+    let spi = SpiDmaBus::new(spi_dma, dma_rx_buf, dma_tx_buf);
+    #[cfg(feature = "async-flush")]
+    let spi = spi.into_async();
+
+    // Attach the SPI device using the chip-select control pin (no delay used)
+    let spi_device = ExclusiveDevice::new_no_delay(spi, cs)
+        .map_err(|_| DisplayError::setup("SPI chip select"))?;
+
+    const DISPLAY_BUFFER_SIZE: usize = 512;
+    static DISPLAY_BUFFER: StaticCell<[u8; DISPLAY_BUFFER_SIZE]> = StaticCell::new();
+    let buffer = DISPLAY_BUFFER.init([0_u8; 512]);
+
+    // Create the SPI interface for the display driver using the SPI device, DC pin, and initialization buffer
+    Ok((SpiInterface::new(spi_device, dc, buffer), p.rst))
 }
 
-impl<'a> DrawTarget for BufferDrawTarget<'a> {
-    type Color = Rgb565;
-    type Error = Infallible;
-
-    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Self::Error>
-    where
-        I: IntoIterator<Item = Pixel<Self::Color>>,
-    {
-        for Pixel(coord, color) in pixels {
-            if coord.x >= 0
-                && coord.x < self.width as i32
-                && coord.y >= 0
-                && coord.y < self.height as i32
-            {
-                let index = (coord.y as usize) * self.width + coord.x as usize;
-                if index < self.buffer.len() {
-                    self.buffer[index] = color;
-                }
-            }
-        }
-        Ok(())
-    }
+/// Creates the quad-SPI interface, returns it with the unused reset pin
+///
+/// The DC pin is SIO1 in this mode, chip select is driven by the SPI peripheral
+/// so it stays asserted for the instruction, address and data phases.
+#[cfg(feature = "qspi")]
+fn create_interface(
+    p: DisplayPeripherals,
+) -> Result<(DisplayInterface<'static>, AnyPin<'static>), DisplayError> {
+    let (sio2, sio3) = p
+        .quad
+        .ok_or(DisplayError::setup("quad-SPI wiring of the board"))?;
+
+    #[allow(clippy::manual_div_ceil)]
+    let (rx_buffer, rx_descriptors, tx_buffer, tx_descriptors) = dma_buffers!(32000);
+    let dma_rx_buf = esp_hal::dma::DmaRxBuf::new(rx_descriptors, rx_buffer)
+        .map_err(|_| DisplayError::setup("DMA receive buffer"))?;
+    let dma_tx_buf = DmaTxBuf::new(tx_descriptors, tx_buffer)
+        .map_err(|_| DisplayError::setup("DMA transmit buffer"))?;
+
+    // Configure SPI with all four data lines
+    let spi_dma = Spi::new(
+        p.spi,
+        SpiConfig::default()
+            .with_frequency(Rate::from_mhz(board::SPI_FREQUENCY_MHZ))
+            .with_mode(Mode::_0),
+    )
+    .map_err(|_| DisplayError::setup("SPI configuration"))?
+    .with_sck(p.sck)
+    .with_cs(p.cs)
+    .with_sio0(p.mosi)
+    .with_sio1(p.dc)
+    .with_sio2(sio2)
+    .with_sio3(sio3)
+    .with_dma(p.dma);
+
+    let spi = SpiDmaBus::new(spi_dma, dma_rx_buf, dma_tx_buf);
+
+    // Pixel data is staged here in chunks before each DMA transfer
+    const DISPLAY_BUFFER_SIZE: usize = 4096;
+    static DISPLAY_BUFFER: StaticCell<[u8; DISPLAY_BUFFER_SIZE]> = StaticCell::new();
+    let buffer = DISPLAY_BUFFER.init([0_u8; DISPLAY_BUFFER_SIZE]);
+
+    Ok((QspiInterface::new(QspiDmaBus(spi), buffer), p.rst))
 }
 
-impl<'a> OriginDimensions for BufferDrawTarget<'a> {
-    fn size(&self) -> Size {
-        Size::new(self.width as u32, self.height as u32)
+/// Sends frames handed over by [`Display::update_with_buffer`] to the panel
+#[cfg(feature = "async-flush")]
+#[embassy_executor::task]
+async fn flush_task(spi: DisplaySpiDevice<'static>, dc: Output<'static>, rst: Output<'static>) {
+    // Pixels are converted into this DRAM buffer before each DMA transfer
+    const STAGING_BUFFER_SIZE: usize = 4096;
+    static STAGING_BUFFER: StaticCell<[u8; STAGING_BUFFER_SIZE]> = StaticCell::new();
+    let staging = STAGING_BUFFER.init([0_u8; STAGING_BUFFER_SIZE]);
+
+    // `None` once a failed re-init took the interface with it
+    let mut bus = Some((spi, dc, rst));
+
+    loop {
+        let mut job = FLUSH_JOBS.receive().await;
+        let operation = job
+            .commands
+            .first()
+            .map_or(Operation::Flush, |&command| Operation::Command(command));
+
+        job.result = match bus.take() {
+            None => Err(DisplayError::new(operation, Cause::NoPanel)),
+            // The init sequence is staged in the pixel buffer
+            Some((spi, dc, rst)) if job.reinit => {
+                init_panel(SpiInterface::new(spi, dc, &mut staging[..]), rst).map(|display| {
+                    let (di, _model, rst) = display.release();
+                    let (spi, dc) = di.release();
+                    bus = rst.map(|rst| (spi, dc, rst));
+                })
+            }
+            Some((mut spi, mut dc, rst)) => {
+                let result = flush(&mut spi, &mut dc, staging, &job, board::WINDOW_OFFSET).await;
+                bus = Some((spi, dc, rst));
+                result.map_err(|error| DisplayError::new(operation, InterfaceError::from(error)))
+            }
+        };
+        FLUSH_DONE.send(job).await;
     }
 }
 
-/// Display interface trait for ST7789 LCD controller
-///
-/// Provides basic drawing operations for text and primitives.
-/// Implementations should handle the low-level display communication.
-pub trait DisplayTrait {
-    /// Error type
-    type Error: Debug;
+/// The quad-SPI bus of the panel
+#[cfg(feature = "qspi")]
+pub struct QspiDmaBus<'a>(SpiDmaBus<'a, esp_hal::Blocking>);
 
-    /// Writes text to the display at the specified position
-    ///
-    /// # Arguments
-    /// * `text` - The text string to display
-    /// * `position` - Starting position coordinates as Point(x,y)
-    ///
-    /// # Returns
-    /// * `Ok(())` on successful write
-    /// * `Err(Error)` if the write operation fails
-    fn write(&mut self, text: &str, position: Point) -> Result<(), Self::Error>;
+#[cfg(feature = "qspi")]
+impl QspiBus for QspiDmaBus<'_> {
+    type Error = Error;
 
-    /// Updates the display with the current framebuffer contents
-    ///
-    /// # Returns
-    /// * `Ok(())` on successful update
-    /// * `Err(Error)` if the update operation fails
-    fn update_with_buffer(&mut self) -> Result<(), Self::Error>;
-
-    /// Draws a line between two points
-    ///
-    /// # Arguments
-    /// * `begin` - Starting point coordinates as Point(x,y)  
-    /// * `end` - Ending point coordinates as Point(x,y)
-    ///
-    /// # Returns
-    /// * `Ok(())` on successful line draw
-    /// * `Err(Error)` if the draw operation fails
-    fn draw_line(&mut self, begin: Point, end: Point) -> Result<(), Self::Error>;
-}
-
-pub struct DisplayPeripherals {
-    pub sck: GPIO47<'static>,
-    pub mosi: GPIO18<'static>,
-    pub cs: GPIO6<'static>,
-    pub dc: GPIO7<'static>,
-    pub rst: GPIO17<'static>,
-    pub spi: SPI2<'static>,
-    pub dma: DMA_CH0<'static>,
-}
+    fn write(
+        &mut self,
+        instruction: u8,
+        address: u32,
+        data: &[u8],
+        data_lines: DataLines,
+    ) -> Result<(), Self::Error> {
+        let data_mode = match data_lines {
+            DataLines::Single => DataMode::Single,
+            DataLines::Quad => DataMode::Quad,
+        };
 
-impl Display {
-    pub fn new(p: DisplayPeripherals) -> Result<Self, DisplayError> {
-        // SPI pins
-        let dc = Output::new(p.dc, Level::Low, OutputConfig::default());
-        let sck = Output::new(p.sck, Level::Low, OutputConfig::default());
-        let mosi = Output::new(p.mosi, Level::Low, OutputConfig::default());
-        let cs = Output::new(p.cs, Level::High, OutputConfig::default());
-
-        #[allow(clippy::manual_div_ceil)]
-        let (rx_buffer, rx_descriptors, tx_buffer, tx_descriptors) = dma_buffers!(32000);
-        let dma_rx_buf = esp_hal::dma::DmaRxBuf::new(rx_descriptors, rx_buffer).unwrap();
-        let dma_tx_buf = DmaTxBuf::new(tx_descriptors, tx_buffer).unwrap();
-
-        // Configure SPI
-        let spi_dma = Spi::new(
-            p.spi,
-            SpiConfig::default()
-                .with_frequency(Rate::from_mhz(80))
-                .with_mode(Mode::_0),
+        self.0.half_duplex_write(
+            data_mode,
+            Command::_8Bit(instruction as u16, DataMode::Single),
+            Address::_24Bit(address, DataMode::Single),
+            0,
+            data,
         )
-        .unwrap()
-        .with_sck(sck)
-        .with_mosi(mosi)
-        .with_dma(p.dma);
-
-        // Create the SPI DMA bus with the configured buffers
-        let spi = SpiDmaBus::new(spi_dma, dma_rx_buf, dma_tx_buf);
-
-        // Attach the SPI device using the chip-select control pin (no delay used)
-        let spi_device = ExclusiveDevice::new_no_delay(spi, cs).unwrap();
-
-        const DISPLAY_BUFFER_SIZE: usize = 512;
-        static DISPLAY_BUFFER: StaticCell<[u8; DISPLAY_BUFFER_SIZE]> = StaticCell::new();
-        let buffer = DISPLAY_BUFFER.init([0_u8; 512]);
-
-        // Create the SPI interface for the display driver using the SPI device, DC pin, and initialization buffer
-        let di = SpiInterface::new(spi_device, dc, buffer);
-
-        let mut delay = Delay::new();
-
-        let rst_pin = p.rst;
-        let display = Builder::new(RM67162, di)
-            .orientation(Orientation {
-                mirrored: false,
-                rotation: Rotation::Deg270,
-            })
-            .reset_pin(Output::new(rst_pin, Level::High, OutputConfig::default()))
-            .init(&mut delay)
-            .unwrap();
-
-        let buffer_size = (DISPLAY_WIDTH as usize) * (DISPLAY_HEIGHT as usize);
-
-        // Both buffers in PSRAM (256KB each - too large for DRAM)
-        let mut front_buffer = Vec::new();
-        front_buffer.resize(buffer_size, Rgb565::BLACK);
-        let mut back_buffer = Vec::new();
-        back_buffer.resize(buffer_size, Rgb565::BLACK);
-
-        Ok(Self {
-            display,
-            front_buffer,
-            back_buffer,
-            current_tiles: TileTracker::new(),
-            prev_tiles: TileTracker::new(),
-        })
     }
 }
 
@@ -255,176 +356,341 @@ impl DisplayTrait for Display {
     type Error = DisplayError;
 
     fn write(&mut self, text: &str, position: Point) -> Result<(), Self::Error> {
-        let mut target = BufferDrawTarget {
-            buffer: &mut self.back_buffer[..],
-            width: DISPLAY_WIDTH as usize,
-            height: DISPLAY_HEIGHT as usize,
-        };
+        self.frame.write(text, position);
+        Ok(())
+    }
 
-        // Estimate text bounds (10x20 font)
-        let text_width = (text.len() as u16) * 10;
-        let text_height = 20u16;
+    fn draw_line(&mut self, start: Point, end: Point) -> Result<(), Self::Error> {
+        self.frame.draw_line(start, end);
+        Ok(())
+    }
 
-        let x = position.x.max(0) as u16;
-        let y = position.y.max(0) as u16;
-        let x2 = (x + text_width).min(DISPLAY_WIDTH - 1);
-        let y2 = (y + text_height).min(DISPLAY_HEIGHT - 1);
+    fn fill_rect(&mut self, area: Rectangle, color: Rgb565) -> Result<(), Self::Error> {
+        self.frame.fill_rect(area, color);
+        Ok(())
+    }
+
+    #[cfg(not(feature = "async-flush"))]
+    async fn update_with_buffer(&mut self) -> Result<(), Self::Error> {
+        let Some((rasterize, rasterized)) = self.present().await? else {
+            return Ok(());
+        };
+        let Some(display) = self.display.as_mut() else {
+            return Err(DisplayError::new(Operation::Flush, Cause::NoPanel));
+        };
+
+        // Send each merged dirty region as one transfer
+        let lut = self.lut.as_ref();
+        for &rect in self.frame.dirty_regions() {
+            let pixels = self.frame.rect_pixels(rect);
+            display
+                .set_pixels(
+                    rect.x_start,
+                    rect.y_start,
+                    rect.x_end,
+                    rect.y_end,
+                    pixels.map(|color| lut.map_or(color, |lut| lut.apply(color))),
+                )
+                .map_err(|error| {
+                    DisplayError::new(Operation::Flush, InterfaceError::from(error))
+                })?;
+        }
 
-        // Mark tiles dirty
-        self.current_tiles.mark_rect(x, y, x2, y2);
+        self.last_update = UpdateStats {
+            rasterize,
+            flush: rasterized.elapsed(),
+            bytes: self.frame.dirty_pixel_count() * BYTES_PER_PIXEL,
+        };
 
-        Text::with_baseline(text, position, TEXT_STYLE, Baseline::Top).draw(&mut target)?;
         Ok(())
     }
 
-    fn draw_line(&mut self, start: Point, end: Point) -> Result<(), Self::Error> {
-        let mut target = BufferDrawTarget {
-            buffer: &mut self.back_buffer[..],
-            width: DISPLAY_WIDTH as usize,
-            height: DISPLAY_HEIGHT as usize,
+    #[cfg(feature = "async-flush")]
+    async fn update_with_buffer(&mut self) -> Result<(), Self::Error> {
+        let Some((rasterize, rasterized)) = self.present().await? else {
+            return Ok(());
         };
 
-        // Mark tiles dirty (add small padding for 2-pixel stroke)
-        let x1 = start.x.max(0).saturating_sub(2) as u16;
-        let y1 = start.y.max(0).saturating_sub(2) as u16;
-        let x2 = (end.x.max(0) + 2).min(DISPLAY_WIDTH as i32 - 1) as u16;
-        let y2 = (end.y.max(0) + 2).min(DISPLAY_HEIGHT as i32 - 1) as u16;
+        self.rects.clear();
+        self.rects.extend_from_slice(self.frame.dirty_regions());
+
+        // Hand the front buffer to the flush task, rendering continues in the back buffer
+        let job = FlushJob {
+            buffer: self.frame.take_front_buffer(),
+            width: self.frame.size().width as usize,
+            rects: core::mem::take(&mut self.rects),
+            commands: Vec::new(),
+            lut: self.lut,
+            reinit: false,
+            result: Ok(()),
+        };
+        FLUSH_JOBS.send(job).await;
+        self.in_flight = true;
 
-        self.current_tiles.mark_rect(x1, y1, x2, y2);
+        self.last_update = UpdateStats {
+            rasterize,
+            flush: rasterized.elapsed(),
+            bytes: self.frame.dirty_pixel_count() * BYTES_PER_PIXEL,
+        };
 
-        Line::new(start, end)
-            .into_styled(LINE_STYLE)
-            .draw(&mut target)?;
         Ok(())
     }
 
-    fn update_with_buffer(&mut self) -> Result<(), Self::Error> {
-        // Swap buffers FIRST so front_buffer has the newly drawn frame
-        core::mem::swap(&mut self.front_buffer, &mut self.back_buffer);
-
-        // Batch adjacent dirty tiles horizontally to reduce DMA transfers
-        for tile_y in 0..TILES_Y {
-            let mut batch_start: Option<usize> = None;
-
-            for tile_x in 0..=TILES_X {
-                let tile_idx = tile_y * TILES_X + tile_x;
-                let is_dirty = tile_x < TILES_X
-                    && (self.current_tiles.is_dirty(tile_idx)
-                        || self.prev_tiles.is_dirty(tile_idx));
-
-                if is_dirty {
-                    // Start or continue batch
-                    if batch_start.is_none() {
-                        batch_start = Some(tile_x);
-                    }
-                } else if let Some(start_x) = batch_start {
-                    // End of batch - send accumulated tiles as one transfer
-                    let x_start = (start_x * TILE_SIZE as usize) as u16;
-                    let x_end =
-                        ((tile_x * TILE_SIZE as usize).min(DISPLAY_WIDTH as usize) - 1) as u16;
-                    let y_start = (tile_y * TILE_SIZE as usize) as u16;
-                    let y_end = (((tile_y + 1) * TILE_SIZE as usize).min(DISPLAY_HEIGHT as usize)
-                        - 1) as u16;
-
-                    let batch_width = (x_end - x_start + 1) as usize;
-
-                    // Create iterator for batched tiles
-                    let batch_pixels = (y_start..=y_end).flat_map(|y| {
-                        let row_start =
-                            (y as usize) * (DISPLAY_WIDTH as usize) + (x_start as usize);
-                        self.front_buffer[row_start..row_start + batch_width]
-                            .iter()
-                            .copied()
-                    });
-
-                    // Send batched region to display
-                    self.display
-                        .set_pixels(x_start, y_start, x_end, y_end, batch_pixels)?;
-
-                    batch_start = None;
-                }
-            }
+    fn size(&self) -> Size {
+        self.frame.size()
+    }
+
+    fn write_unchanged(&mut self, text: &str, position: Point) {
+        self.frame.write_unchanged(text, position);
+    }
+
+    fn erase(&mut self, area: Rectangle) {
+        self.frame.erase(area);
+    }
+
+    fn set_ink(&mut self, color: Rgb565) {
+        self.frame.set_ink(color);
+    }
+
+    fn ink(&self) -> Rgb565 {
+        self.frame.ink()
+    }
+
+    async fn set_brightness(&mut self, level: u8) -> Result<(), Self::Error> {
+        self.send_command(PanelCommand::Brightness(level)).await
+    }
+
+    async fn sleep(&mut self) -> Result<(), Self::Error> {
+        self.send_command(PanelCommand::DisplayOff).await?;
+        self.send_command(PanelCommand::SleepIn).await?;
+        embassy_time::Timer::after_millis(SLEEP_IN_DELAY_MS).await;
+        Ok(())
+    }
+
+    async fn wake(&mut self) -> Result<(), Self::Error> {
+        self.send_command(PanelCommand::SleepOut).await?;
+        embassy_time::Timer::after_millis(SLEEP_OUT_DELAY_MS).await;
+        self.send_command(PanelCommand::DisplayOn).await
+    }
+
+    async fn set_inversion(&mut self, inverted: bool) -> Result<(), Self::Error> {
+        // Relative to the inversion the panel needs for normal colors
+        let panel_inverted = matches!(board::COLOR_INVERSION, ColorInversion::Inverted);
+        let command = if inverted != panel_inverted {
+            PanelCommand::InversionOn
+        } else {
+            PanelCommand::InversionOff
+        };
+        self.send_command(command).await
+    }
+
+    fn set_color_correction(&mut self, lut: ColorLut) {
+        let lut = (lut != ColorLut::IDENTITY).then_some(lut);
+        if lut != self.lut {
+            self.lut = lut;
+            self.frame.invalidate();
         }
+    }
 
-        // Save current tiles for clearing 2 frames later
-        self.prev_tiles = self.current_tiles;
-        self.current_tiles.clear();
+    fn invalidate(&mut self) {
+        self.frame.invalidate();
+    }
 
+    /// Resets the panel and sends its init sequence
+    ///
+    /// If this fails the interface is gone and every later call fails too.
+    #[cfg(not(feature = "async-flush"))]
+    async fn reinit(&mut self) -> Result<(), Self::Error> {
+        let Some(display) = self.display.take() else {
+            return Err(DisplayError::new(Operation::Init, Cause::NoPanel));
+        };
+        let (di, _model, rst) = display.release();
+        let rst = rst.ok_or(DisplayError::new(
+            Operation::Init,
+            Cause::Setup("reset pin"),
+        ))?;
+        self.display = Some(init_panel(di, rst)?);
+        self.frame.invalidate();
+        Ok(())
+    }
+
+    /// Resets the panel and sends its init sequence through the flush task
+    ///
+    /// If this fails the interface is gone and every later call fails too.
+    #[cfg(feature = "async-flush")]
+    async fn reinit(&mut self) -> Result<(), Self::Error> {
+        // The failed frame is what this recovers from
+        let _ = self.wait_for_flush().await;
+        self.run_job(Vec::new(), true).await?;
+        self.frame.invalidate();
         Ok(())
     }
 }
 
 impl Display {
-    /// Draws a small colored point (3x3 pixels) at the specified position
-    pub fn draw_colored_point(
+    /// Takes the front buffer back from the flush task once it has sent it
+    #[cfg(feature = "async-flush")]
+    async fn wait_for_flush(&mut self) -> Result<(), DisplayError> {
+        if self.in_flight {
+            let job = FLUSH_DONE.receive().await;
+            self.in_flight = false;
+            self.frame.restore_front_buffer(job.buffer);
+            self.rects = job.rects;
+            job.result?;
+        }
+        Ok(())
+    }
+
+    /// Sends a DCS command, it is on the panel when this returns
+    #[cfg(not(feature = "async-flush"))]
+    async fn send_command(&mut self, command: PanelCommand) -> Result<(), DisplayError> {
+        let operation = Operation::Command(command);
+        let Some(display) = self.display.as_mut() else {
+            return Err(DisplayError::new(operation, Cause::NoPanel));
+        };
+        // Safety: none of the commands changes what the driver assumes about the panel
+        command
+            .send(unsafe { display.dcs() })
+            .map_err(|error| DisplayError::new(operation, InterfaceError::from(error)))
+    }
+
+    /// Sends a DCS command through the flush task, it is on the panel when this returns
+    #[cfg(feature = "async-flush")]
+    async fn send_command(&mut self, command: PanelCommand) -> Result<(), DisplayError> {
+        self.wait_for_flush().await?;
+        self.run_job(alloc::vec![command], false).await
+    }
+
+    /// Hands a job without regions to the flush task and waits for it, the
+    /// front buffer comes back unchanged
+    #[cfg(feature = "async-flush")]
+    async fn run_job(
         &mut self,
-        position: Point,
-        color: Rgb565,
+        commands: Vec<PanelCommand>,
+        reinit: bool,
     ) -> Result<(), DisplayError> {
-        use embedded_graphics::primitives::{PrimitiveStyleBuilder, Rectangle};
-        use embedded_graphics::Drawable;
+        self.rects.clear();
+        let job = FlushJob {
+            buffer: self.frame.take_front_buffer(),
+            width: self.frame.size().width as usize,
+            rects: core::mem::take(&mut self.rects),
+            commands,
+            lut: None,
+            reinit,
+            result: Ok(()),
+        };
+        FLUSH_JOBS.send(job).await;
+        self.in_flight = true;
+        self.wait_for_flush().await
+    }
 
-        let style = PrimitiveStyleBuilder::new().fill_color(color).build();
+    /// Pixels of the last frame sent to the panel, row by row. With
+    /// `async-flush` this waits until the frame is sent.
+    pub async fn front_buffer(&mut self) -> Result<&[Rgb565], DisplayError> {
+        #[cfg(feature = "async-flush")]
+        self.wait_for_flush().await?;
+        Ok(self.frame.front_buffer())
+    }
 
-        let mut target = BufferDrawTarget {
-            buffer: &mut self.back_buffer[..],
-            width: DISPLAY_WIDTH as usize,
-            height: DISPLAY_HEIGHT as usize,
-        };
+    /// The last frame sent to the panel and the regions of it that were
+    /// sent, waiting like [`Display::front_buffer`]
+    pub async fn sent_frame(&mut self) -> Result<(&[Rgb565], &[DirtyRect]), DisplayError> {
+        #[cfg(feature = "async-flush")]
+        self.wait_for_flush().await?;
+        Ok((self.frame.front_buffer(), self.frame.dirty_regions()))
+    }
 
-        // Draw 3x3 rectangle
-        let x = position.x.saturating_sub(1).max(0) as u16;
-        let y = position.y.saturating_sub(1).max(0) as u16;
-        let x2 = (position.x + 1).min(DISPLAY_WIDTH as i32 - 1) as u16;
-        let y2 = (position.y + 1).min(DISPLAY_HEIGHT as i32 - 1) as u16;
+    /// Rasterizes the frame drawn, swaps it to the front and computes the
+    /// regions to send
+    ///
+    /// Returns the time spent rasterizing and when the frame was ready.
+    #[cfg(not(feature = "dual-core"))]
+    async fn present(&mut self) -> Result<Option<(Duration, Instant)>, DisplayError> {
+        // The back buffer is not part of the transfer in flight
+        let start = Instant::now();
+        let Ok(()) = self.frame.rasterize();
+        let rasterized = Instant::now();
+
+        // Fence: the previous frame has to be sent before its buffer is drawn into again
+        #[cfg(feature = "async-flush")]
+        self.wait_for_flush().await?;
 
-        self.current_tiles.mark_rect(x, y, x2, y2);
+        // Swap buffers FIRST so front_buffer has the newly drawn frame
+        self.frame.swap_buffers();
+        self.frame.update_dirty_regions();
+        self.frame.finish_frame();
+        Ok(Some((rasterized - start, rasterized)))
+    }
 
-        Rectangle::new(position - Point::new(1, 1), Size::new(3, 3))
-            .into_styled(style)
-            .draw(&mut target)?;
+    /// Hands the frame drawn to core 1 and presents the one it rasterized
+    /// meanwhile, swapped to the front with its regions computed
+    ///
+    /// The frame drawn is sent with the next update, so the first update has
+    /// nothing to send and returns `None`. Core 1 works on it while this
+    /// frame is sent and the next one is drawn.
+    #[cfg(feature = "dual-core")]
+    async fn present(&mut self) -> Result<Option<(Duration, Instant)>, DisplayError> {
+        // Fence: the previous frame has to be sent before its buffer is drawn into again
+        #[cfg(feature = "async-flush")]
+        self.wait_for_flush().await?;
+
+        let finished = self.rasterizer.collect().await;
+        let rasterized = Instant::now();
+        let presented = finished.map(|(job, elapsed)| {
+            self.frame.finish_raster_job(job);
+            self.frame.swap_buffers();
+            self.frame.update_dirty_regions();
+            self.frame.finish_frame();
+            (elapsed, rasterized)
+        });
+
+        self.rasterizer.dispatch(self.frame.take_raster_job()).await;
+        Ok(presented)
+    }
 
-        Ok(())
+    /// Timings and size of the last frame sent to the panel
+    pub fn last_update(&self) -> &UpdateStats {
+        &self.last_update
     }
 
     /// Clears only the dirty tiles of the back buffer - call this at the start of each frame
     pub fn clear_buffer(&mut self) {
-        // Clear tiles that were dirty 2 frames ago
-        for tile_idx in 0..TOTAL_TILES {
-            if self.prev_tiles.is_dirty(tile_idx) {
-                let tile_x = (tile_idx % TILES_X) as u16;
-                let tile_y = (tile_idx / TILES_X) as u16;
-
-                let x_start = (tile_x * TILE_SIZE) as usize;
-                let y_start = (tile_y * TILE_SIZE) as usize;
-                let x_end = ((tile_x + 1) * TILE_SIZE).min(DISPLAY_WIDTH) as usize;
-                let y_end = ((tile_y + 1) * TILE_SIZE).min(DISPLAY_HEIGHT) as usize;
-
-                // Clear this tile
-                for y in y_start..y_end {
-                    let row_start = y * (DISPLAY_WIDTH as usize) + x_start;
-                    let row_end = y * (DISPLAY_WIDTH as usize) + x_end;
-                    self.back_buffer[row_start..row_end].fill(Rgb565::BLACK);
-                }
-            }
-        }
+        self.frame.clear_buffer();
+    }
+
+    /// See [`FrameBuffer::set_background`]
+    pub fn set_background(&mut self, enabled: bool) {
+        self.frame.set_background(enabled);
+    }
+
+    pub fn background_pixel(&self, x: u16, y: u16) -> Option<Rgb565> {
+        self.frame.background_pixel(x, y)
+    }
+
+    pub fn set_background_pixel(&mut self, x: u16, y: u16, color: Rgb565) {
+        self.frame.set_background_pixel(x, y, color);
     }
 }
 
+/// Display error of the firmware, with the errors of its panel interfaces
+pub type DisplayError = pixels_core::display::DisplayError<InterfaceError>;
+
 #[derive(Debug)]
-pub enum DisplayError {
-    Infallible,
-    SpiError(#[allow(unused)] SpiError<DeviceError<Error, Infallible>, Infallible>),
+pub enum InterfaceError {
+    Spi(#[allow(unused)] SpiError<DeviceError<Error, Infallible>, Infallible>),
+    #[cfg(feature = "qspi")]
+    Qspi(#[allow(unused)] Error),
 }
 
-impl From<SpiError<DeviceError<Error, Infallible>, Infallible>> for DisplayError {
-    fn from(err: SpiError<DeviceError<Error, Infallible>, Infallible>) -> Self {
-        DisplayError::SpiError(err)
+#[cfg(feature = "qspi")]
+impl From<Error> for InterfaceError {
+    fn from(err: Error) -> Self {
+        InterfaceError::Qspi(err)
     }
 }
 
-impl From<Infallible> for DisplayError {
-    fn from(_: Infallible) -> Self {
-        Self::Infallible
+impl From<SpiError<DeviceError<Error, Infallible>, Infallible>> for InterfaceError {
+    fn from(err: SpiError<DeviceError<Error, Infallible>, Infallible>) -> Self {
+        InterfaceError::Spi(err)
     }
 }
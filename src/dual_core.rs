@@ -0,0 +1,148 @@
+//! Rasterizing on the second core of the ESP32-S3
+//!
+//! Every frame is handed to core 1 as a [`RasterJob`] once it is drawn. Core
+//! 1 rasterizes it while core 0 sends the frame before it to the panel and
+//! draws the one after it, which is swapped in at the next update. Between
+//! jobs core 1 is parked by [`raster_task`], so it does not spin while the
+//! display idles.
+
+use core::sync::atomic::{AtomicBool, Ordering};
+
+use embassy_executor::Spawner;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::channel::Channel;
+use esp_hal::peripherals::CPU_CTRL;
+use esp_hal::system::{AppCoreGuard, Cpu, CpuControl, Error, Stack};
+use esp_hal::time::{Duration, Instant};
+use pixels_core::dual_core::Handoff;
+use pixels_core::framebuffer::RasterJob;
+use static_cell::StaticCell;
+
+/// Stack of the rasterizer on core 1
+const APP_CORE_STACK_SIZE: usize = 8192;
+
+static APP_CORE_STACK: StaticCell<Stack<APP_CORE_STACK_SIZE>> = StaticCell::new();
+
+/// Frames handed over by [`Rasterizer::dispatch`]
+static RASTER_JOBS: Channel<CriticalSectionRawMutex, Job, 1> = Channel::new();
+/// Frames core 1 has finished
+static RASTER_DONE: Channel<CriticalSectionRawMutex, Job, 1> = Channel::new();
+/// The job core 1 works on, from [`raster_task`]
+static APP_CORE_JOBS: Handoff<Job, 1> = Handoff::new();
+/// The job core 1 finished, back to [`raster_task`]
+static APP_CORE_DONE: Handoff<Job, 1> = Handoff::new();
+/// Set once core 1 takes jobs, until then core 0 rasterizes every frame itself
+static APP_CORE_STARTED: AtomicBool = AtomicBool::new(false);
+
+/// A frame to rasterize and the time it took
+struct Job {
+    raster: RasterJob,
+    elapsed: Duration,
+}
+
+impl Job {
+    fn rasterize(&mut self) {
+        let start = Instant::now();
+        let Ok(()) = self.raster.rasterize();
+        self.elapsed = start.elapsed();
+    }
+}
+
+/// Starts the rasterizer on core 1
+///
+/// Without it, frames are rasterized on core 0 alone.
+pub fn start(cpu_ctrl: CPU_CTRL<'static>, spawner: &Spawner) -> Result<(), Error> {
+    let mut cpu_control = CpuControl::new(cpu_ctrl);
+    let stack = APP_CORE_STACK.init(Stack::new());
+
+    let guard = cpu_control.start_app_core(stack, || loop {
+        // Only polls for a moment, core 1 is parked once its job is taken
+        match APP_CORE_JOBS.pop() {
+            Some(mut job) => {
+                job.rasterize();
+                // One job at a time, the result slot is always free
+                let _ = APP_CORE_DONE.push(job);
+            }
+            None => core::hint::spin_loop(),
+        }
+    })?;
+
+    spawner.must_spawn(raster_task(cpu_control, guard));
+    APP_CORE_STARTED.store(true, Ordering::Release);
+    Ok(())
+}
+
+/// Runs the jobs of [`Rasterizer::dispatch`] on core 1, which is parked when
+/// it has none
+///
+/// Core 1 is parked for good when the guard is dropped.
+#[embassy_executor::task]
+async fn raster_task(mut cpu_control: CpuControl<'static>, _guard: AppCoreGuard<'static>) {
+    loop {
+        let job = RASTER_JOBS.receive().await;
+        // One job at a time, the slot is always free
+        let Ok(()) = APP_CORE_JOBS.push(job) else {
+            unreachable!("core 1 has a job already");
+        };
+        cpu_control.unpark_core(Cpu::AppCpu);
+
+        // Core 0 keeps running the other tasks between polls
+        let job = loop {
+            if let Some(job) = APP_CORE_DONE.pop() {
+                break job;
+            }
+            embassy_futures::yield_now().await;
+        };
+        // SAFETY: core 1 gave its job back, it only polls for the next one
+        // and holds no lock
+        unsafe { cpu_control.park_core(Cpu::AppCpu) };
+
+        RASTER_DONE.send(job).await;
+    }
+}
+
+/// Hands frames to core 1 and takes them back, one at a time
+pub struct Rasterizer {
+    /// Whether a job was handed to core 1 and not taken back yet
+    in_flight: bool,
+    /// A job rasterized on core 0, because core 1 did not start
+    done: Option<Job>,
+}
+
+impl Rasterizer {
+    pub const fn new() -> Self {
+        Self {
+            in_flight: false,
+            done: None,
+        }
+    }
+
+    /// Rasterizes `raster` on core 1, or right away on core 0 if it did not start
+    ///
+    /// It has to be taken back with [`Rasterizer::collect`] before the next
+    /// one is dispatched.
+    pub async fn dispatch(&mut self, raster: RasterJob) {
+        let mut job = Job {
+            raster,
+            elapsed: Duration::ZERO,
+        };
+        if APP_CORE_STARTED.load(Ordering::Acquire) {
+            RASTER_JOBS.send(job).await;
+            self.in_flight = true;
+        } else {
+            job.rasterize();
+            self.done = Some(job);
+        }
+    }
+
+    /// Waits for the job dispatched last and returns it with the time it took
+    /// to rasterize, `None` if there is none
+    pub async fn collect(&mut self) -> Option<(RasterJob, Duration)> {
+        if self.in_flight {
+            let job = RASTER_DONE.receive().await;
+            self.in_flight = false;
+            self.done = Some(job);
+        }
+        self.done.take().map(|job| (job.raster, job.elapsed))
+    }
+}
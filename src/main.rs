@@ -6,21 +6,53 @@
     holding buffers for the duration of a data transfer."
 )]
 
-use config::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+use board::{DISPLAY_HEIGHT, DISPLAY_WIDTH};
+#[cfg(feature = "wifi")]
+use config::ASLEEP_POLL_MS;
+use config::{
+    BURN_IN, CRASH_SCREEN_MS, DIMMED_INK, DIM_BRIGHTNESS, DISPLAY_RECOVERY, FPS_GRAPH,
+    FPS_POSITION, IDLE_TIMEOUTS, INPUT_SOURCE, PROFILER_OVERLAY, PROFILER_POSITION,
+    SCREENSHOT_ENCODING, THROTTLED_FRAME_TIME_MS, WATCHDOG_TIMEOUT_MS,
+};
+use console::{Command, Console};
+use display::{Display, DisplayError, DisplayTrait};
 use drivers::cst816x::asynch::CST816xAsync;
+use drivers::cst816x::Event;
 use embassy_time::Delay;
-use display::{Display, DisplayPeripherals, DisplayTrait};
-use drivers::cst816x::{Event};
 use embedded_graphics::pixelcolor::{Rgb565, RgbColor};
-use embedded_graphics::prelude::Point;
+use embedded_graphics::prelude::{Point, Size};
+use embedded_graphics::primitives::Rectangle;
+use embedded_storage::nor_flash::NorFlash;
 use esp_alloc::psram_allocator;
-use esp_backtrace as _;
-use esp_hal::gpio::{InputConfig, Level, Output, OutputConfig, Pull};
-use esp_hal::time::Instant;
-use esp_hal::timer::timg::TimerGroup;
+use esp_bootloader_esp_idf::partitions::PARTITION_TABLE_MAX_LEN;
+use esp_hal::gpio::{InputConfig, Level, Output, OutputConfig, Pull, WakeEvent};
+#[cfg(not(feature = "wifi"))]
+use esp_hal::rtc_cntl::{sleep::GpioWakeupSource, Rtc};
+use esp_hal::time::{Duration, Instant};
+use esp_hal::timer::timg::{MwdtStage, TimerGroup};
+use esp_hal::usb_serial_jtag::UsbSerialJtag;
 use esp_hal::{clock::CpuClock, gpio::Input, i2c::master::I2c};
-use log::info;
-use micromath::{vector::F32x3, F32Ext, Quaternion};
+use esp_println::{println, Printer};
+use esp_storage::FlashStorage;
+use log::{info, warn};
+use pixels_core::arcball::Arcball;
+use pixels_core::burnin::{BurnIn, Hud};
+use pixels_core::config::TILE_SIZE;
+use pixels_core::crash::Cause;
+use pixels_core::fps::FpsMeter;
+use pixels_core::framebuffer::Geometry;
+use pixels_core::gesture::{Gesture, GestureRecognizer, Phase, TouchSample};
+use pixels_core::math::{self, Projection};
+use pixels_core::panel::ColorLut;
+use pixels_core::power::{IdlePolicy, PowerState};
+use pixels_core::recovery::Recovery;
+use pixels_core::replay::{self, Session};
+use pixels_core::scene::{Scene, World};
+use pixels_core::settings::Settings;
+use pixels_core::storage::SettingsStore;
+use pixels_core::touch::{Calibrator, TouchTransform};
+use pixels_core::ui::Ui;
+use profiler::{Profiler, Stage};
 
 extern crate alloc;
 
@@ -28,18 +60,34 @@ extern crate alloc;
 // For more information see: <https://docs.espressif.com/projects/esp-idf/en/stable/esp32/api-reference/system/app_image_format.html#application-description>
 esp_bootloader_esp_idf::esp_app_desc!();
 
+mod board;
 mod config;
+mod console;
 mod display;
-
-// Cube and projection constants
-const FOV: f32 = 200.0; // Field of View
-const PROJECTION_DISTANCE: f32 = 4.0;
-const ROTATION_SPEED: f32 = 0.03;
+#[cfg(feature = "dual-core")]
+mod dual_core;
+#[cfg(feature = "wifi")]
+mod net;
+mod panic;
+#[cfg(feature = "pixelflut")]
+mod pixelflut;
+mod profiler;
+#[cfg(feature = "wifi")]
+mod remote;
+mod screenshot;
+mod storage;
+
+/// Button opening the settings panel, in the top right corner
+const SETTINGS_BUTTON_SIZE: Size = Size::new(100, 30);
 
 #[esp_rtos::main]
-async fn main(_spawner: embassy_executor::Spawner) -> ! {
+async fn main(spawner: embassy_executor::Spawner) -> ! {
     esp_println::logger::init_logger_from_env();
 
+    // Only needed to start the flush and network tasks
+    #[cfg(not(any(feature = "async-flush", feature = "wifi")))]
+    let _ = spawner;
+
     let peripherals = esp_hal::init(esp_hal::Config::default().with_cpu_clock(CpuClock::_240MHz));
 
     esp_alloc::heap_allocator!(#[unsafe(link_section = ".dram2_uninit")] size: 73744);
@@ -48,322 +96,662 @@ async fn main(_spawner: embassy_executor::Spawner) -> ! {
 
     esp_rtos::start(timer_group0.timer0);
 
-    let i2c = I2c::new(peripherals.I2C0, esp_hal::i2c::master::Config::default())
-        .unwrap()
-        .with_sda(peripherals.GPIO3)
-        .with_scl(peripherals.GPIO2)
-        .into_async();
-
-    let display_peripherals = DisplayPeripherals {
-        sck: peripherals.GPIO47,
-        mosi: peripherals.GPIO18,
-        cs: peripherals.GPIO6,
-        dc: peripherals.GPIO7,
-        rst: peripherals.GPIO17,
-        spi: peripherals.SPI2,
-        dma: peripherals.DMA_CH0,
-    };
+    // Resets the device when the render loop hangs, fed every frame
+    let mut watchdog = timer_group0.wdt;
+    watchdog.set_timeout(
+        MwdtStage::Stage0,
+        Duration::from_millis(WATCHDOG_TIMEOUT_MS),
+    );
+    watchdog.enable();
+
+    // Shown until closed, and by the `crash` command until the next reset
+    let crash_report = panic::take_report();
+    let mut crash_screen = crash_report.is_some();
+    if crash_report.is_some() {
+        warn!("The last run crashed, `crash` shows the report");
+    }
+
+    let board = board::board_peripherals!(peripherals);
+    info!("Board: {}", board::NAME);
 
     psram_allocator!(peripherals.PSRAM, esp_hal::psram);
 
-    // Enable the power management IC by setting the PMICEN pin high
-    let mut pmicen = Output::new(peripherals.GPIO38, Level::Low, OutputConfig::default());
-    pmicen.set_high();
-    info!("PMICEN set high");
+    // Core 1 rasterizes each frame while core 0 sends the last one and draws the next
+    #[cfg(feature = "dual-core")]
+    if let Err(error) = dual_core::start(peripherals.CPU_CTRL, &spawner) {
+        warn!("Core 1 did not start, rasterizing on core 0: {:?}", error);
+    }
 
-    let mut display = Display::new(display_peripherals).expect("Display init failed");
+    // Enable the power management IC (or backlight) by setting its enable pin high
+    let _power_enable = board.power_enable.map(|pin| {
+        let mut pin = Output::new(pin, Level::Low, OutputConfig::default());
+        pin.set_high();
+        info!("Power enable pin set high");
+        pin
+    });
+
+    let geometry = Geometry::new(DISPLAY_WIDTH, DISPLAY_HEIGHT, TILE_SIZE);
+    #[cfg(not(feature = "async-flush"))]
+    let display = Display::new(board.display, geometry);
+    #[cfg(feature = "async-flush")]
+    let display = Display::new(board.display, geometry, &spawner);
+    // Nothing to show an error on, the report is shown after the reset
+    let mut display = display.unwrap_or_else(|error| {
+        panic::fail(
+            Cause::Display,
+            format_args!("Display init failed: {}", error),
+        )
+    });
 
     info!("Display initialized!");
 
-    // Define cube vertices
-    let cube_vertices: [F32x3; 8] = [
-        F32x3::from((-1.0, -1.0, -1.0)),
-        F32x3::from((1.0, -1.0, -1.0)),
-        F32x3::from((1.0, 1.0, -1.0)),
-        F32x3::from((-1.0, 1.0, -1.0)),
-        F32x3::from((-1.0, -1.0, 1.0)),
-        F32x3::from((1.0, -1.0, 1.0)),
-        F32x3::from((1.0, 1.0, 1.0)),
-        F32x3::from((-1.0, 1.0, 1.0)),
-    ];
-
-    // Define cube edges (pairs of vertex indices)
-    let cube_edges = [
-        (0, 1),
-        (1, 2),
-        (2, 3),
-        (3, 0), // Back face
-        (4, 5),
-        (5, 6),
-        (6, 7),
-        (7, 4), // Front face
-        (0, 4),
-        (1, 5),
-        (2, 6),
-        (3, 7), // Connecting edges
-    ];
-
-    // Particle system
-    const MAX_PARTICLES: usize = 200;
-    const EMISSION_RATE: usize = 3; // Particles per frame
-    const PARTICLE_SPEED: f32 = 0.02;
-
-    #[derive(Copy, Clone)]
-    struct Particle {
-        pos: F32x3,
-        vel: F32x3,
-        active: bool,
-        color: Rgb565,
-    }
-
-    let mut particles = [Particle {
-        pos: F32x3::from((0.0, 0.0, 0.0)),
-        vel: F32x3::from((0.0, 0.0, 0.0)),
-        active: false,
-        color: Rgb565::WHITE,
-    }; MAX_PARTICLES];
+    let screen = display.size();
+    let screen_width = screen.width as i32;
+    let screen_height = screen.height as i32;
+
+    // Settings saved by the last run, the defaults without a settings partition
+    let mut flash = FlashStorage::new(peripherals.FLASH);
+    let mut partition_table = [0; PARTITION_TABLE_MAX_LEN];
+    let mut store = storage::open(&mut flash, &mut partition_table);
+    let stored = store
+        .as_ref()
+        .and_then(|store| store.load())
+        .unwrap_or_default();
+
+    // Touch input, frame times, commands, the random seed and the settings to
+    // start with, live, recorded or replayed
+    let now = Instant::now().duration_since_epoch();
+    let mut session = Session::new(
+        INPUT_SOURCE,
+        now.as_micros() as u32,
+        stored,
+        now.as_millis(),
+    );
+    let mut settings = session.settings();
+
+    let mut projection = Projection::new(
+        settings.fov,
+        settings.projection_distance,
+        Point::new(screen_width / 2, screen_height / 2),
+    );
+    let arcball = Arcball::new(
+        Point::new(screen_width / 2, screen_height / 2),
+        screen_width.min(screen_height) as f32 / 2.0,
+    );
+    // The cube and its particles
+    let mut world = World::new(arcball, session.seed());
+
+    // initalize touchpad, if the board has one and it answers
+    let mut touchpad = match board.touch {
+        Some(touch) => match I2c::new(touch.i2c, esp_hal::i2c::master::Config::default()) {
+            Ok(i2c) => {
+                let i2c = i2c.with_sda(touch.sda).with_scl(touch.scl).into_async();
+                let mut touch_int =
+                    Input::new(touch.int, InputConfig::default().with_pull(Pull::None));
+                // Wakes the CPU from light sleep, the controller pulls it low on a touch
+                if let Err(error) = touch_int.wakeup_enable(true, WakeEvent::LowLevel) {
+                    warn!("Touch wake-up unavailable: {:?}", error);
+                }
 
-    let mut rotation = Quaternion::IDENTITY;
-    let mut last_time = 0;
-    let half_width = (DISPLAY_WIDTH / 2) as i32;
-    let half_height = (DISPLAY_HEIGHT / 2) as i32;
+                let rst: Option<Output<'static>> = None;
+                let mut touchpad = CST816xAsync::new(i2c, touch_int, rst, Delay);
+                match touchpad.begin().await {
+                    Ok(_) => Some(touchpad),
+                    Err(error) => {
+                        warn!(
+                            "Touch controller not answering, auto-rotation only: {:?}",
+                            error
+                        );
+                        None
+                    }
+                }
+            }
+            Err(error) => {
+                warn!("Touch I2C setup failed, auto-rotation only: {:?}", error);
+                None
+            }
+        },
+        None => {
+            info!("Board has no touch controller, auto-rotation only");
+            None
+        }
+    };
 
-    // initalize touchpad
-    let touch_int = peripherals.GPIO21;
-    let touch_int = Input::new(touch_int, InputConfig::default().with_pull(Pull::None));
+    let crash_hint = if touchpad.is_some() {
+        "Tap to continue"
+    } else {
+        "Continues in a minute"
+    };
 
-    let rst: Option<Output<'static>> = None;
-    let mut touchpad = CST816xAsync::new(i2c, touch_int, rst, Delay);
-    touchpad.begin().await.expect("Failed to initialize touchpad");
+    let mut gestures = GestureRecognizer::new();
+    let mut touch = TouchTransform::new(board::PANEL_SIZE, board::ORIENTATION);
+    touch.set_calibration(settings.touch_calibration);
+    let mut calibrator: Option<Calibrator> = None;
 
-    let mut initial_touch_x: i32 = 0;
-    let mut initial_touch_y: i32 = 0;
+    let mut last_time = 0;
 
-    // Pre-allocated buffer for FPS text to avoid allocations every frame
-    let mut fps_buffer = [0u8; 16];
+    // Dims and then sleeps the screen without input, only if a touch can wake it
+    let mut power = touchpad
+        .is_some()
+        .then(|| IdlePolicy::new(IDLE_TIMEOUTS, last_time));
+    #[cfg(not(feature = "wifi"))]
+    let mut rtc = Rtc::new(peripherals.LPWR);
+
+    // Pre-calculate the automatic rotation quaternion, again when the settings change
+    let mut q_auto = math::axis_angle(math::vec3(0.0, 1.0, 0.0), settings.rotation_speed);
+    // Applied in the first frame
+    let mut settings_changed = true;
+    // Display settings sent to the panel
+    let mut shown: Option<Settings> = None;
+
+    let mut ui = Ui::new();
+    let mut settings_open = false;
+    let settings_button = Rectangle::new(
+        Point::new(screen_width - SETTINGS_BUTTON_SIZE.width as i32, 0),
+        SETTINGS_BUTTON_SIZE,
+    );
+
+    // Burn-in mitigation on OLED panels, nothing moves on LCDs
+    let mut burn_in = board::OLED.then(|| BurnIn::new(BURN_IN, screen));
+    let mut fps_hud = Hud::new(
+        Rectangle::new(FPS_POSITION, FpsMeter::size(FPS_GRAPH)),
+        screen,
+    );
+    let mut button_hud = Hud::new(settings_button, screen);
+    // Only orbits, the profiler overlay is for debugging and has no wear
+    let profiler_hud = Hud::new(Rectangle::new(PROFILER_POSITION, Size::zero()), screen);
+
+    // Commands typed on the USB serial port
+    let (console_rx, _) = UsbSerialJtag::new(peripherals.USB_DEVICE).split();
+    let mut console = Console::new(console_rx);
+    let mut scene = Scene::Cube;
+    let mut paused = false;
+    // Frames to animate while paused
+    let mut steps = 0;
+    // Send the next frame over serial once it is on the panel
+    let mut screenshot_pending = false;
+
+    // Streams the frames to a client over Wi-Fi and takes its touches and commands
+    #[cfg(feature = "wifi")]
+    let stack = {
+        let rng = esp_hal::rng::Rng::new();
+        let seed = (rng.random() as u64) << 32 | rng.random() as u64;
+        net::start(&spawner, peripherals.WIFI, seed)
+    };
+    #[cfg(feature = "wifi")]
+    let mut remote = remote::Remote::new(stack);
+    // Clients drawing on the canvas scene
+    #[cfg(feature = "pixelflut")]
+    let mut pixelflut = pixelflut::Server::new(stack);
 
-    // Pre-calculate the constant automatic rotation quaternion
-    let q_auto = Quaternion::axis_angle(F32x3::from((0.0, 1.0, 0.0)), ROTATION_SPEED);
+    let mut fps = FpsMeter::new();
+    let mut profiler = Profiler::new();
+    // Display errors are retried, then the panel is initialized again
+    let mut recovery = Recovery::new(DISPLAY_RECOVERY);
 
     loop {
+        watchdog.feed();
+        let frame_start = Instant::now();
+        profiler.start();
+        // The first display error of the frame, recovered from at its end
+        let mut failed = None;
+
+        // The canvas scene keeps what was drawn on it
+        display.set_background(scene.has_canvas());
         // Clear buffer at start of frame (optimization: clear before rendering instead of after swap)
         display.clear_buffer();
+        profiler.lap(Stage::Clear);
+
+        let mut sample = None;
+        if let Some(touchpad) = touchpad.as_mut() {
+            if let Ok(touch_event) = touchpad.read_touch().await {
+                let phase = match touch_event.event {
+                    Event::Down => Some(Phase::Down),
+                    Event::Contact => Some(Phase::Move),
+                    Event::Up => Some(Phase::Up),
+                    //ingore other touch events
+                    _ => None,
+                };
 
-        // FPS calculation and display
-        let current_time = Instant::now().duration_since_epoch().as_millis();
-
-        if let Ok(touch_event) = touchpad.read_touch().await {
-                match touch_event.event {
-                    Event::Down => {
-                    initial_touch_x = touch_event.x as i32;
-                    initial_touch_y = touch_event.y as i32;
-                    //println!("Touch Down at ({}, {})", initial_touch_x, initial_touch_y);
-                    }
-                Event::Up => {
-                    // Touch Lift
-                    //println!("Touch Lift at ({}, {})", touch_event.x, touch_event.y);
-
-                    // Calculate the difference between initial and final touch positions
-                    let delta_x = touch_event.x as i32 - initial_touch_x;
-                    let delta_y = touch_event.y as i32 - initial_touch_y;
-
-                    //println!("Touch Delta: ({}, {})", delta_x, delta_y);
-
-                    // Define rotation sensitivity
-                    const ROTATION_SENSITIVITY: f32 = 0.0005;
+                sample = phase.map(|phase| TouchSample {
+                    phase,
+                    position: Point::new(touch_event.x as i32, touch_event.y as i32),
+                    time_ms: 0,
+                });
+            }
+        }
 
-                    // Calculate rotation angles based on touch movement
-                    let angle_y = (delta_x as f32) * ROTATION_SENSITIVITY; // Rotate around Y-axis
-                    let angle_x = (delta_y as f32) * ROTATION_SENSITIVITY; // Rotate around X-axis
+        #[cfg(feature = "wifi")]
+        remote.poll().await;
+        #[cfg(feature = "pixelflut")]
+        pixelflut.serve(&mut display, scene.has_canvas()).await;
 
-                    // Create quaternions for the rotations
-                        let qx = Quaternion::axis_angle(F32x3::from((1.0, 0.0, 0.0)), angle_x);
-                        let qy = Quaternion::axis_angle(F32x3::from((0.0, 1.0, 0.0)), angle_y);
+        // Time for the gestures and the spin
+        let frame = session.frame(Instant::now().duration_since_epoch().as_millis(), sample);
+        if let Some(recording) = session.take_full_recording() {
+            let _ = replay::dump(&recording, &mut Printer);
+        }
+        let current_time = frame.time_ms;
+        fps.tick(current_time);
+        let mut sample = frame.touch;
+        // Touches keep the screen on, the one that wakes the panel does nothing else
+        if let (Some(_), Some(power)) = (sample, power.as_mut()) {
+            power.activity(current_time);
+            if power.state() == PowerState::Asleep {
+                sample = None;
+            }
+        }
+        let frame_ms = current_time.saturating_sub(last_time);
+        let dt = frame_ms as f32 / 1000.0;
+        last_time = current_time;
 
-                    // Update the overall rotation
-                        rotation = qy * qx * rotation;
+        if crash_screen {
+            // The closing touch does nothing else
+            let released = sample.is_some_and(|sample| sample.phase == Phase::Up);
+            crash_screen = !released && current_time < CRASH_SCREEN_MS;
+        } else if let Some(calibrating) = calibrator.as_mut() {
+            let calibration = sample
+                .and_then(|sample| calibrating.touch(sample.phase, touch.orient(sample.position)));
+            if let Some(calibration) = calibration {
+                info!("Touch calibrated: {:?}", calibration);
+                touch.set_calibration(calibration);
+                settings.touch_calibration = calibration;
+                save_settings(store.as_mut(), &settings);
+                calibrator = None;
+            }
+        } else {
+            let sample = sample.map(|sample| TouchSample {
+                position: touch.to_screen(sample.position),
+                ..sample
+            });
+            // Remote touches are in screen coordinates already
+            #[cfg(feature = "wifi")]
+            let sample = sample.or_else(|| {
+                session
+                    .remote_touch(remote.touch())
+                    .map(|(phase, position)| TouchSample {
+                        phase,
+                        position,
+                        time_ms: current_time,
+                    })
+            });
+            #[cfg(feature = "wifi")]
+            if let (Some(_), Some(power)) = (sample, power.as_mut()) {
+                power.activity(current_time);
+            }
+            ui.begin(sample);
+            // Touches on the widgets are not for the scene
+            let sample = sample.filter(|_| !ui.captures_touch());
+            match sample.map(|sample| sample.phase) {
+                Some(Phase::Down) => world.arcball.hold(),
+                Some(Phase::Up) => world.arcball.release(),
+                _ => {}
+            }
 
-                    //println!("Applied rotation: {:?}", &rotation);
+            let polled = gestures.poll(current_time);
+            for gesture in sample
+                .into_iter()
+                .flat_map(|sample| gestures.update(sample))
+                .chain(polled)
+            {
+                match gesture {
+                    Gesture::LongPress(_) => {
+                        info!("Starting touch calibration");
+                        calibrator = Some(Calibrator::new(screen));
+                        world.arcball.release();
                     }
-                _ => {
-                    //ingore other touch events
+                    Gesture::DoubleTap(_) => {
+                        let _ = session.dump(&mut Printer);
+                    }
+                    _ => world.gesture(gesture),
                 }
             }
         }
 
-        // Apply pre-calculated automatic rotation
-            rotation = q_auto * rotation;
-
-        // Emit new particles from center
-        for _ in 0..EMISSION_RATE {
-            // Find an inactive particle slot
-            if let Some(p) = particles.iter_mut().find(|p| !p.active) {
-                // Simple pseudo-random using time
-                let t = current_time as f32;
-                let rand_x = ((t * 0.123) % 1.0) * 2.0 - 1.0;
-                let rand_y = ((t * 0.456) % 1.0) * 2.0 - 1.0;
-                let rand_z = ((t * 0.789) % 1.0) * 2.0 - 1.0;
-
-                // Normalize direction and apply speed
-                let len = (rand_x * rand_x + rand_y * rand_y + rand_z * rand_z).sqrt();
-                let vel = if len > 0.01 {
-                    F32x3::from((
-                        rand_x / len * PARTICLE_SPEED,
-                        rand_y / len * PARTICLE_SPEED,
-                        rand_z / len * PARTICLE_SPEED,
-                    ))
-                } else {
-                    F32x3::from((PARTICLE_SPEED, 0.0, 0.0))
-                };
-
-                // Generate random color
-                let color_seed = (t * 0.321) % 1.0;
-                let color = if color_seed < 0.166 {
-                    Rgb565::RED
-                } else if color_seed < 0.333 {
-                    Rgb565::GREEN
-                } else if color_seed < 0.5 {
-                    Rgb565::BLUE
-                } else if color_seed < 0.666 {
-                    Rgb565::YELLOW
-                } else if color_seed < 0.833 {
-                    Rgb565::CYAN
-                } else {
-                    Rgb565::MAGENTA
-                };
-
-                p.pos = F32x3::from((0.0, 0.0, 0.0)); // Emit from center
-                p.vel = vel;
-                p.active = true;
-                p.color = color;
+        // Serial console and remote commands, responses are printed without a log prefix
+        loop {
+            #[cfg(feature = "wifi")]
+            let remote_line;
+            let live = match console.read_line() {
+                Some(line) => Some(line),
+                #[cfg(feature = "wifi")]
+                None => match remote.command() {
+                    Some(line) => {
+                        remote_line = line;
+                        Some(remote_line.as_str())
+                    }
+                    None => None,
+                },
+                #[cfg(not(feature = "wifi"))]
+                None => None,
+            };
+            // Recorded with the frame, or the recorded ones instead
+            let Some(line) = session.command(live) else {
+                break;
+            };
+            if let Some(power) = power.as_mut() {
+                power.activity(current_time);
             }
-        }
+            let command = match console::parse(line) {
+                Ok(command) => command,
+                Err(error) => {
+                    println!("{}", error);
+                    continue;
+                }
+            };
 
-        // Update particles
-        for p in particles.iter_mut() {
-            if p.active {
-                // Update position
-                p.pos.x += p.vel.x;
-                p.pos.y += p.vel.y;
-                p.pos.z += p.vel.z;
-
-                // Constrain to cube boundaries and bounce
-                if p.pos.x > 1.0 || p.pos.x < -1.0 {
-                    p.vel.x = -p.vel.x;
-                    p.pos.x = p.pos.x.clamp(-1.0, 1.0);
+            match command {
+                Command::Help(command) => {
+                    let _ = console::help(command, &mut Printer);
+                }
+                Command::Get(key) => {
+                    let _ = console::write_settings(&settings, key, &mut Printer);
                 }
-                if p.pos.y > 1.0 || p.pos.y < -1.0 {
-                    p.vel.y = -p.vel.y;
-                    p.pos.y = p.pos.y.clamp(-1.0, 1.0);
+                Command::Set(key, value) => {
+                    if settings.set(key, value) {
+                        settings_changed = true;
+                        let _ = console::write_settings(&settings, Some(key), &mut Printer);
+                    } else {
+                        println!("invalid value '{}' for {}", value, key);
+                    }
+                }
+                Command::Save => save_settings(store.as_mut(), &settings),
+                Command::Scene(Some(next)) => {
+                    scene = next;
+                    println!("scene={}", scene.name());
                 }
-                if p.pos.z > 1.0 || p.pos.z < -1.0 {
-                    p.vel.z = -p.vel.z;
-                    p.pos.z = p.pos.z.clamp(-1.0, 1.0);
+                Command::Scene(None) => {
+                    for name in Scene::NAMES {
+                        println!("{}", name);
+                    }
                 }
+                Command::Pause | Command::Resume => {
+                    paused = command == Command::Pause;
+                    steps = 0;
+                    println!("paused={}", paused);
+                }
+                Command::Step(frames) => {
+                    paused = true;
+                    steps = steps.saturating_add(frames);
+                    println!("steps={}", steps);
+                }
+                Command::Profile => {
+                    let _ = profiler.profile().report(&mut Printer);
+                }
+                Command::Screenshot => screenshot_pending = true,
+                Command::Crash => match crash_report.as_ref() {
+                    Some(report) => {
+                        let _ = report.write(&mut Printer);
+                    }
+                    None => println!("no crash since power-on"),
+                },
+                Command::Reset => {
+                    world.reset();
+                    println!("particles=0");
+                }
+            }
+        }
+        profiler.lap(Stage::Input);
+
+        if let Some(power) = power.as_mut() {
+            let previous = power.state();
+            if let Some(state) = power.update(current_time) {
+                info!("Power state {:?}", state);
+                let brightness = idle_brightness(&settings, state);
+                let result = set_power_state(&mut display, previous, state, brightness).await;
+                check(&mut failed, result);
             }
         }
+        if power
+            .as_ref()
+            .is_some_and(|power| power.state() == PowerState::Asleep)
+        {
+            if recover(&mut display, &mut recovery, failed, true).await {
+                shown = None;
+                settings_changed = true;
+            }
+            // Nothing is rendered until a touch wakes the panel, the watchdog
+            // would reset the device before that
+            #[cfg(not(feature = "wifi"))]
+            {
+                watchdog.disable();
+                rtc.sleep_light(&[&GpioWakeupSource::new()]);
+                watchdog.enable();
+            }
+            #[cfg(feature = "wifi")]
+            embassy_time::Timer::after_millis(ASLEEP_POLL_MS).await;
+            continue;
+        }
 
-        // Render CUBE at center
-        let cube_offset_x = 0; // Centered
-        let mut cube_transformed = [(0i32, 0i32); 8];
-
-        for (i, &v) in cube_vertices.iter().enumerate() {
-            let rotated = rotation.rotate(v);
-            let x = rotated.x;
-            let y = rotated.y;
-            let z = rotated.z + PROJECTION_DISTANCE;
-
-            let projected_point = if z.abs() > 0.01 {
-                let inv_z = 1.0 / z;
-                let px = (x * FOV * inv_z) as i32 + half_width + cube_offset_x;
-                let py = (y * FOV * inv_z) as i32 + half_height;
-                (px, py)
-            } else {
-                (i32::MAX, i32::MAX)
-            };
-            cube_transformed[i] = projected_point;
+        if settings_changed {
+            q_auto = math::axis_angle(math::vec3(0.0, 1.0, 0.0), settings.rotation_speed);
+            projection = Projection::new(
+                settings.fov,
+                settings.projection_distance,
+                Point::new(screen_width / 2, screen_height / 2),
+            );
+            let state = power.as_ref().map_or(PowerState::Active, IdlePolicy::state);
+            let result = show_settings(&mut display, &settings, shown.as_ref(), state).await;
+            check(&mut failed, result);
+            shown = Some(settings);
+            settings_changed = false;
         }
 
-        // Draw cube edges
-        for &(start, end) in &cube_edges {
-            let p1 = cube_transformed[start];
-            let p2 = cube_transformed[end];
+        // The scene and the overlays orbit, worn overlays move or dim, and an
+        // idle screen gets a pixel refresh now and then
+        let mut orbit = Point::zero();
+        let (mut fps_ink, mut button_ink) = (Rgb565::WHITE, Rgb565::WHITE);
+        if let Some(burn_in) = burn_in.as_mut() {
+            let idle = calibrator.is_none()
+                && !settings_open
+                && power
+                    .as_ref()
+                    .is_none_or(|power| power.state() != PowerState::Active);
+            if let Some(color) = burn_in.refresh(current_time, frame_ms, idle) {
+                check(
+                    &mut failed,
+                    display.fill_rect(Rectangle::new(Point::zero(), screen), color),
+                );
+                check(&mut failed, display.update_with_buffer().await);
+                if recover(&mut display, &mut recovery, failed, false).await {
+                    shown = None;
+                    settings_changed = true;
+                }
+                continue;
+            }
 
-            if p1.0 != i32::MAX && p2.0 != i32::MAX {
-                let begin = Point::new(p1.0, p1.1);
-                let end = Point::new(p2.0, p2.1);
-                display.draw_line(begin, end).expect("Draw line failed");
+            burn_in.track(&mut fps_hud, frame_ms);
+            burn_in.track(&mut button_hud, frame_ms);
+            orbit = burn_in.orbit(current_time);
+            if burn_in.dimmed(&fps_hud) {
+                fps_ink = DIMMED_INK;
+            }
+            if burn_in.dimmed(&button_hud) {
+                button_ink = DIMMED_INK;
             }
         }
+        projection.set_center(Point::new(screen_width / 2, screen_height / 2) + orbit);
 
-        // Render particles
-        for p in particles.iter() {
-            if p.active {
-                // Apply rotation to particle position
-                let rotated = rotation.rotate(p.pos);
-                let x = rotated.x;
-                let y = rotated.y;
-                let z = rotated.z + PROJECTION_DISTANCE;
-
-                // Project to screen
-                if z.abs() > 0.01 {
-                    let inv_z = 1.0 / z;
-                    let px = (x * FOV * inv_z) as i32 + half_width;
-                    let py = (y * FOV * inv_z) as i32 + half_height;
-
-                    // Draw particle as colored point
-                    if px >= 1
-                        && px < DISPLAY_WIDTH as i32 - 1
-                        && py >= 1
-                        && py < DISPLAY_HEIGHT as i32 - 1
-                    {
-                        display
-                            .draw_colored_point(Point::new(px, py), p.color)
-                            .expect("Draw particle failed");
-                    }
+        // While paused only drags rotate the cube, `step` animates single frames
+        let animate = !paused || steps > 0;
+        if paused {
+            steps = steps.saturating_sub(1);
+        }
+
+        if animate {
+            world.step(dt, &settings, q_auto);
+        }
+        profiler.lap(Stage::Simulate);
+
+        // Let the flush task start its next DMA transfer before rendering
+        embassy_futures::yield_now().await;
+        profiler.start();
+
+        check(&mut failed, world.draw(&mut display, &projection, scene));
+
+        profiler.lap(Stage::Project);
+
+        let profiler_position = PROFILER_POSITION + profiler_hud.offset(orbit);
+        check(
+            &mut failed,
+            profiler
+                .profile()
+                .draw_overlay(&mut display, PROFILER_OVERLAY, profiler_position),
+        );
+
+        if let Some(calibrating) = calibrator.as_ref() {
+            check(&mut failed, calibrating.draw(&mut display));
+        } else if !crash_screen {
+            if settings_open {
+                let changed = settings.edit(&mut ui, &mut display, &mut settings_open);
+                settings_changed |= check(&mut failed, changed);
+                // Saved once the panel is closed, not on every slider step
+                if !settings_open {
+                    save_settings(store.as_mut(), &settings);
                 }
+            } else {
+                let button = settings_button.translate(button_hud.offset(orbit));
+                display.set_ink(button_ink);
+                settings_open = check(&mut failed, ui.button(&mut display, button, "Settings"));
+                display.set_ink(Rgb565::WHITE);
             }
+            ui.end();
         }
 
-        let ms_per_frame = current_time - last_time;
-        if ms_per_frame > 0 {
-            // Use pre-allocated buffer and format FPS text without heap allocation
-            let fps = 1000 / ms_per_frame;
-            let mut cursor = 0;
-            let prefix = b"FPS: ";
-            fps_buffer[..prefix.len()].copy_from_slice(prefix);
-            cursor += prefix.len();
-
-            // Format the number manually to avoid allocation
-            let mut num = fps;
-            let mut digits = [0u8; 10];
-            let mut digit_count = 0;
-            if num == 0 {
-                digits[0] = b'0';
-                digit_count = 1;
-            } else {
-                while num > 0 {
-                    digits[digit_count] = b'0' + (num % 10) as u8;
-                    num /= 10;
-                    digit_count += 1;
-                }
+        display.set_ink(fps_ink);
+        let fps_position = FPS_POSITION + fps_hud.offset(orbit);
+        check(&mut failed, fps.draw(&mut display, fps_position, FPS_GRAPH));
+        display.set_ink(Rgb565::WHITE);
+
+        // Over everything else until it is closed
+        if let Some(report) = crash_report.as_ref().filter(|_| crash_screen) {
+            check(&mut failed, report.draw(&mut display, crash_hint));
+        }
+
+        check(&mut failed, display.update_with_buffer().await);
+        profiler.end_frame(display.last_update());
+
+        if screenshot_pending && failed.is_none() {
+            screenshot_pending = false;
+            let size = display.size();
+            match display.front_buffer().await {
+                Ok(pixels) => screenshot::send(pixels, size, SCREENSHOT_ENCODING),
+                Err(error) => check(&mut failed, Err(error)),
             }
-            // Reverse digits into buffer
-            for i in 0..digit_count {
-                fps_buffer[cursor] = digits[digit_count - 1 - i];
-                cursor += 1;
+        }
+
+        // Waiting for the frame costs the overlap of async-flush, only with a client
+        #[cfg(feature = "wifi")]
+        if remote.has_client() && failed.is_none() {
+            let size = display.size();
+            match display.sent_frame().await {
+                Ok((pixels, regions)) => remote.send(pixels, size, regions).await,
+                Err(error) => check(&mut failed, Err(error)),
             }
+        }
 
-            let text = core::str::from_utf8(&fps_buffer[..cursor]).unwrap();
-            display
-                .write(text, Point::new(0, 0))
-                .expect("Write text failed");
+        if recover(&mut display, &mut recovery, failed, false).await {
+            shown = None;
+            settings_changed = true;
         }
 
-        last_time = current_time;
+        if power
+            .as_ref()
+            .is_some_and(|power| power.state() == PowerState::Throttled)
+        {
+            let elapsed = frame_start.elapsed().as_millis();
+            embassy_time::Timer::after_millis(THROTTLED_FRAME_TIME_MS.saturating_sub(elapsed))
+                .await;
+        }
+    }
+}
+
+/// The value of `result`, or the default after keeping its error in `failed`
+/// unless there is one already
+fn check<T: Default>(failed: &mut Option<DisplayError>, result: Result<T, DisplayError>) -> T {
+    result.unwrap_or_else(|error| {
+        failed.get_or_insert(error);
+        T::default()
+    })
+}
 
+/// Recovers from the display error of a frame, if there was one, see
+/// [`pixels_core::recovery::recover`]
+///
+/// Returns `true` if the panel was initialized again, it then needs the
+/// display settings again and is put back to sleep if it was `asleep`. Keeps
+/// a crash report and resets when nothing helps.
+async fn recover(
+    display: &mut Display,
+    recovery: &mut Recovery,
+    failed: Option<DisplayError>,
+    asleep: bool,
+) -> bool {
+    pixels_core::recovery::recover(display, recovery, &mut Delay, failed, asleep)
+        .await
+        .unwrap_or_else(|error| {
+            panic::fail(Cause::Display, format_args!("Display failed: {}", error))
+        })
+}
+
+/// Changes the panel brightness or puts it to sleep for an idle state
+async fn set_power_state(
+    display: &mut Display,
+    previous: PowerState,
+    state: PowerState,
+    brightness: u8,
+) -> Result<(), DisplayError> {
+    if previous == PowerState::Asleep {
+        display.wake().await?;
+    }
+    match state {
+        PowerState::Asleep => display.sleep().await,
+        _ => display.set_brightness(brightness).await,
+    }
+}
+
+/// Brightness in an idle state, only dimmed with automatic brightness
+fn idle_brightness(settings: &Settings, state: PowerState) -> u8 {
+    match state {
+        PowerState::Dimmed | PowerState::Throttled if settings.auto_brightness => {
+            DIM_BRIGHTNESS.min(settings.brightness)
+        }
+        _ => settings.brightness,
+    }
+}
+
+/// Sends the display settings that differ from `shown`, all of them without it
+async fn show_settings(
+    display: &mut Display,
+    settings: &Settings,
+    shown: Option<&Settings>,
+    state: PowerState,
+) -> Result<(), DisplayError> {
+    let brightness_changed = shown.is_none_or(|shown| {
+        (shown.brightness, shown.auto_brightness) != (settings.brightness, settings.auto_brightness)
+    });
+    let invert_changed = shown.is_none_or(|shown| shown.invert != settings.invert);
+    let colors_changed = shown
+        .is_none_or(|shown| (shown.gamma, shown.contrast) != (settings.gamma, settings.contrast));
+
+    // Asleep the brightness is set when the panel wakes up
+    if brightness_changed && state != PowerState::Asleep {
         display
-            .update_with_buffer()
-            .expect("Update with buffer failed");
+            .set_brightness(idle_brightness(settings, state))
+            .await?;
+    }
+    if invert_changed {
+        display.set_inversion(settings.invert).await?;
+    }
+    if colors_changed {
+        display.set_color_correction(ColorLut::new(settings.gamma, settings.contrast));
+    }
+    Ok(())
+}
+
+/// Stores `settings` if there is a settings partition
+fn save_settings<F: NorFlash>(store: Option<&mut SettingsStore<F>>, settings: &Settings) {
+    if let Some(Err(error)) = store.map(|store| store.save(settings)) {
+        warn!("Saving settings failed: {:?}", error);
     }
 }
@@ -0,0 +1,90 @@
+//! Wi-Fi station and the network stack
+//!
+//! Joins [`WIFI_SSID`] with DHCP and reconnects whenever the connection is
+//! lost. The stack runs in its own task, sockets are created on the returned
+//! [`Stack`].
+
+use embassy_executor::Spawner;
+use embassy_futures::select::{select, Either};
+use embassy_net::{Runner, Stack, StackResources};
+use embassy_time::{Duration, Timer};
+use esp_hal::peripherals::WIFI;
+use esp_radio::wifi::{ClientConfig, ModeConfig, WifiController, WifiDevice, WifiEvent};
+use log::{info, warn};
+use static_cell::StaticCell;
+
+use crate::config::{WIFI_PASSWORD, WIFI_SSID};
+
+/// DHCP and the remote display
+#[cfg(not(feature = "pixelflut"))]
+const SOCKETS: usize = 3;
+/// DHCP, the remote display and the Pixelflut clients
+#[cfg(feature = "pixelflut")]
+const SOCKETS: usize = 3 + crate::pixelflut::MAX_CLIENTS;
+
+/// Wait before trying to connect again
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Starts the Wi-Fi and network tasks, `seed` randomizes ports and sequence numbers
+pub fn start(spawner: &Spawner, wifi: WIFI<'static>, seed: u64) -> Stack<'static> {
+    static RADIO: StaticCell<esp_radio::Controller<'static>> = StaticCell::new();
+    static RESOURCES: StaticCell<StackResources<SOCKETS>> = StaticCell::new();
+
+    let radio = RADIO.init(esp_radio::init().expect("Radio init failed"));
+    let (controller, interfaces) =
+        esp_radio::wifi::new(radio, wifi, Default::default()).expect("Wi-Fi init failed");
+    let (stack, runner) = embassy_net::new(
+        interfaces.sta,
+        embassy_net::Config::dhcpv4(Default::default()),
+        RESOURCES.init(StackResources::new()),
+        seed,
+    );
+
+    spawner.must_spawn(connection_task(controller, stack));
+    spawner.must_spawn(net_task(runner));
+    stack
+}
+
+#[embassy_executor::task]
+async fn connection_task(mut controller: WifiController<'static>, stack: Stack<'static>) {
+    let config = ModeConfig::Client(
+        ClientConfig::default()
+            .with_ssid(WIFI_SSID.into())
+            .with_password(WIFI_PASSWORD.into()),
+    );
+    if let Err(error) = controller.set_config(&config) {
+        warn!("Wi-Fi configuration failed: {:?}", error);
+        return;
+    }
+
+    loop {
+        if !matches!(controller.is_started(), Ok(true)) {
+            if let Err(error) = controller.start_async().await {
+                warn!("Starting Wi-Fi failed: {:?}", error);
+            }
+        }
+
+        match controller.connect_async().await {
+            Ok(()) => {
+                info!("Wi-Fi connected to {}", WIFI_SSID);
+                // The connection can drop before DHCP is done
+                let disconnected = controller.wait_for_event(WifiEvent::StaDisconnected);
+                let up = select(stack.wait_config_up(), disconnected).await;
+                if let Either::First(()) = up {
+                    if let Some(config) = stack.config_v4() {
+                        info!("Address: {}", config.address.address());
+                    }
+                    controller.wait_for_event(WifiEvent::StaDisconnected).await;
+                }
+                warn!("Wi-Fi disconnected");
+            }
+            Err(error) => warn!("Wi-Fi connection failed: {:?}", error),
+        }
+        Timer::after(RECONNECT_DELAY).await;
+    }
+}
+
+#[embassy_executor::task]
+async fn net_task(mut runner: Runner<'static, WifiDevice<'static>>) -> ! {
+    runner.run().await
+}
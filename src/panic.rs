@@ -0,0 +1,96 @@
+//! Panic handler that keeps a crash report for the next boot
+//!
+//! Prints the message and backtrace like `esp-backtrace` does, then encodes
+//! them into RTC fast memory and resets. That memory keeps its contents over
+//! a software or watchdog reset, not over a power cycle. Writing flash from a
+//! panic is not safe, the SPI bus may be what failed. [`fail`] does the same
+//! for errors that do not panic.
+
+use core::fmt::{self, Write};
+use core::panic::PanicInfo;
+
+use esp_hal::rtc_cntl::SocResetReason;
+use esp_println::println;
+use pixels_core::crash::{Cause, CrashReport, RECORD_LEN};
+
+/// Crash record of the last run, left alone by the startup code
+#[esp_hal::ram(unstable(rtc_fast, persistent))]
+static mut RECORD: [u8; RECORD_LEN] = [0; RECORD_LEN];
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    save_and_reset(
+        "====================== PANIC ======================",
+        Cause::Panic,
+        format_args!("{}", info),
+    )
+}
+
+/// Ends the run on an error the firmware cannot recover from, like a display
+/// that does not start. Keeps a report like a panic does, so the next boot
+/// shows it, and resets, which starts the hardware over.
+pub fn fail(cause: Cause, message: fmt::Arguments) -> ! {
+    save_and_reset(
+        "====================== FAILED =====================",
+        cause,
+        message,
+    )
+}
+
+/// Prints `message` and the backtrace, keeps them in the record and resets
+///
+/// Runs in a critical section that is never left, the reset ends it. With
+/// `dual-core` the other core may crash at the same time: it waits for the
+/// lock in its own `save_and_reset` until the reset, so only the first
+/// report is printed and kept. Interrupts of this core stay off.
+fn save_and_reset(banner: &str, cause: Cause, message: fmt::Arguments) -> ! {
+    // Safety: never released, nothing runs after the reset that would
+    // expect the lock back. The lock is reentrant on this core, so a panic
+    // in here gets through to the reset too.
+    let _ = unsafe { critical_section::acquire() };
+
+    println!("\n\n{}", banner);
+    println!("{}", message);
+
+    let mut report = CrashReport::new(cause);
+    // Cut when too long
+    let _ = report.message.write_fmt(message);
+
+    println!("\nBacktrace:\n");
+    for frame in esp_backtrace::Backtrace::capture().frames() {
+        let program_counter = frame.program_counter();
+        println!("0x{:x}", program_counter);
+        report.push_frame(program_counter as u32);
+    }
+
+    // Safety: only written here, by the one core that holds the critical
+    // section, and by `take_report` before the other core starts
+    report.encode(unsafe { &mut *core::ptr::addr_of_mut!(RECORD) });
+    esp_hal::system::software_reset()
+}
+
+/// The report of the crash that ended the last run, `None` after a clean
+/// start. Clears the record, call once at boot.
+pub fn take_report() -> Option<CrashReport> {
+    // Safety: called before anything can panic on the other core
+    let record = unsafe { &mut *core::ptr::addr_of_mut!(RECORD) };
+    let report = CrashReport::decode(record);
+    record.fill(0);
+
+    report.or_else(|| watchdog_reset().then(|| CrashReport::new(Cause::Watchdog)))
+}
+
+fn watchdog_reset() -> bool {
+    matches!(
+        esp_hal::system::reset_reason(),
+        Some(
+            SocResetReason::CoreMwdt0
+                | SocResetReason::CoreMwdt1
+                | SocResetReason::CoreRtcWdt
+                | SocResetReason::Cpu0Mwdt0
+                | SocResetReason::Cpu0Mwdt1
+                | SocResetReason::Cpu0RtcWdt
+                | SocResetReason::SysRtcWdt
+        )
+    )
+}
@@ -0,0 +1,204 @@
+//! Pixelflut server, clients draw on the canvas scene over TCP
+//!
+//! See [`pixels_protocol::pixelflut`] for the commands. Every frame the
+//! connected clients share [`PIXELFLUT_BUDGET`] commands equally, starting
+//! with a different client each frame, so a fast client cannot starve the
+//! others. What a client sends beyond its share waits in its socket, which
+//! slows the client down through TCP flow control.
+
+use core::future::ready;
+
+use embassy_futures::select::{select, Either};
+use embassy_net::tcp::{State, TcpSocket};
+use embassy_net::Stack;
+use embassy_time::Duration;
+use embedded_graphics::pixelcolor::{Rgb565, RgbColor};
+use log::{info, warn};
+use pixels_protocol::pixelflut::{Canvas, Session, MAX_REPLY_LEN, PORT};
+use static_cell::StaticCell;
+
+use crate::config::PIXELFLUT_BUDGET;
+use crate::display::{Display, DisplayTrait};
+
+/// Clients served at the same time, every one takes a socket
+pub const MAX_CLIENTS: usize = 4;
+/// Input read per frame, this bounds the commands a client gets through
+/// more than [`PIXELFLUT_BUDGET`] does
+const RX_BUFFER_LEN: usize = 8 * 1024;
+const TX_BUFFER_LEN: usize = 1024;
+/// Replies not sent yet, room for a few of the longest
+const REPLY_LEN: usize = 2 * MAX_REPLY_LEN;
+/// Idle connections are closed after this long
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+pub struct Server {
+    clients: [Client; MAX_CLIENTS],
+    /// Client served first in the next frame
+    first: usize,
+}
+
+struct Client {
+    socket: TcpSocket<'static>,
+    session: Session,
+    /// Whether `socket` listens for or has a connection
+    open: bool,
+    reply: [u8; REPLY_LEN],
+    reply_len: usize,
+}
+
+impl Server {
+    pub fn new(stack: Stack<'static>) -> Self {
+        static RX_BUFFERS: StaticCell<[[u8; RX_BUFFER_LEN]; MAX_CLIENTS]> = StaticCell::new();
+        static TX_BUFFERS: StaticCell<[[u8; TX_BUFFER_LEN]; MAX_CLIENTS]> = StaticCell::new();
+
+        let mut rx_buffers = RX_BUFFERS
+            .init([[0; RX_BUFFER_LEN]; MAX_CLIENTS])
+            .iter_mut();
+        let mut tx_buffers = TX_BUFFERS
+            .init([[0; TX_BUFFER_LEN]; MAX_CLIENTS])
+            .iter_mut();
+        let clients = core::array::from_fn(|_| {
+            let (Some(rx), Some(tx)) = (rx_buffers.next(), tx_buffers.next()) else {
+                unreachable!("one buffer per client");
+            };
+            let mut socket = TcpSocket::new(stack, rx, tx);
+            socket.set_timeout(Some(IDLE_TIMEOUT));
+            Client {
+                socket,
+                session: Session::new(),
+                open: false,
+                reply: [0; REPLY_LEN],
+                reply_len: 0,
+            }
+        });
+        info!("Pixelflut on TCP port {}", PORT);
+
+        Self { clients, first: 0 }
+    }
+
+    /// Accepts connections and runs the clients' commands on the background
+    /// of `display`, nothing is drawn while `drawing` is off
+    pub async fn serve(&mut self, display: &mut Display, drawing: bool) {
+        for client in &mut self.clients {
+            client.update().await;
+        }
+
+        let connected = self
+            .clients
+            .iter()
+            .filter(|client| client.socket.state() == State::Established)
+            .count();
+        let share = match connected {
+            0 => return,
+            _ if !drawing => 0,
+            _ => PIXELFLUT_BUDGET / connected as u32,
+        };
+
+        let mut canvas = Screen(display);
+        for index in 0..MAX_CLIENTS {
+            let client = &mut self.clients[(self.first + index) % MAX_CLIENTS];
+            if client.socket.state() == State::Established {
+                client.run(&mut canvas, share).await;
+            }
+        }
+        self.first = (self.first + 1) % MAX_CLIENTS;
+    }
+}
+
+impl Client {
+    /// Listens again once a connection is closed
+    async fn update(&mut self) {
+        match self.socket.state() {
+            State::Closed if self.open => {
+                info!("Pixelflut client left");
+                self.open = false;
+            }
+            State::Closed => {
+                self.session = Session::new();
+                self.reply_len = 0;
+                // Polling the accept once starts listening, the connection is seen in `state`
+                if let Either::First(Err(error)) = select(self.socket.accept(PORT), ready(())).await
+                {
+                    warn!("Pixelflut listen failed: {:?}", error);
+                    return;
+                }
+                self.open = true;
+            }
+            // The client is done sending, finish the replies and close
+            State::CloseWait => {
+                self.send_replies().await;
+                self.socket.close();
+            }
+            _ => {}
+        }
+    }
+
+    async fn run(&mut self, canvas: &mut Screen<'_>, mut budget: u32) {
+        self.send_replies().await;
+
+        // The receive buffer is a ring, a read may stop at its end
+        for _ in 0..2 {
+            let (session, reply, reply_len) = (&mut self.session, &mut self.reply, self.reply_len);
+            let read = self.socket.read_with(|input| {
+                let progress = session.feed(input, canvas, budget, &mut reply[reply_len..]);
+                (progress.consumed, progress)
+            });
+            let progress = match select(read, ready(())).await {
+                Either::First(Ok(progress)) => progress,
+                Either::First(Err(error)) => {
+                    warn!("Pixelflut client dropped: {:?}", error);
+                    self.socket.abort();
+                    return;
+                }
+                Either::Second(()) => break,
+            };
+
+            self.reply_len += progress.replied;
+            budget -= progress.commands;
+            if progress.consumed == 0 || budget == 0 {
+                break;
+            }
+        }
+
+        self.send_replies().await;
+    }
+
+    /// Sends what fits into the socket without waiting
+    async fn send_replies(&mut self) {
+        if self.reply_len == 0 {
+            return;
+        }
+        if let Either::First(Ok(sent)) =
+            select(self.socket.write(&self.reply[..self.reply_len]), ready(())).await
+        {
+            self.reply.copy_within(sent..self.reply_len, 0);
+            self.reply_len -= sent;
+        }
+    }
+}
+
+/// The background of the canvas scene
+struct Screen<'a>(&'a mut Display);
+
+impl Canvas for Screen<'_> {
+    fn size(&self) -> (u16, u16) {
+        let size = self.0.size();
+        (size.width as u16, size.height as u16)
+    }
+
+    fn pixel(&self, x: u16, y: u16) -> u32 {
+        let color = self.0.background_pixel(x, y).unwrap_or(Rgb565::BLACK);
+        // Widened to 8 bits per channel, white stays white
+        let (r, g, b) = (color.r() as u32, color.g() as u32, color.b() as u32);
+        ((r << 3 | r >> 2) << 16) | ((g << 2 | g >> 4) << 8) | (b << 3 | b >> 2)
+    }
+
+    fn set_pixel(&mut self, x: u16, y: u16, color: u32) {
+        let color = Rgb565::new(
+            (color >> 19) as u8 & 0x1F,
+            (color >> 10) as u8 & 0x3F,
+            (color >> 3) as u8 & 0x1F,
+        );
+        self.0.set_background_pixel(x, y, color);
+    }
+}
@@ -0,0 +1,59 @@
+//! Frame profiler
+//!
+//! Times every stage of a frame with the system timer. The statistics over
+//! the last frames are in [`pixels_core::profiler`], they are logged at debug
+//! level every [`PROFILER_LOG_INTERVAL`] frames.
+
+use esp_hal::time::{Duration, Instant};
+
+pub use pixels_core::profiler::*;
+
+use crate::config::PROFILER_LOG_INTERVAL;
+use crate::display::UpdateStats;
+
+pub struct Profiler {
+    profile: Profile,
+    /// End of the last lap
+    mark: Instant,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self {
+            profile: Profile::new(),
+            mark: Instant::now(),
+        }
+    }
+
+    /// Starts timing the next lap, time since the last lap is not recorded
+    pub fn start(&mut self) {
+        self.mark = Instant::now();
+    }
+
+    /// Adds the time since the last lap (or [`Profiler::start`]) to `stage`
+    pub fn lap(&mut self, stage: Stage) {
+        let now = Instant::now();
+        self.record(stage, now - self.mark);
+        self.mark = now;
+    }
+
+    fn record(&mut self, stage: Stage, duration: Duration) {
+        self.profile.record(stage, duration.as_micros());
+    }
+
+    /// Completes the frame with the rasterize and flush stages of the display
+    pub fn end_frame(&mut self, update: &UpdateStats) {
+        self.record(Stage::Rasterize, update.rasterize);
+        self.record(Stage::Flush, update.flush);
+        self.profile.end_frame(update.bytes);
+
+        if self.profile.frames() % PROFILER_LOG_INTERVAL == 0 {
+            self.profile.log();
+        }
+    }
+
+    /// Statistics of the last frames
+    pub fn profile(&self) -> &Profile {
+        &self.profile
+    }
+}
@@ -0,0 +1,204 @@
+//! Remote display over Wi-Fi
+//!
+//! Streams the regions of every frame sent to the panel to one client and
+//! takes its touches and console commands, see [`pixels_protocol::stream`]
+//! for the protocol and `tools/stream` for the client. Remote touches are in
+//! screen coordinates and are recorded like the other input, see
+//! [`pixels_core::replay`].
+
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use core::future::ready;
+
+use embassy_futures::select::{select, Either};
+use embassy_net::udp::{PacketMetadata, UdpSocket};
+use embassy_net::{IpEndpoint, Stack};
+use embedded_graphics::pixelcolor::raw::{RawData, RawU16};
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::{Point, Size};
+use esp_hal::time::{Duration, Instant};
+use log::{info, warn};
+use pixels_core::framebuffer::DirtyRect;
+use pixels_core::gesture::Phase;
+use pixels_protocol::stream::{self, Encoder, Packet, Rect, MAX_PACKET_LEN, PORT, TIMEOUT_MS};
+use static_cell::StaticCell;
+
+/// Packets buffered in each direction
+const QUEUED_PACKETS: usize = 8;
+/// Touches and commands not handled yet, more are dropped
+const MAX_PENDING: usize = 16;
+
+pub struct Remote {
+    socket: UdpSocket<'static>,
+    client: Option<Client>,
+    /// Boxed, it holds a few packets worth of buffers
+    encoder: Box<Encoder>,
+    rects: Vec<Rect>,
+    /// Send the whole frame instead of the dirty regions
+    full_frame: bool,
+    touches: VecDeque<(Phase, Point)>,
+    commands: VecDeque<String>,
+}
+
+struct Client {
+    endpoint: IpEndpoint,
+    seen: Instant,
+}
+
+impl Remote {
+    pub fn new(stack: Stack<'static>) -> Self {
+        static RX_META: StaticCell<[PacketMetadata; QUEUED_PACKETS]> = StaticCell::new();
+        static TX_META: StaticCell<[PacketMetadata; QUEUED_PACKETS]> = StaticCell::new();
+        static RX_BUFFER: StaticCell<[u8; QUEUED_PACKETS * MAX_PACKET_LEN]> = StaticCell::new();
+        static TX_BUFFER: StaticCell<[u8; QUEUED_PACKETS * MAX_PACKET_LEN]> = StaticCell::new();
+
+        let mut socket = UdpSocket::new(
+            stack,
+            RX_META.init([PacketMetadata::EMPTY; QUEUED_PACKETS]),
+            RX_BUFFER.init([0; QUEUED_PACKETS * MAX_PACKET_LEN]),
+            TX_META.init([PacketMetadata::EMPTY; QUEUED_PACKETS]),
+            TX_BUFFER.init([0; QUEUED_PACKETS * MAX_PACKET_LEN]),
+        );
+        socket
+            .bind(PORT)
+            .expect("Binding the remote display port failed");
+        info!("Remote display on UDP port {}", PORT);
+
+        Self {
+            socket,
+            client: None,
+            encoder: Box::new(Encoder::new()),
+            rects: Vec::new(),
+            full_frame: false,
+            touches: VecDeque::new(),
+            commands: VecDeque::new(),
+        }
+    }
+
+    /// Handles the packets received since the last call
+    pub async fn poll(&mut self) {
+        let mut buffer = [0; MAX_PACKET_LEN];
+        while let Some((len, endpoint)) = self.try_receive(&mut buffer).await {
+            let packet = match Packet::decode(&buffer[..len]) {
+                Ok((_, packet)) => packet,
+                Err(error) => {
+                    warn!("Remote packet from {} dropped: {}", endpoint, error);
+                    continue;
+                }
+            };
+
+            // The last client to say anything gets the stream
+            if self
+                .client
+                .as_ref()
+                .is_none_or(|client| client.endpoint != endpoint)
+            {
+                info!("Remote display client {}", endpoint);
+                self.full_frame = true;
+            }
+            self.client = Some(Client {
+                endpoint,
+                seen: Instant::now(),
+            });
+
+            match packet {
+                Packet::Hello => {}
+                Packet::Refresh => self.full_frame = true,
+                Packet::Touch { phase, x, y } => {
+                    let phase = match phase {
+                        stream::Phase::Down => Phase::Down,
+                        stream::Phase::Move => Phase::Move,
+                        stream::Phase::Up => Phase::Up,
+                    };
+                    if self.touches.len() < MAX_PENDING {
+                        self.touches
+                            .push_back((phase, Point::new(x as i32, y as i32)));
+                    }
+                }
+                Packet::Command(line) => {
+                    if self.commands.len() < MAX_PENDING {
+                        self.commands.push_back(String::from(line));
+                    }
+                }
+                other => warn!("Unexpected remote packet {:?}", other),
+            }
+        }
+
+        let timeout = Duration::from_millis(TIMEOUT_MS as u64);
+        if self
+            .client
+            .as_ref()
+            .is_some_and(|client| client.seen.elapsed() > timeout)
+        {
+            info!("Remote display client timed out");
+            self.client = None;
+        }
+    }
+
+    pub fn has_client(&self) -> bool {
+        self.client.is_some()
+    }
+
+    /// Next remote touch in screen coordinates, one per frame like the touch controller
+    pub fn touch(&mut self) -> Option<(Phase, Point)> {
+        self.touches.pop_front()
+    }
+
+    /// Next remote console command
+    pub fn command(&mut self) -> Option<String> {
+        self.commands.pop_front()
+    }
+
+    /// Sends the regions of the frame that were sent to the panel, the whole
+    /// frame for a new client or after a refresh request
+    pub async fn send(&mut self, pixels: &[Rgb565], size: Size, regions: &[DirtyRect]) {
+        let Some(endpoint) = self.client.as_ref().map(|client| client.endpoint) else {
+            return;
+        };
+
+        self.rects.clear();
+        if core::mem::take(&mut self.full_frame) {
+            self.rects.push(Rect {
+                x: 0,
+                y: 0,
+                width: size.width as u16,
+                height: size.height as u16,
+            });
+        } else {
+            self.rects.extend(regions.iter().map(|region| Rect {
+                x: region.x_start,
+                y: region.y_start,
+                width: region.width() as u16,
+                height: region.height() as u16,
+            }));
+        }
+
+        let width = size.width as usize;
+        let pixel =
+            |x: u16, y: u16| RawU16::from(pixels[y as usize * width + x as usize]).into_inner();
+        self.encoder.begin(size.width as u16, size.height as u16);
+        while let Some(packet) = self.encoder.next(&self.rects, pixel) {
+            if let Err(error) = self.socket.send_to(packet, endpoint).await {
+                // The client asks for a refresh when it notices the gap
+                warn!("Remote display send failed: {:?}", error);
+                break;
+            }
+        }
+    }
+
+    /// A received packet and its sender, `None` if nothing is waiting
+    async fn try_receive(&mut self, buffer: &mut [u8]) -> Option<(usize, IpEndpoint)> {
+        // The receive is polled first, `ready` ends the wait right away
+        match select(self.socket.recv_from(buffer), ready(())).await {
+            Either::First(Ok((len, meta))) => Some((len, meta.endpoint)),
+            Either::First(Err(error)) => {
+                warn!("Remote display receive failed: {:?}", error);
+                None
+            }
+            Either::Second(()) => None,
+        }
+    }
+}
@@ -0,0 +1,32 @@
+//! Screenshots over serial
+//!
+//! The frame buffer is sent as a stream of checksummed frames, see
+//! [`pixels_protocol::screenshot`], one hex line per frame prefixed with
+//! `scr:`. `tools/screenshot` turns a saved serial log back into a PNG.
+
+use embedded_graphics::pixelcolor::raw::{RawData, RawU16};
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::Size;
+use esp_println::println;
+use log::info;
+use pixels_protocol::hex::Hex;
+use pixels_protocol::screenshot::{self, Encoding, Info, LINE_PREFIX};
+
+/// Prints `pixels`, row by row with `size.width` pixels per row
+pub fn send(pixels: &[Rgb565], size: Size, encoding: Encoding) {
+    let info = Info {
+        width: size.width as u16,
+        height: size.height as u16,
+        encoding,
+    };
+    let pixels = pixels.iter().map(|&pixel| RawU16::from(pixel).into_inner());
+
+    let len = screenshot::write(info, pixels, |frame| {
+        println!("{}{}", LINE_PREFIX, Hex(frame));
+    });
+
+    info!(
+        "Sent {}x{} screenshot, {} bytes of pixel data",
+        info.width, info.height, len
+    );
+}
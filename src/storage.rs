@@ -0,0 +1,51 @@
+//! Settings partition of the flash, the store itself is
+//! [`pixels_core::storage::SettingsStore`]
+
+use embedded_storage::nor_flash::NorFlash;
+use embedded_storage::Storage;
+use esp_bootloader_esp_idf::partitions::{self, FlashRegion, PARTITION_TABLE_MAX_LEN};
+use log::warn;
+use pixels_core::storage::{Error, SettingsStore, MIN_SECTORS};
+
+use crate::config::SETTINGS_PARTITION;
+
+/// Opens the store in the [`SETTINGS_PARTITION`] data partition, `None` if the
+/// partition table has no such partition, it is too small or it cannot be read
+pub fn open<'a, F: NorFlash + Storage>(
+    flash: &'a mut F,
+    partition_table: &'a mut [u8; PARTITION_TABLE_MAX_LEN],
+) -> Option<SettingsStore<FlashRegion<'a, F>>> {
+    let table = match partitions::read_partition_table(flash, partition_table) {
+        Ok(table) => table,
+        Err(error) => {
+            warn!("Reading the partition table failed: {:?}", error);
+            return None;
+        }
+    };
+
+    let Some(partition) = table
+        .iter()
+        .find(|partition| partition.label_as_str() == SETTINGS_PARTITION)
+    else {
+        warn!(
+            "No '{}' partition, settings are not persisted",
+            SETTINGS_PARTITION
+        );
+        return None;
+    };
+
+    match SettingsStore::new(partition.as_embedded_storage(flash)) {
+        Ok(store) => Some(store),
+        Err(Error::TooSmall) => {
+            warn!(
+                "The '{}' partition has less than {} sectors, settings are not persisted",
+                SETTINGS_PARTITION, MIN_SECTORS
+            );
+            None
+        }
+        Err(Error::Flash(error)) => {
+            warn!("Reading the settings failed: {:?}", error);
+            None
+        }
+    }
+}
@@ -0,0 +1,413 @@
+//! Test client and server for the Pixelflut canvas
+//!
+//! `pixelflut client ADDRESS [CONNECTIONS]` draws a test pattern on a device
+//! built with the `pixelflut` feature, splitting the rows between several
+//! connections, then reads part of it back and reports the pixels that differ.
+//! The device has to show the canvas scene (`scene canvas` on the console).
+//!
+//! `pixelflut server [PORT]` plays the device: it runs the same sessions and
+//! per-frame budget as the firmware on a canvas in memory and prints the
+//! commands per second of every client. Typing `png [FILE]` saves the canvas:
+//!
+//! ```text
+//! cargo run --release -- server &
+//! cargo run --release -- client 127.0.0.1 3
+//! ```
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use pixels_protocol::pixelflut::{self, Session, MAX_REPLY_LEN, PORT};
+
+const USAGE: &str =
+    "usage: pixelflut client ADDRESS[:PORT] [CONNECTIONS] | pixelflut server [PORT]";
+
+/// Every this many pixels in both directions is read back
+const CHECK_STEP: u16 = 8;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["client", address] => client(address, 1),
+        ["client", address, connections] => match connections.parse() {
+            Ok(connections) if connections > 0 => client(address, connections),
+            _ => Err(format!("{connections}: not a number of connections")),
+        },
+        ["server"] => server(PORT),
+        ["server", port] => match port.parse() {
+            Ok(port) => server(port),
+            Err(_) => Err(format!("{port}: not a port")),
+        },
+        _ => Err(USAGE.to_string()),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Test pattern: red across, green down, blue in a checkerboard
+fn pattern(x: u16, y: u16, width: u16, height: u16) -> u32 {
+    let red = x as u32 * 255 / width.max(2) as u32;
+    let green = y as u32 * 255 / height.max(2) as u32;
+    let blue = if (x / 16 + y / 16).is_multiple_of(2) {
+        0xC0
+    } else {
+        0x40
+    };
+    red << 16 | green << 8 | blue
+}
+
+/// Bits of a color that survive RGB565
+fn rgb565_bits(color: u32) -> u32 {
+    color & 0xF8FCF8
+}
+
+fn client(address: &str, connections: usize) -> Result<(), String> {
+    let device = resolve(address)?;
+    let connect = || TcpStream::connect(device).map_err(|error| format!("{device}: {error}"));
+
+    let mut control = connect()?;
+    let mut replies = BufReader::new(control.try_clone().map_err(|error| error.to_string())?);
+    let (width, height) = size(&mut control, &mut replies)?;
+    eprintln!("{device}: {width}x{height} canvas, drawing over {connections} connections");
+
+    // Every connection draws every `connections`th row
+    let started = Instant::now();
+    let drawers: Vec<_> = (0..connections)
+        .map(|first| {
+            let stream = connect()?;
+            Ok(thread::spawn(move || -> io::Result<()> {
+                let mut stream = BufWriter::new(stream);
+                for y in (first as u16..height).step_by(connections) {
+                    for x in 0..width {
+                        let color = pattern(x, y, width, height);
+                        writeln!(stream, "PX {x} {y} {color:06x}")?;
+                    }
+                }
+                // The device may still be drawing after the connection closes
+                stream.flush()
+            }))
+        })
+        .collect::<Result<_, String>>()?;
+    for drawer in drawers {
+        drawer
+            .join()
+            .map_err(|_| "drawing thread panicked".to_string())?
+            .map_err(|error| error.to_string())?;
+    }
+
+    // Connections are served independently, reads on this one can overtake
+    // the draws of the others, so wait for the last pixel to show up
+    let (last_x, last_y) = (width - 1, height - 1);
+    let last = rgb565_bits(pattern(last_x, last_y, width, height));
+    while rgb565_bits(read_pixel(&mut control, &mut replies, last_x, last_y)?) != last {
+        thread::sleep(Duration::from_millis(100));
+    }
+    let pixels = width as f32 * height as f32;
+    eprintln!(
+        "drawn in {:.1} s, {:.0} pixels/s",
+        started.elapsed().as_secs_f32(),
+        pixels / started.elapsed().as_secs_f32()
+    );
+
+    // The reads are written from another thread so neither side waits on the other
+    let mut writer = control.try_clone().map_err(|error| error.to_string())?;
+    let points: Vec<(u16, u16)> = (0..height)
+        .step_by(CHECK_STEP as usize)
+        .flat_map(|y| (0..width).step_by(CHECK_STEP as usize).map(move |x| (x, y)))
+        .collect();
+    let queries = points.clone();
+    let asking = thread::spawn(move || -> io::Result<()> {
+        let mut stream = BufWriter::new(&mut writer);
+        for (x, y) in queries {
+            writeln!(stream, "PX {x} {y}")?;
+        }
+        stream.flush()
+    });
+
+    let mut wrong = 0;
+    for &(x, y) in &points {
+        let color = read_reply(&mut replies, x, y)?;
+        let expected = pattern(x, y, width, height);
+        if rgb565_bits(color) != rgb565_bits(expected) {
+            if wrong < 10 {
+                eprintln!("{x},{y}: {color:06x} instead of {expected:06x}");
+            }
+            wrong += 1;
+        }
+    }
+    asking
+        .join()
+        .map_err(|_| "reading thread panicked".to_string())?
+        .map_err(|error| error.to_string())?;
+
+    match wrong {
+        0 => {
+            eprintln!("{} pixels checked, all match", points.len());
+            Ok(())
+        }
+        _ => Err(format!("{wrong} of {} pixels differ", points.len())),
+    }
+}
+
+fn size(stream: &mut TcpStream, replies: &mut impl BufRead) -> Result<(u16, u16), String> {
+    stream
+        .write_all(b"SIZE\n")
+        .map_err(|error| error.to_string())?;
+    let line = read_line(replies)?;
+    match line.split_whitespace().collect::<Vec<_>>()[..] {
+        ["SIZE", width, height] => match (width.parse(), height.parse()) {
+            (Ok(width), Ok(height)) => Ok((width, height)),
+            _ => Err(format!("bad reply {line:?}")),
+        },
+        _ => Err(format!("bad reply {line:?}")),
+    }
+}
+
+fn read_pixel(
+    stream: &mut TcpStream,
+    replies: &mut impl BufRead,
+    x: u16,
+    y: u16,
+) -> Result<u32, String> {
+    writeln!(stream, "PX {x} {y}").map_err(|error| error.to_string())?;
+    read_reply(replies, x, y)
+}
+
+/// The reply to `PX x y`
+fn read_reply(replies: &mut impl BufRead, x: u16, y: u16) -> Result<u32, String> {
+    let line = read_line(replies)?;
+    let expected = format!("PX {x} {y} ");
+    line.strip_prefix(&expected)
+        .and_then(|color| u32::from_str_radix(color, 16).ok())
+        .ok_or_else(|| format!("bad reply {line:?} to PX {x} {y}"))
+}
+
+fn read_line(replies: &mut impl BufRead) -> Result<String, String> {
+    let mut line = String::new();
+    match replies.read_line(&mut line) {
+        Ok(0) => Err("connection closed".to_string()),
+        Ok(_) => Ok(line.trim_end().to_string()),
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+/// Canvas of the test server, colors are kept in RGB565 like on the device
+struct Canvas {
+    width: u16,
+    height: u16,
+    pixels: Vec<u32>,
+}
+
+impl pixelflut::Canvas for Canvas {
+    fn size(&self) -> (u16, u16) {
+        (self.width, self.height)
+    }
+
+    fn pixel(&self, x: u16, y: u16) -> u32 {
+        // Widened like the device does, white stays white
+        let color = self.pixels[y as usize * self.width as usize + x as usize];
+        let (r, g, b) = (color >> 16, (color >> 8) & 0xFF, color & 0xFF);
+        (r | r >> 5) << 16 | (g | g >> 6) << 8 | (b | b >> 5)
+    }
+
+    fn set_pixel(&mut self, x: u16, y: u16, color: u32) {
+        self.pixels[y as usize * self.width as usize + x as usize] = rgb565_bits(color);
+    }
+}
+
+impl Canvas {
+    fn save(&self, path: &Path) -> Result<(), String> {
+        let file = File::create(path).map_err(|error| format!("{}: {error}", path.display()))?;
+        let mut encoder =
+            png::Encoder::new(BufWriter::new(file), self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let rgb: Vec<u8> = (0..self.height)
+            .flat_map(|y| (0..self.width).map(move |x| (x, y)))
+            .flat_map(|(x, y)| {
+                let color = pixelflut::Canvas::pixel(self, x, y);
+                [(color >> 16) as u8, (color >> 8) as u8, color as u8]
+            })
+            .collect();
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&rgb))
+            .map_err(|error| format!("{}: {error}", path.display()))
+    }
+}
+
+/// A connection to the test server
+struct Client {
+    stream: TcpStream,
+    address: SocketAddr,
+    session: Session,
+    /// Received and not run yet, at most a socket buffer's worth like on the device
+    input: Vec<u8>,
+    commands: u32,
+}
+
+fn server(port: u16) -> Result<(), String> {
+    const WIDTH: u16 = 536;
+    const HEIGHT: u16 = 240;
+    const FRAME_TIME: Duration = Duration::from_millis(33);
+    // Same as the firmware's PIXELFLUT_BUDGET and socket buffer
+    const BUDGET: u32 = 16 * 1024;
+    const INPUT_LEN: usize = 8 * 1024;
+
+    let listener = TcpListener::bind(("0.0.0.0", port)).map_err(|error| error.to_string())?;
+    listener
+        .set_nonblocking(true)
+        .map_err(|error| error.to_string())?;
+    eprintln!("serving a {WIDTH}x{HEIGHT} canvas on port {port}");
+
+    // Lines from stdin, read on their own thread so they do not stall the frames
+    let (lines, input) = mpsc::channel();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines().map_while(Result::ok) {
+            if lines.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut canvas = Canvas {
+        width: WIDTH,
+        height: HEIGHT,
+        pixels: vec![0; WIDTH as usize * HEIGHT as usize],
+    };
+    let mut clients: Vec<Client> = Vec::new();
+    let mut first = 0;
+    let mut since = Instant::now();
+
+    loop {
+        let frame = Instant::now();
+
+        while let Ok(line) = input.try_recv() {
+            match line.split_whitespace().collect::<Vec<_>>()[..] {
+                [] => {}
+                ["png"] | ["png", _] => {
+                    let path = line.split_whitespace().nth(1).unwrap_or("pixelflut.png");
+                    match canvas.save(Path::new(path)) {
+                        Ok(()) => eprintln!("{path}: {WIDTH}x{HEIGHT} pixels"),
+                        Err(error) => eprintln!("{error}"),
+                    }
+                }
+                _ => eprintln!("png [FILE] saves the canvas"),
+            }
+        }
+
+        while let Ok((stream, address)) = listener.accept() {
+            if stream.set_nonblocking(true).is_ok() {
+                eprintln!("{address} connected");
+                clients.push(Client {
+                    stream,
+                    address,
+                    session: Session::new(),
+                    input: Vec::new(),
+                    commands: 0,
+                });
+            }
+        }
+
+        // Like the device: equal shares, a different client first every frame
+        let share = BUDGET / clients.len().max(1) as u32;
+        let count = clients.len();
+        let mut closed = Vec::new();
+        for index in 0..count {
+            let index = (first + index) % count;
+            let client = &mut clients[index];
+            if !serve(client, &mut canvas, share, INPUT_LEN) {
+                closed.push(index);
+            }
+        }
+        closed.sort_unstable();
+        for index in closed.into_iter().rev() {
+            let client = clients.remove(index);
+            eprintln!("{} left", client.address);
+        }
+        first = first.wrapping_add(1);
+
+        if since.elapsed() >= Duration::from_secs(1) {
+            let seconds = since.elapsed().as_secs_f32();
+            for client in clients.iter_mut().filter(|client| client.commands > 0) {
+                eprintln!(
+                    "{}: {:.0} commands/s",
+                    client.address,
+                    client.commands as f32 / seconds
+                );
+                client.commands = 0;
+            }
+            since = Instant::now();
+        }
+
+        thread::sleep(FRAME_TIME.saturating_sub(frame.elapsed()));
+    }
+}
+
+/// Runs up to `budget` commands of `client`, `false` once it is gone
+fn serve(client: &mut Client, canvas: &mut Canvas, budget: u32, input_len: usize) -> bool {
+    let mut open = true;
+    if client.input.len() < input_len {
+        let mut buffer = vec![0; input_len - client.input.len()];
+        match client.stream.read(&mut buffer) {
+            Ok(0) => open = false,
+            Ok(len) => client.input.extend_from_slice(&buffer[..len]),
+            Err(error) if error.kind() == io::ErrorKind::WouldBlock => {}
+            Err(_) => return false,
+        }
+    }
+
+    let mut reply = vec![0; 2 * MAX_REPLY_LEN];
+    let mut budget = budget;
+    let mut replies = Vec::new();
+    while budget > 0 {
+        let progress = client
+            .session
+            .feed(&client.input, canvas, budget, &mut reply);
+        client.input.drain(..progress.consumed);
+        replies.extend_from_slice(&reply[..progress.replied]);
+        budget -= progress.commands;
+        client.commands += progress.commands;
+        if progress.consumed == 0 {
+            break;
+        }
+    }
+
+    // Replies are small, waiting for them to go out is fine for a test server
+    if !replies.is_empty() {
+        let _ = client.stream.set_nonblocking(false);
+        let sent = client.stream.write_all(&replies);
+        let _ = client.stream.set_nonblocking(true);
+        if sent.is_err() {
+            return false;
+        }
+    }
+    open || !client.input.is_empty()
+}
+
+/// `address` with [`PORT`] if it has none
+fn resolve(address: &str) -> Result<SocketAddr, String> {
+    let with_port = if address.contains(':') {
+        address.to_string()
+    } else {
+        format!("{address}:{PORT}")
+    };
+    with_port
+        .to_socket_addrs()
+        .map_err(|error| format!("{address}: {error}"))?
+        .next()
+        .ok_or_else(|| format!("{address}: no address"))
+}
@@ -0,0 +1,233 @@
+//! Turns screenshots sent over serial into PNG files
+//!
+//! Reads a serial log, e.g. saved with `cargo run | tee log.txt` in the
+//! firmware directory, and writes every complete screenshot in it:
+//!
+//! ```text
+//! cargo run --release -- log.txt screenshot.png
+//! ```
+//!
+//! The log is read from stdin if it is `-` or missing. With more than one
+//! screenshot in the log the second one is written to `screenshot-2.png`
+//! and so on.
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use pixels_protocol::rle;
+use pixels_protocol::screenshot::{Encoding, Frame, Info, LINE_PREFIX};
+
+const USAGE: &str = "usage: screenshot [LOG|-] [OUTPUT.png]";
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if args.len() > 2 || args.iter().any(|arg| arg == "-h" || arg == "--help") {
+        eprintln!("{USAGE}");
+        return ExitCode::FAILURE;
+    }
+
+    let input: Box<dyn BufRead> = match args.first().map(String::as_str) {
+        None | Some("-") => Box::new(io::stdin().lock()),
+        Some(path) => match File::open(path) {
+            Ok(file) => Box::new(BufReader::new(file)),
+            Err(error) => {
+                eprintln!("{path}: {error}");
+                return ExitCode::FAILURE;
+            }
+        },
+    };
+    let output = PathBuf::from(args.get(1).map_or("screenshot.png", String::as_str));
+
+    match run(input, &output) {
+        Ok(0) => {
+            eprintln!("no complete screenshot found");
+            ExitCode::FAILURE
+        }
+        Ok(_) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Writes the screenshots in `input`, returns how many were written
+fn run(input: impl BufRead, output: &Path) -> Result<usize, String> {
+    let mut screenshot: Option<Screenshot> = None;
+    let mut written = 0;
+
+    for (number, line) in input.lines().enumerate() {
+        let line = line.map_err(|error| format!("reading the log: {error}"))?;
+        // Log lines may carry color codes or other output before the prefix
+        let Some(start) = line.find(LINE_PREFIX) else {
+            continue;
+        };
+
+        let result = hex(line[start + LINE_PREFIX.len()..].trim_end())
+            .ok_or_else(|| "not a hex line".to_string())
+            .and_then(|bytes| match Frame::decode(&bytes) {
+                Ok((sequence, frame)) => receive(&mut screenshot, sequence, frame),
+                Err(error) => Err(error.to_string()),
+            });
+
+        match result {
+            Ok(Some(image)) => {
+                written += 1;
+                let path = numbered(output, written);
+                save(&image, &path)?;
+                eprintln!(
+                    "{}: {}x{} pixels",
+                    path.display(),
+                    image.info.width,
+                    image.info.height
+                );
+            }
+            Ok(None) => {}
+            Err(error) => {
+                eprintln!("line {}: {error}, screenshot dropped", number + 1);
+                screenshot = None;
+            }
+        }
+    }
+
+    Ok(written)
+}
+
+/// Screenshot being received
+struct Screenshot {
+    info: Info,
+    /// Sequence number of the next frame
+    next: u16,
+    data: Vec<u8>,
+}
+
+/// Finished screenshot
+struct Image {
+    info: Info,
+    pixels: Vec<u16>,
+}
+
+/// Adds a frame to the screenshot being received, returns the image after
+/// the end frame
+fn receive(
+    screenshot: &mut Option<Screenshot>,
+    sequence: u16,
+    frame: Frame<'_>,
+) -> Result<Option<Image>, String> {
+    if let Frame::Header(info) = frame {
+        *screenshot = Some(Screenshot {
+            info,
+            next: sequence.wrapping_add(1),
+            data: Vec::new(),
+        });
+        return Ok(None);
+    }
+
+    // Frames of a screenshot whose header was missed
+    let Some(current) = screenshot.as_mut() else {
+        return Ok(None);
+    };
+    if sequence != current.next {
+        return Err(format!(
+            "frame {sequence} missing, expected {}",
+            current.next
+        ));
+    }
+    current.next = sequence.wrapping_add(1);
+
+    match frame {
+        Frame::Data(data) => {
+            current.data.extend_from_slice(data);
+            Ok(None)
+        }
+        Frame::End { len, crc } => {
+            let Screenshot { info, data, .. } = screenshot.take().expect("checked above");
+            if data.len() != len as usize || pixels_protocol::crc::crc32(&data) != crc {
+                return Err("pixel data does not match its checksum".to_string());
+            }
+            decode(info, &data).map(Some)
+        }
+        Frame::Header(_) => unreachable!("handled above"),
+    }
+}
+
+fn decode(info: Info, data: &[u8]) -> Result<Image, String> {
+    let count = info.width as usize * info.height as usize;
+    let mut pixels = Vec::with_capacity(count);
+    match info.encoding {
+        Encoding::Raw => pixels.extend(
+            data.chunks_exact(2)
+                .map(|pixel| u16::from_le_bytes([pixel[0], pixel[1]])),
+        ),
+        Encoding::Rle => rle::decode(data, |pixel| pixels.push(pixel))
+            .map_err(|_| "run-length data is truncated".to_string())?,
+    }
+
+    if pixels.len() != count {
+        return Err(format!(
+            "{} pixels for a {}x{} screenshot",
+            pixels.len(),
+            info.width,
+            info.height
+        ));
+    }
+    Ok(Image { info, pixels })
+}
+
+fn save(image: &Image, path: &Path) -> Result<(), String> {
+    let file = File::create(path).map_err(|error| format!("{}: {error}", path.display()))?;
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(file),
+        image.info.width as u32,
+        image.info.height as u32,
+    );
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let rgb: Vec<u8> = image
+        .pixels
+        .iter()
+        .flat_map(|&pixel| rgb888(pixel))
+        .collect();
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(&rgb))
+        .map_err(|error| format!("{}: {error}", path.display()))
+}
+
+/// RGB565 widened to 8 bits per channel, white stays white
+fn rgb888(pixel: u16) -> [u8; 3] {
+    let r = (pixel >> 11) as u8 & 0x1F;
+    let g = (pixel >> 5) as u8 & 0x3F;
+    let b = pixel as u8 & 0x1F;
+    [
+        (r << 3) | (r >> 2),
+        (g << 2) | (g >> 4),
+        (b << 3) | (b >> 2),
+    ]
+}
+
+/// `output` for the first screenshot, `output` with `-n` before the
+/// extension for the n-th
+fn numbered(output: &Path, n: usize) -> PathBuf {
+    if n == 1 {
+        return output.to_path_buf();
+    }
+
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match output.extension() {
+        Some(extension) => format!("{stem}-{n}.{}", extension.to_string_lossy()),
+        None => format!("{stem}-{n}"),
+    };
+    output.with_file_name(name)
+}
+
+/// Bytes of a hex string, `None` if it is not one
+fn hex(text: &str) -> Option<Vec<u8>> {
+    (0..text.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok())
+        .collect()
+}
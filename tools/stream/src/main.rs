@@ -0,0 +1,463 @@
+//! Client and test server for the remote display stream
+//!
+//! `stream client ADDRESS` subscribes to a device built with the `wifi`
+//! feature, keeps a copy of its frame and prints the frame rate and data rate
+//! once a second. Lines typed on stdin are sent to the device:
+//!
+//! ```text
+//! touch down|move|up X Y   touch at frame coordinates
+//! png [FILE]               save the current frame, `stream.png` by default
+//! anything else            a console command, e.g. `set fov 1.2`
+//! ```
+//!
+//! `stream server [PORT]` plays the device: it streams a moving square with
+//! the same encoder as the firmware and prints the touches and commands it
+//! receives, so the client can be tried over loopback:
+//!
+//! ```text
+//! cargo run --release -- server &
+//! cargo run --release -- client 127.0.0.1
+//! ```
+
+use std::fs::File;
+use std::io::{self, BufRead, BufWriter};
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use std::path::Path;
+use std::process::ExitCode;
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use pixels_protocol::rle;
+use pixels_protocol::stream::{Encoder, Packet, Phase, Rect, MAX_PACKET_LEN, PORT, TIMEOUT_MS};
+
+const USAGE: &str = "usage: stream client ADDRESS[:PORT] | stream server [PORT]";
+
+/// The client says hello this often, well within [`TIMEOUT_MS`]
+const HELLO_INTERVAL: Duration = Duration::from_secs(1);
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let result = match args.iter().map(String::as_str).collect::<Vec<_>>()[..] {
+        ["client", address] => client(address),
+        ["server"] => server(PORT),
+        ["server", port] => match port.parse() {
+            Ok(port) => server(port),
+            Err(_) => Err(format!("{port}: not a port")),
+        },
+        _ => Err(USAGE.to_string()),
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{error}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Copy of the device's frame
+struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<u16>,
+}
+
+impl Canvas {
+    fn resize(&mut self, width: u16, height: u16) {
+        let (width, height) = (width as usize, height as usize);
+        if (width, height) != (self.width, self.height) {
+            *self = Canvas {
+                width,
+                height,
+                pixels: vec![0; width * height],
+            };
+        }
+    }
+
+    /// Writes decoded rows, `false` if they do not fit the canvas or the data
+    /// does not match their size
+    fn rows(&mut self, x: u16, y: u16, width: u16, rows: u16, data: &[u8]) -> bool {
+        let (x, y, width, rows) = (x as usize, y as usize, width as usize, rows as usize);
+        if x + width > self.width || y + rows > self.height {
+            return false;
+        }
+
+        let mut decoded = Vec::with_capacity(width * rows);
+        if rle::decode(data, |pixel| decoded.push(pixel)).is_err() || decoded.len() != width * rows
+        {
+            return false;
+        }
+        for (row, pixels) in decoded.chunks_exact(width).enumerate() {
+            let start = (y + row) * self.width + x;
+            self.pixels[start..start + width].copy_from_slice(pixels);
+        }
+        true
+    }
+
+    fn save(&self, path: &Path) -> Result<(), String> {
+        let file = File::create(path).map_err(|error| format!("{}: {error}", path.display()))?;
+        let mut encoder =
+            png::Encoder::new(BufWriter::new(file), self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let rgb: Vec<u8> = self
+            .pixels
+            .iter()
+            .flat_map(|&pixel| rgb888(pixel))
+            .collect();
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&rgb))
+            .map_err(|error| format!("{}: {error}", path.display()))
+    }
+}
+
+fn client(address: &str) -> Result<(), String> {
+    let device = resolve(address)?;
+    let socket = UdpSocket::bind(("0.0.0.0", 0)).map_err(|error| error.to_string())?;
+    socket
+        .set_read_timeout(Some(Duration::from_millis(100)))
+        .map_err(|error| error.to_string())?;
+    eprintln!("streaming from {device}");
+
+    // Lines from stdin, read on their own thread so they do not stall the stream
+    let (lines, input) = mpsc::channel();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines().map_while(Result::ok) {
+            if lines.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut sender = Sender::new(
+        socket.try_clone().map_err(|error| error.to_string())?,
+        device,
+    );
+    let mut canvas = Canvas {
+        width: 0,
+        height: 0,
+        pixels: Vec::new(),
+    };
+    let mut buffer = [0; MAX_PACKET_LEN];
+    let mut next_sequence: Option<u16> = None;
+    let mut last_hello: Option<Instant> = None;
+    let mut stats = Stats::new();
+
+    loop {
+        if last_hello.is_none_or(|time| time.elapsed() >= HELLO_INTERVAL) {
+            sender.send(Packet::Hello);
+            last_hello = Some(Instant::now());
+        }
+
+        while let Ok(line) = input.try_recv() {
+            handle_line(&line, &mut sender, &canvas);
+        }
+
+        let len = match socket.recv_from(&mut buffer) {
+            Ok((len, from)) if from == device => len,
+            Ok(_) => continue,
+            Err(error)
+                if matches!(
+                    error.kind(),
+                    io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                ) =>
+            {
+                stats.report();
+                continue;
+            }
+            Err(error) => return Err(error.to_string()),
+        };
+        stats.bytes += len;
+
+        let (sequence, packet) = match Packet::decode(&buffer[..len]) {
+            Ok(decoded) => decoded,
+            Err(error) => {
+                eprintln!("{error}");
+                continue;
+            }
+        };
+        // The device restarts its sequence numbers when it restarts
+        if next_sequence.is_some_and(|next| next != sequence) {
+            stats.lost += sequence.wrapping_sub(next_sequence.unwrap_or(sequence)) as usize;
+            sender.send(Packet::Refresh);
+        }
+        next_sequence = Some(sequence.wrapping_add(1));
+
+        match packet {
+            Packet::Rows {
+                x,
+                y,
+                width,
+                rows,
+                data,
+                ..
+            } => {
+                // Before the first `Present` the size of the frame is unknown
+                if !canvas.rows(x, y, width, rows, data) && canvas.width > 0 {
+                    eprintln!("rows {x},{y} {width}x{rows} do not fit the frame, refreshing");
+                    sender.send(Packet::Refresh);
+                }
+            }
+            Packet::Present { width, height, .. } => {
+                if (width as usize, height as usize) != (canvas.width, canvas.height) {
+                    // Rows of a frame of another size were dropped, get them again
+                    canvas.resize(width, height);
+                    sender.send(Packet::Refresh);
+                }
+                stats.frames += 1;
+            }
+            other => eprintln!("unexpected {other:?}"),
+        }
+        stats.report();
+    }
+}
+
+fn handle_line(line: &str, sender: &mut Sender, canvas: &Canvas) {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words[..] {
+        [] => {}
+        ["touch", phase, x, y] => {
+            let phase = match phase {
+                "down" => Phase::Down,
+                "move" => Phase::Move,
+                "up" => Phase::Up,
+                _ => {
+                    eprintln!("touch phase is down, move or up");
+                    return;
+                }
+            };
+            match (x.parse(), y.parse()) {
+                (Ok(x), Ok(y)) => sender.send(Packet::Touch { phase, x, y }),
+                _ => eprintln!("touch position is two numbers"),
+            }
+        }
+        ["png"] | ["png", _] => {
+            let path = Path::new(words.get(1).copied().unwrap_or("stream.png"));
+            match canvas.save(path) {
+                Ok(()) => eprintln!(
+                    "{}: {}x{} pixels",
+                    path.display(),
+                    canvas.width,
+                    canvas.height
+                ),
+                Err(error) => eprintln!("{error}"),
+            }
+        }
+        _ => sender.send(Packet::Command(line.trim())),
+    }
+}
+
+/// Frames, data and lost packets, printed once a second
+struct Stats {
+    since: Instant,
+    frames: usize,
+    bytes: usize,
+    lost: usize,
+}
+
+impl Stats {
+    fn new() -> Self {
+        Stats {
+            since: Instant::now(),
+            frames: 0,
+            bytes: 0,
+            lost: 0,
+        }
+    }
+
+    fn report(&mut self) {
+        let elapsed = self.since.elapsed();
+        if elapsed < Duration::from_secs(1) {
+            return;
+        }
+        let seconds = elapsed.as_secs_f32();
+        eprintln!(
+            "{:.1} fps, {:.0} kB/s, {} packets lost",
+            self.frames as f32 / seconds,
+            self.bytes as f32 / seconds / 1000.0,
+            self.lost
+        );
+        *self = Stats::new();
+    }
+}
+
+fn server(port: u16) -> Result<(), String> {
+    const WIDTH: u16 = 536;
+    const HEIGHT: u16 = 240;
+    const SQUARE: u16 = 40;
+    const FRAME_TIME: Duration = Duration::from_millis(33);
+
+    let socket = UdpSocket::bind(("0.0.0.0", port)).map_err(|error| error.to_string())?;
+    socket
+        .set_nonblocking(true)
+        .map_err(|error| error.to_string())?;
+    eprintln!("serving a {WIDTH}x{HEIGHT} test frame on port {port}");
+
+    let mut client: Option<(SocketAddr, Instant)> = None;
+    let mut encoder = Encoder::new();
+    let mut buffer = [0; MAX_PACKET_LEN];
+    let mut full_frame = false;
+    // Top left corner of the square, last frame and this one
+    let mut square = (0u16, 0u16);
+    let mut velocity = (3i32, 2i32);
+    let mut held = false;
+
+    loop {
+        let start = Instant::now();
+
+        while let Ok((len, from)) = socket.recv_from(&mut buffer) {
+            let packet = match Packet::decode(&buffer[..len]) {
+                Ok((_, packet)) => packet,
+                Err(error) => {
+                    eprintln!("{from}: {error}");
+                    continue;
+                }
+            };
+            if client.is_none_or(|(address, _)| address != from) {
+                eprintln!("{from} subscribed");
+                full_frame = true;
+            }
+            client = Some((from, Instant::now()));
+
+            match packet {
+                Packet::Hello => {}
+                Packet::Refresh => full_frame = true,
+                Packet::Touch { phase, x, y } => {
+                    eprintln!("touch {phase:?} at {x},{y}");
+                    held = phase != Phase::Up;
+                    if held {
+                        square = (
+                            x.saturating_sub(SQUARE / 2).min(WIDTH - SQUARE),
+                            y.saturating_sub(SQUARE / 2).min(HEIGHT - SQUARE),
+                        );
+                    }
+                }
+                Packet::Command(line) => eprintln!("command: {line}"),
+                other => eprintln!("{from}: unexpected {other:?}"),
+            }
+        }
+        if client.is_some_and(|(_, seen)| seen.elapsed() > Duration::from_millis(TIMEOUT_MS as u64))
+        {
+            eprintln!("client timed out");
+            client = None;
+        }
+
+        let last = square;
+        if !held {
+            let bounce = |position: u16, velocity: &mut i32, max: u16| {
+                let next = position as i32 + *velocity;
+                if next < 0 || next > (max - SQUARE) as i32 {
+                    *velocity = -*velocity;
+                }
+                (position as i32 + *velocity).clamp(0, (max - SQUARE) as i32) as u16
+            };
+            square = (
+                bounce(square.0, &mut velocity.0, WIDTH),
+                bounce(square.1, &mut velocity.1, HEIGHT),
+            );
+        }
+
+        if let Some((address, _)) = client {
+            let pixel = |x: u16, y: u16| {
+                let inside = (square.0..square.0 + SQUARE).contains(&x)
+                    && (square.1..square.1 + SQUARE).contains(&y);
+                if inside {
+                    // Red to blue across the square
+                    let blend = (x - square.0) * 31 / SQUARE;
+                    (31 - blend) << 11 | blend
+                } else {
+                    // Dim grid so that stale pixels stand out
+                    if x.is_multiple_of(32) || y.is_multiple_of(32) {
+                        0x2104
+                    } else {
+                        0
+                    }
+                }
+            };
+            // The old and the new square, like the firmware's dirty regions
+            let rects = if std::mem::take(&mut full_frame) {
+                vec![Rect {
+                    x: 0,
+                    y: 0,
+                    width: WIDTH,
+                    height: HEIGHT,
+                }]
+            } else {
+                [last, square]
+                    .map(|(x, y)| Rect {
+                        x,
+                        y,
+                        width: SQUARE,
+                        height: SQUARE,
+                    })
+                    .to_vec()
+            };
+
+            encoder.begin(WIDTH, HEIGHT);
+            while let Some(packet) = encoder.next(&rects, pixel) {
+                if let Err(error) = socket.send_to(packet, address) {
+                    eprintln!("{address}: {error}");
+                }
+            }
+        }
+
+        thread::sleep(FRAME_TIME.saturating_sub(start.elapsed()));
+    }
+}
+
+/// Sends packets to the device with increasing sequence numbers
+struct Sender {
+    socket: UdpSocket,
+    device: SocketAddr,
+    sequence: u16,
+    buffer: [u8; MAX_PACKET_LEN],
+}
+
+impl Sender {
+    fn new(socket: UdpSocket, device: SocketAddr) -> Self {
+        Sender {
+            socket,
+            device,
+            sequence: 0,
+            buffer: [0; MAX_PACKET_LEN],
+        }
+    }
+
+    fn send(&mut self, packet: Packet<'_>) {
+        let len = packet.encode(self.sequence, &mut self.buffer);
+        self.sequence = self.sequence.wrapping_add(1);
+        if let Err(error) = self.socket.send_to(&self.buffer[..len], self.device) {
+            eprintln!("{}: {error}", self.device);
+        }
+    }
+}
+
+/// `address` with [`PORT`] if it has none
+fn resolve(address: &str) -> Result<SocketAddr, String> {
+    let with_port = if address.contains(':') {
+        address.to_string()
+    } else {
+        format!("{address}:{PORT}")
+    };
+    with_port
+        .to_socket_addrs()
+        .map_err(|error| format!("{address}: {error}"))?
+        .next()
+        .ok_or_else(|| format!("{address}: no address"))
+}
+
+/// RGB565 widened to 8 bits per channel, white stays white
+fn rgb888(pixel: u16) -> [u8; 3] {
+    let r = (pixel >> 11) as u8 & 0x1F;
+    let g = (pixel >> 5) as u8 & 0x3F;
+    let b = pixel as u8 & 0x1F;
+    [
+        (r << 3) | (r >> 2),
+        (g << 2) | (g >> 4),
+        (b << 3) | (b >> 2),
+    ]
+}